@@ -0,0 +1,186 @@
+//
+// Copyright (c) 2022-2023 Nathaniel Houghton <nathan@brainwerk.org>
+//
+// Permission to use, copy, modify, and distribute this software for
+// any purpose with or without fee is hereby granted, provided that
+// the above copyright notice and this permission notice appear in all
+// copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL
+// WARRANTIES WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE
+// AUTHOR BE LIABLE FOR ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL
+// DAMAGES OR ANY DAMAGES WHATSOEVER RESULTING FROM LOSS OF USE, DATA
+// OR PROFITS, WHETHER IN AN ACTION OF CONTRACT, NEGLIGENCE OR OTHER
+// TORTIOUS ACTION, ARISING OUT OF OR IN CONNECTION WITH THE USE OR
+// PERFORMANCE OF THIS SOFTWARE.
+//
+
+// Timing how fast the basic operations on a HOG file run, so that the numbers
+// can be compared between releases (and between machines, for the same HOG
+// file).
+//
+// Every benchmark reads the whole HOG file once per round, and is run for a
+// number of rounds, so that a single slow round (the first one, reading the
+// file into the cache, or one interrupted by something else running) does not
+// decide the result: the median round is what is reported.
+
+use std::io::{self, Read, Seek};
+use std::time::{Duration, Instant};
+
+use crate::error::HogError;
+use crate::hog::HogFileReader;
+use crate::index::HogIndex;
+use crate::util::Sha256Writer;
+
+// Fewest rounds a benchmark is run for, however long they take.
+const MIN_ROUNDS: u32 = 3;
+
+/// A benchmark that run() can time.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Benchmark {
+    /// Walking the records of the HOG file to build its index, without
+    /// reading the contents of the files.
+    IndexScan,
+
+    /// Reading the contents of every file, and throwing them away.
+    Extract,
+
+    /// Calculating the SHA-256 digest of every file.
+    Hash,
+}
+
+impl Benchmark {
+    /// All the benchmarks, in the order they are usually run in.
+    pub const ALL: [Benchmark; 3] = [Benchmark::IndexScan, Benchmark::Extract, Benchmark::Hash];
+
+    /// Returns a stable name for the benchmark, for scripts to match on.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Benchmark::IndexScan => "index-scan",
+            Benchmark::Extract => "extract",
+            Benchmark::Hash => "hash",
+        }
+    }
+}
+
+/// How long a benchmark took.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BenchResult {
+    pub benchmark: Benchmark,
+
+    /// The number of rounds run.
+    pub rounds: u32,
+
+    /// The number of files, and bytes of their contents, handled in each
+    /// round.
+    pub files: u64,
+    pub bytes: u64,
+
+    /// The time taken by the fastest and by the median round.
+    pub fastest: Duration,
+    pub median: Duration,
+}
+
+impl BenchResult {
+    /// Returns the number of bytes handled per second, in the median round.
+    pub fn bytes_per_sec(&self) -> f64 {
+        self.bytes as f64 / self.median.as_secs_f64().max(f64::MIN_POSITIVE)
+    }
+}
+
+/// Runs "benchmark" on the HOG file read by "hog_file", for at least
+/// "min_time" (and at least three rounds).
+pub fn run<R: Read + Seek>(
+    hog_file: &mut HogFileReader<R>,
+    benchmark: Benchmark,
+    min_time: Duration,
+) -> Result<BenchResult, HogError> {
+    let start = Instant::now();
+    let mut times = Vec::new();
+    let mut counts = (0, 0);
+
+    while times.len() < MIN_ROUNDS as usize || start.elapsed() < min_time {
+        let round_start = Instant::now();
+
+        counts = run_round(hog_file, benchmark)?;
+        times.push(round_start.elapsed());
+    }
+
+    times.sort();
+
+    Ok(BenchResult {
+        benchmark,
+        rounds: times.len() as u32,
+        files: counts.0,
+        bytes: counts.1,
+        fastest: times[0],
+        median: times[times.len() / 2],
+    })
+}
+
+// Runs a single round of "benchmark", returning the number of files and bytes
+// handled.
+fn run_round<R: Read + Seek>(
+    hog_file: &mut HogFileReader<R>,
+    benchmark: Benchmark,
+) -> Result<(u64, u64), HogError> {
+    if benchmark == Benchmark::IndexScan {
+        let index = HogIndex::build(hog_file)?;
+        let bytes = index.entries.iter().map(|x| u64::from(x.length)).sum();
+
+        return Ok((index.entries.len() as u64, bytes));
+    }
+
+    let mut counts = (0, 0);
+    let mut iter = hog_file.records()?;
+
+    while let Some(record) = iter.next() {
+        let record = record?;
+
+        match benchmark {
+            Benchmark::Hash => {
+                let mut hasher = Sha256Writer::new(io::sink());
+
+                iter.copy_cur_file(&mut hasher)?;
+                hasher.finish();
+            }
+            _ => iter.copy_cur_file(&mut io::sink())?,
+        }
+
+        counts.0 += 1;
+        counts.1 += u64::from(record.length);
+    }
+
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixture;
+
+    #[test]
+    fn test_run() {
+        let fixture = fixture::generate(20, None).unwrap();
+        let mut hog_file = HogFileReader::from_reader(io::Cursor::new(fixture.data)).unwrap();
+        let bytes = (0..20).map(|x| fixture::entry(x).1.len() as u64).sum();
+
+        for benchmark in Benchmark::ALL {
+            let result = run(&mut hog_file, benchmark, Duration::ZERO).unwrap();
+
+            assert_eq!(result.benchmark, benchmark);
+            assert_eq!(result.rounds, MIN_ROUNDS);
+            assert_eq!((result.files, result.bytes), (20, bytes));
+            assert!(result.fastest <= result.median);
+        }
+
+        let broken = fixture::generate(20, Some(fixture::Corruption::Truncate)).unwrap();
+        let mut hog_file = HogFileReader::from_reader(io::Cursor::new(broken.data)).unwrap();
+
+        assert!(matches!(
+            run(&mut hog_file, Benchmark::Extract, Duration::ZERO),
+            Err(HogError::ExtractFailure(e)) if e.kind() == io::ErrorKind::UnexpectedEof
+        ));
+    }
+}
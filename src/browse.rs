@@ -0,0 +1,349 @@
+//
+// Copyright (c) 2022-2023 Nathaniel Houghton <nathan@brainwerk.org>
+//
+// Permission to use, copy, modify, and distribute this software for
+// any purpose with or without fee is hereby granted, provided that
+// the above copyright notice and this permission notice appear in all
+// copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL
+// WARRANTIES WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE
+// AUTHOR BE LIABLE FOR ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL
+// DAMAGES OR ANY DAMAGES WHATSOEVER RESULTING FROM LOSS OF USE, DATA
+// OR PROFITS, WHETHER IN AN ACTION OF CONTRACT, NEGLIGENCE OR OTHER
+// TORTIOUS ACTION, ARISING OUT OF OR IN CONNECTION WITH THE USE OR
+// PERFORMANCE OF THIS SOFTWARE.
+//
+
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use hogdump::error::HogError;
+use hogdump::hog::{HogFileReader, HogRecord};
+use hogdump::image::{self, Image};
+use hogdump::sniff::{self, ContentType};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::{open_output_file, ExtractOptions};
+
+// Bytes of each file shown in the hex view.
+const HEX_PREVIEW_LEN: usize = 64 * 1024;
+
+// A file in the HOG file being browsed. The whole HOG file is read into memory
+// up front, which is fine for the sizes HOG files come in.
+struct Entry {
+    record: HogRecord,
+    content_type: ContentType,
+    data: Vec<u8>,
+    marked: bool,
+}
+
+// State of the interactive HOG file browser.
+struct Browser<'a> {
+    archive: &'a Path,
+    opts: &'a ExtractOptions,
+    entries: Vec<Entry>,
+    list_state: ListState,
+
+    // The selected file decoded as an image, decoded when it is selected
+    // rather than on every redraw. None if it is not an image that can be
+    // decoded.
+    image: Option<Result<Image, HogError>>,
+
+    hex: bool,
+    scroll: u16,
+    status: String,
+}
+
+// Formats "data" like "hexdump -C" does.
+fn hex_dump(data: &[u8]) -> Vec<Line<'static>> {
+    data.chunks(16)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|x| format!("{:02x}", x)).collect();
+            let ascii: String = chunk
+                .iter()
+                .map(|&x| {
+                    if x.is_ascii_graphic() || x == b' ' {
+                        x as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+
+            Line::from(format!(
+                "{:08x}  {:<47}  |{}|",
+                i * 16,
+                hex.join(" "),
+                ascii
+            ))
+        })
+        .collect()
+}
+
+// Draws "image" scaled down to fit in "width" by "height" characters, each
+// one an upper half block colored as two pixels, one above the other.
+fn thumbnail(image: &Image, width: u16, height: u16) -> Vec<Line<'static>> {
+    let image = image.thumbnail(width.into(), u32::from(height) * 2);
+    let color = |[r, g, b]: [u8; 3]| Color::Rgb(r, g, b);
+
+    (0..image.height)
+        .step_by(2)
+        .map(|y| {
+            (0..image.width)
+                .map(|x| {
+                    let style = Style::new().fg(color(image.rgb(x, y)));
+                    let style = match y + 1 < image.height {
+                        true => style.bg(color(image.rgb(x, y + 1))),
+                        false => style,
+                    };
+
+                    Span::styled("\u{2580}", style)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+impl<'a> Browser<'a> {
+    fn new(archive: &'a Path, opts: &'a ExtractOptions) -> Result<Self, HogError> {
+        let mut hog_file = HogFileReader::open_limited(&archive, &opts.read.limits)?;
+        let mut entries = Vec::new();
+
+        for item in hog_file.iter_with_data(None)? {
+            let (record, data) = item?;
+            let content_type = sniff::sniff(&data[..data.len().min(sniff::SNIFF_LEN)]);
+
+            entries.push(Entry {
+                record,
+                content_type,
+                data,
+                marked: false,
+            });
+        }
+
+        let mut browser = Self {
+            archive,
+            opts,
+            entries,
+            list_state: ListState::default(),
+            image: None,
+            hex: false,
+            scroll: 0,
+            status: "q: quit  space: mark  x: extract  tab: hex/text  pgup/pgdn: scroll".into(),
+        };
+        browser.select(0);
+
+        Ok(browser)
+    }
+
+    fn selected(&self) -> Option<&Entry> {
+        self.entries.get(self.list_state.selected()?)
+    }
+
+    // Returns the preview of the selected file, as text if it looks like text
+    // or as a thumbnail fitting in "width" by "height" characters if it is an
+    // image (and text view is selected), otherwise as a hex dump.
+    fn preview(&self, width: u16, height: u16) -> Vec<Line<'static>> {
+        let entry = match self.selected() {
+            Some(x) => x,
+            None => return Vec::new(),
+        };
+
+        if !self.hex {
+            match &self.image {
+                Some(Ok(image)) => return thumbnail(image, width, height),
+                Some(Err(e)) => return vec![Line::from(e.to_string())],
+                None => {}
+            }
+        }
+
+        let text = match entry.content_type {
+            _ if self.hex => None,
+            ContentType::Text => Some(String::from_utf8_lossy(&entry.data).into_owned()),
+            ContentType::Txb => Some(
+                entry
+                    .data
+                    .iter()
+                    .map(|&x| sniff::decode_txb_byte(x) as char)
+                    .collect(),
+            ),
+            _ => None,
+        };
+
+        match text {
+            Some(text) => text.lines().map(|x| Line::from(x.to_string())).collect(),
+            None => hex_dump(&entry.data[..entry.data.len().min(HEX_PREVIEW_LEN)]),
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, status] =
+            Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+        let [list_area, preview_area] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .areas(main);
+
+        let items: Vec<ListItem> = self
+            .entries
+            .iter()
+            .map(|x| {
+                ListItem::new(format!(
+                    "{} {:<12} {:>9} {}",
+                    if x.marked { '*' } else { ' ' },
+                    x.record,
+                    x.record.length,
+                    x.content_type
+                ))
+            })
+            .collect();
+
+        let list = List::new(items)
+            .block(Block::bordered().title(self.archive.display().to_string()))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+
+        frame.render_stateful_widget(list, list_area, &mut self.list_state);
+
+        let title = match self.selected() {
+            Some(x) => x.record.to_string(),
+            None => String::new(),
+        };
+        // Inside the border.
+        let (width, height) = (
+            preview_area.width.saturating_sub(2),
+            preview_area.height.saturating_sub(2),
+        );
+        let preview = Paragraph::new(self.preview(width, height))
+            .block(Block::bordered().title(title))
+            .scroll((self.scroll, 0));
+
+        frame.render_widget(preview, preview_area);
+        frame.render_widget(Paragraph::new(self.status.as_str()), status);
+    }
+
+    // Extracts the marked files, or the selected file if none are marked.
+    fn extract(&mut self) {
+        let mut indexes: Vec<usize> = (0..self.entries.len())
+            .filter(|&i| self.entries[i].marked)
+            .collect();
+
+        if indexes.is_empty() {
+            indexes.extend(self.list_state.selected());
+        }
+
+        let (mut written, mut skipped, mut failed) = (0, 0, 0);
+
+        for i in indexes {
+            let entry = &self.entries[i];
+            let path = PathBuf::from(
+                self.opts
+                    .plan
+                    .case
+                    .apply(&entry.record.filename.to_string_lossy()),
+            );
+
+            let result = open_output_file(
+                &self.opts.dir.join(path),
+                self.opts.overwrite,
+                self.opts.read.no_follow,
+            )
+            .and_then(|f| match f {
+                Some((mut f, _in_progress)) => {
+                    f.write_all(&entry.data)
+                        .and_then(|_| f.flush())
+                        .map_err(HogError::ExtractFailure)?;
+
+                    Ok(true)
+                }
+                None => Ok(false),
+            });
+
+            match result {
+                Ok(true) => written += 1,
+                Ok(false) => skipped += 1,
+                Err(_) => failed += 1,
+            }
+        }
+
+        self.status = format!(
+            "extracted {} files, skipped {} (already exist), {} failed",
+            written, skipped, failed
+        );
+    }
+
+    fn select(&mut self, index: usize) {
+        if self.entries.is_empty() {
+            return;
+        }
+
+        let index = index.min(self.entries.len() - 1);
+        let entry = &self.entries[index];
+
+        // Images past the limits say so, other files are previewed as text
+        // or hex instead.
+        self.image = match image::decode(entry.content_type, &entry.data, &self.opts.read.limits) {
+            Some(Ok(image)) => Some(Ok(image)),
+            Some(Err(e @ HogError::LimitExceeded(_))) => Some(Err(e)),
+            _ => None,
+        };
+
+        self.list_state.select(Some(index));
+        self.scroll = 0;
+    }
+
+    // Runs the browser until the user quits.
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            let key = match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => key,
+                _ => continue,
+            };
+
+            let cur = self.list_state.selected().unwrap_or(0);
+
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Down | KeyCode::Char('j') => self.select(cur + 1),
+                KeyCode::Up | KeyCode::Char('k') => self.select(cur.saturating_sub(1)),
+                KeyCode::Home => self.select(0),
+                KeyCode::End => self.select(usize::MAX),
+                KeyCode::PageDown => self.scroll = self.scroll.saturating_add(20),
+                KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(20),
+                KeyCode::Tab => {
+                    self.hex = !self.hex;
+                    self.scroll = 0;
+                }
+                KeyCode::Char(' ') => {
+                    if let Some(entry) = self.entries.get_mut(cur) {
+                        entry.marked = !entry.marked;
+                    }
+
+                    self.select(cur + 1);
+                }
+                KeyCode::Char('x') => self.extract(),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Browses the files in a HOG file interactively. Files are extracted
+/// according to "opts".
+pub fn browse(archive: &Path, opts: &ExtractOptions) -> Result<(), HogError> {
+    let mut browser = Browser::new(archive, opts)?;
+    let mut terminal = ratatui::try_init().map_err(HogError::OutputFailure)?;
+    let result = browser.run(&mut terminal);
+
+    ratatui::restore();
+
+    result.map_err(HogError::OutputFailure)
+}
@@ -0,0 +1,77 @@
+//
+// Copyright (c) 2022-2023 Nathaniel Houghton <nathan@brainwerk.org>
+//
+// Permission to use, copy, modify, and distribute this software for
+// any purpose with or without fee is hereby granted, provided that
+// the above copyright notice and this permission notice appear in all
+// copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL
+// WARRANTIES WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE
+// AUTHOR BE LIABLE FOR ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL
+// DAMAGES OR ANY DAMAGES WHATSOEVER RESULTING FROM LOSS OF USE, DATA
+// OR PROFITS, WHETHER IN AN ACTION OF CONTRACT, NEGLIGENCE OR OTHER
+// TORTIOUS ACTION, ARISING OUT OF OR IN CONNECTION WITH THE USE OR
+// PERFORMANCE OF THIS SOFTWARE.
+//
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// Files that are currently being written. If hogdump is interrupted, these are
+// incomplete, and are removed.
+static IN_PROGRESS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+// Exit status used when interrupted (128 + SIGINT, like a shell would report).
+const INTERRUPTED_STATUS: i32 = 130;
+
+// Installs a Ctrl-C handler that removes any files that are being written, and
+// then exits.
+pub fn install_handler() {
+    let result = ctrlc::set_handler(|| {
+        let files = IN_PROGRESS.lock().unwrap_or_else(|e| e.into_inner());
+
+        for file in files.iter() {
+            if std::fs::remove_file(file).is_ok() {
+                eprintln!(
+                    "interrupted: removed incomplete file \"{}\"",
+                    file.display()
+                );
+            }
+        }
+
+        std::process::exit(INTERRUPTED_STATUS);
+    });
+
+    if let Err(e) = result {
+        eprintln!("warning: failed to install Ctrl-C handler: {}", e);
+    }
+}
+
+// Marks a file as being written, until the InProgress is dropped.
+pub struct InProgress {
+    path: PathBuf,
+}
+
+impl InProgress {
+    // Marks "path" as being written. It is removed if hogdump is interrupted
+    // before the returned InProgress is dropped.
+    pub fn new(path: &Path) -> Self {
+        let path = path.to_path_buf();
+
+        IN_PROGRESS.lock().unwrap().push(path.clone());
+
+        Self { path }
+    }
+}
+
+impl Drop for InProgress {
+    fn drop(&mut self) {
+        let mut files = IN_PROGRESS.lock().unwrap();
+
+        if let Some(pos) = files.iter().position(|x| *x == self.path) {
+            files.swap_remove(pos);
+        }
+    }
+}
@@ -0,0 +1,490 @@
+//
+// Copyright (c) 2022-2023 Nathaniel Houghton <nathan@brainwerk.org>
+//
+// Permission to use, copy, modify, and distribute this software for
+// any purpose with or without fee is hereby granted, provided that
+// the above copyright notice and this permission notice appear in all
+// copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL
+// WARRANTIES WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE
+// AUTHOR BE LIABLE FOR ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL
+// DAMAGES OR ANY DAMAGES WHATSOEVER RESULTING FROM LOSS OF USE, DATA
+// OR PROFITS, WHETHER IN AN ACTION OF CONTRACT, NEGLIGENCE OR OTHER
+// TORTIOUS ACTION, ARISING OUT OF OR IN CONNECTION WITH THE USE OR
+// PERFORMANCE OF THIS SOFTWARE.
+//
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Cursor, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+
+use crate::hog::{self, HogzMetadata};
+use crate::util::{self, Limits};
+
+/// Compression formats that HOG files can be stored in.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Compression {
+    Gzip,
+    Xz,
+}
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const XZ_MAGIC: [u8; 6] = [0xfd, b'7', b'z', b'X', b'Z', 0];
+const ZIP_MAGIC: [u8; 4] = *b"PK\x03\x04";
+
+// Returns true if the file at "path" is a ZIP archive.
+fn is_zip(path: &Path) -> bool {
+    let mut magic = [0; ZIP_MAGIC.len()];
+
+    File::open(path)
+        .and_then(|mut f| f.read_exact(&mut magic))
+        .is_ok()
+        && magic == ZIP_MAGIC
+}
+
+/// Returns the HOG files inside the ZIP archive at "path", or None if it is
+/// not a ZIP archive.
+///
+/// Each HOG file is returned as "path" followed by its name inside the ZIP
+/// archive (as if the ZIP archive were a directory), which HogInput::open()
+/// accepts.
+pub fn zip_hog_members(path: &impl AsRef<Path>) -> io::Result<Option<Vec<PathBuf>>> {
+    let path = path.as_ref();
+
+    if !is_zip(path) {
+        return Ok(None);
+    }
+
+    let zip = zip::ZipArchive::new(BufReader::new(File::open(path)?)).map_err(io::Error::other)?;
+    let mut members = Vec::new();
+
+    for name in zip.file_names() {
+        let name = name.map_err(io::Error::other)?;
+
+        if name.to_ascii_lowercase().ends_with(".hog") {
+            members.push(path.join(&*name));
+        }
+    }
+
+    Ok(Some(members))
+}
+
+// Reads the file at "path" inside a ZIP archive (see zip_hog_members()) into
+// memory, within "limits". Returns None if no part of "path" is a ZIP archive.
+fn read_zip_member(path: &Path, limits: &Limits) -> io::Result<Option<Vec<u8>>> {
+    for zip_path in path.ancestors().skip(1) {
+        if !zip_path.is_file() {
+            continue;
+        }
+
+        if !is_zip(zip_path) {
+            return Ok(None);
+        }
+
+        let Ok(member_path) = path.strip_prefix(zip_path) else {
+            continue;
+        };
+
+        // Names inside ZIP archives always use forward slashes.
+        let name: Vec<_> = member_path.iter().map(|x| x.to_string_lossy()).collect();
+
+        let f = BufReader::new(File::open(zip_path)?);
+        let mut zip = zip::ZipArchive::new(f).map_err(io::Error::other)?;
+        let mut member = zip
+            .by_name(&name.join("/"))
+            .map_err(|e| io::Error::new(ErrorKind::NotFound, e))?;
+        let mut data = limits.writer(Vec::new());
+
+        io::copy(&mut member, &mut data)?;
+
+        return Ok(Some(data.into_inner()));
+    }
+
+    Ok(None)
+}
+
+impl Compression {
+    /// Returns the compression format of data starting with "magic", or None
+    /// if it does not look compressed.
+    pub fn detect(magic: &[u8]) -> Option<Self> {
+        if magic.starts_with(&GZIP_MAGIC) {
+            Some(Compression::Gzip)
+        } else if magic.starts_with(&XZ_MAGIC) {
+            Some(Compression::Xz)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the compression format of the file at "path", or None if it
+    /// does not look compressed.
+    pub fn detect_file(path: &impl AsRef<Path>) -> io::Result<Option<Self>> {
+        let mut magic = Vec::with_capacity(XZ_MAGIC.len());
+
+        File::open(path)?
+            .take(XZ_MAGIC.len() as u64)
+            .read_to_end(&mut magic)?;
+
+        Ok(Self::detect(&magic))
+    }
+
+    /// Decompresses all of "r" into memory.
+    pub fn decompress(&self, r: impl Read) -> io::Result<Vec<u8>> {
+        self.decompress_limited(r, &Limits::default())
+    }
+
+    /// Decompresses all of "r" into memory like decompress(), failing if
+    /// that goes past "limits" (with an error that wraps a LimitExceeded, see
+    /// HogError::from_io()).
+    pub fn decompress_limited(&self, r: impl Read, limits: &Limits) -> io::Result<Vec<u8>> {
+        let mut data = limits.writer(Vec::new());
+
+        match self {
+            Compression::Gzip => {
+                io::copy(&mut MultiGzDecoder::new(r), &mut data)?;
+            }
+            Compression::Xz => {
+                lzma_rs::xz_decompress(&mut BufReader::new(r), &mut data).map_err(|e| match e {
+                    lzma_rs::error::Error::IoError(e) => e,
+                    e => io::Error::new(io::ErrorKind::InvalidData, e.to_string()),
+                })?;
+            }
+        }
+
+        Ok(data.into_inner())
+    }
+}
+
+/// Where HOG files are read from. Compressed HOG files (and HOG files inside
+/// ZIP archives) are read into memory when they are opened, because reading
+/// them requires seeking.
+pub enum HogInput {
+    File(BufReader<File>),
+    Memory(Cursor<Vec<u8>>),
+    // A hogz file, converted to a HOG file in memory, and its metadata.
+    Hogz(Cursor<Vec<u8>>, HogzMetadata),
+    #[cfg(feature = "s3")]
+    S3(crate::s3::S3Reader),
+}
+
+impl HogInput {
+    /// Opens the file at "path", decompressing it first if needed. "path"
+    /// may also refer to a file inside a ZIP archive, such as
+    /// "mission.zip/mission.hog".
+    ///
+    /// When built with the "s3" feature, "path" can also be an s3://bucket/key
+    /// URL.
+    pub fn open(path: &impl AsRef<Path>) -> io::Result<Self> {
+        Self::open_limited(path, &Limits::default())
+    }
+
+    /// Opens the file at "path" like open(), but decompressing it (or reading
+    /// it out of a ZIP archive) fails if that goes past "limits".
+    pub fn open_limited(path: &impl AsRef<Path>, limits: &Limits) -> io::Result<Self> {
+        if let Some(url) = path.as_ref().to_str().filter(|x| x.starts_with("s3://")) {
+            return Self::open_s3(url, limits);
+        }
+
+        let file = match File::open(path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == ErrorKind::NotFound || e.kind() == ErrorKind::NotADirectory => {
+                return match read_zip_member(path.as_ref(), limits)? {
+                    Some(data) => Ok(HogInput::Memory(Cursor::new(data))),
+                    None => Err(e),
+                };
+            }
+            Err(e) => return Err(e),
+        };
+
+        Self::from_file(file, limits)
+    }
+
+    /// Opens the file at "path" like open_limited(), but fails if "path" is a
+    /// symbolic link (see util::open_no_follow()). Files inside ZIP archives
+    /// and S3 URLs cannot be opened this way.
+    pub fn open_no_follow(path: &impl AsRef<Path>, limits: &Limits) -> io::Result<Self> {
+        let file = util::open_no_follow(OpenOptions::new().read(true), path.as_ref())?;
+
+        Self::from_file(file, limits)
+    }
+
+    // Reads the HOG file "file", decompressing it first (within "limits") if
+    // needed.
+    fn from_file(file: File, limits: &Limits) -> io::Result<Self> {
+        let mut file = BufReader::new(file);
+        let mut magic = Vec::with_capacity(XZ_MAGIC.len());
+
+        (&mut file)
+            .take(XZ_MAGIC.len() as u64)
+            .read_to_end(&mut magic)?;
+        file.seek(SeekFrom::Start(0))?;
+
+        if magic.starts_with(&hog::HOGZ_SIGNATURE) {
+            let (data, metadata) = hog::decode_hogz_with_metadata(&mut file, limits)?;
+
+            return Ok(HogInput::Hogz(Cursor::new(data), metadata));
+        }
+
+        match Compression::detect(&magic) {
+            Some(compression) => Ok(HogInput::Memory(Cursor::new(
+                compression.decompress_limited(file, limits)?,
+            ))),
+            None => Ok(HogInput::File(file)),
+        }
+    }
+
+    #[cfg(feature = "s3")]
+    fn open_s3(url: &str, limits: &Limits) -> io::Result<Self> {
+        let mut reader = crate::s3::S3Reader::open(url, &crate::s3::S3Config::from_env())?;
+        let mut magic = Vec::with_capacity(XZ_MAGIC.len());
+
+        (&mut reader)
+            .take(XZ_MAGIC.len() as u64)
+            .read_to_end(&mut magic)?;
+        reader.seek(SeekFrom::Start(0))?;
+
+        if magic.starts_with(&hog::HOGZ_SIGNATURE) {
+            let (data, metadata) = hog::decode_hogz_with_metadata(&mut reader, limits)?;
+
+            return Ok(HogInput::Hogz(Cursor::new(data), metadata));
+        }
+
+        match Compression::detect(&magic) {
+            Some(compression) => Ok(HogInput::Memory(Cursor::new(
+                compression.decompress_limited(reader, limits)?,
+            ))),
+            None => Ok(HogInput::S3(reader)),
+        }
+    }
+
+    /// Returns the metadata of the hogz file this was opened from, if it was
+    /// one.
+    pub fn hogz_metadata(&self) -> Option<&HogzMetadata> {
+        match self {
+            HogInput::Hogz(_, metadata) => Some(metadata),
+            _ => None,
+        }
+    }
+
+    #[cfg(not(feature = "s3"))]
+    fn open_s3(_url: &str, _limits: &Limits) -> io::Result<Self> {
+        Err(io::Error::new(
+            ErrorKind::Unsupported,
+            "S3 support is not built in (enable the \"s3\" feature)",
+        ))
+    }
+}
+
+impl Read for HogInput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            HogInput::File(f) => f.read(buf),
+            HogInput::Memory(m) | HogInput::Hogz(m, _) => m.read(buf),
+            #[cfg(feature = "s3")]
+            HogInput::S3(r) => r.read(buf),
+        }
+    }
+}
+
+impl Seek for HogInput {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            HogInput::File(f) => f.seek(pos),
+            HogInput::Memory(m) | HogInput::Hogz(m, _) => m.seek(pos),
+            #[cfg(feature = "s3")]
+            HogInput::S3(r) => r.seek(pos),
+        }
+    }
+}
+
+/// Where HOG files are written to, compressing them if requested.
+pub enum HogOutput {
+    File(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+
+    // There is no streaming xz encoder, so the HOG file is compressed all at
+    // once by finish().
+    Xz(BufWriter<File>, Vec<u8>),
+}
+
+impl HogOutput {
+    pub fn new(file: File, compression: Option<Compression>) -> Self {
+        let file = BufWriter::new(file);
+
+        match compression {
+            None => HogOutput::File(file),
+            Some(Compression::Gzip) => {
+                HogOutput::Gzip(GzEncoder::new(file, flate2::Compression::best()))
+            }
+            Some(Compression::Xz) => HogOutput::Xz(file, Vec::new()),
+        }
+    }
+
+    /// Writes out everything that is left (compressing it, if needed).
+    pub fn finish(self) -> io::Result<()> {
+        self.finish_compression()?.flush()
+    }
+
+    /// Finishes compressing the HOG file, returning the file it is written
+    /// to, which may still have buffered data that is not written out yet.
+    pub fn finish_compression(self) -> io::Result<BufWriter<File>> {
+        match self {
+            HogOutput::File(f) => Ok(f),
+            HogOutput::Gzip(e) => e.finish(),
+            HogOutput::Xz(mut f, data) => {
+                lzma_rs::xz_compress(&mut data.as_slice(), &mut f)?;
+                Ok(f)
+            }
+        }
+    }
+}
+
+impl Write for HogOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            HogOutput::File(f) => f.write(buf),
+            HogOutput::Gzip(e) => e.write(buf),
+            HogOutput::Xz(_, data) => data.write(buf),
+        }
+    }
+
+    fn write_vectored(&mut self, bufs: &[io::IoSlice]) -> io::Result<usize> {
+        match self {
+            HogOutput::File(f) => f.write_vectored(bufs),
+            HogOutput::Gzip(e) => e.write_vectored(bufs),
+            HogOutput::Xz(_, data) => data.write_vectored(bufs),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            HogOutput::File(f) => f.flush(),
+            HogOutput::Gzip(e) => e.flush(),
+            HogOutput::Xz(..) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::error::{HogError, LimitExceeded};
+
+    #[test]
+    fn test_round_trip() {
+        let data = b"DHFelephant".repeat(100);
+
+        for compression in [Compression::Gzip, Compression::Xz] {
+            let path = std::env::temp_dir().join(format!(
+                "hogdump-{}-compress-{:?}",
+                std::process::id(),
+                compression
+            ));
+
+            let mut out = HogOutput::new(File::create(&path).unwrap(), Some(compression));
+            out.write_all(&data).unwrap();
+            out.finish().unwrap();
+
+            assert_eq!(Compression::detect_file(&path).unwrap(), Some(compression));
+
+            let mut input = HogInput::open(&path).unwrap();
+            let mut read_back = Vec::new();
+            input.seek(SeekFrom::Start(3)).unwrap();
+            input.read_to_end(&mut read_back).unwrap();
+
+            assert_eq!(read_back, data[3..]);
+
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_decompress_limited() {
+        let data = vec![0; 1 << 20];
+        let limits = Limits {
+            max_len: Some(1000),
+            max_time: None,
+        };
+
+        for compression in [Compression::Gzip, Compression::Xz] {
+            let path = std::env::temp_dir().join(format!(
+                "hogdump-{}-limited-{:?}",
+                std::process::id(),
+                compression
+            ));
+
+            let mut out = HogOutput::new(File::create(&path).unwrap(), Some(compression));
+            out.write_all(&data).unwrap();
+            out.finish().unwrap();
+
+            let e = match HogInput::open_limited(&path, &limits) {
+                Ok(_) => panic!("{:?} was decompressed past the limit", compression),
+                Err(e) => HogError::from_io(e, HogError::OpenHogFailure),
+            };
+
+            assert!(matches!(
+                e,
+                HogError::LimitExceeded(LimitExceeded::Size(1000))
+            ));
+            assert!(HogInput::open(&path).is_ok());
+
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_zip() {
+        let dir = std::env::temp_dir().join(format!("hogdump-{}-zip", std::process::id()));
+        let zip_path = dir.join("mission.zip");
+
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut zip = zip::ZipWriter::new(File::create(&zip_path).unwrap());
+        let opts = zip::write::SimpleFileOptions::default();
+        zip.start_file("MISSION.HOG", opts).unwrap();
+        zip.write_all(b"DHF").unwrap();
+        zip.start_file("mission.msn", opts).unwrap();
+        zip.write_all(b"name = Test").unwrap();
+        zip.start_file("extra/other.hog", opts).unwrap();
+        zip.write_all(b"DHFxyz").unwrap();
+        zip.finish().unwrap();
+
+        let members = zip_hog_members(&zip_path).unwrap().unwrap();
+        assert_eq!(
+            members,
+            [
+                zip_path.join("MISSION.HOG"),
+                zip_path.join("extra/other.hog")
+            ]
+        );
+
+        let mut data = Vec::new();
+        HogInput::open(&members[1])
+            .unwrap()
+            .read_to_end(&mut data)
+            .unwrap();
+        assert_eq!(data, b"DHFxyz");
+
+        assert!(HogInput::open(&zip_path.join("missing.hog")).is_err());
+        assert!(zip_hog_members(&members[0]).unwrap().is_none());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(Compression::detect(b"DHF"), None);
+        assert_eq!(
+            Compression::detect(&[0x1f, 0x8b, 8]),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(Compression::detect(&XZ_MAGIC), Some(Compression::Xz));
+        assert_eq!(Compression::detect(&[]), None);
+    }
+}
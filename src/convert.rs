@@ -0,0 +1,136 @@
+//
+// Copyright (c) 2022-2023 Nathaniel Houghton <nathan@brainwerk.org>
+//
+// Permission to use, copy, modify, and distribute this software for
+// any purpose with or without fee is hereby granted, provided that
+// the above copyright notice and this permission notice appear in all
+// copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL
+// WARRANTIES WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE
+// AUTHOR BE LIABLE FOR ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL
+// DAMAGES OR ANY DAMAGES WHATSOEVER RESULTING FROM LOSS OF USE, DATA
+// OR PROFITS, WHETHER IN AN ACTION OF CONTRACT, NEGLIGENCE OR OTHER
+// TORTIOUS ACTION, ARISING OUT OF OR IN CONNECTION WITH THE USE OR
+// PERFORMANCE OF THIS SOFTWARE.
+//
+
+// Rules for converting Descent 1 missions to Descent 2 missions.
+//
+// The HOG file format is the same for both games, and most files are used
+// unchanged. The mission file is rewritten as a Descent 2 (.mn2) one. Levels
+// cannot be converted automatically (the textures and robots differ), so they
+// are kept as they are, and reported as work that remains to be done by hand.
+
+use crate::mission::Mission;
+
+/// What happens to a file of a Descent 1 mission HOG file when converting it
+/// to Descent 2.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum EntryConversion {
+    /// The file is copied unchanged.
+    Copy,
+
+    /// The Descent 1 mission file. It is left out of the HOG file, and
+    /// replaced by a Descent 2 mission file next to it.
+    MissionFile,
+
+    /// A Descent 1 level, which is copied unchanged. It still has to be
+    /// converted to a Descent 2 level, named "rl2_name", in a level editor.
+    Level { rl2_name: String },
+
+    /// A briefing or ending, which is copied unchanged. Descent 2 shows
+    /// briefings differently, so it should be checked by hand.
+    Briefing,
+}
+
+// Returns the extension of "name", in lowercase.
+fn extension(name: &str) -> Option<String> {
+    name.rsplit_once('.').map(|x| x.1.to_ascii_lowercase())
+}
+
+/// Returns what happens to the file stored as "name" in a Descent 1 mission
+/// HOG file, based on its extension.
+pub fn convert_entry(name: &str) -> EntryConversion {
+    match extension(name).as_deref() {
+        Some("msn") => EntryConversion::MissionFile,
+        Some("rdl" | "sdl") => EntryConversion::Level {
+            rl2_name: d2_level_name(name),
+        },
+        Some("txb" | "tex") => EntryConversion::Briefing,
+        _ => EntryConversion::Copy,
+    }
+}
+
+/// Returns the name of the Descent 2 level replacing the Descent 1 level
+/// "name" (with a .rl2 extension, matching the case of the old one). Other
+/// names are returned unchanged.
+pub fn d2_level_name(name: &str) -> String {
+    match name.rsplit_once('.') {
+        Some((base, ext)) if matches!(extension(name).as_deref(), Some("rdl" | "sdl")) => {
+            if ext.bytes().any(|x| x.is_ascii_lowercase()) {
+                format!("{}.rl2", base)
+            } else {
+                format!("{}.RL2", base)
+            }
+        }
+        _ => name.to_string(),
+    }
+}
+
+/// Returns the Descent 2 version of a Descent 1 mission, which plays the
+/// same levels once they are converted.
+pub fn convert_mission(mission: &Mission) -> Mission {
+    Mission {
+        name: mission.name.clone(),
+        levels: mission.levels.iter().map(|x| d2_level_name(x)).collect(),
+        secret_levels: mission
+            .secret_levels
+            .iter()
+            .map(|(level, from)| (d2_level_name(level), *from))
+            .collect(),
+        briefing: mission.briefing.clone(),
+        ending: mission.ending.clone(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_convert_entry() {
+        assert_eq!(convert_entry("mymiss.MSN"), EntryConversion::MissionFile);
+        assert_eq!(
+            convert_entry("level01.rdl"),
+            EntryConversion::Level {
+                rl2_name: "level01.rl2".to_string()
+            }
+        );
+        assert_eq!(convert_entry("mymiss.txb"), EntryConversion::Briefing);
+        assert_eq!(convert_entry("title.pcx"), EntryConversion::Copy);
+        assert_eq!(convert_entry("readme"), EntryConversion::Copy);
+
+        assert_eq!(d2_level_name("LEVEL01.RDL"), "LEVEL01.RL2");
+        assert_eq!(d2_level_name("level01.rl2"), "level01.rl2");
+    }
+
+    #[test]
+    fn test_convert_mission() {
+        let mission = Mission::parse(
+            "name = My Mission\n\
+             num_levels = 2\n\
+             level01.rdl\n\
+             LEVEL02.RDL\n\
+             num_secrets = 1\n\
+             levels1.rdl,1\n",
+        )
+        .unwrap();
+
+        let d2 = convert_mission(&mission);
+        assert_eq!(d2.name.as_deref(), Some("My Mission"));
+        assert_eq!(d2.levels, ["level01.rl2", "LEVEL02.RL2"]);
+        assert_eq!(d2.secret_levels, [("levels1.rl2".to_string(), 1)]);
+    }
+}
@@ -0,0 +1,718 @@
+//
+// Copyright (c) 2022-2023 Nathaniel Houghton <nathan@brainwerk.org>
+//
+// Permission to use, copy, modify, and distribute this software for
+// any purpose with or without fee is hereby granted, provided that
+// the above copyright notice and this permission notice appear in all
+// copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL
+// WARRANTIES WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE
+// AUTHOR BE LIABLE FOR ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL
+// DAMAGES OR ANY DAMAGES WHATSOEVER RESULTING FROM LOSS OF USE, DATA
+// OR PROFITS, WHETHER IN AN ACTION OF CONTRACT, NEGLIGENCE OR OTHER
+// TORTIOUS ACTION, ARISING OUT OF OR IN CONNECTION WITH THE USE OR
+// PERFORMANCE OF THIS SOFTWARE.
+//
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::SystemTime;
+
+use serde_json::{json, Value};
+
+use hogdump::compress::HogInput;
+use hogdump::error::HogError;
+use hogdump::hog::HogFileReader;
+use hogdump::index::{HogIndex, IndexEntry};
+use hogdump::util::{self, Limits};
+
+use crate::open_output_file;
+use crate::output::Reporter;
+
+// JSON-RPC 2.0 error codes.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+// Error code for requests that failed because of a HogError (the name of the
+// error is in the "data" of the error).
+const HOG_ERROR: i64 = -32000;
+
+// Error code for connections turned away because too many are open already.
+const SERVER_BUSY: i64 = -32001;
+
+// Longest request line accepted. A connection sending a longer one is closed,
+// as there is no telling where the next request starts.
+const MAX_LINE_LEN: u64 = 1024 * 1024;
+
+// An error to send back for a request.
+struct RpcError {
+    code: i64,
+    message: String,
+    data: Option<Value>,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+}
+
+impl From<HogError> for RpcError {
+    fn from(e: HogError) -> Self {
+        Self {
+            code: HOG_ERROR,
+            message: e.to_string(),
+            data: Some(json!({ "error": e.name() })),
+        }
+    }
+}
+
+// Most HOG files kept open at once. Opening another one closes the one that
+// was used longest ago.
+const MAX_ARCHIVES: usize = 16;
+
+// Most connections served at once. Further clients get an error and are
+// disconnected, rather than each getting a thread of its own.
+const MAX_CONNECTIONS: usize = 32;
+
+// An open HOG file, with its index built. It is reopened (and reindexed) when
+// the HOG file on disk changes. The reader is locked on its own, so requests
+// for other HOG files do not wait for it.
+struct CachedArchive {
+    len: u64,
+    modified: Option<SystemTime>,
+    reader: Arc<Mutex<HogFileReader<HogInput>>>,
+
+    // When it was last asked for, in requests since the daemon started.
+    last_used: u64,
+}
+
+// The HOG files opened so far, by the path they were asked for as.
+#[derive(Default)]
+struct Cache {
+    archives: Mutex<HashMap<PathBuf, CachedArchive>>,
+    requests: AtomicU64,
+
+    // Limits on decompressing the HOG files.
+    limits: Limits,
+}
+
+impl Cache {
+    // Returns the HOG file at "path", opening and indexing it unless it was
+    // already, and has not changed since. The cache is only locked to look it
+    // up and to add it, not while it is opened.
+    fn archive(&self, path: &Path) -> Result<Arc<Mutex<HogFileReader<HogInput>>>, HogError> {
+        let metadata = std::fs::metadata(path).map_err(HogError::OpenHogFailure)?;
+        let modified = metadata.modified().ok();
+        let now = self.requests.fetch_add(1, Ordering::Relaxed);
+        let lock = || self.archives.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if let Some(cached) = lock().get_mut(path) {
+            if cached.len == metadata.len() && cached.modified == modified {
+                cached.last_used = now;
+
+                return Ok(Arc::clone(&cached.reader));
+            }
+        }
+
+        let mut reader = HogFileReader::open_limited(&path, &self.limits)?;
+        let index = HogIndex::build(&mut reader)?;
+        let reader = Arc::new(Mutex::new(reader.with_index(index)?));
+        let mut archives = lock();
+
+        archives.insert(
+            path.to_path_buf(),
+            CachedArchive {
+                len: metadata.len(),
+                modified,
+                reader: Arc::clone(&reader),
+                last_used: now,
+            },
+        );
+
+        while archives.len() > MAX_ARCHIVES {
+            let oldest = archives
+                .iter()
+                .min_by_key(|(_, x)| x.last_used)
+                .map(|(path, _)| path.clone());
+
+            if let Some(oldest) = oldest {
+                archives.remove(&oldest);
+            }
+        }
+
+        Ok(reader)
+    }
+}
+
+// What the daemon serves requests with.
+struct Daemon {
+    cache: Cache,
+
+    // Where extracted files go. The "output" of an extract request is relative
+    // to it, and may not lead out of it.
+    output_dir: PathBuf,
+}
+
+impl Daemon {
+    fn new(output_dir: &Path, limits: Limits) -> Self {
+        Self {
+            cache: Cache {
+                limits,
+                ..Default::default()
+            },
+            output_dir: output_dir.to_path_buf(),
+        }
+    }
+
+    // Returns where to write the "output" of an extract request: somewhere in
+    // the output directory, never through a "..", from the root or through a
+    // symbolic link.
+    fn output_path(&self, output: &str) -> Result<PathBuf, RpcError> {
+        let path = Path::new(output);
+
+        if output.is_empty() || !path.components().all(|x| matches!(x, Component::Normal(_))) {
+            return Err(RpcError::new(
+                INVALID_PARAMS,
+                format!(
+                    "\"{}\" is not a relative path inside the output directory",
+                    output
+                ),
+            ));
+        }
+
+        util::check_no_symlink_dirs(&self.output_dir, path).map_err(HogError::OpenOutputFailure)?;
+
+        Ok(self.output_dir.join(path))
+    }
+}
+
+// Returns the string parameter "name" of a request.
+fn str_param<'a>(params: &'a Value, name: &str) -> Result<&'a str, RpcError> {
+    params[name]
+        .as_str()
+        .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("missing parameter \"{}\"", name)))
+}
+
+// Returns the file given by the "name" or "index" parameter of a request in
+// "reader", seeked to its contents. An "index" that is given has to be a
+// number of at least 0.
+fn seek_entry(
+    reader: &mut HogFileReader<HogInput>,
+    params: &Value,
+) -> Result<IndexEntry, RpcError> {
+    let index = match &params["index"] {
+        Value::Null => None,
+        index => Some(index.as_u64().ok_or_else(|| {
+            RpcError::new(
+                INVALID_PARAMS,
+                format!("parameter \"index\" is not a file index: {}", index),
+            )
+        })?),
+    };
+
+    let entry = match index {
+        Some(index) => reader.entry_at(index)?.ok_or_else(|| {
+            let count = reader.index().map_or(0, |x| x.entries.len() as u64);
+            HogError::IndexOutOfRange(index, count)
+        })?,
+        None => {
+            let name = str_param(params, "name")?;
+            reader
+                .find(name)?
+                .ok_or_else(|| HogError::EntryNotFound(name.to_string()))?
+        }
+    };
+
+    reader
+        .get_mut()
+        .seek(SeekFrom::Start(entry.offset))
+        .map_err(HogError::SeekFailure)?;
+
+    Ok(entry)
+}
+
+// Runs a single request, returning its result.
+fn call(daemon: &Daemon, method: &str, params: &Value) -> Result<Value, RpcError> {
+    let archive = || -> Result<_, RpcError> {
+        Ok(daemon
+            .cache
+            .archive(Path::new(str_param(params, "archive")?))?)
+    };
+
+    match method {
+        "list" => {
+            let reader = archive()?;
+            let reader = reader.lock().unwrap_or_else(PoisonError::into_inner);
+            let entries: Vec<Value> = reader
+                .index()
+                .map(|x| x.entries.as_slice())
+                .unwrap_or_default()
+                .iter()
+                .map(|x| json!({ "name": x.name, "offset": x.offset, "length": x.length }))
+                .collect();
+
+            Ok(json!({ "entries": entries }))
+        }
+        "hash" => {
+            let reader = archive()?;
+            let mut reader = reader.lock().unwrap_or_else(PoisonError::into_inner);
+            let entry = seek_entry(&mut reader, params)?;
+            let digest = util::sha256(&mut reader.get_mut().take(entry.length.into()))
+                .map_err(HogError::ReadFailure)?;
+
+            Ok(json!({ "sha256": util::to_hex(&digest) }))
+        }
+        "extract" => {
+            let output = daemon.output_path(str_param(params, "output")?)?;
+            let overwrite = params["overwrite"].as_bool().unwrap_or(false);
+            let reader = archive()?;
+            let mut reader = reader.lock().unwrap_or_else(PoisonError::into_inner);
+            let entry = seek_entry(&mut reader, params)?;
+
+            // Symbolic links are never followed out of the output directory.
+            let (mut out_f, _in_progress) = open_output_file(&output, overwrite, true)?
+                .ok_or_else(|| {
+                    HogError::OpenOutputFailure(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        format!("\"{}\" already exists", output.display()),
+                    ))
+                })?;
+
+            let bytes = util::copy_n(reader.get_mut(), &mut out_f, entry.length.into())
+                .and_then(|x| out_f.flush().map(|_| x))
+                .map_err(HogError::ExtractFailure)?;
+
+            if bytes < entry.length.into() {
+                return Err(HogError::UnexpectedEof.into());
+            }
+
+            Ok(json!({ "bytes": bytes }))
+        }
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("unknown method \"{}\"", method),
+        )),
+    }
+}
+
+// Handles a single request line, returning the response to send (none for
+// notifications, which have no id).
+fn handle_line(daemon: &Daemon, line: &str) -> Option<Value> {
+    let request: Value = match serde_json::from_str(line) {
+        Ok(x) => x,
+        Err(e) => {
+            return Some(response(
+                Value::Null,
+                Err(RpcError::new(PARSE_ERROR, e.to_string())),
+            ))
+        }
+    };
+
+    let id = request.get("id").cloned();
+    let result = match request["method"].as_str() {
+        Some(method) => call(daemon, method, &request["params"]),
+        None => Err(RpcError::new(INVALID_REQUEST, "missing method")),
+    };
+
+    id.map(|id| response(id, result))
+}
+
+fn response(id: Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(e) => {
+            let mut error = json!({ "code": e.code, "message": e.message });
+
+            if let Some(data) = e.data {
+                error["data"] = data;
+            }
+
+            json!({ "jsonrpc": "2.0", "id": id, "error": error })
+        }
+    }
+}
+
+// Handles the requests on a single connection, one JSON-RPC request per
+// line, until the client closes it.
+fn handle(stream: impl Read + Write, daemon: &Daemon) -> io::Result<()> {
+    let mut stream = BufReader::new(stream);
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+
+        let len = stream.by_ref().take(MAX_LINE_LEN).read_line(&mut line)?;
+
+        if len == 0 {
+            return Ok(());
+        }
+
+        if len as u64 == MAX_LINE_LEN && !line.ends_with('\n') {
+            let e = RpcError::new(INVALID_REQUEST, "request too long");
+            writeln!(stream.get_mut(), "{}", response(Value::Null, Err(e)))?;
+            return stream.get_mut().flush();
+        }
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if let Some(response) = handle_line(daemon, &line) {
+            writeln!(stream.get_mut(), "{}", response)?;
+            stream.get_mut().flush()?;
+        }
+    }
+}
+
+// Counts a connection as served until it is dropped.
+struct Connection(Arc<AtomicUsize>);
+
+impl Connection {
+    // Returns None if MAX_CONNECTIONS are being served already.
+    fn start(active: &Arc<AtomicUsize>) -> Option<Self> {
+        active
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |x| {
+                (x < MAX_CONNECTIONS).then_some(x + 1)
+            })
+            .ok()
+            .map(|_| Self(Arc::clone(active)))
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+// Creates the socket at "socket", accessible only to the user running the
+// daemon. It is bound in a directory only that user can get into, and only
+// moved to "socket" once its permissions are set, so that nobody else can
+// connect in between.
+#[cfg(unix)]
+fn bind_private(socket: &Path) -> io::Result<std::os::unix::net::UnixListener> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+    use std::os::unix::net::UnixListener;
+
+    if socket.symlink_metadata().is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("\"{}\" already exists", socket.display()),
+        ));
+    }
+
+    let mut dir_name = socket.file_name().unwrap_or_default().to_os_string();
+    dir_name.push(format!(".{}.tmp", std::process::id()));
+    let dir = socket.with_file_name(dir_name);
+    let bound = dir.join("socket");
+
+    std::fs::DirBuilder::new().mode(0o700).create(&dir)?;
+
+    let result = UnixListener::bind(&bound).and_then(|listener| {
+        std::fs::set_permissions(&bound, std::fs::Permissions::from_mode(0o600))?;
+        std::fs::rename(&bound, socket)?;
+
+        Ok(listener)
+    });
+
+    let _ = std::fs::remove_file(&bound);
+    let _ = std::fs::remove_dir(&dir);
+
+    result
+}
+
+/// Answers JSON-RPC requests to list, extract and hash the files of HOG files
+/// on the Unix socket at "socket", until interrupted. HOG files stay open, and
+/// indexed, between requests (up to MAX_ARCHIVES of them). Extracted files are
+/// written under "output_dir", and compressed HOG files are decompressed
+/// within "limits".
+///
+/// The socket is only accessible to the user running the daemon.
+#[cfg(unix)]
+pub fn daemon(
+    socket: &Path,
+    output_dir: &Path,
+    limits: Limits,
+    reporter: &Reporter,
+) -> Result<(), HogError> {
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::UnixStream;
+
+    // A socket left behind by an earlier daemon that nothing answers on is
+    // replaced.
+    let stale = std::fs::symlink_metadata(socket).is_ok_and(|x| x.file_type().is_socket())
+        && UnixStream::connect(socket).is_err();
+
+    if stale {
+        let _ = std::fs::remove_file(socket);
+    }
+
+    let listener = bind_private(socket).map_err(HogError::ListenFailure)?;
+    let daemon = Arc::new(Daemon::new(output_dir, limits));
+    let active = Arc::new(AtomicUsize::new(0));
+
+    reporter.daemon_listening(socket);
+
+    // Every connection is handled on its own thread, so a client keeping its
+    // connection open does not hold up the others. A client going away
+    // mid-request should not stop the daemon.
+    for mut stream in listener.incoming().flatten() {
+        let Some(connection) = Connection::start(&active) else {
+            let e = RpcError::new(SERVER_BUSY, "too many connections");
+            let _ = writeln!(stream, "{}", response(Value::Null, Err(e)));
+
+            continue;
+        };
+        let daemon = Arc::clone(&daemon);
+
+        std::thread::spawn(move || {
+            let _connection = connection;
+            let _ = handle(stream, &daemon);
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn daemon(
+    _socket: &Path,
+    _output_dir: &Path,
+    _limits: Limits,
+    _reporter: &Reporter,
+) -> Result<(), HogError> {
+    Err(HogError::ListenFailure(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "--daemon is only supported on Unix",
+    )))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use hogdump::fixture;
+
+    fn temp_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("hogdump-{}-{}", std::process::id(), test));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn request(daemon: &Daemon, method: &str, params: Value) -> Value {
+        let line = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        handle_line(daemon, &line.to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_handle_line() {
+        let dir = temp_dir("daemon");
+        let archive = dir.join("f.hog");
+        std::fs::write(&archive, fixture::generate(4, None).unwrap().data).unwrap();
+
+        let daemon = Daemon::new(&dir, Limits::default());
+        let archive = archive.to_str().unwrap();
+        let (name, contents) = fixture::entry(2);
+
+        let list = request(&daemon, "list", json!({ "archive": archive }));
+        let entries = list["result"]["entries"].as_array().unwrap();
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[2]["name"], name);
+        assert_eq!(entries[2]["length"], contents.len());
+
+        let digest = util::to_hex(&util::sha256(&mut contents.as_slice()).unwrap());
+        let hash = request(&daemon, "hash", json!({ "archive": archive, "name": name }));
+        assert_eq!(hash["result"]["sha256"], digest);
+        let hash = request(&daemon, "hash", json!({ "archive": archive, "index": 2 }));
+        assert_eq!(hash["result"]["sha256"], digest);
+
+        let params = json!({ "archive": archive, "name": name, "output": "out" });
+        let extract = request(&daemon, "extract", params.clone());
+        assert_eq!(extract["result"]["bytes"], contents.len());
+        assert_eq!(std::fs::read(dir.join("out")).unwrap(), contents);
+
+        // The output is not overwritten unless asked for.
+        let extract = request(&daemon, "extract", params);
+        assert_eq!(extract["error"]["code"], HOG_ERROR);
+
+        // Nothing is written outside of the output directory.
+        let outside = dir.join("outside");
+        for output in [
+            outside.to_str().unwrap(),
+            "../outside",
+            "a/../../outside",
+            "",
+        ] {
+            let params = json!({ "archive": archive, "name": name, "output": output });
+            let extract = request(&daemon, "extract", params);
+            assert_eq!(extract["error"]["code"], INVALID_PARAMS);
+        }
+
+        #[cfg(unix)]
+        {
+            let elsewhere = dir.join("elsewhere");
+            std::fs::create_dir(&elsewhere).unwrap();
+            std::os::unix::fs::symlink(&elsewhere, dir.join("link")).unwrap();
+            let params = json!({ "archive": archive, "name": name, "output": "link/x.txt" });
+            let extract = request(&daemon, "extract", params);
+            assert_eq!(extract["error"]["code"], HOG_ERROR);
+            assert!(!elsewhere.join("x.txt").exists());
+        }
+
+        let missing = request(
+            &daemon,
+            "hash",
+            json!({ "archive": archive, "name": "nothere" }),
+        );
+        assert_eq!(missing["error"]["code"], HOG_ERROR);
+        assert_eq!(missing["error"]["data"]["error"], "EntryNotFound");
+
+        let params = request(&daemon, "hash", json!({ "archive": archive }));
+        assert_eq!(params["error"]["code"], INVALID_PARAMS);
+
+        for index in [json!(-1), json!("3"), json!(1.5)] {
+            let params = json!({ "archive": archive, "name": name, "index": index });
+            let hash = request(&daemon, "hash", params);
+            assert_eq!(hash["error"]["code"], INVALID_PARAMS);
+        }
+
+        let unknown = request(&daemon, "delete", json!({}));
+        assert_eq!(unknown["error"]["code"], METHOD_NOT_FOUND);
+
+        let parse = handle_line(&daemon, "{\"jsonrpc\":").unwrap();
+        assert_eq!(parse["error"]["code"], PARSE_ERROR);
+        assert_eq!(parse["id"], Value::Null);
+
+        let invalid = handle_line(&daemon, "{\"id\":1}").unwrap();
+        assert_eq!(invalid["error"]["code"], INVALID_REQUEST);
+
+        // Notifications get no response.
+        assert!(handle_line(&daemon, "{\"method\":\"list\"}").is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_cache() {
+        let dir = temp_dir("daemon-cache");
+        let cache = Cache::default();
+        let paths: Vec<PathBuf> = (0..=MAX_ARCHIVES)
+            .map(|i| dir.join(format!("{}.hog", i)))
+            .collect();
+
+        for path in &paths {
+            std::fs::write(path, fixture::generate(1, None).unwrap().data).unwrap();
+        }
+
+        // Opening one more than fit closes the one used longest ago.
+        let first = cache.archive(&paths[0]).unwrap();
+        for path in &paths[1..] {
+            cache.archive(&paths[0]).unwrap();
+            cache.archive(path).unwrap();
+        }
+
+        let archives = cache.archives.lock().unwrap();
+        assert_eq!(archives.len(), MAX_ARCHIVES);
+        assert!(archives.contains_key(&paths[0]));
+        assert!(!archives.contains_key(&paths[1]));
+        assert!(Arc::ptr_eq(&archives[&paths[0]].reader, &first));
+        drop(archives);
+
+        // A HOG file that changed is opened again.
+        std::fs::write(&paths[0], fixture::generate(2, None).unwrap().data).unwrap();
+        let reopened = cache.archive(&paths[0]).unwrap();
+        assert!(!Arc::ptr_eq(&reopened, &first));
+        assert_eq!(reopened.lock().unwrap().index().unwrap().entries.len(), 2);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_connection_limit() {
+        let active = Arc::new(AtomicUsize::new(0));
+        let mut connections: Vec<Connection> = (0..MAX_CONNECTIONS)
+            .map(|_| Connection::start(&active).unwrap())
+            .collect();
+
+        assert!(Connection::start(&active).is_none());
+        connections.pop();
+        assert!(Connection::start(&active).is_some());
+        drop(connections);
+        assert_eq!(active.load(Ordering::Acquire), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_bind_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temp_dir("daemon-bind");
+        let socket = dir.join("hogdump.sock");
+
+        let listener = bind_private(&socket).unwrap();
+        let mode = socket.symlink_metadata().unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(std::os::unix::net::UnixStream::connect(&socket).is_ok());
+        drop(listener);
+
+        // Nothing is left behind, and an existing file is not replaced.
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        assert!(bind_private(&socket).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_handle() {
+        let daemon = Daemon::new(Path::new("."), Limits::default());
+        let mut input = b"\n{\"id\":1,\"method\":\"nothing\"}\n".to_vec();
+        input.resize(input.len() + MAX_LINE_LEN as usize, b' ');
+        input.extend(b"\n{\"id\":2,\"method\":\"nothing\"}\n");
+
+        let mut stream = io::Cursor::new(input);
+        let mut output = Vec::new();
+        handle(ReadWrite(&mut stream, &mut output), &daemon).unwrap();
+
+        // The request after the one that was too long is not answered.
+        let output = String::from_utf8(output).unwrap();
+        let responses: Vec<Value> = output
+            .lines()
+            .map(|x| serde_json::from_str(x).unwrap())
+            .collect();
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0]["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(responses[1]["error"]["code"], INVALID_REQUEST);
+        assert_eq!(responses[1]["error"]["message"], "request too long");
+    }
+
+    // A stream reading from one buffer and writing to another.
+    struct ReadWrite<'a>(&'a mut io::Cursor<Vec<u8>>, &'a mut Vec<u8>);
+
+    impl Read for ReadWrite<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl Write for ReadWrite<'_> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.1.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,278 @@
+//
+// Copyright (c) 2022-2023 Nathaniel Houghton <nathan@brainwerk.org>
+//
+// Permission to use, copy, modify, and distribute this software for
+// any purpose with or without fee is hereby granted, provided that
+// the above copyright notice and this permission notice appear in all
+// copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL
+// WARRANTIES WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE
+// AUTHOR BE LIABLE FOR ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL
+// DAMAGES OR ANY DAMAGES WHATSOEVER RESULTING FROM LOSS OF USE, DATA
+// OR PROFITS, WHETHER IN AN ACTION OF CONTRACT, NEGLIGENCE OR OTHER
+// TORTIOUS ACTION, ARISING OUT OF OR IN CONNECTION WITH THE USE OR
+// PERFORMANCE OF THIS SOFTWARE.
+//
+
+use std::fmt;
+use std::time::Duration;
+
+// Events in a demo file, which is a stream of them.
+const EVENT_EOF: u8 = 0;
+const EVENT_START_DEMO: u8 = 1;
+const EVENT_START_FRAME: u8 = 2;
+
+// Bytes at the start of each frame: the start frame event, the length of the
+// frame before it (u16), the frame number (i32) and how long the frame lasts
+// (a 16.16 fixed point number of seconds).
+const FRAME_HEADER_LEN: usize = 11;
+
+// The newest demo version written by any version of the game.
+const MAX_DEMO_VERSION: u8 = 16;
+
+/// Bytes of the start of a demo file that parse_demo() needs.
+pub const DEMO_HEAD_LEN: usize = 3;
+
+/// Bytes of the end of a demo file that parse_demo() needs.
+pub const DEMO_TAIL_LEN: usize = 2;
+
+/// Largest demo file that is read whole to find its duration (with
+/// demo_duration()).
+pub const MAX_DEMO_LEN: u64 = 16 * 1024 * 1024;
+
+/// What can be told about a demo recording (.dem) without playing it back.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DemoInfo {
+    /// Demo format version. Descent 1 wrote up to version 13, Descent 2
+    /// versions 15 and up.
+    pub version: u8,
+
+    /// The game that recorded the demo: 1 for the Descent 1 shareware
+    /// version, 2 for the registered version, 3 for Descent 2.
+    pub game_type: u8,
+
+    /// The level the recording ends on, if the demo was finished properly
+    /// (negative for secret levels).
+    pub level: Option<i8>,
+
+    /// How long the recording takes to play back, if it was found (see
+    /// demo_duration()).
+    pub duration: Option<Duration>,
+}
+
+impl DemoInfo {
+    /// Returns the name of the game that recorded the demo.
+    pub fn game(&self) -> &'static str {
+        match self.game_type {
+            1 => "Descent 1 shareware",
+            2 => "Descent 1",
+            3 => "Descent 2",
+            _ => "unknown game",
+        }
+    }
+}
+
+impl fmt::Display for DemoInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} demo (version {})", self.game(), self.version)?;
+
+        match self.level {
+            Some(level) if level < 0 => write!(f, ", ends on secret level {}", -i16::from(level)),
+            Some(level) => write!(f, ", ends on level {}", level),
+            None => write!(f, ", not finished"),
+        }?;
+
+        if let Some(duration) = self.duration {
+            let secs = duration.as_secs();
+
+            write!(f, ", lasts {}:{:02}", secs / 60, secs % 60)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses what is known about a demo from "head" and "tail", the first
+/// DEMO_HEAD_LEN and last DEMO_TAIL_LEN bytes of it. Returns None if it does
+/// not look like a demo.
+///
+/// The duration of the demo is left out, since it can only be found by going
+/// through every frame of it (with demo_duration()).
+pub fn parse_demo(head: &[u8], tail: &[u8]) -> Option<DemoInfo> {
+    let [EVENT_START_DEMO, version, game_type] = *head.get(..DEMO_HEAD_LEN)? else {
+        return None;
+    };
+
+    if version == 0 || version > MAX_DEMO_VERSION || !(1..=3).contains(&game_type) {
+        return None;
+    }
+
+    // A finished demo ends with the level number, followed by an end of
+    // file event.
+    let level = match *tail {
+        [.., level, EVENT_EOF] => Some(level as i8),
+        _ => None,
+    };
+
+    Some(DemoInfo {
+        version,
+        game_type,
+        level,
+        duration: None,
+    })
+}
+
+/// Returns how long the demo "data" (all of it) takes to play back, which is
+/// the time of all of its frames added up. Returns None if the demo was not
+/// finished properly, or its frames cannot be followed.
+///
+/// Each frame starts with the length of the frame before it, so the frames are
+/// followed backwards from the end of the demo, like the game does when
+/// rewinding.
+pub fn demo_duration(data: &[u8]) -> Option<Duration> {
+    let u16_at = |pos: usize| {
+        let bytes = data.get(pos..pos.checked_add(2)?)?;
+
+        Some(usize::from(u16::from_le_bytes([bytes[0], bytes[1]])))
+    };
+    let i32_at = |bytes: &[u8]| i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+
+    // A finished demo ends with an end of file event, followed by the length
+    // of the last frame, the state of the player, the number of bytes since
+    // the event (u16), the level number and another end of file event.
+    let trailer_pos = data.len().checked_sub(4)?;
+    let eof_pos = trailer_pos.checked_sub(u16_at(trailer_pos)? + 1)?;
+
+    if data[eof_pos] != EVENT_EOF {
+        return None;
+    }
+
+    let mut frame_pos = eof_pos.checked_sub(u16_at(eof_pos + 1)?)?;
+    let mut total = 0u64;
+    let mut number = None;
+
+    // The frame before the first one is whatever the length of it points at,
+    // which does not have the number of the frame before.
+    while let Some(header) = data.get(frame_pos..frame_pos + FRAME_HEADER_LEN) {
+        let frame_number = i32_at(&header[3..]);
+        let time = i32_at(&header[7..]);
+
+        if header[0] != EVENT_START_FRAME
+            || number.is_some_and(|x| frame_number.checked_add(1) != Some(x))
+        {
+            break;
+        }
+
+        total += u64::try_from(time).ok()?;
+        number = Some(frame_number);
+
+        match frame_pos.checked_sub(u16_at(frame_pos + 1)?) {
+            Some(x) if x < frame_pos => frame_pos = x,
+            _ => break,
+        }
+    }
+
+    number?;
+
+    Some(Duration::from_nanos(
+        (u128::from(total) * 1_000_000_000 / 65536) as u64,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_demo() {
+        let d2 = parse_demo(&[1, 15, 3], &[7, 0]).unwrap();
+        assert_eq!(
+            d2,
+            DemoInfo {
+                version: 15,
+                game_type: 3,
+                level: Some(7),
+                duration: None,
+            }
+        );
+        assert_eq!(
+            d2.to_string(),
+            "Descent 2 demo (version 15), ends on level 7"
+        );
+
+        let d1 = parse_demo(&[1, 13, 2, 0xff], &[0xfe, 0]).unwrap();
+        assert_eq!(d1.game(), "Descent 1");
+        assert_eq!(
+            d1.to_string(),
+            "Descent 1 demo (version 13), ends on secret level 2"
+        );
+
+        assert_eq!(parse_demo(&[1, 5, 1], &[3, 9]).unwrap().level, None);
+        assert_eq!(parse_demo(&[1, 5, 1], &[0]).unwrap().level, None);
+
+        assert_eq!(parse_demo(&[2, 15, 3], &[7, 0]), None);
+        assert_eq!(parse_demo(&[1, 40, 3], &[7, 0]), None);
+        assert_eq!(parse_demo(&[1, 15, 4], &[7, 0]), None);
+        assert_eq!(parse_demo(&[1, 15], &[7, 0]), None);
+    }
+
+    // Returns a finished demo with a frame lasting each of "times" (in 1/65536
+    // seconds).
+    fn demo(times: &[i32]) -> Vec<u8> {
+        let mut data = vec![EVENT_START_DEMO, 15, 3];
+        let mut frame_pos = 0;
+
+        for (number, time) in times.iter().enumerate() {
+            let pos = data.len();
+
+            data.push(EVENT_START_FRAME);
+            data.extend(((pos - frame_pos) as u16).to_le_bytes());
+            data.extend((number as i32).to_le_bytes());
+            data.extend(time.to_le_bytes());
+            data.extend([5; 20]);
+
+            frame_pos = pos;
+        }
+
+        let eof_pos = data.len();
+        data.push(EVENT_EOF);
+        data.extend(((eof_pos - frame_pos) as u16).to_le_bytes());
+        data.extend([9; 8]);
+        data.extend(10u16.to_le_bytes());
+        data.extend([4, EVENT_EOF]);
+
+        data
+    }
+
+    #[test]
+    fn test_demo_duration() {
+        let data = demo(&[65536 * 60, 65536 * 64, 32768]);
+        let duration = demo_duration(&data).unwrap();
+        assert_eq!(duration, Duration::from_millis(124_500));
+
+        let info = DemoInfo {
+            duration: Some(duration),
+            ..parse_demo(&data, &data).unwrap()
+        };
+        assert_eq!(
+            info.to_string(),
+            "Descent 2 demo (version 15), ends on level 4, lasts 2:04"
+        );
+
+        assert_eq!(demo_duration(&demo(&[65536])), Some(Duration::from_secs(1)));
+
+        // Demos that were not finished, or with frames that cannot be
+        // followed.
+        assert_eq!(demo_duration(&demo(&[])), None);
+        assert_eq!(demo_duration(&data[..data.len() - 1]), None);
+        assert_eq!(demo_duration(&demo(&[65536, -1])), None);
+
+        let mut data = data;
+        let len = data.len();
+        data[len - 4] = 12;
+        assert_eq!(demo_duration(&data), None);
+        assert_eq!(demo_duration(&[]), None);
+    }
+}
@@ -0,0 +1,438 @@
+//
+// Copyright (c) 2022-2023 Nathaniel Houghton <nathan@brainwerk.org>
+//
+// Permission to use, copy, modify, and distribute this software for
+// any purpose with or without fee is hereby granted, provided that
+// the above copyright notice and this permission notice appear in all
+// copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL
+// WARRANTIES WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE
+// AUTHOR BE LIABLE FOR ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL
+// DAMAGES OR ANY DAMAGES WHATSOEVER RESULTING FROM LOSS OF USE, DATA
+// OR PROFITS, WHETHER IN AN ACTION OF CONTRACT, NEGLIGENCE OR OTHER
+// TORTIOUS ACTION, ARISING OUT OF OR IN CONNECTION WITH THE USE OR
+// PERFORMANCE OF THIS SOFTWARE.
+//
+
+use std::collections::HashSet;
+use std::io::{Read, Seek};
+
+use crate::error::HogError;
+use crate::hog::HogFileReader;
+use crate::index::{HogIndex, IndexEntry};
+use crate::sniff::{self, ContentType};
+
+/// Number of unchanged lines shown around the changes in a text diff.
+pub const CONTEXT_LINES: usize = 3;
+
+// Largest number of line pairs text_diff() compares (after leaving out the
+// lines both texts start and end with), to keep the diff of large, very
+// different texts from taking too long.
+const MAX_DIFF_CELLS: usize = 16 * 1024 * 1024;
+
+/// How a file differs between two HOG files.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Change {
+    /// Only in the new HOG file.
+    Added,
+    /// Only in the old HOG file.
+    Removed,
+    /// In both HOG files, with different contents.
+    Changed,
+}
+
+impl Change {
+    /// Returns the name of the change, as used in the output.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Change::Added => "added",
+            Change::Removed => "removed",
+            Change::Changed => "changed",
+        }
+    }
+}
+
+/// A file that differs between two HOG files.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct EntryDiff {
+    pub name: String,
+    pub change: Change,
+
+    /// Length of the file in the old HOG file (None if it was added).
+    pub old_length: Option<u32>,
+
+    /// Length of the file in the new HOG file (None if it was removed).
+    pub new_length: Option<u32>,
+
+    /// How the contents differ, for changed files (if asked for).
+    pub content: Option<ContentDiff>,
+}
+
+/// How the contents of a changed file differ.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ContentDiff {
+    /// Offset of the first byte that differs (or where the shorter file
+    /// ends).
+    pub first_offset: u64,
+
+    /// Number of bytes that differ at the same offset, plus the difference
+    /// in length.
+    pub differing_bytes: u64,
+
+    /// A unified diff of the text, if both files are text (TXB files are
+    /// decoded first), and it is not too large to work out.
+    pub text_diff: Option<String>,
+}
+
+impl ContentDiff {
+    /// Compares the contents "old" and "new" of a file named "name".
+    pub fn new(name: &str, old: &[u8], new: &[u8]) -> Self {
+        let same = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+        let differing = old.iter().zip(new).filter(|(a, b)| a != b).count();
+        let differing_bytes = differing as u64 + old.len().abs_diff(new.len()) as u64;
+
+        let text = |data: &[u8], content_type| match content_type {
+            ContentType::Txb => Some(
+                data.iter()
+                    .map(|&x| sniff::decode_txb_byte(x))
+                    .collect::<Vec<u8>>(),
+            ),
+            ContentType::Text => Some(data.to_vec()),
+            _ => None,
+        };
+
+        let old_type = sniff::sniff(&old[..old.len().min(sniff::SNIFF_LEN)]);
+        let new_type = sniff::sniff(&new[..new.len().min(sniff::SNIFF_LEN)]);
+
+        let text_diff = match (text(old, old_type), text(new, new_type)) {
+            (Some(old), Some(new)) if old_type == new_type => text_diff(
+                &format!("a/{}", name),
+                &format!("b/{}", name),
+                &String::from_utf8_lossy(&old),
+                &String::from_utf8_lossy(&new),
+            ),
+            _ => None,
+        };
+
+        Self {
+            first_offset: same as u64,
+            differing_bytes,
+            text_diff,
+        }
+    }
+}
+
+// A line of a text diff.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Edit {
+    Same,
+    Delete,
+    Insert,
+}
+
+// Returns the edits turning "old" into "new", keeping the longest common
+// subsequence of lines. Returns None if the texts are too large to compare.
+fn edits(old: &[&str], new: &[&str]) -> Option<Vec<Edit>> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+
+    if (a.len() + 1).saturating_mul(b.len() + 1) > MAX_DIFF_CELLS {
+        return None;
+    }
+
+    // lcs[i][j] is the length of the longest common subsequence of a[i..]
+    // and b[j..].
+    let width = b.len() + 1;
+    let mut lcs = vec![0u32; (a.len() + 1) * width];
+
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i * width + j] = if a[i] == b[j] {
+                lcs[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut edits = vec![Edit::Same; prefix];
+    let (mut i, mut j) = (0, 0);
+
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            edits.push(Edit::Same);
+            i += 1;
+            j += 1;
+        } else if i < a.len()
+            && (j == b.len() || lcs[(i + 1) * width + j] >= lcs[i * width + j + 1])
+        {
+            edits.push(Edit::Delete);
+            i += 1;
+        } else {
+            edits.push(Edit::Insert);
+            j += 1;
+        }
+    }
+
+    edits.resize(edits.len() + suffix, Edit::Same);
+
+    Some(edits)
+}
+
+// Returns the start and the number of lines of a hunk, as written in its
+// header ("start" is 0 based, and the line before the hunk is given when it
+// has no lines).
+fn hunk_range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, count),
+    }
+}
+
+/// Returns a unified diff (like `diff -u` writes) of the texts "old" and
+/// "new", with CONTEXT_LINES of context. Returns an empty string if they have
+/// the same lines, and None if they are too large to compare.
+pub fn text_diff(old_label: &str, new_label: &str, old: &str, new: &str) -> Option<String> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let edits = edits(&old, &new)?;
+
+    // The position in both texts before each edit.
+    let mut positions = Vec::with_capacity(edits.len() + 1);
+    let (mut i, mut j) = (0, 0);
+
+    for edit in &edits {
+        positions.push((i, j));
+
+        match edit {
+            Edit::Same => {
+                i += 1;
+                j += 1;
+            }
+            Edit::Delete => i += 1,
+            Edit::Insert => j += 1,
+        }
+    }
+
+    positions.push((i, j));
+
+    let changes: Vec<usize> = (0..edits.len())
+        .filter(|&x| edits[x] != Edit::Same)
+        .collect();
+    let mut diff = String::new();
+
+    if changes.is_empty() {
+        return Some(diff);
+    }
+
+    diff.push_str(&format!("--- {}\n+++ {}\n", old_label, new_label));
+
+    // Changes closer than twice the context share a hunk.
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+
+    for &x in &changes {
+        match hunks.last_mut() {
+            Some(hunk) if x - hunk.1 <= 2 * CONTEXT_LINES + 1 => hunk.1 = x,
+            _ => hunks.push((x, x)),
+        }
+    }
+
+    for (first, last) in hunks {
+        let start = first.saturating_sub(CONTEXT_LINES);
+        let end = (last + CONTEXT_LINES + 1).min(edits.len());
+        let (old_start, new_start) = positions[start];
+        let (old_end, new_end) = positions[end];
+
+        diff.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(old_start, old_end - old_start),
+            hunk_range(new_start, new_end - new_start)
+        ));
+
+        for x in start..end {
+            let (i, j) = positions[x];
+            let line = match edits[x] {
+                Edit::Same => format!(" {}\n", old[i]),
+                Edit::Delete => format!("-{}\n", old[i]),
+                Edit::Insert => format!("+{}\n", new[j]),
+            };
+
+            diff.push_str(&line);
+        }
+    }
+
+    Some(diff)
+}
+
+// Returns the files of an index, leaving out any file with the same name
+// (compared without regard to case) as an earlier one, which cannot be
+// looked up by name.
+fn unique_entries(index: &HogIndex) -> Vec<&IndexEntry> {
+    let mut seen = HashSet::new();
+
+    index
+        .entries
+        .iter()
+        .filter(|x| seen.insert(x.name.to_ascii_lowercase()))
+        .collect()
+}
+
+/// Compares the files of two HOG files by name (without regard to case),
+/// returning those that were added, removed or changed going from "old" to
+/// "new": first the removed and changed files, in the order of "old", then
+/// the added files, in the order of "new". With "content", how the contents
+/// of changed files differ is worked out as well.
+pub fn diff_archives<R: Read + Seek, S: Read + Seek>(
+    old: &mut HogFileReader<R>,
+    new: &mut HogFileReader<S>,
+    content: bool,
+) -> Result<Vec<EntryDiff>, HogError> {
+    let old_index = HogIndex::build(old)?;
+    let new_index = HogIndex::build(new)?;
+    let mut diffs = Vec::new();
+
+    for old_entry in unique_entries(&old_index) {
+        let new_entry = match new_index.find(&old_entry.name) {
+            Some(x) => x,
+            None => {
+                diffs.push(EntryDiff {
+                    name: old_entry.name.clone(),
+                    change: Change::Removed,
+                    old_length: Some(old_entry.length),
+                    new_length: None,
+                    content: None,
+                });
+
+                continue;
+            }
+        };
+
+        // Files of different lengths are changed without reading them,
+        // unless their contents are compared anyway.
+        let data = if old_entry.length == new_entry.length || content {
+            Some((old.read_entry(old_entry)?, new.read_entry(new_entry)?))
+        } else {
+            None
+        };
+
+        if data.as_ref().is_some_and(|(a, b)| a == b) {
+            continue;
+        }
+
+        diffs.push(EntryDiff {
+            name: old_entry.name.clone(),
+            change: Change::Changed,
+            old_length: Some(old_entry.length),
+            new_length: Some(new_entry.length),
+            content: data
+                .filter(|_| content)
+                .map(|(a, b)| ContentDiff::new(&old_entry.name, &a, &b)),
+        });
+    }
+
+    for new_entry in unique_entries(&new_index) {
+        if old_index.find(&new_entry.name).is_none() {
+            diffs.push(EntryDiff {
+                name: new_entry.name.clone(),
+                change: Change::Added,
+                old_length: None,
+                new_length: Some(new_entry.length),
+                content: None,
+            });
+        }
+    }
+
+    Ok(diffs)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    fn hog_data(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut data = b"DHF".to_vec();
+
+        for (name, contents) in files {
+            let mut field = [0; 13];
+            field[..name.len()].copy_from_slice(name.as_bytes());
+
+            data.extend(field);
+            data.extend((contents.len() as u32).to_le_bytes());
+            data.extend(*contents);
+        }
+
+        data
+    }
+
+    #[test]
+    fn test_text_diff() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\n";
+
+        assert_eq!(text_diff("old", "new", old, old).unwrap(), "");
+        assert_eq!(
+            text_diff("old", "new", old, new).unwrap(),
+            "--- old\n+++ new\n\
+             @@ -1,5 +1,5 @@\n a\n-b\n+B\n c\n d\n e\n\
+             @@ -8,3 +8,4 @@\n h\n i\n j\n+k\n"
+        );
+        assert_eq!(
+            text_diff("old", "new", "", "x\n").unwrap(),
+            "--- old\n+++ new\n@@ -0,0 +1 @@\n+x\n"
+        );
+    }
+
+    #[test]
+    fn test_content_diff() {
+        let diff = ContentDiff::new("a.bin", b"\x00\x01\x02\x03", b"\x00\x09\x02\x08\x04");
+
+        assert_eq!(diff.first_offset, 1);
+        assert_eq!(diff.differing_bytes, 3);
+        assert_eq!(diff.text_diff, None);
+
+        let diff = ContentDiff::new("a.txt", b"one\ntwo\n", b"one\n2\n");
+
+        assert_eq!(diff.first_offset, 4);
+        assert_eq!(
+            diff.text_diff.unwrap(),
+            "--- a/a.txt\n+++ b/a.txt\n@@ -1,2 +1,2 @@\n one\n-two\n+2\n"
+        );
+    }
+
+    #[test]
+    fn test_diff_archives() {
+        let old = hog_data(&[("a.txt", b"same"), ("b.txt", b"old"), ("c.txt", b"gone")]);
+        let new = hog_data(&[("A.TXT", b"same"), ("new.txt", b"!"), ("b.txt", b"new")]);
+        let mut old = HogFileReader::from_reader(Cursor::new(old)).unwrap();
+        let mut new = HogFileReader::from_reader(Cursor::new(new)).unwrap();
+
+        let diffs = diff_archives(&mut old, &mut new, false).unwrap();
+        let changes: Vec<_> = diffs.iter().map(|x| (x.name.as_str(), x.change)).collect();
+
+        assert_eq!(
+            changes,
+            [
+                ("b.txt", Change::Changed),
+                ("c.txt", Change::Removed),
+                ("new.txt", Change::Added)
+            ]
+        );
+        assert_eq!(diffs[0].content, None);
+
+        let diffs = diff_archives(&mut old, &mut new, true).unwrap();
+
+        assert_eq!(diffs[0].content.as_ref().unwrap().differing_bytes, 3);
+    }
+}
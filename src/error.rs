@@ -19,6 +19,31 @@
 use std::error::Error;
 use std::fmt;
 use std::io::{self};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::fixture::MAX_ENTRIES;
+use crate::hog::{sanitize_name, EscapedName};
+
+/// Broad classes of HogError, for handling errors without matching every
+/// variant (more of which are added over time).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HogErrorKind {
+    /// Reading or writing a file failed.
+    Io,
+    /// A HOG file (or another input file, such as a manifest or level) is
+    /// damaged, truncated, or is not what it was expected to be.
+    Corrupt,
+    /// What was asked for cannot be done with HOG files, such as storing a
+    /// file that is too large or has a name that is too long.
+    Unsupported,
+    /// What was asked for was refused by a rule that was chosen, such as the
+    /// name policy or a data limit.
+    Policy,
+    /// What was asked for does not make sense for the given files, such as
+    /// extracting a file that is not in the HOG file.
+    Usage,
+}
 
 #[derive(Debug)]
 pub enum HogError {
@@ -28,6 +53,7 @@ pub enum HogError {
     SignatureReadFailure(io::Error),
     SignatureWriteFailure(io::Error),
     InvalidSignature,
+    TooShort(u64),
     ReadHeaderError(io::Error),
     UnexpectedEof,
     InvalidFilename,
@@ -36,7 +62,183 @@ pub enum HogError {
     SeekFailure(io::Error),
     HogFilenameTooLong,
     FileTooLarge(u64),
+    EmptyFile,
     BadHogFilename(String),
+    OutputFailure(io::Error),
+    OffsetOutOfRange(u64, u64),
+    EntryNotFound(String),
+    NoCurrentFile,
+    DuplicateHogFilename(String, String),
+    InvalidDosFilename(String),
+    UnsafeName(String),
+    NameNotAllowed(String, String),
+    DataLimitExceeded(u64, u64),
+    ReadFailure(io::Error),
+    InvalidManifest(String),
+    NoHogInZip,
+    InvalidLevel(String),
+    InvalidMission(String),
+    InvalidPig(String),
+    InvalidImage(String),
+    InvalidPog(String),
+    InvalidHam(String),
+    LimitExceeded(LimitExceeded),
+    ListenFailure(io::Error),
+    SelfCheckFailed(String),
+    IndexOutOfRange(u64, u64),
+    CaseCollision(String, String),
+    DigestMismatch(String),
+    NotMappable,
+    InvalidIndex(String),
+    InvalidListing(String),
+    QuarantineFailure(io::Error),
+    InvalidTemplate(String),
+    InvalidCommand(String),
+    FilterFailure(io::Error),
+    FilterFailed(String, String),
+    TooManyFixtureFiles(u32),
+}
+
+impl HogError {
+    /// Returns the name of the error, which stays the same across releases
+    /// (unlike the error message).
+    pub fn name(&self) -> &'static str {
+        match self {
+            HogError::OpenHogFailure(_) => "OpenHogFailure",
+            HogError::OpenOutputFailure(_) => "OpenOutputFailure",
+            HogError::OpenInputFailure(_) => "OpenInputFailure",
+            HogError::SignatureReadFailure(_) => "SignatureReadFailure",
+            HogError::SignatureWriteFailure(_) => "SignatureWriteFailure",
+            HogError::InvalidSignature => "InvalidSignature",
+            HogError::TooShort(_) => "TooShort",
+            HogError::ReadHeaderError(_) => "ReadHeaderError",
+            HogError::UnexpectedEof => "UnexpectedEof",
+            HogError::InvalidFilename => "InvalidFilename",
+            HogError::ExtractFailure(_) => "ExtractFailure",
+            HogError::AppendToHogFailure(_) => "AppendToHogFailure",
+            HogError::SeekFailure(_) => "SeekFailure",
+            HogError::HogFilenameTooLong => "HogFilenameTooLong",
+            HogError::FileTooLarge(_) => "FileTooLarge",
+            HogError::EmptyFile => "EmptyFile",
+            HogError::BadHogFilename(_) => "BadHogFilename",
+            HogError::OutputFailure(_) => "OutputFailure",
+            HogError::OffsetOutOfRange(_, _) => "OffsetOutOfRange",
+            HogError::EntryNotFound(_) => "EntryNotFound",
+            HogError::NoCurrentFile => "NoCurrentFile",
+            HogError::DuplicateHogFilename(_, _) => "DuplicateHogFilename",
+            HogError::InvalidDosFilename(_) => "InvalidDosFilename",
+            HogError::UnsafeName(_) => "UnsafeName",
+            HogError::NameNotAllowed(_, _) => "NameNotAllowed",
+            HogError::DataLimitExceeded(_, _) => "DataLimitExceeded",
+            HogError::ReadFailure(_) => "ReadFailure",
+            HogError::InvalidManifest(_) => "InvalidManifest",
+            HogError::NoHogInZip => "NoHogInZip",
+            HogError::InvalidLevel(_) => "InvalidLevel",
+            HogError::InvalidMission(_) => "InvalidMission",
+            HogError::InvalidPig(_) => "InvalidPig",
+            HogError::InvalidImage(_) => "InvalidImage",
+            HogError::InvalidPog(_) => "InvalidPog",
+            HogError::InvalidHam(_) => "InvalidHam",
+            HogError::LimitExceeded(_) => "LimitExceeded",
+            HogError::ListenFailure(_) => "ListenFailure",
+            HogError::SelfCheckFailed(_) => "SelfCheckFailed",
+            HogError::IndexOutOfRange(_, _) => "IndexOutOfRange",
+            HogError::CaseCollision(_, _) => "CaseCollision",
+            HogError::DigestMismatch(_) => "DigestMismatch",
+            HogError::NotMappable => "NotMappable",
+            HogError::InvalidIndex(_) => "InvalidIndex",
+            HogError::InvalidListing(_) => "InvalidListing",
+            HogError::QuarantineFailure(_) => "QuarantineFailure",
+            HogError::InvalidTemplate(_) => "InvalidTemplate",
+            HogError::InvalidCommand(_) => "InvalidCommand",
+            HogError::FilterFailure(_) => "FilterFailure",
+            HogError::FilterFailed(_, _) => "FilterFailed",
+            HogError::TooManyFixtureFiles(_) => "TooManyFixtureFiles",
+        }
+    }
+
+    /// Returns the class of the error.
+    ///
+    /// I/O errors saying that the data ended early or was invalid (such as
+    /// when decompressing a HOG file) are Corrupt rather than Io, since it is
+    /// the file that is at fault rather than the reading.
+    pub fn kind(&self) -> HogErrorKind {
+        match self {
+            HogError::OpenHogFailure(e)
+            | HogError::OpenOutputFailure(e)
+            | HogError::OpenInputFailure(e)
+            | HogError::SignatureReadFailure(e)
+            | HogError::SignatureWriteFailure(e)
+            | HogError::ReadHeaderError(e)
+            | HogError::ExtractFailure(e)
+            | HogError::AppendToHogFailure(e)
+            | HogError::SeekFailure(e)
+            | HogError::OutputFailure(e)
+            | HogError::ReadFailure(e)
+            | HogError::ListenFailure(e)
+            | HogError::QuarantineFailure(e)
+            | HogError::FilterFailure(e) => match e.kind() {
+                io::ErrorKind::UnexpectedEof | io::ErrorKind::InvalidData => HogErrorKind::Corrupt,
+                _ => HogErrorKind::Io,
+            },
+            HogError::InvalidSignature
+            | HogError::TooShort(_)
+            | HogError::UnexpectedEof
+            | HogError::InvalidFilename
+            | HogError::InvalidManifest(_)
+            | HogError::InvalidLevel(_)
+            | HogError::InvalidPig(_)
+            | HogError::InvalidImage(_)
+            | HogError::InvalidPog(_)
+            | HogError::InvalidHam(_)
+            | HogError::SelfCheckFailed(_)
+            | HogError::DigestMismatch(_)
+            | HogError::InvalidIndex(_)
+            | HogError::InvalidListing(_) => HogErrorKind::Corrupt,
+            HogError::HogFilenameTooLong
+            | HogError::FileTooLarge(_)
+            | HogError::NoHogInZip
+            | HogError::NotMappable => HogErrorKind::Unsupported,
+            HogError::EmptyFile
+            | HogError::DuplicateHogFilename(_, _)
+            | HogError::InvalidDosFilename(_)
+            | HogError::UnsafeName(_)
+            | HogError::NameNotAllowed(_, _)
+            | HogError::DataLimitExceeded(_, _)
+            | HogError::InvalidMission(_)
+            | HogError::CaseCollision(_, _)
+            | HogError::LimitExceeded(_) => HogErrorKind::Policy,
+            HogError::BadHogFilename(_)
+            | HogError::OffsetOutOfRange(_, _)
+            | HogError::EntryNotFound(_)
+            | HogError::NoCurrentFile
+            | HogError::IndexOutOfRange(_, _)
+            | HogError::InvalidTemplate(_)
+            | HogError::InvalidCommand(_)
+            | HogError::FilterFailed(_, _)
+            | HogError::TooManyFixtureFiles(_) => HogErrorKind::Usage,
+        }
+    }
+
+    /// Converts an I/O error into a HogError with "wrap" (such as
+    /// HogError::OpenHogFailure), unless it is because a limit was reached
+    /// (see util::LimitedWriter), which is a HogError::LimitExceeded instead.
+    pub fn from_io(e: io::Error, wrap: impl FnOnce(io::Error) -> HogError) -> HogError {
+        match e.get_ref().and_then(|x| x.downcast_ref::<LimitExceeded>()) {
+            Some(limit) => HogError::LimitExceeded(*limit),
+            None => wrap(e),
+        }
+    }
+
+    /// Returns true if the error is because of a damaged or invalid file.
+    pub fn is_corrupt(&self) -> bool {
+        self.kind() == HogErrorKind::Corrupt
+    }
+
+    /// Returns true if the error is because reading or writing a file failed.
+    pub fn is_io(&self) -> bool {
+        self.kind() == HogErrorKind::Io
+    }
 }
 
 impl Error for HogError {}
@@ -50,6 +252,12 @@ impl fmt::Display for HogError {
             HogError::SignatureReadFailure(e) => write!(f, "reading HOG signature failed: {}", e),
             HogError::SignatureWriteFailure(e) => write!(f, "writing HOG signature failed: {}", e),
             HogError::InvalidSignature => write!(f, "file did not have correct HOG signature"),
+            HogError::TooShort(len) => write!(
+                f,
+                "file is {} bytes long, too short to be a HOG file (even an empty HOG file has \
+                 the 3 byte signature)",
+                len
+            ),
             HogError::ReadHeaderError(e) => write!(f, "reading HOG record header failed: {}", e),
             HogError::UnexpectedEof => write!(f, "unexpected end of file encountered"),
             HogError::InvalidFilename => write!(f, "invalid filename found in HOG record header"),
@@ -62,12 +270,316 @@ impl fmt::Display for HogError {
             ),
             HogError::FileTooLarge(len) => write!(
                 f,
-                "file of {} bytes cannot be stored in HOG (it is too large)",
-                len
+                "file of {} bytes cannot be stored in HOG (files are limited to {} bytes)",
+                len,
+                u32::MAX
             ),
+            HogError::EmptyFile => write!(f, "empty files are not allowed in this HOG file"),
             HogError::BadHogFilename(name) => {
                 write!(f, "could not find filename basename of file: {}", name)
             }
+            HogError::OutputFailure(e) => write!(f, "failed to write output: {}", e),
+            HogError::OffsetOutOfRange(offset, len) => write!(
+                f,
+                "offset {} is past the end of the file ({} bytes)",
+                offset, len
+            ),
+            HogError::EntryNotFound(name) => write!(f, "no file named \"{}\" in HOG file", name),
+            HogError::NoCurrentFile => write!(
+                f,
+                "no file to read: the records were not read up to one, or it was read already"
+            ),
+            HogError::DuplicateHogFilename(name, other) => write!(
+                f,
+                "filename \"{}\" is already used by \"{}\" in HOG file",
+                name, other
+            ),
+            HogError::InvalidDosFilename(name) => {
+                write!(f, "filename \"{}\" is not a valid DOS (8.3) filename", name)
+            }
+            HogError::UnsafeName(name) => write!(
+                f,
+                "filename \"{}\" is not a plain filename (it is a path, or empty)",
+                EscapedName(Path::new(name))
+            ),
+            HogError::NameNotAllowed(name, reason) => {
+                write!(f, "filename \"{}\" is not allowed: {}", name, reason)
+            }
+            HogError::DataLimitExceeded(len, max_len) => write!(
+                f,
+                "file of {} bytes is larger than the {} byte limit for reading it into memory",
+                len, max_len
+            ),
+            HogError::ReadFailure(e) => write!(f, "failed to read file from HOG: {}", e),
+            HogError::InvalidManifest(msg) => write!(f, "invalid manifest: {}", msg),
+            HogError::NoHogInZip => write!(f, "ZIP archive does not contain any HOG files"),
+            HogError::InvalidLevel(msg) => write!(f, "invalid level file: {}", msg),
+            HogError::InvalidMission(msg) => write!(f, "invalid mission: {}", msg),
+            HogError::InvalidPig(msg) => write!(f, "invalid PIG file: {}", msg),
+            HogError::InvalidImage(msg) => write!(f, "invalid image: {}", msg),
+            HogError::InvalidPog(msg) => write!(f, "invalid POG file: {}", msg),
+            HogError::InvalidHam(msg) => write!(f, "invalid HAM file: {}", msg),
+            HogError::LimitExceeded(limit) => write!(f, "{}", limit),
+            HogError::ListenFailure(e) => write!(f, "failed to listen for connections: {}", e),
+            HogError::SelfCheckFailed(msg) => write!(f, "self-check failed: {}", msg),
+            HogError::IndexOutOfRange(index, count) => write!(
+                f,
+                "no file at index {} in HOG file (it contains {} files)",
+                index, count
+            ),
+            HogError::CaseCollision(name, earlier) => write!(
+                f,
+                "\"{}\" and \"{}\" are the same file on case insensitive filesystems",
+                EscapedName(Path::new(name)),
+                EscapedName(Path::new(earlier))
+            ),
+            HogError::DigestMismatch(name) => write!(
+                f,
+                "contents of \"{}\" do not match the expected SHA-256 digest",
+                EscapedName(Path::new(name))
+            ),
+            HogError::NotMappable => write!(
+                f,
+                "HOG file is compressed, so the files in it have no offsets to map"
+            ),
+            HogError::InvalidIndex(msg) => write!(f, "invalid index map: {}", msg),
+            HogError::InvalidListing(msg) => write!(f, "invalid listing: {}", msg),
+            HogError::QuarantineFailure(e) => {
+                write!(f, "failed to put HOG file into quarantine: {}", e)
+            }
+            HogError::InvalidTemplate(msg) => write!(f, "invalid output template: {}", msg),
+            HogError::InvalidCommand(msg) => write!(f, "invalid filter command: {}", msg),
+            HogError::FilterFailure(e) => write!(f, "failed to run filter command: {}", e),
+            HogError::FilterFailed(name, status) => write!(
+                f,
+                "filter command failed for \"{}\": {}",
+                EscapedName(Path::new(name)),
+                status
+            ),
+            HogError::TooManyFixtureFiles(count) => write!(
+                f,
+                "a fixture can have at most {} files, not {}",
+                MAX_ENTRIES, count
+            ),
         }
     }
 }
+
+/// A limit on converting something (see util::Limits) that was reached.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LimitExceeded {
+    /// The output would be larger than this many bytes.
+    Size(u64),
+    /// It took longer than this.
+    Time(Duration),
+}
+
+impl Error for LimitExceeded {}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LimitExceeded::Size(len) => {
+                write!(f, "output is larger than the limit of {} bytes", len)
+            }
+            LimitExceeded::Time(time) => write!(
+                f,
+                "took longer than the limit of {} seconds",
+                time.as_secs_f64()
+            ),
+        }
+    }
+}
+
+/// An error encoding or decoding the filename field of a HOG record header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameError {
+    /// The name (in bytes) does not fit in the field along with its NUL
+    /// terminator.
+    TooLong(usize),
+    /// The name contains a NUL byte, which would end it early.
+    ContainsNul,
+    /// The stored name is not valid UTF-8.
+    InvalidUtf8,
+}
+
+impl Error for NameError {}
+
+impl fmt::Display for NameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NameError::TooLong(len) => write!(
+                f,
+                "name is {} bytes long, HOG filenames must be shorter than 13 bytes",
+                len
+            ),
+            NameError::ContainsNul => write!(f, "name contains a NUL byte"),
+            NameError::InvalidUtf8 => write!(f, "name is not valid UTF-8"),
+        }
+    }
+}
+
+/// A problem found in a HOG file that does not stop it from being read, but
+/// that may mean the HOG file was not written the way the game expects.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HogWarning {
+    /// The filename of a file is not a DOS 8.3 filename, so the game may not
+    /// be able to load it.
+    SuspiciousName(PathBuf),
+    /// The filename of a file is not a plain filename (see
+    /// hog::is_plain_name()), so it is changed when extracting it.
+    UnsafeName(PathBuf),
+    /// A file is empty.
+    EmptyFile(PathBuf),
+    /// The HOG file ends with this many bytes after the last file, too few to
+    /// be a record header.
+    TrailingBytes(u64),
+    /// The signature was found at this offset, as this (such as "dhf"). Only
+    /// the lenient readers accept a signature that is not "DHF" at the start.
+    LenientSignature(u64, String),
+    /// This many bytes of slack at this offset, which were not a record, were
+    /// skipped to get to the next record header.
+    Slack(u64, u64),
+    /// A padding record (a record with a name of all NUL bytes) of this many
+    /// bytes at this offset was skipped.
+    Padding(u64, u64),
+    /// An extracted file has this SHA-256 digest (the second one), instead of
+    /// the one it was expected to have (the first one).
+    DigestMismatch(PathBuf, String, String),
+    /// A file added to a new HOG file has the same contents as a file added
+    /// before it.
+    DuplicateContents(PathBuf, PathBuf),
+    /// The metadata embedded by --embed-metadata could not be read, for this
+    /// reason.
+    UnreadableMetadata(String),
+    /// A POG file could not be read for --with-pig, for this reason, so the
+    /// textures of its level are not paired.
+    UnreadablePog(PathBuf, String),
+    /// A --glob pattern did not match any files.
+    NoMatches,
+    /// A directory could not be searched for a --glob pattern, for this
+    /// reason, so files in it are left out.
+    UnreadableDirectory(PathBuf, String),
+}
+
+impl HogWarning {
+    /// Returns a stable name for the kind of warning, for scripts to match
+    /// on.
+    pub fn name(&self) -> &'static str {
+        match self {
+            HogWarning::SuspiciousName(_) => "SuspiciousName",
+            HogWarning::UnsafeName(_) => "UnsafeName",
+            HogWarning::EmptyFile(_) => "EmptyFile",
+            HogWarning::TrailingBytes(_) => "TrailingBytes",
+            HogWarning::LenientSignature(..) => "LenientSignature",
+            HogWarning::Slack(..) => "Slack",
+            HogWarning::Padding(..) => "Padding",
+            HogWarning::DigestMismatch(..) => "DigestMismatch",
+            HogWarning::DuplicateContents(..) => "DuplicateContents",
+            HogWarning::UnreadableMetadata(_) => "UnreadableMetadata",
+            HogWarning::UnreadablePog(..) => "UnreadablePog",
+            HogWarning::NoMatches => "NoMatches",
+            HogWarning::UnreadableDirectory(..) => "UnreadableDirectory",
+        }
+    }
+}
+
+impl fmt::Display for HogWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HogWarning::SuspiciousName(name) => {
+                write!(f, "\"{}\" is not a DOS 8.3 filename", EscapedName(name))
+            }
+            HogWarning::UnsafeName(name) => write!(
+                f,
+                "\"{}\" is not a plain filename, it is extracted as \"{}\"",
+                EscapedName(name),
+                EscapedName(Path::new(&sanitize_name(&name.to_string_lossy())))
+            ),
+            HogWarning::EmptyFile(name) => write!(f, "\"{}\" is empty", EscapedName(name)),
+            HogWarning::TrailingBytes(len) => {
+                write!(f, "{} bytes after the last file are not a record", len)
+            }
+            HogWarning::LenientSignature(offset, signature) => write!(
+                f,
+                "signature \"{}\" found at offset {}, instead of \"DHF\" at offset 0",
+                EscapedName(Path::new(signature)),
+                offset
+            ),
+            HogWarning::Slack(offset, len) => write!(
+                f,
+                "skipped {} bytes of slack at offset {}, before the next record",
+                len, offset
+            ),
+            HogWarning::Padding(offset, len) => write!(
+                f,
+                "skipped a padding record (a file with no name) of {} bytes at offset {}",
+                len, offset
+            ),
+            HogWarning::DigestMismatch(name, expected, actual) => write!(
+                f,
+                "\"{}\" has SHA-256 digest {}, expected {}",
+                EscapedName(name),
+                actual,
+                expected
+            ),
+            HogWarning::DuplicateContents(name, original) => write!(
+                f,
+                "\"{}\" has the same contents as \"{}\"",
+                EscapedName(name),
+                EscapedName(original)
+            ),
+            HogWarning::UnreadableMetadata(reason) => {
+                write!(f, "cannot read the embedded metadata: {}", reason)
+            }
+            HogWarning::UnreadablePog(name, reason) => {
+                write!(f, "cannot read \"{}\": {}", EscapedName(name), reason)
+            }
+            HogWarning::NoMatches => write!(f, "no files match this pattern"),
+            HogWarning::UnreadableDirectory(dir, reason) => {
+                write!(f, "cannot search \"{}\": {}", EscapedName(dir), reason)
+            }
+        }
+    }
+}
+
+impl From<NameError> for HogError {
+    fn from(e: NameError) -> Self {
+        match e {
+            NameError::TooLong(_) => HogError::HogFilenameTooLong,
+            NameError::ContainsNul | NameError::InvalidUtf8 => HogError::InvalidFilename,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_kind() {
+        let denied = io::Error::new(io::ErrorKind::PermissionDenied, "denied");
+        let short = io::Error::new(io::ErrorKind::UnexpectedEof, "short");
+
+        assert!(HogError::OpenHogFailure(denied).is_io());
+        assert!(HogError::ReadFailure(short).is_corrupt());
+        assert!(HogError::UnexpectedEof.is_corrupt());
+        assert!(!HogError::UnexpectedEof.is_io());
+        assert_eq!(
+            HogError::FileTooLarge(1 << 33).kind(),
+            HogErrorKind::Unsupported
+        );
+        assert_eq!(
+            HogError::InvalidDosFilename("a.b.c".into()).kind(),
+            HogErrorKind::Policy
+        );
+        assert_eq!(
+            HogError::EntryNotFound("x.txt".into()).kind(),
+            HogErrorKind::Usage
+        );
+        assert_eq!(
+            HogError::from(NameError::ContainsNul).kind(),
+            HogErrorKind::Corrupt
+        );
+    }
+}
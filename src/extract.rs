@@ -0,0 +1,960 @@
+//
+// Copyright (c) 2022-2023 Nathaniel Houghton <nathan@brainwerk.org>
+//
+// Permission to use, copy, modify, and distribute this software for
+// any purpose with or without fee is hereby granted, provided that
+// the above copyright notice and this permission notice appear in all
+// copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL
+// WARRANTIES WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE
+// AUTHOR BE LIABLE FOR ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL
+// DAMAGES OR ANY DAMAGES WHATSOEVER RESULTING FROM LOSS OF USE, DATA
+// OR PROFITS, WHETHER IN AN ACTION OF CONTRACT, NEGLIGENCE OR OTHER
+// TORTIOUS ACTION, ARISING OUT OF OR IN CONNECTION WITH THE USE OR
+// PERFORMANCE OF THIS SOFTWARE.
+//
+
+use std::collections::HashMap;
+use std::io::{self, ErrorKind, Read, Seek, Write};
+use std::ops::RangeInclusive;
+use std::path::{Component, Path, PathBuf};
+
+use regex::Regex;
+
+use crate::error::HogError;
+use crate::hog::{sanitize_name, HogFileReader, HogRecordIter, NameCase};
+use crate::index::IndexEntry;
+use crate::sniff::Category;
+use crate::util;
+
+/// How to sort extracted files into directories.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Organize {
+    /// Based on the filename extension.
+    ByExtension,
+
+    /// Based on the type guessed from the file contents.
+    ByType,
+}
+
+/// What to do when a file would be extracted to the same path as an earlier
+/// file (from the same HOG file) on a case insensitive filesystem.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CaseCollision {
+    /// Extract the file to its own path anyway. On a case insensitive
+    /// filesystem, the earlier file is then there already.
+    Keep,
+
+    /// Extract the file with a numbered suffix added to its name.
+    Suffix,
+
+    /// Fail with HogError::CaseCollision.
+    Error,
+
+    /// Replace the earlier file.
+    LastWins,
+}
+
+// A value an OutputTemplate can fill in.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum TemplateField {
+    Archive,
+    Index,
+    Name,
+    Stem,
+    Ext,
+}
+
+// A piece of an OutputTemplate: either text used as is, or a field, along
+// with the number of digits to pad it to with zeros (only for the index).
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum TemplatePart {
+    Text(String),
+    Field(TemplateField, usize),
+}
+
+// Most digits "{index}" can be padded to, which is as many as the largest
+// index has.
+const MAX_INDEX_WIDTH: usize = 20;
+
+/// A template for the paths files are extracted to, such as
+/// "{archive}_{index:03}_{name}". The fields that can be used are:
+///
+/// * {archive}: the name of the HOG file, without its extension
+/// * {index}: the (0 based) position of the file in the HOG file
+/// * {name}: the filename as stored in the HOG file
+/// * {stem} and {ext}: the filename without its extension, and the extension
+///
+/// "{index:03}" pads the index to 3 digits with zeros (up to 20 digits).
+/// "{{" and "}}" stand for plain braces. The template may contain "/" to put
+/// the files in subdirectories.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct OutputTemplate {
+    parts: Vec<TemplatePart>,
+}
+
+impl OutputTemplate {
+    /// Parses a template, returning HogError::InvalidTemplate if it uses an
+    /// unknown field or its braces do not match.
+    pub fn parse(template: &str) -> Result<Self, HogError> {
+        let invalid = |msg: &str| HogError::InvalidTemplate(msg.to_string());
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '}' => return Err(invalid("unmatched \"}\"")),
+                '{' => {
+                    let rest = chars.as_str();
+                    let end = rest.find('}').ok_or_else(|| invalid("unmatched \"{\""))?;
+                    let (field, width) = match rest[..end].split_once(':') {
+                        Some((field, width)) => (field, Some(width)),
+                        None => (&rest[..end], None),
+                    };
+
+                    let field = match field {
+                        "archive" => TemplateField::Archive,
+                        "index" => TemplateField::Index,
+                        "name" => TemplateField::Name,
+                        "stem" => TemplateField::Stem,
+                        "ext" => TemplateField::Ext,
+                        _ => {
+                            return Err(HogError::InvalidTemplate(format!(
+                                "unknown field \"{{{}}}\"",
+                                field
+                            )))
+                        }
+                    };
+
+                    let width = match width {
+                        None => 0,
+                        Some(_) if field != TemplateField::Index => {
+                            return Err(invalid("only {index} can be padded"))
+                        }
+                        Some(width) => width
+                            .parse()
+                            .ok()
+                            .filter(|&x| x <= MAX_INDEX_WIDTH)
+                            .ok_or_else(|| {
+                                invalid("the padding must be a number of digits, up to 20")
+                            })?,
+                    };
+
+                    if !text.is_empty() {
+                        parts.push(TemplatePart::Text(std::mem::take(&mut text)));
+                    }
+
+                    parts.push(TemplatePart::Field(field, width));
+                    chars = rest[end + 1..].chars();
+                }
+                _ => text.push(c),
+            }
+        }
+
+        if !text.is_empty() {
+            parts.push(TemplatePart::Text(text));
+        }
+
+        Ok(Self { parts })
+    }
+
+    /// Returns the path for the file stored as "name" at "index" in the HOG
+    /// file named "archive" (without its extension).
+    ///
+    /// An error is returned if the path would not stay inside the directory
+    /// being extracted to (such as when it starts with "/" or has a ".."
+    /// component), or has no filename.
+    pub fn render(&self, archive: &str, index: u64, name: &str) -> Result<PathBuf, HogError> {
+        let (stem, ext) = match name.rsplit_once('.') {
+            Some((stem, ext)) if !stem.is_empty() => (stem, ext),
+            _ => (name, ""),
+        };
+
+        let path: PathBuf = self
+            .parts
+            .iter()
+            .map(|part| match part {
+                TemplatePart::Text(text) => text.clone(),
+                TemplatePart::Field(TemplateField::Archive, _) => archive.to_string(),
+                TemplatePart::Field(TemplateField::Index, width) => {
+                    format!("{:0width$}", index, width = width)
+                }
+                TemplatePart::Field(TemplateField::Name, _) => name.to_string(),
+                TemplatePart::Field(TemplateField::Stem, _) => stem.to_string(),
+                TemplatePart::Field(TemplateField::Ext, _) => ext.to_string(),
+            })
+            .collect::<String>()
+            .into();
+
+        if path.file_name().is_none()
+            || !path.components().all(|x| matches!(x, Component::Normal(_)))
+        {
+            return Err(HogError::InvalidTemplate(format!(
+                "\"{}\" is not a path inside the output directory",
+                path.display()
+            )));
+        }
+
+        Ok(path)
+    }
+}
+
+/// Options deciding which files of a HOG file are extracted, and where to.
+#[derive(Clone, Debug)]
+pub struct ExtractOptions {
+    /// Change the case of the extracted filenames.
+    pub case: NameCase,
+
+    pub organize: Option<Organize>,
+    pub case_collisions: CaseCollision,
+
+    /// Leave out empty files.
+    pub skip_empty: bool,
+
+    /// Positions of the files to extract (empty for all of them).
+    pub indexes: Vec<RangeInclusive<u64>>,
+
+    /// Only extract the files whose names match this.
+    pub name_match: Option<Regex>,
+
+    /// Name the extracted files after this template, rather than after the
+    /// names they are stored as.
+    pub template: Option<OutputTemplate>,
+
+    /// Name of the HOG file being extracted, without its extension (for the
+    /// {archive} field of the template). See for_archive().
+    pub archive: String,
+}
+
+impl Default for ExtractOptions {
+    fn default() -> Self {
+        Self {
+            case: NameCase::Preserve,
+            organize: None,
+            case_collisions: CaseCollision::Keep,
+            skip_empty: false,
+            indexes: Vec::new(),
+            name_match: None,
+            template: None,
+            archive: String::new(),
+        }
+    }
+}
+
+impl ExtractOptions {
+    /// Returns these options, for extracting the HOG file at "path".
+    pub fn for_archive(&self, path: &Path) -> Self {
+        Self {
+            archive: path
+                .file_stem()
+                .map(|x| x.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            ..self.clone()
+        }
+    }
+
+    /// Returns true if the file stored as "name" at "index" should be
+    /// extracted (or at least be part of the plan), based on the given indexes
+    /// and name_match.
+    pub fn selects(&self, index: u64, name: &Path) -> bool {
+        (self.indexes.is_empty() || self.indexes.iter().any(|x| x.contains(&index)))
+            && self
+                .name_match
+                .as_ref()
+                .is_none_or(|x| x.is_match(&name.to_string_lossy()))
+    }
+
+    /// Returns the path to extract the file stored as "name" (the current file
+    /// of "iter") to, relative to the directory being extracted to. Nothing
+    /// is created on disk. A name that is not a plain filename is sanitized
+    /// first (see sanitize_name()), so it never names a directory.
+    ///
+    /// The path always stays inside the directory being extracted to: one
+    /// that would not (such as a name that is a drive on Windows, like "c:")
+    /// is an UnsafeName error.
+    pub fn output_path<R: Read + Seek>(
+        &self,
+        iter: &mut HogRecordIter<R>,
+        name: &Path,
+    ) -> Result<PathBuf, HogError> {
+        let file_name = self.case.apply(&sanitize_name(&name.to_string_lossy()));
+        let file_name = match &self.template {
+            Some(template) => template.render(&self.archive, iter.record_index(), &file_name)?,
+            None => PathBuf::from(file_name),
+        };
+
+        let path = match self.organize {
+            None => file_name,
+            Some(Organize::ByExtension) => Path::new(
+                Category::from_extension(
+                    &name
+                        .extension()
+                        .map(|x| x.to_string_lossy())
+                        .unwrap_or_default(),
+                )
+                .dir_name(),
+            )
+            .join(file_name),
+            Some(Organize::ByType) => {
+                Path::new(iter.sniff_cur_file()?.category().dir_name()).join(file_name)
+            }
+        };
+
+        if !path.components().all(|x| matches!(x, Component::Normal(_))) {
+            return Err(HogError::UnsafeName(name.to_string_lossy().into_owned()));
+        }
+
+        Ok(path)
+    }
+}
+
+/// A single file in an ExtractPlan.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PlannedEntry {
+    /// The (0 based) position of the file in the HOG file.
+    pub index: u64,
+
+    /// Filename as stored in the HOG file.
+    pub name: PathBuf,
+    pub length: u32,
+
+    /// Where to extract the file to, relative to the directory being
+    /// extracted to.
+    pub path: PathBuf,
+
+    /// False if the file is left out (because it is empty).
+    pub extract: bool,
+
+    /// The path of an earlier file that this one collides with on case
+    /// insensitive filesystems, if any. "path" is the same as it when
+    /// replacing the earlier file.
+    pub collision: Option<PathBuf>,
+}
+
+impl PlannedEntry {
+    /// Returns true if this file replaces an earlier file it collided with.
+    pub fn replaces_earlier(&self) -> bool {
+        self.collision.as_ref() == Some(&self.path)
+    }
+}
+
+/// Decides, for every file of a HOG file, whether it is extracted and where
+/// to, before anything is written. Extracting, and only displaying what would
+/// be extracted, both follow the same plan.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct ExtractPlan {
+    /// The selected files, in the order they are stored in the HOG file.
+    pub entries: Vec<PlannedEntry>,
+
+    /// Number of records read from the HOG file.
+    pub record_count: u64,
+}
+
+// Returns "path" with ".N" added to the filename (see util::numbered_path()),
+// for the lowest N (starting at 1) that "is_free" accepts.
+fn suffixed_path(path: &Path, is_free: impl Fn(&Path) -> bool) -> PathBuf {
+    (1u64..)
+        .map(|n| util::numbered_path(path, n))
+        .find(|x| is_free(x))
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+// Paths are compared without regard to case, like case insensitive
+// filesystems do.
+fn fold_case(path: &Path) -> String {
+    path.to_string_lossy().to_lowercase()
+}
+
+impl ExtractPlan {
+    /// Plans the extraction of the files read from "iter", which should not
+    /// have returned any records yet.
+    ///
+    /// Planning stops at the first record that cannot be read, or that
+    /// collides with an earlier file when CaseCollision::Error is used. The
+    /// error is returned along with the plan for the files before it (which
+    /// can still be extracted), and "iter" is left at the failed record.
+    pub fn new<R: Read + Seek>(
+        iter: &mut HogRecordIter<R>,
+        opts: &ExtractOptions,
+    ) -> (Self, Option<HogError>) {
+        let mut plan = Self::default();
+
+        // The paths planned so far, with their case folded.
+        let mut paths: HashMap<String, PathBuf> = HashMap::new();
+
+        while let Some(record) = iter.next() {
+            let record = match record {
+                Ok(x) => x,
+                Err(e) => return (plan, Some(e)),
+            };
+            let index = iter.record_index();
+            plan.record_count = index + 1;
+
+            if !opts.selects(index, &record.filename) {
+                continue;
+            }
+
+            let extract = !(opts.skip_empty && record.length == 0);
+            let path = match opts.output_path(iter, &record.filename) {
+                Ok(x) => x,
+                Err(e) => return (plan, Some(e)),
+            };
+
+            let mut entry = PlannedEntry {
+                index,
+                name: record.filename,
+                length: record.length,
+                path,
+                extract,
+                collision: None,
+            };
+
+            if !extract {
+                plan.entries.push(entry);
+                continue;
+            }
+
+            if let Some(earlier) = paths.get(&fold_case(&entry.path)) {
+                entry.path = match opts.case_collisions {
+                    CaseCollision::Error => {
+                        let e = HogError::CaseCollision(
+                            entry.path.display().to_string(),
+                            earlier.display().to_string(),
+                        );
+
+                        return (plan, Some(e));
+                    }
+                    CaseCollision::Keep => entry.path,
+                    CaseCollision::Suffix => {
+                        suffixed_path(&entry.path, |x| !paths.contains_key(&fold_case(x)))
+                    }
+                    CaseCollision::LastWins => earlier.clone(),
+                };
+                entry.collision = Some(earlier.clone());
+            }
+
+            paths.insert(fold_case(&entry.path), entry.path.clone());
+            plan.entries.push(entry);
+        }
+
+        (plan, None)
+    }
+
+    /// Returns the number of files that are extracted, and their total size
+    /// in bytes.
+    pub fn extracted(&self) -> (u64, u64) {
+        self.entries
+            .iter()
+            .filter(|x| x.extract)
+            .fold((0, 0), |(files, bytes), x| {
+                (files + 1, bytes + u64::from(x.length))
+            })
+    }
+
+    /// Returns the index given in "opts" that is past the end of the HOG
+    /// file, if any.
+    pub fn missing_index(&self, opts: &ExtractOptions) -> Option<u64> {
+        opts.indexes
+            .iter()
+            .find(|x| *x.end() >= self.record_count)
+            .map(|x| std::cmp::max(*x.start(), self.record_count))
+    }
+}
+
+/// Where extract_all() writes the extracted files to.
+pub trait EntrySink {
+    /// Returns the writer to write the contents of "entry" to.
+    fn open(&mut self, entry: &PlannedEntry) -> Result<Box<dyn Write + '_>, HogError>;
+}
+
+impl<F> EntrySink for F
+where
+    F: FnMut(&PlannedEntry) -> Result<Box<dyn Write>, HogError>,
+{
+    fn open(&mut self, entry: &PlannedEntry) -> Result<Box<dyn Write + '_>, HogError> {
+        self(entry)
+    }
+}
+
+// Keeps the extracted files in memory, keyed by the planned path.
+impl EntrySink for HashMap<PathBuf, Vec<u8>> {
+    fn open(&mut self, entry: &PlannedEntry) -> Result<Box<dyn Write + '_>, HogError> {
+        let buf = self.entry(entry.path.clone()).or_default();
+        buf.clear();
+
+        Ok(Box::new(buf))
+    }
+}
+
+/// An EntrySink that writes the contents of every file back to back into a
+/// single writer, keeping track of where each one starts.
+pub struct ConcatSink<W> {
+    out: W,
+    offset: u64,
+    entries: Vec<IndexEntry>,
+}
+
+impl<W: Write> ConcatSink<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            offset: 0,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Returns the writer, and where in it each file was written (the offsets
+    /// are from the start of what was written to it).
+    pub fn finish(self) -> (W, Vec<IndexEntry>) {
+        (self.out, self.entries)
+    }
+}
+
+impl<W: Write> EntrySink for ConcatSink<W> {
+    fn open(&mut self, entry: &PlannedEntry) -> Result<Box<dyn Write + '_>, HogError> {
+        self.entries.push(IndexEntry {
+            name: entry.name.to_string_lossy().into_owned(),
+            offset: self.offset,
+            length: entry.length,
+        });
+        self.offset += u64::from(entry.length);
+
+        Ok(Box::new(&mut self.out))
+    }
+}
+
+/// An EntrySink that writes every file into a single writer as a frame: the
+/// length of its name (as a 32 bit little endian number), its name, the length
+/// of its contents (likewise), and its contents. The stream can be read back
+/// with read_frame().
+pub struct FrameSink<W> {
+    out: W,
+}
+
+impl<W: Write> FrameSink<W> {
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+
+    /// Returns the writer.
+    pub fn finish(self) -> W {
+        self.out
+    }
+}
+
+impl<W: Write> EntrySink for FrameSink<W> {
+    fn open(&mut self, entry: &PlannedEntry) -> Result<Box<dyn Write + '_>, HogError> {
+        let name = entry.name.to_string_lossy();
+
+        self.out
+            .write_all(&(name.len() as u32).to_le_bytes())
+            .and_then(|_| self.out.write_all(name.as_bytes()))
+            .and_then(|_| self.out.write_all(&entry.length.to_le_bytes()))
+            .map_err(HogError::ExtractFailure)?;
+
+        Ok(Box::new(&mut self.out))
+    }
+}
+
+/// Reads the next frame written by FrameSink from "r", returning the name and
+/// the contents of the file, or None at the end of the stream.
+pub fn read_frame(r: &mut impl Read) -> io::Result<Option<(String, Vec<u8>)>> {
+    let mut len = [0; 4];
+
+    match r.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+
+    // Read as it arrives, so that a bad length does not allocate up to 4 GiB
+    // before the stream turns out to be shorter.
+    let mut name = Vec::new();
+    let name_len = u64::from(u32::from_le_bytes(len));
+
+    if r.take(name_len).read_to_end(&mut name)? as u64 != name_len {
+        return Err(ErrorKind::UnexpectedEof.into());
+    }
+
+    let name = String::from_utf8(name).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+
+    r.read_exact(&mut len)?;
+    let mut data = Vec::new();
+    let length = u64::from(u32::from_le_bytes(len));
+
+    if r.take(length).read_to_end(&mut data)? as u64 != length {
+        return Err(ErrorKind::UnexpectedEof.into());
+    }
+
+    Ok(Some((name, data)))
+}
+
+/// Extracts the files of "hog" chosen by "opts", writing each one to the
+/// writer opened by "sink", and returns the plan that was followed.
+///
+/// If planning failed, the files before the failed record are still
+/// extracted before the error is returned.
+pub fn extract_all<R: Read + Seek>(
+    hog: &mut HogFileReader<R>,
+    opts: &ExtractOptions,
+    sink: &mut impl EntrySink,
+) -> Result<ExtractPlan, HogError> {
+    let (plan, error) = ExtractPlan::new(&mut hog.records()?, opts);
+
+    let mut entries = plan.entries.iter().filter(|x| x.extract).peekable();
+    let mut iter = hog.records()?;
+
+    while entries.peek().is_some() {
+        match iter.next() {
+            Some(record) => record?,
+            None => break,
+        };
+
+        let entry = match entries.next_if(|x| x.index == iter.record_index()) {
+            Some(x) => x,
+            None => continue,
+        };
+
+        let mut out = sink.open(entry)?;
+        iter.copy_cur_file(&mut out)?;
+        out.flush().map_err(HogError::ExtractFailure)?;
+    }
+
+    match error {
+        Some(e) => Err(e),
+        None => Ok(plan),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::hog::HogFileReader;
+    use std::io::Cursor;
+
+    // Builds a HOG file in memory, containing "files".
+    fn hog(files: &[(&str, &[u8])]) -> HogFileReader<Cursor<Vec<u8>>> {
+        let mut data = b"DHF".to_vec();
+
+        for (name, contents) in files {
+            let mut field = [0; 13];
+            field[..name.len()].copy_from_slice(name.as_bytes());
+
+            data.extend(field);
+            data.extend((contents.len() as u32).to_le_bytes());
+            data.extend(*contents);
+        }
+
+        HogFileReader::from_reader(Cursor::new(data)).unwrap()
+    }
+
+    fn plan(
+        hog: &mut HogFileReader<Cursor<Vec<u8>>>,
+        opts: &ExtractOptions,
+    ) -> (ExtractPlan, Option<HogError>) {
+        ExtractPlan::new(&mut hog.records().unwrap(), opts)
+    }
+
+    fn paths(plan: &ExtractPlan) -> Vec<&str> {
+        plan.entries
+            .iter()
+            .map(|x| x.path.to_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_plan() {
+        let mut hog = hog(&[
+            ("LEVEL01.RDL", b"LVLP\x01\x00\x00\x00"),
+            ("empty.txt", b""),
+            ("a.pcx", b"\xff\x00\x13"),
+        ]);
+
+        let (plan, error) = plan(&mut hog, &ExtractOptions::default());
+        assert!(error.is_none());
+        assert_eq!(plan.record_count, 3);
+        assert_eq!(paths(&plan), ["LEVEL01.RDL", "empty.txt", "a.pcx"]);
+        assert_eq!(plan.entries[1].index, 1);
+        assert_eq!(plan.entries[1].length, 0);
+        assert_eq!(plan.extracted(), (3, 11));
+
+        let opts = ExtractOptions {
+            case: NameCase::Lower,
+            organize: Some(Organize::ByExtension),
+            skip_empty: true,
+            indexes: vec![0..=1],
+            ..Default::default()
+        };
+        let (plan, _) = self::plan(&mut hog, &opts);
+        assert_eq!(paths(&plan), ["levels/level01.rdl", "text/empty.txt"]);
+        assert!(plan.entries[0].extract);
+        assert!(!plan.entries[1].extract);
+        assert_eq!(plan.missing_index(&opts), None);
+
+        let opts = ExtractOptions {
+            organize: Some(Organize::ByType),
+            indexes: vec![0..=0, 2..=5],
+            ..Default::default()
+        };
+        let (plan, _) = self::plan(&mut hog, &opts);
+        assert_eq!(paths(&plan), ["levels/LEVEL01.RDL", "other/a.pcx"]);
+        assert_eq!(plan.missing_index(&opts), Some(3));
+
+        let opts = ExtractOptions {
+            name_match: Some(Regex::new(r"(?i)^level\d+\.rdl$|\.pcx$").unwrap()),
+            ..Default::default()
+        };
+        let (plan, _) = self::plan(&mut hog, &opts);
+        assert_eq!(paths(&plan), ["LEVEL01.RDL", "a.pcx"]);
+        assert_eq!(plan.entries[1].index, 2);
+        assert_eq!(plan.record_count, 3);
+    }
+
+    #[test]
+    fn test_plan_traversal() {
+        let names = ["../a.txt", "/b.txt", "..", "..\\..\\c.txt", "d/../../e"];
+        let mut hog = hog(&names.map(|x| (x, &b"x"[..])));
+
+        let template = OutputTemplate::parse("{index}-{name}").unwrap();
+
+        for opts in [
+            ExtractOptions::default(),
+            ExtractOptions {
+                organize: Some(Organize::ByExtension),
+                ..Default::default()
+            },
+            ExtractOptions {
+                template: Some(template),
+                ..Default::default()
+            },
+        ] {
+            let (plan, error) = plan(&mut hog, &opts);
+            assert!(error.is_none());
+            assert_eq!(plan.entries.len(), names.len());
+
+            // Every file is planned to go inside the directory being
+            // extracted to, under a single plain filename.
+            for entry in &plan.entries {
+                let file_name = entry.path.file_name().unwrap();
+
+                assert!(entry
+                    .path
+                    .components()
+                    .all(|x| matches!(x, Component::Normal(_))));
+                assert!(crate::hog::is_plain_name(&file_name.to_string_lossy()));
+                assert_eq!(
+                    entry.path.components().count(),
+                    1 + usize::from(opts.organize.is_some())
+                );
+            }
+        }
+
+        let (plan, _) = plan(&mut hog, &ExtractOptions::default());
+        assert_eq!(
+            paths(&plan),
+            [".._a.txt", "_b.txt", "__", ".._.._c.txt", "d_.._.._e"]
+        );
+    }
+
+    #[test]
+    fn test_plan_case_collisions() {
+        let mut hog = hog(&[
+            ("A.TXT", b"1"),
+            ("a.txt.1", b"2"),
+            ("a.txt", b"3"),
+            ("b.txt", b"4"),
+        ]);
+
+        let (plan, error) = plan(&mut hog, &ExtractOptions::default());
+        assert!(error.is_none());
+        assert_eq!(paths(&plan), ["A.TXT", "a.txt.1", "a.txt", "b.txt"]);
+        assert_eq!(plan.entries[2].collision, Some(PathBuf::from("A.TXT")));
+        assert!(!plan.entries[2].replaces_earlier());
+
+        let opts = ExtractOptions {
+            case_collisions: CaseCollision::Suffix,
+            ..Default::default()
+        };
+        let (plan, _) = self::plan(&mut hog, &opts);
+        assert_eq!(paths(&plan), ["A.TXT", "a.txt.1", "a.txt.2", "b.txt"]);
+        assert_eq!(plan.entries[2].collision, Some(PathBuf::from("A.TXT")));
+        assert!(!plan.entries[2].replaces_earlier());
+
+        let opts = ExtractOptions {
+            case_collisions: CaseCollision::LastWins,
+            ..Default::default()
+        };
+        let (plan, _) = self::plan(&mut hog, &opts);
+        assert_eq!(paths(&plan), ["A.TXT", "a.txt.1", "A.TXT", "b.txt"]);
+        assert!(plan.entries[2].replaces_earlier());
+
+        let opts = ExtractOptions {
+            case_collisions: CaseCollision::Error,
+            ..Default::default()
+        };
+        let (plan, error) = self::plan(&mut hog, &opts);
+        assert_eq!(paths(&plan), ["A.TXT", "a.txt.1"]);
+        assert!(matches!(error, Some(HogError::CaseCollision(_, _))));
+    }
+
+    #[test]
+    fn test_extract_all() {
+        let mut hog = hog(&[("a.txt", b"one"), ("empty.txt", b""), ("b.txt", b"two")]);
+
+        let mut files: HashMap<PathBuf, Vec<u8>> = HashMap::new();
+        let opts = ExtractOptions {
+            case: NameCase::Upper,
+            skip_empty: true,
+            ..Default::default()
+        };
+        let plan = extract_all(&mut hog, &opts, &mut files).unwrap();
+        assert_eq!(plan.extracted(), (2, 6));
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[Path::new("A.TXT")], b"one");
+        assert_eq!(files[Path::new("B.TXT")], b"two");
+
+        let mut opened = Vec::new();
+        let mut sink = |entry: &PlannedEntry| -> Result<Box<dyn Write>, HogError> {
+            opened.push(entry.index);
+            Ok(Box::new(std::io::sink()))
+        };
+        let opts = ExtractOptions {
+            indexes: vec![2..=2],
+            ..Default::default()
+        };
+        extract_all(&mut hog, &opts, &mut sink).unwrap();
+        assert_eq!(opened, [2]);
+
+        let mut hog = self::hog(&[("a.txt", b"1"), ("A.TXT", b"2")]);
+        let opts = ExtractOptions {
+            case_collisions: CaseCollision::Error,
+            ..Default::default()
+        };
+        let mut files: HashMap<PathBuf, Vec<u8>> = HashMap::new();
+        let result = extract_all(&mut hog, &opts, &mut files);
+        assert!(matches!(result, Err(HogError::CaseCollision(_, _))));
+        assert_eq!(files[Path::new("a.txt")], b"1");
+    }
+
+    #[test]
+    fn test_concat_sink() {
+        let mut hog = hog(&[("a.txt", b"one"), ("empty.txt", b""), ("b.txt", b"two!")]);
+        let mut sink = ConcatSink::new(Vec::new());
+
+        extract_all(&mut hog, &ExtractOptions::default(), &mut sink).unwrap();
+
+        let (data, entries) = sink.finish();
+        assert_eq!(data, b"onetwo!");
+        assert_eq!(
+            entries
+                .iter()
+                .map(|x| (x.name.as_str(), x.offset, x.length))
+                .collect::<Vec<_>>(),
+            [("a.txt", 0, 3), ("empty.txt", 3, 0), ("b.txt", 3, 4)]
+        );
+    }
+
+    #[test]
+    fn test_frame_sink() {
+        let mut hog = hog(&[("a.txt", b"one"), ("empty.txt", b""), ("b.txt", b"two!")]);
+        let mut sink = FrameSink::new(Vec::new());
+
+        extract_all(&mut hog, &ExtractOptions::default(), &mut sink).unwrap();
+
+        let data = sink.finish();
+        assert_eq!(&data[..13], b"\x05\0\0\0a.txt\x03\0\0\0");
+
+        let mut r = data.as_slice();
+        let mut frames = Vec::new();
+        while let Some(frame) = read_frame(&mut r).unwrap() {
+            frames.push(frame);
+        }
+
+        assert_eq!(
+            frames,
+            [
+                ("a.txt".to_string(), b"one".to_vec()),
+                ("empty.txt".to_string(), Vec::new()),
+                ("b.txt".to_string(), b"two!".to_vec()),
+            ]
+        );
+
+        // A frame cut short is an error, rather than the end of the stream.
+        let mut r = &data[..data.len() - 1];
+        assert!((0..2).all(|_| read_frame(&mut r).is_ok()));
+        assert!(read_frame(&mut r).is_err());
+
+        // So is a name longer than the rest of the stream.
+        let mut r = &b"\xff\xff\xff\xffa.txt"[..];
+        assert!(read_frame(&mut r).is_err());
+    }
+
+    #[test]
+    fn test_output_template() {
+        let template = OutputTemplate::parse("{archive}_{index:03}_{name}").unwrap();
+        assert_eq!(
+            template.render("descent", 7, "LEVEL01.RDL").unwrap(),
+            Path::new("descent_007_LEVEL01.RDL")
+        );
+
+        let template = OutputTemplate::parse("{{{ext}}}/{stem}-{index}.{ext}").unwrap();
+        assert_eq!(
+            template.render("d", 12, "a.pcx").unwrap(),
+            Path::new("{pcx}/a-12.pcx")
+        );
+        assert_eq!(
+            template.render("d", 0, "README").unwrap(),
+            Path::new("{}/README-0.")
+        );
+
+        for bad in [
+            "{name",
+            "name}",
+            "{size}",
+            "{name:03}",
+            "{index:x}",
+            "{index:21}",
+            "{index:999999999}",
+        ] {
+            assert!(matches!(
+                OutputTemplate::parse(bad),
+                Err(HogError::InvalidTemplate(_))
+            ));
+        }
+
+        for (bad, name) in [
+            ("../{name}", "a.txt"),
+            ("/tmp/{name}", "a.txt"),
+            ("{ext}", "README"),
+        ] {
+            let template = OutputTemplate::parse(bad).unwrap();
+            assert!(matches!(
+                template.render("d", 0, name),
+                Err(HogError::InvalidTemplate(_))
+            ));
+        }
+
+        // Names that the template makes the same collide like names that
+        // only differ by case do.
+        let mut hog = hog(&[("b.txt", b"1"), ("c.txt", b"2")]);
+        let opts = ExtractOptions {
+            template: Some(OutputTemplate::parse("{archive}.{ext}").unwrap()),
+            case_collisions: CaseCollision::Suffix,
+            ..Default::default()
+        }
+        .for_archive(Path::new("dir/mission.hog"));
+        let (plan, _) = plan(&mut hog, &opts);
+        assert_eq!(paths(&plan), ["mission.txt", "mission.txt.1"]);
+    }
+}
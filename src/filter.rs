@@ -0,0 +1,249 @@
+//
+// Copyright (c) 2022-2023 Nathaniel Houghton <nathan@brainwerk.org>
+//
+// Permission to use, copy, modify, and distribute this software for
+// any purpose with or without fee is hereby granted, provided that
+// the above copyright notice and this permission notice appear in all
+// copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL
+// WARRANTIES WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE
+// AUTHOR BE LIABLE FOR ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL
+// DAMAGES OR ANY DAMAGES WHATSOEVER RESULTING FROM LOSS OF USE, DATA
+// OR PROFITS, WHETHER IN AN ACTION OF CONTRACT, NEGLIGENCE OR OTHER
+// TORTIOUS ACTION, ARISING OUT OF OR IN CONNECTION WITH THE USE OR
+// PERFORMANCE OF THIS SOFTWARE.
+//
+
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::time::Instant;
+
+use crate::error::{HogError, LimitExceeded};
+use crate::util::Limits;
+
+/// A command that files are piped through as they are extracted or added,
+/// given as a template like "pngquant - {name}". The template is split into
+/// words at whitespace, and {name} in any of them is replaced by the name of
+/// the file. The command is run directly (not by a shell), so that a name
+/// cannot be taken as part of the command.
+#[derive(Clone, Debug)]
+pub struct FilterCommand {
+    words: Vec<String>,
+    limits: Limits,
+}
+
+impl FilterCommand {
+    /// Parses a command template, returning HogError::InvalidCommand if it is
+    /// empty.
+    pub fn parse(template: &str) -> Result<Self, HogError> {
+        let words: Vec<String> = template.split_whitespace().map(String::from).collect();
+
+        if words.is_empty() {
+            return Err(HogError::InvalidCommand("command is empty".to_string()));
+        }
+
+        Ok(Self {
+            words,
+            limits: Limits::default(),
+        })
+    }
+
+    /// Returns the command with "limits" on how much each run of it may
+    /// output, and how long it may take (after which it is killed).
+    pub fn with_limits(self, limits: Limits) -> Self {
+        Self { limits, ..self }
+    }
+
+    /// Returns the command to run for the file "name", with its arguments.
+    pub fn command(&self, name: &str) -> Command {
+        let mut words = self.words.iter().map(|x| x.replace("{name}", name));
+        let mut command = Command::new(words.next().unwrap_or_default());
+
+        command.args(words);
+
+        command
+    }
+
+    /// Runs the command for the file "name", writing "input" to its standard
+    /// input and copying what it writes to its standard output to "output".
+    /// Returns the number of bytes it wrote.
+    ///
+    /// The command failing (exiting with a nonzero status) is an error, as is
+    /// writing to "output" failing, or the command going past the limits
+    /// (HogError::LimitExceeded).
+    pub fn run(&self, name: &str, input: &[u8], output: &mut dyn Write) -> Result<u64, HogError> {
+        let start = Instant::now();
+        let mut child = self
+            .command(name)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(HogError::FilterFailure)?;
+
+        let (Some(mut stdin), Some(mut stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            let _ = child.kill();
+            let _ = child.wait();
+
+            return Err(HogError::FilterFailure(io::Error::other(
+                "no pipe to the command",
+            )));
+        };
+
+        let child = Mutex::new(child);
+        let timed_out = AtomicBool::new(false);
+
+        // The input is written from another thread, so that neither side
+        // waits on the other when the pipes fill up. Commands that do not
+        // read all of it are not an error (only their exit status counts).
+        let copied = std::thread::scope(|s| {
+            s.spawn(move || match stdin.write_all(input) {
+                Err(e) if e.kind() != io::ErrorKind::BrokenPipe => Err(e),
+                _ => Ok(()),
+            });
+
+            // A command that outlives the time limit is killed, which ends
+            // its output.
+            let (done, finished) = mpsc::channel::<()>();
+
+            if let Some(max_time) = self.limits.max_time {
+                let (child, timed_out) = (&child, &timed_out);
+
+                s.spawn(move || {
+                    if finished.recv_timeout(max_time) == Err(mpsc::RecvTimeoutError::Timeout) {
+                        timed_out.store(true, Ordering::Relaxed);
+
+                        let _ = child.lock().map(|mut x| x.kill());
+                    }
+                });
+            }
+
+            let mut buf = [0; 64 * 1024];
+            let mut copied = 0;
+
+            let result = loop {
+                let len = match stdout.read(&mut buf) {
+                    Ok(0) => break Ok(copied),
+                    Ok(len) => len,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => break Err(HogError::FilterFailure(e)),
+                };
+
+                if let Err(limit) = self.limits.check(copied + len as u64, start.elapsed()) {
+                    break Err(HogError::LimitExceeded(limit));
+                }
+
+                if let Err(e) = output.write_all(&buf[..len]) {
+                    break Err(HogError::ExtractFailure(e));
+                }
+
+                copied += len as u64;
+            };
+
+            drop(done);
+
+            // The command is not waited for if it cannot go on, since it
+            // could be stuck writing output that nothing reads anymore.
+            if result.is_err() {
+                let _ = child.lock().map(|mut x| x.kill());
+            }
+
+            result
+        });
+
+        let mut child = child.into_inner().unwrap_or_else(|e| e.into_inner());
+        let status = child.wait().map_err(HogError::FilterFailure)?;
+
+        if let (Some(max_time), true) = (self.limits.max_time, timed_out.into_inner()) {
+            return Err(HogError::LimitExceeded(LimitExceeded::Time(max_time)));
+        }
+
+        let copied = copied?;
+
+        if !status.success() {
+            return Err(HogError::FilterFailed(name.to_string(), status.to_string()));
+        }
+
+        Ok(copied)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::*;
+
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse() {
+        assert!(matches!(
+            FilterCommand::parse("  "),
+            Err(HogError::InvalidCommand(_))
+        ));
+
+        let filter = FilterCommand::parse("convert  -  {name}.png").unwrap();
+        let command = filter.command("a b.pcx");
+
+        assert_eq!(command.get_program(), "convert");
+        assert_eq!(command.get_args().collect::<Vec<_>>(), ["-", "a b.pcx.png"]);
+    }
+
+    #[test]
+    fn test_run() {
+        let mut output = Vec::new();
+        let filter = FilterCommand::parse("tr a-z A-Z").unwrap();
+
+        assert_eq!(filter.run("a.txt", b"hello", &mut output).unwrap(), 5);
+        assert_eq!(output, b"HELLO");
+
+        // Commands that do not read their input can still write output.
+        let mut output = Vec::new();
+        let filter = FilterCommand::parse("echo {name}").unwrap();
+
+        filter.run("a.txt", &[b'x'; 1 << 20], &mut output).unwrap();
+        assert_eq!(output, b"a.txt\n");
+
+        let filter = FilterCommand::parse("false").unwrap();
+
+        assert!(matches!(
+            filter.run("a.txt", b"", &mut Vec::new()),
+            Err(HogError::FilterFailed(..))
+        ));
+
+        let limits = Limits {
+            max_len: Some(3),
+            max_time: None,
+        };
+        let filter = FilterCommand::parse("yes").unwrap().with_limits(limits);
+
+        assert!(matches!(
+            filter.run("a.txt", b"", &mut Vec::new()),
+            Err(HogError::LimitExceeded(LimitExceeded::Size(3)))
+        ));
+
+        let limits = Limits {
+            max_len: None,
+            max_time: Some(Duration::from_millis(50)),
+        };
+        let filter = FilterCommand::parse("sleep 10")
+            .unwrap()
+            .with_limits(limits);
+        let start = Instant::now();
+
+        assert!(matches!(
+            filter.run("a.txt", b"", &mut Vec::new()),
+            Err(HogError::LimitExceeded(LimitExceeded::Time(_)))
+        ));
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let filter = FilterCommand::parse("/nonexistent/command").unwrap();
+
+        assert!(matches!(
+            filter.run("a.txt", b"", &mut Vec::new()),
+            Err(HogError::FilterFailure(_))
+        ));
+    }
+}
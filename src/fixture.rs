@@ -0,0 +1,285 @@
+//
+// Copyright (c) 2022-2023 Nathaniel Houghton <nathan@brainwerk.org>
+//
+// Permission to use, copy, modify, and distribute this software for
+// any purpose with or without fee is hereby granted, provided that
+// the above copyright notice and this permission notice appear in all
+// copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL
+// WARRANTIES WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE
+// AUTHOR BE LIABLE FOR ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL
+// DAMAGES OR ANY DAMAGES WHATSOEVER RESULTING FROM LOSS OF USE, DATA
+// OR PROFITS, WHETHER IN AN ACTION OF CONTRACT, NEGLIGENCE OR OTHER
+// TORTIOUS ACTION, ARISING OUT OF OR IN CONNECTION WITH THE USE OR
+// PERFORMANCE OF THIS SOFTWARE.
+//
+
+// Synthetic HOG files, for testing programs that read them.
+//
+// The files are the same on every run: the contents of each one only depend on
+// its index. A fixture can be broken in one of a few ways that real HOG files
+// are found broken in, in a single place that is reported back, so that the
+// behaviour of a reader can be checked against it.
+
+use crate::error::HogError;
+use crate::hog::{self, HDR_LEN, HOG_SIGNATURE};
+
+/// Largest number of files a fixture can have (their names are numbered with
+/// four digits).
+pub const MAX_ENTRIES: u32 = 9999;
+
+/// How a fixture is broken.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Corruption {
+    /// The contents of the last file are cut short by the end of the HOG
+    /// file.
+    Truncate,
+
+    /// The filename of the file in the middle is not valid UTF-8. Its length
+    /// is still right, so readers can skip it and read the rest.
+    BadName,
+
+    /// The length of the file in the middle is too large, so the next record
+    /// header is looked for in the middle of the one that follows.
+    BadLength,
+}
+
+/// A generated HOG file.
+#[derive(Clone, Debug)]
+pub struct Fixture {
+    /// Contents of the HOG file.
+    pub data: Vec<u8>,
+
+    /// Index and header offset of the broken record, if the fixture was
+    /// broken.
+    pub corrupted: Option<(u32, u64)>,
+}
+
+/// Returns the name and contents of file "index" of a fixture.
+pub fn entry(index: u32) -> (String, Vec<u8>) {
+    // Every few files is binary, and a few are empty, as in real HOG files.
+    match index % 8 {
+        3 => (
+            format!("file{:04}.bin", index),
+            (0..=index % 251).map(|x| (x * 7) as u8).collect(),
+        ),
+        7 => (format!("file{:04}.txt", index), Vec::new()),
+        _ => (
+            format!("file{:04}.txt", index),
+            format!("fixture file {}\n", index)
+                .repeat(index as usize % 16 + 1)
+                .into_bytes(),
+        ),
+    }
+}
+
+/// Generates a HOG file with "entries" files, broken as "corruption" says, if
+/// given. More than MAX_ENTRIES files is a HogError::TooManyFixtureFiles.
+pub fn generate(entries: u32, corruption: Option<Corruption>) -> Result<Fixture, HogError> {
+    if entries > MAX_ENTRIES {
+        return Err(HogError::TooManyFixtureFiles(entries));
+    }
+
+    let mut data = HOG_SIGNATURE.to_vec();
+    let middle = entries / 2;
+    let mut corrupted = None;
+
+    for index in 0..entries {
+        let (name, contents) = entry(index);
+        // The names always fit.
+        let mut field = hog::encode_name(&name).unwrap_or_default();
+        let mut length = contents.len() as u32;
+
+        match corruption {
+            Some(Corruption::BadName) if index == middle => field[0] = 0xff,
+            Some(Corruption::BadLength) if index == middle => length += HDR_LEN as u32 / 2,
+            _ => {}
+        }
+
+        if corruption.is_some() && index == middle {
+            corrupted = Some((index, data.len() as u64));
+        }
+
+        data.extend(field);
+        data.extend(length.to_le_bytes());
+        data.extend(contents);
+    }
+
+    if let (Some(Corruption::Truncate), Some(last)) = (corruption, entries.checked_sub(1)) {
+        let (_, contents) = entry(last);
+        let offset = data.len() - contents.len() - HDR_LEN;
+
+        // An empty file cannot be cut short, so its header is cut instead
+        // (leaving bytes too few to be a header).
+        let cut = std::cmp::max(contents.len().div_ceil(2), HDR_LEN / 2);
+
+        data.truncate(data.len() - cut);
+        corrupted = Some((last, offset as u64));
+    }
+
+    Ok(Fixture { data, corrupted })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::hog::HogFileReader;
+    use crate::index::HogIndex;
+    use crate::manifest::{EmbeddedMetadata, Manifest};
+    use crate::mission::Mission;
+    use crate::util::Limits;
+    use crate::{demo, extract, image, level, listing, pig, sniff, table};
+    use std::io::Cursor;
+
+    // Reads the records of "data", returning the name of each one, or None
+    // for errors.
+    fn read(data: Vec<u8>) -> Vec<Option<String>> {
+        let mut reader = HogFileReader::from_reader(Cursor::new(data)).unwrap();
+
+        reader
+            .records()
+            .unwrap()
+            .map(|x| x.ok().map(|x| x.filename.to_string_lossy().into_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn test_generate() {
+        let fixture = generate(10, None).unwrap();
+        let mut reader = HogFileReader::from_reader(Cursor::new(fixture.data.clone())).unwrap();
+        let all: Vec<_> = reader
+            .iter_with_data(None)
+            .unwrap()
+            .map(|x| x.unwrap())
+            .collect();
+
+        assert_eq!(all.len(), 10);
+        assert_eq!(fixture.corrupted, None);
+
+        for (index, (record, data)) in all.into_iter().enumerate() {
+            let (name, contents) = entry(index as u32);
+
+            assert_eq!(record.filename.to_str(), Some(name.as_str()));
+            assert_eq!(data, contents);
+        }
+
+        assert_eq!(generate(10, None).unwrap().data, fixture.data);
+        assert_eq!(generate(0, None).unwrap().data, HOG_SIGNATURE);
+        assert!(generate(MAX_ENTRIES, None).is_ok());
+        assert!(matches!(
+            generate(MAX_ENTRIES + 1, None),
+            Err(HogError::TooManyFixtureFiles(_))
+        ));
+    }
+
+    #[test]
+    fn test_corruption() {
+        let names = read(generate(5, Some(Corruption::BadName)).unwrap().data);
+        assert_eq!(names.len(), 5);
+        assert_eq!(names[2], None);
+        assert!(names[3].is_some());
+
+        let fixture = generate(5, Some(Corruption::BadLength)).unwrap();
+        assert_eq!(fixture.corrupted.map(|x| x.0), Some(2));
+        let names = read(fixture.data);
+        assert!(names[..3].iter().all(|x| x.is_some()));
+        assert_eq!(names.last(), Some(&None));
+
+        // The last file is not empty, so its contents are cut short.
+        let fixture = generate(5, Some(Corruption::Truncate)).unwrap();
+        let mut reader = HogFileReader::from_reader(Cursor::new(fixture.data)).unwrap();
+        let mut iter = reader.records().unwrap();
+        assert_eq!(iter.by_ref().filter(|x| x.is_ok()).count(), 5);
+        assert_eq!(iter.errors_encountered(), 1);
+
+        let fixture = generate(8, Some(Corruption::Truncate)).unwrap();
+        let mut reader = HogFileReader::from_reader(Cursor::new(fixture.data)).unwrap();
+        let mut iter = reader.records().unwrap();
+        assert_eq!(iter.by_ref().filter(|x| x.is_ok()).count(), 7);
+        assert_eq!(iter.trailing_bytes(), HDR_LEN as u64 / 2 + 1);
+    }
+
+    // Runs "data" through the parsers of the files found in HOG files (and
+    // next to them).
+    fn parse_file(data: &[u8]) {
+        let text = String::from_utf8_lossy(data);
+
+        let _ = image::decode(sniff::sniff(data), data, &Limits::default());
+        let _ = image::decode_pcx(data, &Limits::default());
+        let _ = image::decode_iff(data, &Limits::default());
+        let _ = level::parse_level_header(data);
+        let _ = level::level_textures(data);
+        let _ = pig::PigFile::read(&mut &data[..]);
+        let _ = pig::PogFile::read(&mut &data[..]);
+        let _ = pig::HamFile::read(&mut &data[..]);
+        let _ = demo::parse_demo(data, data);
+        let _ = demo::demo_duration(data);
+        let _ = table::parse_table(data);
+        let _ = Mission::parse(&text);
+        let _ = EmbeddedMetadata::parse(data);
+        let _ = Manifest::parse(&text);
+        let _ = HogIndex::parse(&text);
+        let _ = listing::parse(&text);
+
+        let mut frames = data;
+        while let Ok(Some(_)) = extract::read_frame(&mut frames) {}
+
+        #[cfg(feature = "s3")]
+        let _ = crate::s3::dechunk(data);
+    }
+
+    // Runs "data" through the library's parsers. Only the lack of a panic
+    // matters, not what they make of it.
+    fn parse_all(data: &[u8]) {
+        if let Ok(mut reader) = HogFileReader::from_reader_lenient(Cursor::new(data)) {
+            if let Ok(iter) = reader.iter_with_data(Some(4096)) {
+                for (_, contents) in iter.flatten() {
+                    parse_file(&contents);
+                }
+            }
+
+            let _ = HogIndex::build(&mut reader);
+        }
+
+        for part in [data, data.get(HOG_SIGNATURE.len()..).unwrap_or_default()] {
+            parse_file(part);
+        }
+    }
+
+    #[test]
+    fn test_malformed_input() {
+        let fixture = generate(9, None).unwrap();
+
+        for corruption in [
+            Corruption::Truncate,
+            Corruption::BadName,
+            Corruption::BadLength,
+        ] {
+            parse_all(&generate(9, Some(corruption)).unwrap().data);
+        }
+
+        for len in 0..fixture.data.len() {
+            parse_all(&fixture.data[..len]);
+        }
+
+        // Flips a few bytes at a time, at (repeatably) random places.
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+
+        for _ in 0..500 {
+            let mut data = fixture.data.clone();
+
+            for _ in 0..4 {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+
+                let offset = state as usize % data.len();
+                data[offset] ^= (state >> 32) as u8 | 1;
+            }
+
+            parse_all(&data);
+        }
+    }
+}
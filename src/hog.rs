@@ -16,42 +16,156 @@
 // PERFORMANCE OF THIS SOFTWARE.
 //
 
+use std::collections::HashMap;
+use std::fmt;
 use std::fs::File;
-use std::io::{BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::io::{BufReader, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::iter::FusedIterator;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use bytemuck::{Pod, Zeroable};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
 
-use crate::error::HogError;
-use crate::util;
+use crate::compress::{Compression, HogInput, HogOutput};
+use crate::error::{HogError, HogWarning, NameError};
+use crate::index::{HogIndex, IndexEntry};
+use crate::sniff::{self, ContentType};
+use crate::util::{self, Limits};
 
-const HOG_SIGNATURE: [u8; 3] = *b"DHF";
+/// Signature at the start of every HOG file.
+pub const HOG_SIGNATURE: [u8; 3] = *b"DHF";
+
+/// Number of bytes at the start of a file that the lenient readers (such as
+/// HogFileReader::open_lenient()) look through for the signature.
+pub const LENIENT_SIGNATURE_SPAN: u64 = 16;
+
+/// Most bytes of slack between records that HogFileReader::set_tolerate_slack()
+/// can skip. The tools that leave slack only leave a few bytes.
+pub const MAX_SLACK: u64 = 4096;
+
+/// Signature of hogz files. This is not a format the game knows about: it is a
+/// HOG file with every file stored zlib compressed, which hogdump only writes
+/// when asked to (and reads like any other HOG file).
+pub const HOGZ_SIGNATURE: [u8; 3] = *b"DHZ";
 
 // The "raw" HOG file record format, as contained in the HOG file on disk
 #[derive(Pod, Zeroable, Copy, Clone)]
 #[repr(C, packed)]
 struct RawHogRecord {
-    filename: [u8; 13],
+    filename: [u8; NAME_LEN],
 
     // On disk, this is little endian.
     length: u32,
 }
 
-impl RawHogRecord {
-    // In the raw, on disk record, the end of the filename is padded with "0"
-    // bytes. This function attempts to strip off the null padding, and then
-    // interpret the extracted bytes as UTF-8.
-    fn filename_as_str(&self) -> Result<&str, HogError> {
-        // Strip off the null padding, if present.
-        let filename_part = self.filename.splitn(2, |x| *x == 0).next().unwrap();
+/// Length of the filename field of a HOG record header.
+pub const NAME_LEN: usize = 13;
+
+/// Encodes a filename for the filename field of a HOG record header: the name
+/// followed by NUL bytes, filling the rest of the field. At least one NUL byte
+/// always follows the name, so it can be at most 12 bytes long.
+///
+/// Only the encoding is checked here. Whether the name is otherwise usable
+/// (for example, a DOS filename) is up to the FilenamePolicy.
+pub fn encode_name(name: &str) -> Result<[u8; NAME_LEN], NameError> {
+    let bytes = name.as_bytes();
+
+    if bytes.len() >= NAME_LEN {
+        return Err(NameError::TooLong(bytes.len()));
+    }
+
+    if bytes.contains(&0) {
+        return Err(NameError::ContainsNul);
+    }
+
+    let mut field = [0; NAME_LEN];
+    field[..bytes.len()].copy_from_slice(bytes);
+
+    Ok(field)
+}
+
+/// Decodes the filename field of a HOG record header. The name ends at the
+/// first NUL byte (anything after it is padding, and is ignored), and must be
+/// valid UTF-8.
+///
+/// A name filling all 13 bytes, without a NUL byte, is accepted, since some
+/// tools write them, even though encode_name() never does.
+pub fn decode_name(field: &[u8; NAME_LEN]) -> Result<String, NameError> {
+    let name = field.split(|x| *x == 0).next().unwrap_or_default();
+
+    match std::str::from_utf8(name) {
+        Ok(name) => Ok(name.to_string()),
+        Err(_) => Err(NameError::InvalidUtf8),
+    }
+}
 
-        // Attempt to interpret as UTF-8, failing if decoding fails.
-        std::str::from_utf8(filename_part).map_err(|_| HogError::InvalidFilename)
+// The characters that separate the parts of a path, on one platform or
+// another. Names stored in HOG files are plain filenames, so these are never
+// taken as separators in them, whichever platform hogdump runs on.
+const PATH_SEPARATORS: [char; 2] = ['/', '\\'];
+
+/// Returns true if "name" (as stored in a HOG file) can be used as a filename
+/// as it is, on any platform: it has no "/" or "\\" in it, and is not empty,
+/// "." or "..".
+pub fn is_plain_name(name: &str) -> bool {
+    !matches!(name, "" | "." | "..") && !name.contains(PATH_SEPARATORS)
+}
+
+/// Returns "name" (as stored in a HOG file) made into a plain filename (see
+/// is_plain_name()) to extract it as: "/" and "\\" are replaced by "_", and so
+/// are the dots of "." and "..". An empty name becomes "_".
+///
+/// This is done the same way on every platform, so that a HOG file extracts
+/// to the same files everywhere.
+pub fn sanitize_name(name: &str) -> String {
+    match name {
+        "" => "_".to_string(),
+        "." | ".." => "_".repeat(name.len()),
+        _ => name.replace(PATH_SEPARATORS, "_"),
+    }
+}
+
+/// Displays a filename (or a path made from one) from a HOG file, with the
+/// characters that could mess with a terminal escaped: control characters are
+/// displayed as `\x01` and bidirectional formatting characters as `\u{202e}`.
+/// Backslashes are left alone, since they separate the parts of paths on
+/// Windows.
+///
+/// HOG files can come from anywhere, so any name from one should be displayed
+/// this way.
+pub struct EscapedName<'a>(pub &'a Path);
+
+// Returns true for the characters that change how the text around them is
+// displayed, without being control characters.
+fn is_format_char(c: char) -> bool {
+    matches!(c, '\u{200b}'..='\u{200f}' | '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}')
+}
+
+impl fmt::Display for EscapedName<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut escaped = String::new();
+
+        for c in self.0.to_string_lossy().chars() {
+            match c {
+                c if c.is_control() => escaped.push_str(&format!("\\x{:02x}", c as u32)),
+                c if is_format_char(c) => escaped.push_str(&format!("\\u{{{:x}}}", c as u32)),
+                c => escaped.push(c),
+            }
+        }
+
+        // Padded, so that names can be lined up in columns.
+        f.pad(&escaped)
     }
 }
 
 // An easier to use HogRecord, derived from the RawHogRecord, taking care of
 // things such as endianness of the length field, and sanitizing the filename.
+//
+// It is displayed as its filename, escaped the way EscapedName does.
+#[derive(Debug)]
 pub struct HogRecord {
     pub filename: PathBuf,
     pub length: u32,
@@ -63,7 +177,7 @@ impl TryFrom<&RawHogRecord> for HogRecord {
 
     fn try_from(raw_hdr: &RawHogRecord) -> Result<Self, Self::Error> {
         Ok(HogRecord {
-            filename: raw_hdr.filename_as_str()?.into(),
+            filename: decode_name(&raw_hdr.filename)?.into(),
 
             // Raw record format is little endian, so convert to platform
             // native.
@@ -72,9 +186,192 @@ impl TryFrom<&RawHogRecord> for HogRecord {
     }
 }
 
+impl fmt::Display for HogRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        EscapedName(&self.filename).fmt(f)
+    }
+}
+
+impl HogRecord {
+    /// Returns the warnings about the file: a name that is not a plain
+    /// filename, or that the game may not be able to load, or being empty.
+    pub fn warnings(&self) -> Vec<HogWarning> {
+        let mut warnings = Vec::new();
+        let name = self.filename.to_string_lossy();
+
+        if !is_plain_name(&name) {
+            warnings.push(HogWarning::UnsafeName(self.filename.clone()));
+        } else if !is_dos_filename(&name) {
+            warnings.push(HogWarning::SuspiciousName(self.filename.clone()));
+        }
+
+        if self.length == 0 {
+            warnings.push(HogWarning::EmptyFile(self.filename.clone()));
+        }
+
+        warnings
+    }
+}
+
+/// Length of the on disk HOG record header.
+pub const HDR_LEN: usize = std::mem::size_of::<RawHogRecord>();
+
+// The record header of hogz files, which also has the length of the file
+// contents as stored (compressed).
+#[derive(Pod, Zeroable, Copy, Clone)]
+#[repr(C, packed)]
+struct RawHogzRecord {
+    filename: [u8; NAME_LEN],
+    stored_length: u32,
+    length: u32,
+}
+
+const HOGZ_HDR_LEN: u64 = std::mem::size_of::<RawHogzRecord>() as u64;
+
+/// What a hogz file stores about its files besides their names and contents.
+/// This is kept in a metadata record at the end of the hogz file (a record
+/// with an empty name, like a padding record), which is left out if there is
+/// nothing to store.
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct HogzMetadata {
+    // Modification times of the files by their (0 based) index, in seconds
+    // since the Unix epoch.
+    mtimes: HashMap<u64, u64>,
+}
+
+impl HogzMetadata {
+    /// Returns the modification time stored for the file at "index", if any.
+    pub fn mtime(&self, index: u64) -> Option<SystemTime> {
+        let secs = self.mtimes.get(&index)?;
+
+        Some(UNIX_EPOCH + Duration::from_secs(*secs))
+    }
+
+    /// Stores "mtime" as the modification time of the file at "index" (to
+    /// the second).
+    pub fn set_mtime(&mut self, index: u64, mtime: SystemTime) {
+        if let Ok(since_epoch) = mtime.duration_since(UNIX_EPOCH) {
+            self.mtimes.insert(index, since_epoch.as_secs());
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mtimes.is_empty()
+    }
+
+    // Parses the contents of a metadata record: a line for each value, like
+    // "mtime\t<index>\t<seconds>". Lines for other kinds of values are
+    // skipped, so that more can be added later.
+    fn parse(data: &[u8]) -> std::io::Result<Self> {
+        let invalid = || std::io::Error::new(ErrorKind::InvalidData, "invalid hogz metadata");
+        let text = std::str::from_utf8(data).map_err(|_| invalid())?;
+        let mut metadata = Self::default();
+
+        for line in text.lines() {
+            let fields: Vec<&str> = line.split('\t').collect();
+
+            if let ["mtime", index, secs] = fields[..] {
+                let index = index.parse().map_err(|_| invalid())?;
+                let secs = secs.parse().map_err(|_| invalid())?;
+
+                metadata.mtimes.insert(index, secs);
+            }
+        }
+
+        Ok(metadata)
+    }
+
+    // Returns the contents of the metadata record.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut mtimes: Vec<_> = self.mtimes.iter().collect();
+        mtimes.sort();
+
+        mtimes
+            .iter()
+            .map(|(index, secs)| format!("mtime\t{}\t{}\n", index, secs))
+            .collect::<String>()
+            .into_bytes()
+    }
+}
+
+/// Converts the hogz file read from "r" (starting at its signature) to a
+/// regular HOG file in memory, decompressing every file.
+pub fn decode_hogz(r: &mut impl Read) -> std::io::Result<Vec<u8>> {
+    Ok(decode_hogz_with_metadata(r, &Limits::default())?.0)
+}
+
+/// Converts a hogz file like decode_hogz(), also returning its metadata. The
+/// HOG file it is converted to must stay within "limits" (the error when it
+/// does not wraps a LimitExceeded, see HogError::from_io()).
+pub fn decode_hogz_with_metadata(
+    r: &mut impl Read,
+    limits: &Limits,
+) -> std::io::Result<(Vec<u8>, HogzMetadata)> {
+    let invalid = |msg: &str| std::io::Error::new(ErrorKind::InvalidData, msg.to_string());
+
+    let mut signature = [0; 3];
+    r.read_exact(&mut signature)?;
+
+    if signature != HOGZ_SIGNATURE {
+        return Err(invalid("not a hogz file"));
+    }
+
+    let mut data = limits.writer(HOG_SIGNATURE.to_vec());
+    let mut metadata = HogzMetadata::default();
+    let mut hdr = Vec::with_capacity(HOGZ_HDR_LEN as usize);
+
+    loop {
+        hdr.clear();
+        r.take(HOGZ_HDR_LEN).read_to_end(&mut hdr)?;
+
+        if hdr.is_empty() {
+            return Ok((data.into_inner(), metadata));
+        }
+
+        if hdr.len() as u64 != HOGZ_HDR_LEN {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+
+        let raw_hdr: RawHogzRecord = bytemuck::pod_read_unaligned(&hdr);
+        let length = u32::from_le(raw_hdr.length);
+        let stored_length = u64::from(u32::from_le(raw_hdr.stored_length));
+
+        if raw_hdr.filename == [0; NAME_LEN] {
+            let mut contents = limits.writer(Vec::new());
+            std::io::copy(&mut ZlibDecoder::new(r.take(stored_length)), &mut contents)?;
+
+            metadata = HogzMetadata::parse(contents.get_ref())?;
+            continue;
+        }
+
+        data.write_all(&raw_hdr.filename)?;
+        data.write_all(&length.to_le_bytes())?;
+
+        // Decompressing stops one byte past the stored length, so that
+        // contents that expand far beyond it are caught without holding all
+        // of them.
+        let mut contents = ZlibDecoder::new(r.take(stored_length)).take(u64::from(length) + 1);
+        let start = data.get_ref().len();
+        std::io::copy(&mut contents, &mut data)?;
+
+        if data.get_ref().len() - start != length as usize {
+            return Err(invalid("hogz file contents do not have the stored length"));
+        }
+    }
+}
+
+impl RawHogRecord {
+    // Padding records (written by HogFileWriter::set_align()) have a filename
+    // field of all NUL bytes. Their contents are just there to take up space.
+    // Other tools may have meant them as files, so skipping them is reported
+    // (see HogRecordIter::padding_before()).
+    fn is_padding(&self) -> bool {
+        self.filename == [0; NAME_LEN]
+    }
+}
+
 // Attempt to read a HOG file record header, consuming just the header.
-fn read_record_header(r: &mut impl Read) -> Result<Option<HogRecord>, HogError> {
-    const HDR_LEN: usize = std::mem::size_of::<RawHogRecord>();
+fn read_record_header(r: &mut impl Read) -> Result<Option<RawHogRecord>, HogError> {
     let mut raw_bytes = [0; HDR_LEN];
     let mut offset = 0;
 
@@ -92,9 +389,7 @@ fn read_record_header(r: &mut impl Read) -> Result<Option<HogRecord>, HogError>
                     offset += len;
 
                     if offset == HDR_LEN {
-                        let raw_hdr: &RawHogRecord = bytemuck::from_bytes(&raw_bytes);
-
-                        return Ok(Some(raw_hdr.try_into()?));
+                        return Ok(Some(*bytemuck::from_bytes(&raw_bytes)));
                     }
                 }
             }
@@ -106,9 +401,244 @@ fn read_record_header(r: &mut impl Read) -> Result<Option<HogRecord>, HogError>
     }
 }
 
+/// Rules for which filenames may be added to a HOG file. The built in rules
+/// are those of StandardNamePolicy, other programs embedding this library can
+/// implement their own (for example, to allow the long filenames a source port
+/// supports, up to the 12 bytes the filename field can hold).
+pub trait FilenamePolicy: Send + Sync {
+    /// Checks whether "name" may be stored in a HOG file, returning an Err
+    /// (such as HogError::NameNotAllowed) if not. Only names that fit in the
+    /// filename field of the record header are passed to this.
+    fn check_name(&self, name: &str) -> Result<(), HogError>;
+}
+
+/// The name policies built into hogdump.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StandardNamePolicy {
+    /// DOS 8.3 filenames, made up of ASCII characters DOS allows in
+    /// filenames. This is what the original game can actually load.
+    Dos,
+
+    /// Anything that fits in the 13 byte filename field of the record header.
+    Loose,
+}
+
+/// The name StandardNamePolicy had before other policies could be
+/// implemented, kept so that code using it still builds.
+pub type NamePolicy = StandardNamePolicy;
+
+impl FilenamePolicy for StandardNamePolicy {
+    fn check_name(&self, name: &str) -> Result<(), HogError> {
+        if *self == StandardNamePolicy::Dos && !is_dos_filename(name) {
+            return Err(HogError::InvalidDosFilename(name.to_string()));
+        }
+
+        Ok(())
+    }
+}
+
+/// How to change the case of filenames.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NameCase {
+    Upper,
+    Lower,
+    Preserve,
+}
+
+impl NameCase {
+    /// Returns "name" with its case changed. Only ASCII letters are changed.
+    pub fn apply(&self, name: &str) -> String {
+        match self {
+            NameCase::Upper => name.to_ascii_uppercase(),
+            NameCase::Lower => name.to_ascii_lowercase(),
+            NameCase::Preserve => name.to_string(),
+        }
+    }
+}
+
+/// How much is done to make sure a HOG file is safely on disk when
+/// HogFileWriter::finish() returns.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Durability {
+    /// Nothing: data that is still buffered is written when the file is
+    /// closed, and errors writing it are not noticed.
+    None,
+
+    /// The buffered data is written out, and errors doing so are returned.
+    #[default]
+    Flush,
+
+    /// Like Flush, and the file is synced to disk.
+    Fsync,
+
+    /// Like Fsync, and the directory containing the file is synced too, so
+    /// that the file itself survives a crash.
+    FsyncDir,
+}
+
+// Characters (other than letters and digits) that DOS allows in filenames.
+const DOS_FILENAME_CHARS: &[u8] = b"!#$%&'()-@^_`{}~";
+
+// Checks that "name" is a valid DOS 8.3 filename: a 1 to 8 character base
+// name, optionally followed by a dot and a 1 to 3 character extension.
+fn is_dos_filename(name: &str) -> bool {
+    let valid_chars = |part: &str| {
+        part.bytes()
+            .all(|c| c.is_ascii_alphanumeric() || DOS_FILENAME_CHARS.contains(&c))
+    };
+
+    let (base, ext) = match name.split_once('.') {
+        Some((base, ext)) => (base, Some(ext)),
+        None => (name, None),
+    };
+
+    if base.is_empty() || base.len() > 8 || !valid_chars(base) {
+        return false;
+    }
+
+    match ext {
+        Some(ext) => !ext.is_empty() && ext.len() <= 3 && valid_chars(ext),
+        None => true,
+    }
+}
+
+/// Returns the filename that is stored in a HOG file when adding the file at
+/// "path" (the base name of the file).
+///
+/// If the filename cannot be represented in a HOG file, or is not allowed by
+/// "policy", an Err is returned.
+pub fn hog_filename(
+    path: &impl AsRef<Path>,
+    policy: &(impl FilenamePolicy + ?Sized),
+) -> Result<String, HogError> {
+    let file_name = match path.as_ref().file_name() {
+        Some(x) => x.to_string_lossy(),
+        None => {
+            return Err(HogError::BadHogFilename(
+                path.as_ref().to_string_lossy().into_owned(),
+            ))
+        }
+    };
+
+    if file_name.len() >= 13 {
+        return Err(HogError::HogFilenameTooLong);
+    }
+
+    policy.check_name(&file_name)?;
+
+    Ok(file_name.into_owned())
+}
+
+/// Like hog_filename(), but non-ASCII characters in the filename are first
+/// replaced by their closest ASCII equivalents (see transliterate()), so that
+/// the stored name is one the original game can display.
+pub fn hog_filename_transliterated(
+    path: &impl AsRef<Path>,
+    policy: &(impl FilenamePolicy + ?Sized),
+) -> Result<String, HogError> {
+    match path.as_ref().file_name() {
+        Some(name) => hog_filename(&transliterate(&name.to_string_lossy()), policy),
+        None => hog_filename(path, policy),
+    }
+}
+
+// Returns the closest ASCII equivalent of the non-ASCII character "c", for
+// the Latin-1 letters and symbols and the most common other European letters.
+fn ascii_equivalent(c: char) -> Option<&'static str> {
+    let ascii = match c {
+        'À' | 'Á' | 'Â' | 'Ã' | 'Ä' | 'Å' | 'Ā' | 'Ă' | 'Ą' => "A",
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+        'Æ' => "AE",
+        'æ' => "ae",
+        'Ç' | 'Ć' | 'Č' => "C",
+        'ç' | 'ć' | 'č' => "c",
+        'Ð' | 'Ď' | 'Đ' => "D",
+        'ð' | 'ď' | 'đ' => "d",
+        'È' | 'É' | 'Ê' | 'Ë' | 'Ē' | 'Ė' | 'Ę' | 'Ě' => "E",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ė' | 'ę' | 'ě' => "e",
+        'Ğ' => "G",
+        'ğ' => "g",
+        'Ì' | 'Í' | 'Î' | 'Ï' | 'Ī' | 'İ' => "I",
+        'ì' | 'í' | 'î' | 'ï' | 'ī' | 'ı' => "i",
+        'Ł' => "L",
+        'ł' => "l",
+        'Ñ' | 'Ń' | 'Ň' => "N",
+        'ñ' | 'ń' | 'ň' => "n",
+        'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ö' | 'Ø' | 'Ō' | 'Ő' => "O",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ő' => "o",
+        'Œ' => "OE",
+        'œ' => "oe",
+        'Ř' => "R",
+        'ř' => "r",
+        'Ś' | 'Ş' | 'Š' => "S",
+        'ś' | 'ş' | 'š' => "s",
+        'ß' => "ss",
+        'Ţ' | 'Ť' => "T",
+        'ţ' | 'ť' => "t",
+        'Þ' => "TH",
+        'þ' => "th",
+        'Ù' | 'Ú' | 'Û' | 'Ü' | 'Ū' | 'Ů' | 'Ű' => "U",
+        'ù' | 'ú' | 'û' | 'ü' | 'ū' | 'ů' | 'ű' => "u",
+        '×' => "x",
+        'Ý' | 'Ÿ' => "Y",
+        'ý' | 'ÿ' => "y",
+        'Ź' | 'Ż' | 'Ž' => "Z",
+        'ź' | 'ż' | 'ž' => "z",
+        _ => return None,
+    };
+
+    Some(ascii)
+}
+
+/// Returns "name" with each non-ASCII character replaced by its closest ASCII
+/// equivalent, such as "e" for "é" or "ss" for "ß". Characters that have none
+/// are replaced by "_".
+///
+/// Only letters of the Latin script (the Latin-1 ones and the most common
+/// other European ones) have an equivalent. Names in other scripts, such as
+/// Greek, Cyrillic or Japanese, end up as little more than underscores.
+pub fn transliterate(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            _ if c.is_ascii() => c.to_string(),
+            _ => ascii_equivalent(c).unwrap_or("_").to_string(),
+        })
+        .collect()
+}
+
+// Size of the buffer HogFileWriter copies files through.
+const COPY_BUF_LEN: usize = 64 * 1024;
+
+// How much memory is reserved up front for a file read into memory. The rest
+// is only allocated as it is read, since the length in a record header may be
+// far larger than what the HOG file holds.
+const RESERVE_LEN: u64 = 64 * 1024;
+
 // A helper struct used to create new HOG files on disk.
 pub struct HogFileWriter {
-    file: BufWriter<File>,
+    file: HogOutput,
+    path: PathBuf,
+    durability: Durability,
+    name_policy: Box<dyn FilenamePolicy>,
+    name_case: NameCase,
+    transliterate: bool,
+    allow_empty: bool,
+    align: u32,
+
+    // Store the files compressed, in a hogz file.
+    hogz: bool,
+
+    // Written at the end of a hogz file, by finish().
+    hogz_metadata: HogzMetadata,
+
+    // Number of files appended so far.
+    num_records: u64,
+
+    // Offset that the next record header is written at.
+    offset: u64,
+
+    // Buffer the added files are copied through, reused for every file.
+    buf: Vec<u8>,
 }
 
 impl HogFileWriter {
@@ -117,157 +647,979 @@ impl HogFileWriter {
     /// If this function encounters an error opening the file, or writing the
     /// magic signature bytes, it returns an Err.
     pub fn create(path: &impl AsRef<Path>) -> Result<Self, HogError> {
+        Self::create_compressed(path, None)
+    }
+
+    /// Creates a new HOG file like create(), which is compressed with
+    /// "compression" (if given) as it is written.
+    pub fn create_compressed(
+        path: &impl AsRef<Path>,
+        compression: Option<Compression>,
+    ) -> Result<Self, HogError> {
+        Self::create_with(path, compression, false)
+    }
+
+    /// Creates a new hogz file (see HOGZ_SIGNATURE), which the game cannot
+    /// read. It is written like a HOG file, except that the contents of every
+    /// file are compressed. Alignment does not apply to hogz files.
+    pub fn create_hogz(path: &impl AsRef<Path>) -> Result<Self, HogError> {
+        Self::create_with(path, None, true)
+    }
+
+    fn create_with(
+        path: &impl AsRef<Path>,
+        compression: Option<Compression>,
+        hogz: bool,
+    ) -> Result<Self, HogError> {
         let file = File::create(path).map_err(HogError::OpenHogFailure)?;
-        let mut file = BufWriter::new(file);
+        let mut file = HogOutput::new(file, compression);
+        let signature = if hogz { HOGZ_SIGNATURE } else { HOG_SIGNATURE };
 
-        file.write_all(&HOG_SIGNATURE)
+        file.write_all(&signature)
             .map_err(HogError::SignatureWriteFailure)?;
 
-        Ok(Self { file })
+        Ok(Self {
+            file,
+            path: path.as_ref().to_path_buf(),
+            durability: Durability::Flush,
+            name_policy: Box::new(StandardNamePolicy::Loose),
+            name_case: NameCase::Preserve,
+            transliterate: false,
+            allow_empty: true,
+            align: 1,
+            hogz,
+            hogz_metadata: HogzMetadata::default(),
+            num_records: 0,
+            offset: signature.len() as u64,
+            buf: vec![0; COPY_BUF_LEN],
+        })
     }
 
-    /// Appends a HOG file record header and the files contents to this HOG
-    /// file.
-    ///
-    /// Note that thare are special restrictions on the filenames that can be
-    /// added to a HOG file.  In general, the file name is made up of 13 or
-    /// fewer ASCII characters. This function will return an error if the
-    /// filename cannot be represented in a HOG file.
-    pub fn append_file(&mut self, path: &impl AsRef<Path>) -> Result<u64, HogError> {
-        let in_file = File::open(path).map_err(HogError::OpenInputFailure)?;
-        let mut in_file = BufReader::new(in_file);
-        let file_len = in_file
-            .get_ref()
-            .metadata()
-            .map_err(HogError::AppendToHogFailure)?
-            .len();
+    /// Finishes writing the HOG file. Dropping the HogFileWriter also writes
+    /// out uncompressed and gzip compressed HOG files, but any errors are lost
+    /// and the metadata of hogz files is left out. Xz compressed HOG files are
+    /// only compressed here, so nothing is written for them at all unless
+    /// this is called.
+    pub fn finish(mut self) -> Result<(), HogError> {
+        if self.hogz && !self.hogz_metadata.is_empty() {
+            let metadata = self.hogz_metadata.to_bytes();
+            let hdr = RawHogRecord {
+                filename: [0; NAME_LEN],
+                length: u32::to_le(metadata.len() as u32),
+            };
 
-        if file_len > u32::MAX.into() {
-            return Err(HogError::FileTooLarge(file_len));
+            self.append_hogz_record("", &hdr, metadata.len() as u64, &mut metadata.as_slice())?;
         }
 
-        let file_name = match path.as_ref().file_name() {
-            Some(x) => x.to_string_lossy(),
-            None => {
-                return Err(HogError::BadHogFilename(
-                    path.as_ref().to_string_lossy().into_owned(),
-                ))
-            }
-        };
+        let file = self
+            .file
+            .finish_compression()
+            .map_err(HogError::AppendToHogFailure)?;
 
-        let mut out_filename: Vec<u8> = file_name.bytes().collect();
-        if out_filename.len() >= 13 {
-            return Err(HogError::HogFilenameTooLong);
+        if self.durability == Durability::None {
+            return Ok(());
         }
 
-        out_filename.resize(13, 0);
+        let file = file
+            .into_inner()
+            .map_err(|e| HogError::AppendToHogFailure(e.into_error()))?;
 
-        let hdr = RawHogRecord {
-            filename: out_filename.try_into().unwrap(),
+        if self.durability == Durability::Flush {
+            return Ok(());
+        }
 
-            // Convert to LE when storing into the raw record.
-            length: u32::to_le(file_len as u32),
-        };
+        file.sync_all().map_err(HogError::AppendToHogFailure)?;
 
-        self.file
-            .write_all(bytemuck::bytes_of(&hdr))
-            .map_err(HogError::AppendToHogFailure)?;
+        if self.durability == Durability::FsyncDir {
+            util::sync_parent_dir(&self.path).map_err(HogError::AppendToHogFailure)?;
+        }
 
-        std::io::copy(&mut in_file, &mut self.file).map_err(HogError::AppendToHogFailure)
+        Ok(())
     }
-}
 
-// A helper struct used to read HOG files from disk.
-pub struct HogFileReader {
-    file: BufReader<File>,
-}
+    /// Sets how much finish() does to make sure the HOG file is safely on
+    /// disk. The default is Durability::Flush.
+    pub fn set_durability(&mut self, durability: Durability) {
+        self.durability = durability;
+    }
 
-impl HogFileReader {
-    /// Opens an existing HOG file.
-    ///
-    /// If this function encounters an error opening the file, or validating the magic signature,
-    /// it returns an Err.
-    pub fn open(path: &impl AsRef<Path>) -> Result<Self, HogError> {
-        let file = File::open(path).map_err(HogError::OpenHogFailure)?;
-        let mut file = BufReader::new(file);
-        let mut signature = [0; 3];
+    /// Sets the modification time stored for the file appended last. Only
+    /// hogz files store modification times, this does nothing for HOG files.
+    /// append_file() and append_file_as() store the modification time of the
+    /// file they add by themselves.
+    pub fn set_mtime(&mut self, mtime: SystemTime) {
+        if self.hogz && self.num_records > 0 {
+            self.hogz_metadata.set_mtime(self.num_records - 1, mtime);
+        }
+    }
 
-        file.read_exact(&mut signature)
-            .map_err(HogError::SignatureReadFailure)?;
+    /// Sets the rules for which filenames may be added to the HOG file by
+    /// append_file(). The default is StandardNamePolicy::Loose.
+    pub fn set_name_policy(&mut self, policy: impl FilenamePolicy + 'static) {
+        self.name_policy = Box::new(policy);
+    }
 
-        if signature != HOG_SIGNATURE {
-            return Err(HogError::InvalidSignature);
-        }
+    /// Sets how the case of filenames is changed when they are stored in the
+    /// HOG file by append_file(). The default is NameCase::Preserve.
+    pub fn set_name_case(&mut self, case: NameCase) {
+        self.name_case = case;
+    }
 
-        Ok(Self { file })
+    /// Sets whether non-ASCII characters in the names of the files added by
+    /// append_file() and append_reader() are replaced by their closest ASCII
+    /// equivalents (see transliterate()). The default is to keep them.
+    pub fn set_transliterate(&mut self, transliterate: bool) {
+        self.transliterate = transliterate;
     }
 
-    /// Returns an iterator over the HOG file records.
-    ///
-    /// The underlying file is rewound first, meaning the iterator always starts at the beginning
-    /// of the file. If the rewind fails, an error will be returned instead of the iterator.
-    pub fn records(&mut self) -> Result<HogRecordIter, HogError> {
-        self.file
-            .seek(SeekFrom::Start(3))
-            .map_err(HogError::SeekFailure)?;
+    // Returns the name the file at "path" is stored as, following the name
+    // policy, name case and transliteration settings.
+    fn stored_name(&self, path: &impl AsRef<Path>) -> Result<String, HogError> {
+        let stored_name = if self.transliterate {
+            hog_filename_transliterated(path, self.name_policy.as_ref())?
+        } else {
+            hog_filename(path, self.name_policy.as_ref())?
+        };
 
-        Ok(HogRecordIter {
-            hogfile: self,
-            cur_file_len: None,
-            hit_error: false,
-        })
+        Ok(self.name_case.apply(&stored_name))
     }
-}
 
-// A HogRecord Iterator that cann be used to walk over the individual files in
-// the HOG file.
-pub struct HogRecordIter<'a> {
-    hogfile: &'a mut HogFileReader,
-    cur_file_len: Option<u64>,
-    hit_error: bool,
-}
+    /// Sets whether empty (zero length) files may be added to the HOG file.
+    /// When not allowed, adding an empty file returns HogError::EmptyFile and
+    /// nothing is written. The default is to allow them.
+    pub fn set_allow_empty(&mut self, allow: bool) {
+        self.allow_empty = allow;
+    }
 
-impl<'a> Iterator for HogRecordIter<'a> {
-    type Item = Result<HogRecord, HogError>;
+    /// Sets the alignment of the contents of the files added from now on: the
+    /// contents of each file start at a multiple of "align" bytes from the
+    /// start of the HOG file. The default (1) adds no padding.
+    ///
+    /// The padding is stored as padding records (nameless files), which are
+    /// skipped when reading the HOG file.
+    pub fn set_align(&mut self, align: u32) {
+        self.align = align.max(1);
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.hit_error {
-            return None;
+    // Writes a padding record, if one is needed to align the contents of the
+    // next file.
+    fn write_padding(&mut self) -> Result<(), HogError> {
+        let align = u64::from(self.align);
+        let misalignment = (self.offset + HDR_LEN as u64) % align;
+
+        if misalignment == 0 {
+            return Ok(());
         }
 
-        match self.cur_file_len.take() {
-            Some(length) => {
-                // User did not copy on skip the file, so just skip it.
-                match self.hogfile.file.seek(SeekFrom::Current(length as i64)) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        self.hit_error = true;
+        // The padding record moves the next record by its header as well as
+        // its contents.
+        let gap = align - misalignment;
+        let length = (gap + align - HDR_LEN as u64 % align) % align;
 
-                        return Some(Err(HogError::SeekFailure(e)));
-                    }
-                }
-            }
-            None => {}
+        let hdr = RawHogRecord {
+            filename: [0; NAME_LEN],
+            length: u32::to_le(length as u32),
+        };
+
+        util::copy_n_after(
+            &mut std::io::repeat(0),
+            &mut self.file,
+            bytemuck::bytes_of(&hdr),
+            length,
+            &mut self.buf,
+        )
+        .map_err(HogError::AppendToHogFailure)?;
+
+        self.offset += HDR_LEN as u64 + length;
+
+        Ok(())
+    }
+
+    /// Appends a HOG file record header and the files contents to this HOG
+    /// file.
+    ///
+    /// Note that thare are special restrictions on the filenames that can be
+    /// added to a HOG file.  In general, the file name is made up of 13 or
+    /// fewer ASCII characters. This function will return an error if the
+    /// filename cannot be represented in a HOG file, or is not allowed by the
+    /// name policy of this HOG file.
+    ///
+    /// Returns the record that was written, containing the filename and length
+    /// as stored in the HOG file.
+    pub fn append_file(&mut self, path: &impl AsRef<Path>) -> Result<HogRecord, HogError> {
+        let stored_name = self.stored_name(path)?;
+
+        self.append_file_as(path, &stored_name)
+    }
+
+    /// Appends a file to the HOG file, stored as "stored_name" rather than the
+    /// name of the file on disk. The name is stored exactly as given, the name
+    /// policy and name case of this HOG file are not applied.
+    ///
+    /// If this function encounters an error reading the file, or writing to
+    /// the HOG file, or the filename is too long to be stored in a HOG file,
+    /// it returns an Err.
+    pub fn append_file_as(
+        &mut self,
+        path: &impl AsRef<Path>,
+        stored_name: &str,
+    ) -> Result<HogRecord, HogError> {
+        if stored_name.len() >= 13 {
+            return Err(HogError::HogFilenameTooLong);
         }
 
-        let hdr = read_record_header(&mut self.hogfile.file);
+        let in_file = File::open(path).map_err(HogError::OpenInputFailure)?;
+        let mut in_file = BufReader::new(in_file);
+        let metadata = in_file
+            .get_ref()
+            .metadata()
+            .map_err(HogError::AppendToHogFailure)?;
 
-        match hdr {
-            Ok(Some(hdr)) => {
-                self.cur_file_len = Some(hdr.length.into());
+        let record = self.append_record(stored_name, metadata.len(), &mut in_file)?;
 
-                Some(Ok(hdr))
-            }
-            Ok(None) => None,
-            Err(x) => Some(Err(x)),
+        if let Ok(mtime) = metadata.modified() {
+            self.set_mtime(mtime);
         }
+
+        Ok(record)
     }
-}
 
-// While iterating over each HogRecord, copy_cur_file() can be called on the
-// iterator to save the contents of the last emitted HogRecord to an arbitrary
-// Writer. If copy_cur_file() is not called on the item, then the body of the
-// file is simply skipped by the Iterator when moving on to the next file.
+    /// Appends "length" bytes read from "reader" to the HOG file. The file is
+    /// stored with the base name of "path", like append_file() does (but
+    /// "path" is not opened).
+    ///
+    /// If "reader" ends before "length" bytes were read, an Err is returned,
+    /// and the HOG file is left incomplete.
+    pub fn append_reader(
+        &mut self,
+        path: &impl AsRef<Path>,
+        length: u64,
+        reader: &mut impl Read,
+    ) -> Result<HogRecord, HogError> {
+        let stored_name = self.stored_name(path)?;
+
+        self.append_record(&stored_name, length, reader)
+    }
+
+    /// Appends "length" bytes read from "reader" to the HOG file, stored as
+    /// "stored_name" exactly, like append_file_as() does.
+    pub fn append_reader_as(
+        &mut self,
+        stored_name: &str,
+        length: u64,
+        reader: &mut impl Read,
+    ) -> Result<HogRecord, HogError> {
+        if stored_name.len() >= 13 {
+            return Err(HogError::HogFilenameTooLong);
+        }
+
+        self.append_record(stored_name, length, reader)
+    }
+
+    // Writes a record header for a file of "length" bytes, followed by the
+    // file contents read from "reader".
+    fn append_record(
+        &mut self,
+        stored_name: &str,
+        length: u64,
+        reader: &mut impl Read,
+    ) -> Result<HogRecord, HogError> {
+        if length > u32::MAX.into() {
+            return Err(HogError::FileTooLarge(length));
+        }
+
+        if length == 0 && !self.allow_empty {
+            return Err(HogError::EmptyFile);
+        }
+
+        // Whatever the name policy, a name that would be taken as a path is
+        // never stored.
+        if !is_plain_name(stored_name) {
+            return Err(HogError::UnsafeName(stored_name.to_string()));
+        }
+
+        let hdr = RawHogRecord {
+            filename: encode_name(stored_name)?,
+
+            // Convert to LE when storing into the raw record.
+            length: u32::to_le(length as u32),
+        };
+
+        if self.hogz {
+            return self.append_hogz_record(stored_name, &hdr, length, reader);
+        }
+
+        self.write_padding()?;
+
+        let copied = util::copy_n_after(
+            reader,
+            &mut self.file,
+            bytemuck::bytes_of(&hdr),
+            length,
+            &mut self.buf,
+        )
+        .map_err(HogError::AppendToHogFailure)?;
+
+        if copied != length {
+            return Err(HogError::AppendToHogFailure(std::io::Error::new(
+                ErrorKind::UnexpectedEof,
+                format!("expected {} bytes, found {}", length, copied),
+            )));
+        }
+
+        self.offset += HDR_LEN as u64 + length;
+        self.num_records += 1;
+
+        Ok(HogRecord {
+            filename: stored_name.into(),
+            length: length as u32,
+        })
+    }
+}
+
+impl HogFileWriter {
+    // Like append_record(), for hogz files: the file contents are compressed
+    // into memory first, since the header has to have their compressed length.
+    fn append_hogz_record(
+        &mut self,
+        stored_name: &str,
+        hdr: &RawHogRecord,
+        length: u64,
+        reader: &mut impl Read,
+    ) -> Result<HogRecord, HogError> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+
+        let copied =
+            util::copy_n(reader, &mut encoder, length).map_err(HogError::AppendToHogFailure)?;
+
+        if copied != length {
+            return Err(HogError::AppendToHogFailure(std::io::Error::new(
+                ErrorKind::UnexpectedEof,
+                format!("expected {} bytes, found {}", length, copied),
+            )));
+        }
+
+        let data = encoder.finish().map_err(HogError::AppendToHogFailure)?;
+        let stored_length =
+            u32::try_from(data.len()).map_err(|_| HogError::FileTooLarge(data.len() as u64))?;
+
+        let hogz_hdr = RawHogzRecord {
+            filename: hdr.filename,
+            stored_length: u32::to_le(stored_length),
+            length: hdr.length,
+        };
+
+        self.file
+            .write_all(bytemuck::bytes_of(&hogz_hdr))
+            .and_then(|_| util::copy_n(&mut data.as_slice(), &mut self.file, data.len() as u64))
+            .map_err(HogError::AppendToHogFailure)?;
+
+        self.offset += HOGZ_HDR_LEN + data.len() as u64;
+
+        if !stored_name.is_empty() {
+            self.num_records += 1;
+        }
+
+        Ok(HogRecord {
+            filename: stored_name.into(),
+            length: length as u32,
+        })
+    }
+}
+
+// A helper struct used to read HOG files, from disk or from any other
+// seekable reader.
+pub struct HogFileReader<R = HogInput> {
+    file: R,
+
+    // The signature as found, and the offset of the first record header
+    // (right after it). Only the lenient readers accept a signature that is
+    // not "DHF" at the start of the file.
+    signature: [u8; 3],
+    start: u64,
+
+    // Used by find(), built by the first call to it unless one was given.
+    index: Option<HogIndex>,
+
+    // Most bytes of slack between records that HogRecordIter skips (see
+    // set_tolerate_slack()).
+    tolerate_slack: u64,
+
+    // Whether HogRecordIter returns an error for names that are not plain
+    // filenames (see set_strict_names()).
+    strict_names: bool,
+}
+
+impl HogFileReader {
+    /// Opens an existing HOG file. Gzip and xz compressed HOG files are
+    /// detected, and decompressed into memory.
+    ///
+    /// If this function encounters an error opening the file, or validating the magic signature,
+    /// it returns an Err.
+    pub fn open(path: &impl AsRef<Path>) -> Result<Self, HogError> {
+        let file = HogInput::open(path).map_err(HogError::OpenHogFailure)?;
+
+        Self::from_reader(file)
+    }
+
+    /// Opens an existing HOG file like open(), but decompressing it fails
+    /// with HogError::LimitExceeded if that goes past "limits".
+    pub fn open_limited(path: &impl AsRef<Path>, limits: &Limits) -> Result<Self, HogError> {
+        let file = HogInput::open_limited(path, limits)
+            .map_err(|e| HogError::from_io(e, HogError::OpenHogFailure))?;
+
+        Self::from_reader(file)
+    }
+
+    /// Opens an existing HOG file like open(), using "index" (such as one
+    /// saved from an earlier open) to find files instead of walking the
+    /// records of the HOG file, if it belongs to this HOG file.
+    pub fn open_with_index(path: &impl AsRef<Path>, index: HogIndex) -> Result<Self, HogError> {
+        Self::open(path)?.with_index(index)
+    }
+
+    /// Opens an existing HOG file like open(), but accepts a signature that
+    /// is in lowercase, or that comes after some junk bytes, as some tools
+    /// write them (see from_reader_lenient()).
+    pub fn open_lenient(path: &impl AsRef<Path>) -> Result<Self, HogError> {
+        let file = HogInput::open(path).map_err(HogError::OpenHogFailure)?;
+
+        Self::from_reader_lenient(file)
+    }
+}
+
+impl<R: Read + Seek> HogFileReader<R> {
+    /// Reads a HOG file from "file", which must be positioned at the start of
+    /// the HOG file.
+    ///
+    /// If this function encounters an error validating the magic signature,
+    /// it returns an Err.
+    pub fn from_reader(mut file: R) -> Result<Self, HogError> {
+        let mut head = Vec::new();
+
+        (&mut file)
+            .take(HOG_SIGNATURE.len() as u64)
+            .read_to_end(&mut head)
+            .map_err(HogError::SignatureReadFailure)?;
+
+        // Tell a file that ends before its signature apart from an empty HOG
+        // file, which is just the signature.
+        if head.len() < HOG_SIGNATURE.len() {
+            return Err(HogError::TooShort(head.len() as u64));
+        }
+
+        let mut signature = [0; 3];
+        signature.copy_from_slice(&head);
+
+        if signature != HOG_SIGNATURE {
+            return Err(HogError::InvalidSignature);
+        }
+
+        Ok(Self {
+            file,
+            index: None,
+            signature,
+            start: HOG_SIGNATURE.len() as u64,
+            tolerate_slack: 0,
+            strict_names: false,
+        })
+    }
+
+    /// Reads a HOG file from "file" like from_reader(), but the signature can
+    /// be in any case, and can start anywhere in the first
+    /// LENIENT_SIGNATURE_SPAN bytes. signature_warning() tells whether it was
+    /// not where it should be.
+    pub fn from_reader_lenient(mut file: R) -> Result<Self, HogError> {
+        let mut head = Vec::new();
+
+        (&mut file)
+            .take(LENIENT_SIGNATURE_SPAN)
+            .read_to_end(&mut head)
+            .map_err(HogError::SignatureReadFailure)?;
+
+        if head.len() < HOG_SIGNATURE.len() {
+            return Err(HogError::TooShort(head.len() as u64));
+        }
+
+        let offset = head
+            .windows(HOG_SIGNATURE.len())
+            .position(|x| x.eq_ignore_ascii_case(&HOG_SIGNATURE))
+            .ok_or(HogError::InvalidSignature)?;
+        let mut signature = [0; 3];
+        signature.copy_from_slice(&head[offset..offset + HOG_SIGNATURE.len()]);
+
+        Ok(Self {
+            file,
+            index: None,
+            signature,
+            start: (offset + HOG_SIGNATURE.len()) as u64,
+            tolerate_slack: 0,
+            strict_names: false,
+        })
+    }
+
+    /// Sets the most bytes of slack (padding that some tools left between
+    /// records) that are skipped when reading the records of the HOG file.
+    /// When the bytes where a record header should be are not one, but one
+    /// starts within "max" bytes after them, the bytes in between are skipped
+    /// (see HogRecordIter::slack_before()). The default (0) skips nothing,
+    /// and at most MAX_SLACK bytes are skipped.
+    pub fn set_tolerate_slack(&mut self, max: u64) {
+        self.tolerate_slack = max.min(MAX_SLACK);
+    }
+
+    /// Sets whether reading a record whose name is not a plain filename (see
+    /// is_plain_name()) is an UnsafeName error. Iteration can go on after it,
+    /// with the next record. By default (false), such names are accepted,
+    /// with a warning (see HogRecord::warnings()), and are only sanitized
+    /// when extracting.
+    pub fn set_strict_names(&mut self, strict: bool) {
+        self.strict_names = strict;
+    }
+
+    /// Returns a warning if the signature was not "DHF" at the start of the
+    /// file (which only the lenient readers accept).
+    pub fn signature_warning(&self) -> Option<HogWarning> {
+        let offset = self.start - HOG_SIGNATURE.len() as u64;
+
+        if offset == 0 && self.signature == HOG_SIGNATURE {
+            return None;
+        }
+
+        Some(HogWarning::LenientSignature(
+            offset,
+            String::from_utf8_lossy(&self.signature).into_owned(),
+        ))
+    }
+
+    /// Returns the reader the HOG file is read from.
+    pub fn get_ref(&self) -> &R {
+        &self.file
+    }
+
+    /// Returns the reader the HOG file is read from, mutably.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.file
+    }
+
+    /// Uses "index" to find files, if it was built from this HOG file (its
+    /// digest matches). Otherwise, it is ignored, and find() builds the index
+    /// itself.
+    pub fn with_index(mut self, index: HogIndex) -> Result<Self, HogError> {
+        if index
+            .matches(&mut self.file)
+            .map_err(HogError::ReadFailure)?
+        {
+            self.index = Some(index);
+        }
+
+        Ok(self)
+    }
+
+    /// Returns the index used by find(), if it has been loaded or built.
+    pub fn index(&self) -> Option<&HogIndex> {
+        self.index.as_ref()
+    }
+
+    /// Returns the first file named "name" (compared without regard to
+    /// case). The records of the HOG file are walked the first time this is
+    /// called, unless an index was given.
+    pub fn find(&mut self, name: &str) -> Result<Option<IndexEntry>, HogError> {
+        Ok(self.cached_index()?.find(name).cloned())
+    }
+
+    /// Returns the file at "index" (counting from 0) in the HOG file, or None
+    /// if it contains fewer files. Like find(), this only walks the records of
+    /// the HOG file if no index was given or built yet.
+    pub fn entry_at(&mut self, index: u64) -> Result<Option<IndexEntry>, HogError> {
+        let entries = &self.cached_index()?.entries;
+
+        Ok(usize::try_from(index)
+            .ok()
+            .and_then(|x| entries.get(x))
+            .cloned())
+    }
+
+    /// Reads the contents of the files at the indexes in "range" into memory,
+    /// seeking straight to each one. Fails with IndexOutOfRange if the range
+    /// goes past the last file.
+    pub fn extract_range(
+        &mut self,
+        range: Range<u64>,
+    ) -> Result<Vec<(IndexEntry, Vec<u8>)>, HogError> {
+        let entries = &self.cached_index()?.entries;
+        let count = entries.len() as u64;
+
+        if range.end > count && range.start < range.end {
+            return Err(HogError::IndexOutOfRange(range.end - 1, count));
+        }
+
+        let entries = entries
+            .get(range.start as usize..range.end as usize)
+            .unwrap_or_default()
+            .to_vec();
+
+        entries
+            .into_iter()
+            .map(|entry| {
+                let data = self.read_entry(&entry)?;
+                Ok((entry, data))
+            })
+            .collect()
+    }
+
+    // Returns the index used by find(), building it first if needed.
+    fn cached_index(&mut self) -> Result<&HogIndex, HogError> {
+        let index = match self.index.take() {
+            Some(x) => x,
+            None => HogIndex::build(self)?,
+        };
+
+        Ok(self.index.insert(index))
+    }
+
+    /// Reads the contents of a file returned by find() or entry_at() into
+    /// memory.
+    pub fn read_entry(&mut self, entry: &IndexEntry) -> Result<Vec<u8>, HogError> {
+        let length = u64::from(entry.length);
+        let mut data = Vec::with_capacity(length.min(RESERVE_LEN) as usize);
+
+        self.file
+            .seek(SeekFrom::Start(entry.offset))
+            .map_err(HogError::SeekFailure)?;
+        util::copy_exactly_n(&mut self.file, &mut data, length).map_err(HogError::ReadFailure)?;
+
+        Ok(data)
+    }
+
+    /// Returns an iterator over the HOG file records.
+    ///
+    /// The underlying file is rewound first, meaning the iterator always starts at the beginning
+    /// of the file. If the rewind fails, an error will be returned instead of the iterator.
+    pub fn records(&mut self) -> Result<HogRecordIter<'_, R>, HogError> {
+        self.file
+            .seek(SeekFrom::Start(self.start))
+            .map_err(HogError::SeekFailure)?;
+
+        let start = self.start;
+
+        Ok(HogRecordIter {
+            hogfile: self,
+            cur_file_len: None,
+            done: false,
+            errors: 0,
+            num_records: 0,
+            next_offset: start,
+            record_index: 0,
+            record_offset: start,
+            trailing_bytes: 0,
+            slack_before: None,
+            padding_before: Vec::new(),
+        })
+    }
+
+    /// Returns an iterator over the record headers of the HOG file, exactly
+    /// as stored, for debugging. File contents are skipped without being
+    /// read, and headers that HogRecordIter would reject (such as ones with
+    /// invalid filenames) are returned too, along with padding records.
+    pub fn record_headers(&mut self) -> Result<RecordHeaderIter<'_, R>, HogError> {
+        let file_len = self
+            .file
+            .seek(SeekFrom::End(0))
+            .map_err(HogError::SeekFailure)?;
+
+        let start = self.start;
+
+        Ok(RecordHeaderIter {
+            hogfile: self,
+            next_offset: start,
+            file_len,
+            done: false,
+        })
+    }
+
+    /// Returns an iterator over the HOG file records, along with the contents
+    /// of each file, read into memory.
+    ///
+    /// If "max_len" is provided, files larger than "max_len" bytes are not
+    /// read. An error is returned for them instead, after which iteration can
+    /// continue with the next file.
+    pub fn iter_with_data(&mut self, max_len: Option<u64>) -> Result<HogDataIter<'_, R>, HogError> {
+        Ok(HogDataIter {
+            records: self.records()?,
+            max_len,
+            data_errors: 0,
+        })
+    }
+}
+
+impl HogRecordIter<'_, HogInput> {
+    /// Returns the modification time stored for the last file returned by
+    /// next(), if any (only hogz files store them).
+    pub fn mtime(&self) -> Option<SystemTime> {
+        self.hogfile.file.hogz_metadata()?.mtime(self.record_index)
+    }
+}
+
+// A HogRecord Iterator that cann be used to walk over the individual files in
+// the HOG file.
+pub struct HogRecordIter<'a, R = HogInput> {
+    hogfile: &'a mut HogFileReader<R>,
+    cur_file_len: Option<u64>,
+
+    // Set once next() has returned None, or an error iteration cannot go on
+    // after, so that it keeps returning None from then on.
+    done: bool,
+
+    // Number of errors returned by next().
+    errors: u64,
+
+    // Number of records successfully read so far, and the file offset of the
+    // next record header.
+    num_records: u64,
+    next_offset: u64,
+
+    // Index and header file offset of the last record read (or attempted to
+    // be read).
+    record_index: u64,
+    record_offset: u64,
+
+    // Number of bytes left at the end of the file that were too few to be a
+    // record header.
+    trailing_bytes: u64,
+
+    // The offset and length of the slack skipped right before the last
+    // record read.
+    slack_before: Option<(u64, u64)>,
+
+    // The offset and length of each padding record skipped right before the
+    // last record read.
+    padding_before: Vec<(u64, u64)>,
+}
+
+// Returns true if "hdr", at "offset" in a file of "end" bytes, looks like a
+// real record header: a file with a printable name, or a padding record,
+// that fits in the file.
+fn plausible_header(hdr: &RawHogRecord, offset: u64, end: u64, allow_padding: bool) -> bool {
+    let fits = offset + HDR_LEN as u64 + u64::from(u32::from_le(hdr.length)) <= end;
+
+    if hdr.is_padding() {
+        return allow_padding && fits;
+    }
+
+    match decode_name(&hdr.filename) {
+        Ok(name) => fits && !name.is_empty() && !name.chars().any(|c| c.is_control()),
+        Err(_) => false,
+    }
+}
+
+impl<R: Read + Seek> HogRecordIter<'_, R> {
+    // With slack tolerated, moves on to the next record header if the bytes
+    // at "next_offset" are not one, but one starts within tolerate_slack
+    // bytes after them. The file is left at the record header to read.
+    fn skip_slack(&mut self) -> Result<(), HogError> {
+        let file = &mut self.hogfile.file;
+        let start = self.next_offset;
+        let end = file.seek(SeekFrom::End(0)).map_err(HogError::SeekFailure)?;
+        let mut buf = Vec::new();
+
+        file.seek(SeekFrom::Start(start))
+            .map_err(HogError::SeekFailure)?;
+        file.take(self.hogfile.tolerate_slack + HDR_LEN as u64)
+            .read_to_end(&mut buf)
+            .map_err(HogError::ReadHeaderError)?;
+
+        let header_at = |skip: usize, allow_padding| {
+            buf.get(skip..skip + HDR_LEN).is_some_and(|x| {
+                let hdr: RawHogRecord = *bytemuck::from_bytes(x);
+
+                plausible_header(&hdr, start + skip as u64, end, allow_padding)
+            })
+        };
+
+        // Too few bytes for a header are left for the iterator to report.
+        let skip = if buf.len() < HDR_LEN || header_at(0, true) {
+            0
+        } else {
+            (1..=buf.len() - HDR_LEN)
+                .find(|&x| header_at(x, false))
+                .unwrap_or(0)
+        };
+
+        if skip > 0 {
+            self.slack_before = Some((start, skip as u64));
+            self.next_offset += skip as u64;
+        }
+
+        file.seek(SeekFrom::Start(self.next_offset))
+            .map_err(HogError::SeekFailure)?;
+
+        Ok(())
+    }
+
+    /// Returns the offset and the length of the slack (see
+    /// HogFileReader::set_tolerate_slack()) that was skipped right before the
+    /// last record returned, if any.
+    pub fn slack_before(&self) -> Option<(u64, u64)> {
+        self.slack_before
+    }
+
+    /// Returns the offset and the length of each padding record (a record with
+    /// a name of all NUL bytes) that was skipped right before the last record
+    /// returned, or before the end of the HOG file once next() has returned
+    /// None.
+    pub fn padding_before(&self) -> &[(u64, u64)] {
+        &self.padding_before
+    }
+}
+
+impl<'a, R: Read + Seek> Iterator for HogRecordIter<'a, R> {
+    type Item = Result<HogRecord, HogError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record = self.next_record();
+
+        match record {
+            Some(Err(_)) => self.errors += 1,
+            None => self.done = true,
+            Some(Ok(_)) => {}
+        }
+
+        record
+    }
+}
+
+impl<R: Read + Seek> FusedIterator for HogRecordIter<'_, R> {}
+
+impl<R: Read + Seek> HogRecordIter<'_, R> {
+    fn next_record(&mut self) -> Option<Result<HogRecord, HogError>> {
+        if self.done {
+            return None;
+        }
+
+        if let Some(length) = self.cur_file_len.take() {
+            // User did not copy on skip the file, so just skip it.
+            match self.hogfile.file.seek(SeekFrom::Current(length as i64)) {
+                Ok(_) => {}
+                Err(e) => {
+                    self.done = true;
+
+                    return Some(Err(HogError::SeekFailure(e)));
+                }
+            }
+        }
+
+        self.slack_before = None;
+        self.padding_before.clear();
+
+        loop {
+            if self.hogfile.tolerate_slack > 0 {
+                if let Err(e) = self.skip_slack() {
+                    self.done = true;
+
+                    return Some(Err(e));
+                }
+            }
+
+            let last_record = (self.record_index, self.record_offset);
+            self.record_index = self.num_records;
+            self.record_offset = self.next_offset;
+
+            let hdr = read_record_header(&mut self.hogfile.file);
+
+            match hdr {
+                Ok(Some(raw_hdr)) => {
+                    let length = u32::from_le(raw_hdr.length);
+
+                    // Padding records are not files, they are skipped (and
+                    // not counted).
+                    if raw_hdr.is_padding() {
+                        self.padding_before.push((self.next_offset, length.into()));
+                        self.next_offset += HDR_LEN as u64 + u64::from(length);
+
+                        if let Err(e) = self.hogfile.file.seek(SeekFrom::Current(length.into())) {
+                            self.done = true;
+
+                            return Some(Err(HogError::SeekFailure(e)));
+                        }
+
+                        continue;
+                    }
+
+                    // The length is usable even if the filename is not, so the
+                    // file can still be skipped, and iteration can continue with
+                    // the next record after an InvalidFilename error.
+                    self.cur_file_len = Some(length.into());
+                    self.num_records += 1;
+                    self.next_offset += HDR_LEN as u64 + u64::from(length);
+
+                    return Some(HogRecord::try_from(&raw_hdr).and_then(|record| {
+                        let name = record.filename.to_string_lossy();
+
+                        if self.hogfile.strict_names && !is_plain_name(&name) {
+                            return Err(HogError::UnsafeName(name.into_owned()));
+                        }
+
+                        Ok(record)
+                    }));
+                }
+                Ok(None) => {
+                    // Seeking past the end of the file works, so a last file
+                    // that was cut short is only noticed here, when the end
+                    // of the file comes before where the next record should
+                    // start.
+                    let end = match self.hogfile.file.seek(SeekFrom::End(0)) {
+                        Ok(x) => x,
+                        Err(e) => {
+                            self.done = true;
+
+                            return Some(Err(HogError::SeekFailure(e)));
+                        }
+                    };
+
+                    if end < self.next_offset {
+                        self.done = true;
+                        self.next_offset = end;
+                        (self.record_index, self.record_offset) = last_record;
+
+                        return Some(Err(HogError::UnexpectedEof));
+                    }
+
+                    return None;
+                }
+                Err(HogError::UnexpectedEof) => {
+                    // Some bytes are left, but too few for a record header.
+                    // They are not a file, so iteration just ends, and
+                    // trailing_bytes() tells how many there were.
+                    let end = match self.hogfile.file.seek(SeekFrom::End(0)) {
+                        Ok(x) => x,
+                        Err(e) => {
+                            self.done = true;
+
+                            return Some(Err(HogError::SeekFailure(e)));
+                        }
+                    };
+
+                    self.done = true;
+                    self.trailing_bytes = end - self.next_offset;
+                    self.next_offset = end;
+
+                    return None;
+                }
+                Err(x) => {
+                    self.done = true;
+
+                    return Some(Err(x));
+                }
+            }
+        }
+    }
+}
+
+// While iterating over each HogRecord, copy_cur_file() can be called on the
+// iterator to save the contents of the last emitted HogRecord to an arbitrary
+// Writer. If copy_cur_file() is not called on the item, then the body of the
+// file is simply skipped by the Iterator when moving on to the next file.
 //
 // This function is implemented for the iterator, rather than the emitted
 // element, because the iterator needs to keep track of the cursor position in
@@ -278,17 +1630,1314 @@ impl<'a> Iterator for HogRecordIter<'a> {
 // when next() is called. This should have the advantage of allowing
 // copy_cur_file to be implemented on HogRecord itself, which could move the
 // file cursor without impacting the iterator behavior.
-impl<'a> HogRecordIter<'a> {
+impl<'a, R: Read + Seek> HogRecordIter<'a, R> {
+    /// Returns the (0 based) index of the last record returned by next(), or
+    /// of the record that could not be read if next() returned an error.
+    pub fn record_index(&self) -> u64 {
+        self.record_index
+    }
+
+    /// Returns the number of bytes at the end of the HOG file that were left
+    /// over after the last file, too few to be a record header. This is only
+    /// known once next() has returned None.
+    pub fn trailing_bytes(&self) -> u64 {
+        self.trailing_bytes
+    }
+
+    /// Returns the number of errors next() has returned so far. After errors
+    /// that iteration can go on after (such as InvalidFilename), this keeps
+    /// counting the errors of the records that follow.
+    pub fn errors_encountered(&self) -> u64 {
+        self.errors
+    }
+
+    /// Returns the number of bytes of the HOG file scanned so far, counted
+    /// from the start of the file: up to the end of the last record returned
+    /// by next() (whether or not its contents were read), including any
+    /// padding records and slack skipped on the way. Once next() has returned
+    /// None after reaching the end of the file, this is the file length.
+    pub fn bytes_scanned(&self) -> u64 {
+        self.next_offset
+    }
+
+    /// Returns the offset in the HOG file of the header of the last record
+    /// returned by next(), or of the record that could not be read if next()
+    /// returned an error.
+    pub fn record_offset(&self) -> u64 {
+        self.record_offset
+    }
+
+    /// Returns the offset in the HOG file of the contents of the last file
+    /// returned by next().
+    pub fn data_offset(&self) -> u64 {
+        self.record_offset + HDR_LEN as u64
+    }
+
     /// Copy the last encountered file to the destation buffer.
+    ///
+    /// Like the other methods reading the last encountered file, this returns
+    /// a NoCurrentFile error if next() has not returned a file yet, or if the
+    /// file was already read (or skipped) by an earlier call. If reading or
+    /// writing fails part way, the file is left as it was before the call, so
+    /// next() still skips over it.
     pub fn copy_cur_file(&mut self, out_f: &mut impl Write) -> Result<(), HogError> {
         match self.cur_file_len.take() {
             Some(length) => {
-                util::copy_exactly_n(&mut self.hogfile.file, out_f, length as u64)
-                    .map_err(HogError::ExtractFailure)?;
+                util::copy_exactly_n(&mut self.hogfile.file, out_f, length).map_err(|e| {
+                    self.restore_cur_file(length);
+
+                    HogError::ExtractFailure(e)
+                })?;
+
+                Ok(())
+            }
+            None => Err(HogError::NoCurrentFile),
+        }
+    }
+
+    /// Reads all of the last encountered file, without saving it anywhere,
+    /// to check that it can be read.
+    pub fn check_cur_file(&mut self) -> Result<(), HogError> {
+        match self.cur_file_len.take() {
+            Some(length) => {
+                util::copy_exactly_n(&mut self.hogfile.file, &mut std::io::sink(), length)
+                    .map_err(|e| {
+                        self.restore_cur_file(length);
+
+                        HogError::ReadFailure(e)
+                    })?;
+
+                Ok(())
+            }
+            None => Err(HogError::NoCurrentFile),
+        }
+    }
+
+    /// Copy part of the last encountered file to the destination buffer.
+    ///
+    /// Copying starts "offset" bytes into the file, and stops after "length"
+    /// bytes, or at the end of the file (whichever comes first). If "length"
+    /// is None, the rest of the file is copied. Returns the number of bytes
+    /// copied.
+    ///
+    /// It is an error for "offset" to be past the end of the file.
+    pub fn copy_cur_file_range(
+        &mut self,
+        out_f: &mut impl Write,
+        offset: u64,
+        length: Option<u64>,
+    ) -> Result<u64, HogError> {
+        let file_len = match self.cur_file_len.take() {
+            Some(length) => length,
+            None => return Err(HogError::NoCurrentFile),
+        };
+
+        if offset > file_len {
+            self.cur_file_len = Some(file_len);
+
+            return Err(HogError::OffsetOutOfRange(offset, file_len));
+        }
+
+        if let Err(e) = self.hogfile.file.seek(SeekFrom::Current(offset as i64)) {
+            self.restore_cur_file(file_len);
+
+            return Err(HogError::SeekFailure(e));
+        }
+
+        let remaining = file_len - offset;
+        let copy_len = length.map_or(remaining, |x| std::cmp::min(x, remaining));
+
+        if let Err(e) = util::copy_exactly_n(&mut self.hogfile.file, out_f, copy_len) {
+            self.restore_cur_file(file_len);
+
+            return Err(HogError::ExtractFailure(e));
+        }
+
+        // Whatever is left after the copy is skipped over by next().
+        self.cur_file_len = Some(remaining - copy_len);
+
+        Ok(copy_len)
+    }
+
+    // Goes back to where the "length" bytes left of the last encountered file
+    // start, after reading them failed part way, so that next() can still skip
+    // over the file (or it can be read again). If even that fails, where the
+    // next record starts is lost, so iteration stops.
+    fn restore_cur_file(&mut self, length: u64) {
+        match self
+            .hogfile
+            .file
+            .seek(SeekFrom::Start(self.next_offset - length))
+        {
+            Ok(_) => self.cur_file_len = Some(length),
+            Err(_) => self.done = true,
+        }
+    }
+
+    /// Reads up to "max_len" bytes from the start of the last encountered
+    /// file. This does not consume anything, so the file can still be copied
+    /// afterwards.
+    pub fn peek_cur_file(&mut self, max_len: u64) -> Result<Vec<u8>, HogError> {
+        let file_len = match self.cur_file_len {
+            Some(length) => length,
+            None => return Err(HogError::NoCurrentFile),
+        };
+
+        let peek_len = std::cmp::min(file_len, max_len);
+        let mut data = Vec::with_capacity(peek_len as usize);
+
+        util::copy_exactly_n(&mut self.hogfile.file, &mut data, peek_len)
+            .map_err(HogError::ReadFailure)?;
+
+        self.hogfile
+            .file
+            .seek(SeekFrom::Current(-(peek_len as i64)))
+            .map_err(HogError::SeekFailure)?;
+
+        Ok(data)
+    }
+
+    /// Copies all of the last encountered file to "out_f", without consuming
+    /// it (like peek_cur_file()), for reading a file that may be too large to
+    /// peek into memory.
+    pub fn stream_cur_file(&mut self, out_f: &mut impl Write) -> Result<(), HogError> {
+        let file_len = match self.cur_file_len {
+            Some(length) => length,
+            None => return Err(HogError::NoCurrentFile),
+        };
+
+        let result = util::copy_exactly_n(&mut self.hogfile.file, out_f, file_len);
+
+        // Also put back after a failure part way, like copy_cur_file() does.
+        self.restore_cur_file(file_len);
+
+        result.map(|_| ()).map_err(HogError::ReadFailure)
+    }
+
+    /// Returns up to "max_len" bytes from the end of the last encountered
+    /// file, without consuming any of it (like peek_cur_file()).
+    pub fn peek_cur_file_tail(&mut self, max_len: u64) -> Result<Vec<u8>, HogError> {
+        let file_len = match self.cur_file_len {
+            Some(length) => length,
+            None => return Err(HogError::NoCurrentFile),
+        };
+
+        let peek_len = std::cmp::min(file_len, max_len);
+        let mut data = Vec::with_capacity(peek_len as usize);
+
+        self.hogfile
+            .file
+            .seek(SeekFrom::Current((file_len - peek_len) as i64))
+            .map_err(HogError::SeekFailure)?;
+
+        util::copy_exactly_n(&mut self.hogfile.file, &mut data, peek_len)
+            .map_err(HogError::ReadFailure)?;
+
+        self.hogfile
+            .file
+            .seek(SeekFrom::Current(-(file_len as i64)))
+            .map_err(HogError::SeekFailure)?;
+
+        Ok(data)
+    }
+
+    /// Guesses the type of the last encountered file, by looking at the first
+    /// bytes of its contents. This does not consume anything, so the file can
+    /// still be copied afterwards.
+    pub fn sniff_cur_file(&mut self) -> Result<ContentType, HogError> {
+        let data = self.peek_cur_file(sniff::SNIFF_LEN as u64)?;
+
+        Ok(sniff::sniff(&data))
+    }
+}
+
+/// A record header, as stored in the HOG file.
+pub struct RecordHeader {
+    /// Offset of the header in the HOG file.
+    pub offset: u64,
+    pub bytes: [u8; HDR_LEN],
+
+    /// True if the file contents run past the end of the HOG file.
+    pub truncated: bool,
+}
+
+impl RecordHeader {
+    fn raw(&self) -> &RawHogRecord {
+        bytemuck::from_bytes(&self.bytes)
+    }
 
-                Ok(())
+    /// Returns the file length field.
+    pub fn length(&self) -> u32 {
+        u32::from_le(self.raw().length)
+    }
+
+    /// Returns true if this is a padding record, rather than a file.
+    pub fn is_padding(&self) -> bool {
+        self.raw().is_padding()
+    }
+
+    /// Decodes the header the same way HogRecordIter does.
+    pub fn record(&self) -> Result<HogRecord, HogError> {
+        HogRecord::try_from(self.raw())
+    }
+}
+
+// An iterator over the record headers of a HOG file. Created by
+// HogFileReader::record_headers().
+pub struct RecordHeaderIter<'a, R = HogInput> {
+    hogfile: &'a mut HogFileReader<R>,
+    next_offset: u64,
+    file_len: u64,
+    done: bool,
+}
+
+impl<'a, R: Read + Seek> Iterator for RecordHeaderIter<'a, R> {
+    type Item = Result<RecordHeader, HogError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        if let Err(e) = self.hogfile.file.seek(SeekFrom::Start(self.next_offset)) {
+            self.done = true;
+
+            return Some(Err(HogError::SeekFailure(e)));
+        }
+
+        let raw_hdr = match read_record_header(&mut self.hogfile.file) {
+            Ok(Some(x)) => x,
+            Ok(None) => {
+                self.done = true;
+
+                return None;
+            }
+            Err(e) => {
+                self.done = true;
+
+                return Some(Err(e));
+            }
+        };
+
+        let offset = self.next_offset;
+        let body_end = offset + HDR_LEN as u64 + u64::from(u32::from_le(raw_hdr.length));
+
+        // There is no next record to find after a truncated one.
+        let truncated = body_end > self.file_len;
+        self.done = truncated;
+        self.next_offset = body_end;
+
+        Some(Ok(RecordHeader {
+            offset,
+            bytes: bytemuck::cast(raw_hdr),
+            truncated,
+        }))
+    }
+}
+
+impl<R: Read + Seek> FusedIterator for RecordHeaderIter<'_, R> {}
+
+// An iterator over the records of a HOG file, which also reads the contents of
+// each file into memory. Created by HogFileReader::iter_with_data().
+pub struct HogDataIter<'a, R = HogInput> {
+    records: HogRecordIter<'a, R>,
+    max_len: Option<u64>,
+
+    // Number of errors returned by next() for files whose records were read
+    // (files too large, or that could not be read).
+    data_errors: u64,
+}
+
+impl<R: Read + Seek> HogDataIter<'_, R> {
+    /// Returns the number of errors next() has returned so far (see
+    /// HogRecordIter::errors_encountered()).
+    pub fn errors_encountered(&self) -> u64 {
+        self.records.errors_encountered() + self.data_errors
+    }
+
+    /// Returns the number of bytes of the HOG file scanned so far (see
+    /// HogRecordIter::bytes_scanned()).
+    pub fn bytes_scanned(&self) -> u64 {
+        self.records.bytes_scanned()
+    }
+
+    fn next_file(&mut self) -> Option<Result<(HogRecord, Vec<u8>), HogError>> {
+        let hdr = match self.records.next()? {
+            Ok(hdr) => hdr,
+            Err(e) => return Some(Err(e)),
+        };
+
+        let length = u64::from(hdr.length);
+
+        if let Some(max_len) = self.max_len {
+            if length > max_len {
+                // The file contents are skipped by the next call to next().
+                return Some(Err(HogError::DataLimitExceeded(length, max_len)));
+            }
+        }
+
+        let mut data = Vec::with_capacity(length.min(RESERVE_LEN) as usize);
+
+        match self.records.copy_cur_file(&mut data) {
+            Ok(()) => Some(Ok((hdr, data))),
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+impl<'a, R: Read + Seek> Iterator for HogDataIter<'a, R> {
+    type Item = Result<(HogRecord, Vec<u8>), HogError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let errors = self.records.errors_encountered();
+        let file = self.next_file();
+
+        // Errors from the record iterator are already counted by it.
+        if matches!(file, Some(Err(_))) && self.records.errors_encountered() == errors {
+            self.data_errors += 1;
+        }
+
+        file
+    }
+}
+
+impl<R: Read + Seek> FusedIterator for HogDataIter<'_, R> {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_encode_decode_name() {
+        let field = encode_name("level01.rdl").unwrap();
+        assert_eq!(&field, b"level01.rdl\0\0");
+        assert_eq!(decode_name(&field).unwrap(), "level01.rdl");
+
+        assert_eq!(encode_name("").unwrap(), [0; NAME_LEN]);
+        assert_eq!(encode_name("abcdefghijkl").unwrap()[12], 0);
+        assert_eq!(encode_name("abcdefghijklm"), Err(NameError::TooLong(13)));
+        assert_eq!(encode_name("a\0b"), Err(NameError::ContainsNul));
+
+        // Padding after the terminator is ignored, and unterminated names
+        // are accepted when reading.
+        assert_eq!(decode_name(b"a.txt\0garbage").unwrap(), "a.txt");
+        assert_eq!(decode_name(b"abcdefghijklm").unwrap(), "abcdefghijklm");
+        assert_eq!(
+            decode_name(b"bad\xff.txt\0\0\0\0\0"),
+            Err(NameError::InvalidUtf8)
+        );
+    }
+
+    #[test]
+    fn test_is_dos_filename() {
+        assert!(is_dos_filename("LEVEL01.RDL"));
+        assert!(is_dos_filename("level01.rdl"));
+        assert!(is_dos_filename("README"));
+        assert!(is_dos_filename("A.B"));
+        assert!(is_dos_filename("MY_LVL~1.HMP"));
+
+        assert!(!is_dos_filename("LEVEL001.RDLX"));
+        assert!(!is_dos_filename("LONGLEVEL.RDL"));
+        assert!(!is_dos_filename(".RDL"));
+        assert!(!is_dos_filename("LEVEL."));
+        assert!(!is_dos_filename("A.B.C"));
+        assert!(!is_dos_filename("MY LVL.RDL"));
+        assert!(!is_dos_filename("LEVEL+1.RDL"));
+        assert!(!is_dos_filename("NIVEAUÉ.RDL"));
+    }
+
+    #[test]
+    fn test_hog_filename() {
+        assert_eq!(
+            hog_filename(&"tmp/level01.rdl", &StandardNamePolicy::Dos).unwrap(),
+            "level01.rdl"
+        );
+        assert_eq!(
+            hog_filename(&"tmp/a.b.c", &StandardNamePolicy::Loose).unwrap(),
+            "a.b.c"
+        );
+        assert!(matches!(
+            hog_filename(&"tmp/a.b.c", &StandardNamePolicy::Dos),
+            Err(HogError::InvalidDosFilename(_))
+        ));
+        assert!(matches!(
+            hog_filename(&"thirteen_char", &StandardNamePolicy::Loose),
+            Err(HogError::HogFilenameTooLong)
+        ));
+    }
+
+    // A name policy only allowing lowercase names, like a program embedding
+    // the library could use.
+    struct LowercaseNames;
+
+    impl FilenamePolicy for LowercaseNames {
+        fn check_name(&self, name: &str) -> Result<(), HogError> {
+            if name.chars().any(|c| c.is_ascii_uppercase()) {
+                return Err(HogError::NameNotAllowed(name.into(), "uppercase".into()));
+            }
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_custom_name_policy() {
+        assert_eq!(
+            hog_filename(&"tmp/long_name.x", &LowercaseNames).unwrap(),
+            "long_name.x"
+        );
+        assert!(matches!(
+            hog_filename(&"tmp/LEVEL01.RDL", &LowercaseNames),
+            Err(HogError::NameNotAllowed(_, _))
+        ));
+
+        let dir = temp_dir("custom_name_policy");
+        let hog_path = dir.join("test.hog");
+        let mut writer = HogFileWriter::create(&hog_path).unwrap();
+
+        writer.set_name_policy(LowercaseNames);
+        assert!(writer.append_reader(&"a.txt", 1, &mut &b"a"[..]).is_ok());
+        assert!(matches!(
+            writer.append_reader(&"B.TXT", 1, &mut &b"b"[..]),
+            Err(HogError::NameNotAllowed(_, _))
+        ));
+        writer.finish().unwrap();
+
+        // The policies built in are still available by their old name.
+        let policy: NamePolicy = StandardNamePolicy::Dos;
+        assert!(hog_filename(&"tmp/long_name.x", &policy).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_transliterate() {
+        assert_eq!(transliterate("café.txt"), "cafe.txt");
+        assert_eq!(transliterate("Straße.PCX"), "Strasse.PCX");
+        assert_eq!(transliterate("ŁÓDŹ.rdl"), "LODZ.rdl");
+        assert_eq!(transliterate("日本.txt"), "__.txt");
+
+        // The length limit applies to the transliterated name, which is
+        // shorter than the UTF-8 encoded original.
+        assert_eq!(
+            hog_filename_transliterated(&"tmp/élève01.rdl", &StandardNamePolicy::Dos).unwrap(),
+            "eleve01.rdl"
+        );
+        assert!(matches!(
+            hog_filename_transliterated(&"tmp/trés_long.txt", &StandardNamePolicy::Loose),
+            Err(HogError::HogFilenameTooLong)
+        ));
+    }
+
+    // Creates an empty temporary directory for a test, unique to this test
+    // process.
+    fn temp_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("hogdump-{}-{}", std::process::id(), test));
+
+        std::fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn test_iter_with_data() {
+        let dir = temp_dir("iter_with_data");
+        let small = dir.join("small.txt");
+        let large = dir.join("large.bin");
+        let hog = dir.join("data.hog");
+
+        std::fs::write(&small, b"elephant").unwrap();
+        std::fs::write(&large, [0x55; 100]).unwrap();
+
+        let mut writer = HogFileWriter::create(&hog).unwrap();
+        writer.append_file(&small).unwrap();
+        writer.append_file(&large).unwrap();
+        writer.append_file(&small).unwrap();
+        drop(writer);
+
+        let mut reader = HogFileReader::open(&hog).unwrap();
+        let all: Vec<_> = reader
+            .iter_with_data(None)
+            .unwrap()
+            .map(|x| x.unwrap())
+            .collect();
+
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0].1, b"elephant");
+        assert_eq!(all[1].1, [0x55; 100]);
+        assert_eq!(all[2].0.filename, small.file_name().unwrap());
+
+        let mut iter = reader.iter_with_data(Some(50)).unwrap();
+        let limited: Vec<_> = iter.by_ref().collect();
+
+        assert_eq!(iter.errors_encountered(), 1);
+        assert_eq!(iter.bytes_scanned(), 3 + 3 * 17 + 8 + 100 + 8);
+        assert!(iter.next().is_none());
+
+        assert_eq!(limited.len(), 3);
+        assert!(limited[0].is_ok());
+        assert!(matches!(
+            limited[1],
+            Err(HogError::DataLimitExceeded(100, 50))
+        ));
+        assert_eq!(limited[2].as_ref().unwrap().1, b"elephant");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_sniff_cur_file() {
+        let dir = temp_dir("sniff_cur_file");
+        let text = dir.join("a.txt");
+        let hog = dir.join("sniff.hog");
+
+        std::fs::write(&text, b"name = Test Mission\n").unwrap();
+
+        let mut writer = HogFileWriter::create(&hog).unwrap();
+        writer.append_file(&text).unwrap();
+        drop(writer);
+
+        let mut reader = HogFileReader::open(&hog).unwrap();
+        let mut iter = reader.records().unwrap();
+        iter.next().unwrap().unwrap();
+
+        assert_eq!(iter.sniff_cur_file().unwrap(), ContentType::Text);
+        assert_eq!(iter.peek_cur_file(4).unwrap(), b"name");
+        assert_eq!(iter.peek_cur_file_tail(8).unwrap(), b"Mission\n");
+        assert_eq!(
+            iter.peek_cur_file_tail(100).unwrap(),
+            b"name = Test Mission\n"
+        );
+
+        let mut streamed = Vec::new();
+        iter.stream_cur_file(&mut streamed).unwrap();
+        assert_eq!(streamed, b"name = Test Mission\n");
+
+        // Sniffing and streaming must not consume any of the file.
+        let mut data = Vec::new();
+        iter.copy_cur_file(&mut data).unwrap();
+        assert_eq!(data, b"name = Test Mission\n");
+        assert!(iter.next().is_none());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_append_reader() {
+        let dir = temp_dir("append_reader");
+        let hog = dir.join("reader.hog");
+
+        let mut writer = HogFileWriter::create(&hog).unwrap();
+        writer.set_name_case(NameCase::Upper);
+
+        let record = writer
+            .append_reader(&"some/dir/a.txt", 3, &mut &b"abcdef"[..])
+            .unwrap();
+        assert_eq!(record.filename, Path::new("A.TXT"));
+        assert_eq!(record.length, 3);
+
+        assert!(writer
+            .append_reader(&"b.txt", 10, &mut &b"abc"[..])
+            .is_err());
+        drop(writer);
+
+        let mut reader = HogFileReader::open(&hog).unwrap();
+        let (record, data) = reader
+            .iter_with_data(None)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.filename, Path::new("A.TXT"));
+        assert_eq!(data, b"abc");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_empty_files() {
+        let dir = temp_dir("empty_files");
+        let hog = dir.join("empty.hog");
+
+        let mut writer = HogFileWriter::create(&hog).unwrap();
+        writer
+            .append_reader(&"empty.txt", 0, &mut std::io::empty())
+            .unwrap();
+        writer.append_reader(&"a.txt", 1, &mut &b"a"[..]).unwrap();
+
+        writer.set_allow_empty(false);
+        assert!(matches!(
+            writer.append_reader(&"empty2.txt", 0, &mut std::io::empty()),
+            Err(HogError::EmptyFile)
+        ));
+        writer.append_reader(&"b.txt", 1, &mut &b"b"[..]).unwrap();
+        drop(writer);
+
+        let mut reader = HogFileReader::open(&hog).unwrap();
+        let files: Vec<_> = reader
+            .iter_with_data(None)
+            .unwrap()
+            .map(|x| x.unwrap())
+            .map(|(record, data)| (record.filename, record.length, data))
+            .collect();
+        assert_eq!(
+            files,
+            [
+                (PathBuf::from("empty.txt"), 0, vec![]),
+                (PathBuf::from("a.txt"), 1, b"a".to_vec()),
+                (PathBuf::from("b.txt"), 1, b"b".to_vec()),
+            ]
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_align() {
+        let dir = temp_dir("align");
+        let hog = dir.join("align.hog");
+
+        let mut writer = HogFileWriter::create(&hog).unwrap();
+        writer.set_align(512);
+
+        for (name, len) in [("a.bin", 1000), ("b.bin", 0), ("c.bin", 495), ("d.bin", 3)] {
+            writer
+                .append_reader(&name, len, &mut std::io::repeat(0xaa))
+                .unwrap();
+        }
+        drop(writer);
+
+        let mut reader = HogFileReader::open(&hog).unwrap();
+        let mut iter = reader.records().unwrap();
+        let mut names = Vec::new();
+        let mut padded = 0;
+
+        while let Some(record) = iter.next() {
+            let record = record.unwrap();
+
+            assert_eq!(iter.data_offset(), iter.record_offset() + HDR_LEN as u64);
+            assert_eq!(iter.data_offset() % 512, 0);
+            assert_eq!(iter.record_index(), names.len() as u64);
+
+            // The padding skipped to get here (if any was needed) is
+            // reported, and ends where the record starts.
+            if let Some(&(offset, len)) = iter.padding_before().last() {
+                assert_eq!(offset + HDR_LEN as u64 + len, iter.record_offset());
+                padded += 1;
+            }
+
+            let mut data = Vec::new();
+            iter.copy_cur_file(&mut data).unwrap();
+            assert!(data.iter().all(|&x| x == 0xaa));
+
+            names.push(record.filename);
+        }
+
+        assert_eq!(
+            names,
+            ["a.bin", "b.bin", "c.bin", "d.bin"].map(PathBuf::from)
+        );
+        assert_eq!(padded, 3);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_skip_bad_record() {
+        let mut data = b"DHF".to_vec();
+        data.extend(b"bad\xff.txt\0\0\0\0\0");
+        data.extend(2u32.to_le_bytes());
+        data.extend(b"xx");
+        data.extend(b"good.txt\0\0\0\0\0");
+        data.extend(3u32.to_le_bytes());
+        data.extend(b"abc");
+        data.extend(b"trunc");
+
+        let len = data.len() as u64;
+        let mut reader = HogFileReader::from_reader(std::io::Cursor::new(data)).unwrap();
+        let mut iter = reader.records().unwrap();
+
+        assert!(matches!(iter.next(), Some(Err(HogError::InvalidFilename))));
+        assert_eq!((iter.errors_encountered(), iter.bytes_scanned()), (1, 22));
+
+        let record = iter.next().unwrap().unwrap();
+        assert_eq!(record.filename, Path::new("good.txt"));
+        assert_eq!(iter.record_index(), 1);
+        assert_eq!((iter.errors_encountered(), iter.bytes_scanned()), (1, 42));
+
+        let mut contents = Vec::new();
+        iter.copy_cur_file(&mut contents).unwrap();
+        assert_eq!(contents, b"abc");
+
+        // Bytes too few to be a header end iteration, and are counted.
+        assert!(iter.next().is_none());
+        assert_eq!(iter.trailing_bytes(), 5);
+        assert_eq!((iter.errors_encountered(), iter.bytes_scanned()), (1, len));
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_tolerate_slack() {
+        let mut data = b"DHF".to_vec();
+        for (name, contents, slack) in [
+            (&b"a.txt"[..], &b"hello"[..], 3),
+            (&b"b.txt"[..], &b"hi"[..], 4),
+            (&b"c.txt"[..], &b"x"[..], 0),
+        ] {
+            let mut field = [0; NAME_LEN];
+            field[..name.len()].copy_from_slice(name);
+
+            data.extend(field);
+            data.extend((contents.len() as u32).to_le_bytes());
+            data.extend(contents);
+            data.extend(vec![0xcc; slack]);
+        }
+
+        // Without tolerance, the slack is read as a bad header.
+        let mut reader = HogFileReader::from_reader(std::io::Cursor::new(data.clone())).unwrap();
+        let mut iter = reader.records().unwrap();
+        assert!(iter.next().unwrap().is_ok());
+        assert!(iter.next().unwrap().is_err());
+
+        let mut reader = HogFileReader::from_reader(std::io::Cursor::new(data)).unwrap();
+        reader.set_tolerate_slack(8);
+        let mut iter = reader.records().unwrap();
+
+        let mut found = Vec::new();
+        while let Some(record) = iter.next() {
+            found.push((record.unwrap().filename, iter.slack_before()));
+        }
+
+        assert_eq!(
+            found,
+            [
+                (PathBuf::from("a.txt"), None),
+                (PathBuf::from("b.txt"), Some((25, 3))),
+                (PathBuf::from("c.txt"), Some((47, 4))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_escaped_name() {
+        let record = HogRecord {
+            filename: "LEVEL\x01.RDL".into(),
+            length: 0,
+        };
+
+        assert_eq!(record.to_string(), "LEVEL\\x01.RDL");
+        assert_eq!(format!("{:<14}|", record), "LEVEL\\x01.RDL |");
+        assert_eq!(
+            EscapedName(Path::new("a\x1b[2J.txt")).to_string(),
+            "a\\x1b[2J.txt"
+        );
+        assert_eq!(
+            EscapedName(Path::new("\u{202e}txt.exe")).to_string(),
+            "\\u{202e}txt.exe"
+        );
+        assert_eq!(
+            EscapedName(Path::new("caf\u{e9}.txt")).to_string(),
+            "caf\u{e9}.txt"
+        );
+    }
+
+    #[test]
+    fn test_record_warnings() {
+        let record = |name: &str, length| HogRecord {
+            filename: name.into(),
+            length,
+        };
+
+        assert!(record("level01.rdl", 4).warnings().is_empty());
+        assert_eq!(
+            record("long_name.txt", 4).warnings(),
+            [HogWarning::SuspiciousName("long_name.txt".into())]
+        );
+        assert_eq!(
+            record("../a.txt", 0).warnings(),
+            [
+                HogWarning::UnsafeName("../a.txt".into()),
+                HogWarning::EmptyFile("../a.txt".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plain_names() {
+        assert!(is_plain_name("a.txt"));
+        assert!(is_plain_name("..a"));
+        assert!(!is_plain_name("../a.txt"));
+        assert!(!is_plain_name("a\\b.txt"));
+        assert!(!is_plain_name("/a.txt"));
+        assert!(!is_plain_name(".."));
+        assert!(!is_plain_name(""));
+
+        assert_eq!(sanitize_name("a.txt"), "a.txt");
+        assert_eq!(sanitize_name("../a.txt"), ".._a.txt");
+        assert_eq!(sanitize_name("c:\\a\\b"), "c:_a_b");
+        assert_eq!(sanitize_name(".."), "__");
+        assert_eq!(sanitize_name(""), "_");
+
+        let mut data = b"DHF".to_vec();
+        for name in [&b"sub/a.txt\0\0\0\0"[..], b"b.txt\0\0\0\0\0\0\0\0"] {
+            data.extend(name);
+            data.extend(1u32.to_le_bytes());
+            data.extend(b"x");
+        }
+
+        let mut reader = HogFileReader::from_reader(std::io::Cursor::new(data)).unwrap();
+        let names = |reader: &mut HogFileReader<_>| {
+            reader
+                .records()
+                .unwrap()
+                .map(|x| x.map(|x| x.filename))
+                .collect::<Vec<_>>()
+        };
+
+        assert!(
+            matches!(&names(&mut reader)[..], [Ok(a), Ok(b)] if a == Path::new("sub/a.txt") && b == Path::new("b.txt"))
+        );
+
+        reader.set_strict_names(true);
+        assert!(matches!(
+            &names(&mut reader)[..],
+            [Err(HogError::UnsafeName(a)), Ok(b)] if a == "sub/a.txt" && b == Path::new("b.txt")
+        ));
+
+        let dir = temp_dir("plain_names");
+        let mut writer = HogFileWriter::create(&dir.join("a.hog")).unwrap();
+        for name in ["a\\b.txt", "..", ""] {
+            assert!(matches!(
+                writer.append_reader_as(name, 1, &mut &b"x"[..]),
+                Err(HogError::UnsafeName(x)) if x == name
+            ));
+        }
+        drop(writer);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_from_reader() {
+        let mut data = b"DHF".to_vec();
+        data.extend(b"a.txt\0\0\0\0\0\0\0\0");
+        data.extend(3u32.to_le_bytes());
+        data.extend(b"abc");
+
+        let mut reader = HogFileReader::from_reader(std::io::Cursor::new(data)).unwrap();
+        let all: Vec<_> = reader
+            .iter_with_data(None)
+            .unwrap()
+            .map(|x| x.unwrap())
+            .collect();
+
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].0.filename, Path::new("a.txt"));
+        assert_eq!(all[0].1, b"abc");
+
+        assert!(matches!(
+            HogFileReader::from_reader(std::io::Cursor::new(b"PK\x03\x04")),
+            Err(HogError::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn test_empty_hog_file() {
+        let dir = temp_dir("empty_hog_file");
+        let hog_path = dir.join("empty.hog");
+
+        HogFileWriter::create(&hog_path).unwrap().finish().unwrap();
+        assert_eq!(std::fs::read(&hog_path).unwrap(), b"DHF");
+
+        let mut reader = HogFileReader::open(&hog_path).unwrap();
+        assert_eq!(reader.records().unwrap().count(), 0);
+
+        assert!(matches!(
+            HogFileReader::from_reader(std::io::Cursor::new(b"")),
+            Err(HogError::TooShort(0))
+        ));
+        assert!(matches!(
+            HogFileReader::from_reader_lenient(std::io::Cursor::new(b"DH")),
+            Err(HogError::TooShort(2))
+        ));
+    }
+
+    #[test]
+    fn test_durability() {
+        let dir = temp_dir("durability");
+
+        for durability in [
+            Durability::None,
+            Durability::Flush,
+            Durability::Fsync,
+            Durability::FsyncDir,
+        ] {
+            let hog_path = dir.join(format!("{:?}.hog", durability));
+            let mut writer = HogFileWriter::create(&hog_path).unwrap();
+
+            writer.set_durability(durability);
+            writer.append_reader(&"a.txt", 3, &mut &b"abc"[..]).unwrap();
+            writer.finish().unwrap();
+
+            let mut reader = HogFileReader::open(&hog_path).unwrap();
+            let entry = reader.find("a.txt").unwrap().unwrap();
+
+            assert_eq!(reader.read_entry(&entry).unwrap(), b"abc");
+        }
+    }
+
+    #[test]
+    fn test_entry_at() {
+        let fixture = crate::fixture::generate(10, None).unwrap();
+        let mut reader = HogFileReader::from_reader(std::io::Cursor::new(fixture.data)).unwrap();
+
+        let entry = reader.entry_at(3).unwrap().unwrap();
+        let (name, contents) = crate::fixture::entry(3);
+
+        assert_eq!(entry.name, name);
+        assert_eq!(reader.read_entry(&entry).unwrap(), contents);
+        assert_eq!(reader.entry_at(10).unwrap(), None);
+        assert_eq!(reader.entry_at(u64::MAX).unwrap(), None);
+
+        let range = reader.extract_range(7..10).unwrap();
+
+        assert_eq!(range.len(), 3);
+        for (i, (entry, data)) in (7..10).zip(range) {
+            assert_eq!((entry.name, data), crate::fixture::entry(i));
+        }
+
+        assert!(reader.extract_range(5..5).unwrap().is_empty());
+        assert!(matches!(
+            reader.extract_range(8..11),
+            Err(HogError::IndexOutOfRange(10, 10))
+        ));
+    }
+
+    #[test]
+    fn test_huge_length() {
+        // A record claiming 4 GiB, in a HOG file that ends right after it.
+        let mut data = HOG_SIGNATURE.to_vec();
+        data.extend(encode_name("huge.bin").unwrap());
+        data.extend(u32::MAX.to_le_bytes());
+        data.extend(b"short");
+
+        let mut reader = HogFileReader::from_reader(std::io::Cursor::new(data)).unwrap();
+        let all: Vec<_> = reader.iter_with_data(None).unwrap().collect();
+
+        assert!(matches!(all[0], Err(HogError::ExtractFailure(_))));
+        assert!(all.iter().all(|x| x.is_err()));
+
+        let entry = IndexEntry {
+            name: "huge.bin".to_string(),
+            offset: (HOG_SIGNATURE.len() + HDR_LEN) as u64,
+            length: u32::MAX,
+        };
+        assert!(matches!(
+            reader.read_entry(&entry),
+            Err(HogError::ReadFailure(_))
+        ));
+    }
+
+    #[test]
+    fn test_copy_failure() {
+        let fixture = crate::fixture::generate(3, None).unwrap();
+        let mut reader = HogFileReader::from_reader(std::io::Cursor::new(fixture.data)).unwrap();
+        let mut iter = reader.records().unwrap();
+        let failing = || {
+            util::Limits {
+                max_len: Some(3),
+                max_time: None,
             }
-            None => panic!("attempted to copy file without first scanning for the header"),
+            .writer(Vec::new())
+        };
+        let mut out = Vec::new();
+
+        // A write failing part way leaves the file to be read again, or
+        // skipped over.
+        iter.next().unwrap().unwrap();
+        assert!(matches!(
+            iter.copy_cur_file_range(&mut failing(), 2, None),
+            Err(HogError::ExtractFailure(_))
+        ));
+        assert_eq!(iter.copy_cur_file_range(&mut out, 2, Some(4)).unwrap(), 4);
+        assert!(matches!(
+            iter.copy_cur_file_range(&mut failing(), 1, None),
+            Err(HogError::ExtractFailure(_))
+        ));
+        assert!(matches!(
+            iter.copy_cur_file(&mut failing()),
+            Err(HogError::ExtractFailure(_))
+        ));
+        iter.copy_cur_file(&mut out).unwrap();
+        assert_eq!(out, crate::fixture::entry(0).1[2..]);
+
+        let hdr = iter.next().unwrap().unwrap();
+        assert_eq!(hdr.filename, Path::new(&crate::fixture::entry(1).0));
+        assert!(iter.copy_cur_file(&mut failing()).is_err());
+
+        let hdr = iter.next().unwrap().unwrap();
+        let mut data = Vec::new();
+        assert_eq!(hdr.filename, Path::new(&crate::fixture::entry(2).0));
+        iter.copy_cur_file(&mut data).unwrap();
+        assert_eq!(data, crate::fixture::entry(2).1);
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_no_current_file() {
+        let fixture = crate::fixture::generate(2, None).unwrap();
+        let mut reader = HogFileReader::from_reader(std::io::Cursor::new(fixture.data)).unwrap();
+        let mut iter = reader.records().unwrap();
+        let mut out = Vec::new();
+
+        assert!(matches!(
+            iter.copy_cur_file(&mut out),
+            Err(HogError::NoCurrentFile)
+        ));
+
+        iter.next().unwrap().unwrap();
+        iter.copy_cur_file(&mut out).unwrap();
+
+        assert!(matches!(
+            iter.check_cur_file(),
+            Err(HogError::NoCurrentFile)
+        ));
+        assert!(matches!(
+            iter.peek_cur_file(4),
+            Err(HogError::NoCurrentFile)
+        ));
+        assert_eq!(out, crate::fixture::entry(0).1);
+    }
+
+    #[test]
+    fn test_record_headers() {
+        let mut data = b"DHF".to_vec();
+
+        for (name, length, body_len) in [
+            (&b"a.txt"[..], 2, 2),
+            (&[0; NAME_LEN][..], 3, 3),
+            (&b"bad\xff"[..], 1, 1),
+            (&b"big.raw"[..], 100, 10),
+        ] {
+            let mut field = [0; NAME_LEN];
+            field[..name.len()].copy_from_slice(name);
+
+            data.extend(field);
+            data.extend(u32::to_le_bytes(length));
+            data.extend(vec![b'x'; body_len]);
+        }
+
+        let mut reader = HogFileReader::from_reader(std::io::Cursor::new(data)).unwrap();
+        let headers: Vec<_> = reader
+            .record_headers()
+            .unwrap()
+            .map(|x| x.unwrap())
+            .collect();
+
+        let offsets: Vec<_> = headers.iter().map(|x| x.offset).collect();
+        assert_eq!(offsets, [3, 22, 42, 60]);
+
+        assert_eq!(&headers[0].bytes[..6], b"a.txt\0");
+        assert_eq!(headers[0].length(), 2);
+        assert_eq!(headers[0].record().unwrap().filename, Path::new("a.txt"));
+
+        assert!(headers[1].is_padding());
+        assert!(matches!(
+            headers[2].record(),
+            Err(HogError::InvalidFilename)
+        ));
+        assert!(!headers[2].truncated);
+        assert!(headers[3].truncated);
+        assert_eq!(headers[3].length(), 100);
+    }
+
+    #[test]
+    fn test_large_hog_file() {
+        // Only the record headers are written, the rest of the file is a
+        // hole, so this does not take up 4 GiB of disk space (on filesystems
+        // with sparse files).
+        let dir = temp_dir("large_hog_file");
+        let hog = dir.join("large.hog");
+        let big_offset = 3 + HDR_LEN as u64 + u64::from(u32::MAX);
+
+        let mut f = File::create(&hog).unwrap();
+        f.write_all(b"DHFbig.bin\0\0\0\0\0\0\xff\xff\xff\xff")
+            .unwrap();
+        f.seek(SeekFrom::Start(big_offset)).unwrap();
+        f.write_all(b"small.txt\0\0\0\0\x02\0\0\0hi").unwrap();
+        drop(f);
+
+        let mut reader = HogFileReader::open(&hog).unwrap();
+        let mut iter = reader.records().unwrap();
+
+        assert_eq!(iter.next().unwrap().unwrap().length, u32::MAX);
+        assert_eq!(iter.sniff_cur_file().unwrap(), ContentType::Unknown);
+
+        let small = iter.next().unwrap().unwrap();
+        assert_eq!(small.filename, Path::new("small.txt"));
+        assert_eq!(iter.record_offset(), big_offset);
+
+        let mut data = Vec::new();
+        iter.copy_cur_file(&mut data).unwrap();
+        assert_eq!(data, b"hi");
+        assert!(iter.next().is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_lenient_signature() {
+        let record = b"a.txt\0\0\0\0\0\0\0\0\x02\0\0\0hi";
+        let hog = |prefix: &[u8]| std::io::Cursor::new([prefix, record].concat());
+
+        assert!(matches!(
+            HogFileReader::from_reader(hog(b"\xefdhf")),
+            Err(HogError::InvalidSignature)
+        ));
+
+        let mut reader = HogFileReader::from_reader_lenient(hog(b"\xefdhf")).unwrap();
+        assert_eq!(
+            reader.signature_warning(),
+            Some(HogWarning::LenientSignature(1, "dhf".into()))
+        );
+
+        let mut iter = reader.records().unwrap();
+        let record = iter.next().unwrap().unwrap();
+        assert_eq!(record.filename, Path::new("a.txt"));
+        assert_eq!(iter.data_offset(), 4 + HDR_LEN as u64);
+        assert!(iter.next().is_none());
+
+        let reader = HogFileReader::from_reader_lenient(hog(b"DHF")).unwrap();
+        assert_eq!(reader.signature_warning(), None);
+
+        assert!(matches!(
+            HogFileReader::from_reader_lenient(hog(&[b'x'; 16])),
+            Err(HogError::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn test_truncated_last_file() {
+        let data = b"DHFa.txt\0\0\0\0\0\0\0\0\x0a\0\0\0short".to_vec();
+        let mut reader = HogFileReader::from_reader(std::io::Cursor::new(data)).unwrap();
+        let mut iter = reader.records().unwrap();
+
+        assert!(iter.next().unwrap().is_ok());
+        assert!(matches!(iter.next(), Some(Err(HogError::UnexpectedEof))));
+        assert_eq!((iter.record_index(), iter.record_offset()), (0, 3));
+        assert!(iter.next().is_none());
+        assert_eq!((iter.errors_encountered(), iter.bytes_scanned()), (1, 25));
+    }
+
+    #[test]
+    fn test_hogz() {
+        let dir = temp_dir("hogz");
+        let hog = dir.join("test.hogz");
+
+        let mut writer = HogFileWriter::create_hogz(&hog).unwrap();
+        writer.set_align(512);
+        writer
+            .append_reader(&"big.raw", 10000, &mut std::io::repeat(0x80))
+            .unwrap();
+        writer
+            .append_reader(&"empty.txt", 0, &mut std::io::empty())
+            .unwrap();
+        assert!(writer
+            .append_reader(&"short.txt", 5, &mut b"abc".as_slice())
+            .is_err());
+        drop(writer);
+
+        let stored = std::fs::read(&hog).unwrap();
+        assert!(stored.starts_with(&HOGZ_SIGNATURE));
+        assert!(stored.len() < 1000);
+
+        let mut reader = HogFileReader::open(&hog).unwrap();
+        let all: Vec<_> = reader
+            .iter_with_data(None)
+            .unwrap()
+            .map(|x| x.unwrap())
+            .collect();
+
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].0.length, 10000);
+        assert_eq!(all[0].1, [0x80; 10000]);
+        assert_eq!(all[1].0.filename, Path::new("empty.txt"));
+        assert!(all[1].1.is_empty());
+
+        // Files that would convert to more than the limit are refused.
+        let limits = Limits {
+            max_len: Some(5000),
+            max_time: None,
+        };
+        assert!(matches!(
+            HogFileReader::open_limited(&hog, &limits),
+            Err(HogError::LimitExceeded(_))
+        ));
+
+        // A regular HOG file is not a hogz file.
+        let mut data = b"DHF".as_slice();
+        assert!(decode_hogz(&mut data).is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_hogz_mtimes() {
+        let dir = temp_dir("hogz_mtimes");
+        let hog = dir.join("test.hogz");
+        let input = dir.join("input.txt");
+        let mtime = UNIX_EPOCH + Duration::from_secs(800000000);
+
+        std::fs::write(&input, b"abc").unwrap();
+        File::options()
+            .write(true)
+            .open(&input)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+
+        let mut writer = HogFileWriter::create_hogz(&hog).unwrap();
+        writer
+            .append_reader(&"nomtime.txt", 3, &mut b"def".as_slice())
+            .unwrap();
+        writer.append_file(&input).unwrap();
+        writer
+            .append_reader(&"set.txt", 3, &mut b"ghi".as_slice())
+            .unwrap();
+        writer.set_mtime(mtime + Duration::from_secs(60));
+        writer.finish().unwrap();
+
+        let mut reader = HogFileReader::open(&hog).unwrap();
+        let mut iter = reader.records().unwrap();
+        let mut found = Vec::new();
+
+        while let Some(record) = iter.next() {
+            found.push((record.unwrap().filename, iter.mtime()));
         }
+
+        assert_eq!(
+            found,
+            [
+                (PathBuf::from("nomtime.txt"), None),
+                (PathBuf::from("input.txt"), Some(mtime)),
+                (
+                    PathBuf::from("set.txt"),
+                    Some(mtime + Duration::from_secs(60))
+                ),
+            ]
+        );
+
+        // Regular HOG files do not store modification times.
+        let hog = dir.join("test.hog");
+        let mut writer = HogFileWriter::create(&hog).unwrap();
+        writer.append_file(&input).unwrap();
+        writer.finish().unwrap();
+
+        let mut reader = HogFileReader::open(&hog).unwrap();
+        let mut iter = reader.records().unwrap();
+        iter.next().unwrap().unwrap();
+        assert_eq!(iter.mtime(), None);
+
+        std::fs::remove_dir_all(dir).unwrap();
     }
 }
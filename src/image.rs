@@ -0,0 +1,725 @@
+//
+// Copyright (c) 2022-2023 Nathaniel Houghton <nathan@brainwerk.org>
+//
+// Permission to use, copy, modify, and distribute this software for
+// any purpose with or without fee is hereby granted, provided that
+// the above copyright notice and this permission notice appear in all
+// copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL
+// WARRANTIES WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE
+// AUTHOR BE LIABLE FOR ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL
+// DAMAGES OR ANY DAMAGES WHATSOEVER RESULTING FROM LOSS OF USE, DATA
+// OR PROFITS, WHETHER IN AN ACTION OF CONTRACT, NEGLIGENCE OR OTHER
+// TORTIOUS ACTION, ARISING OUT OF OR IN CONNECTION WITH THE USE OR
+// PERFORMANCE OF THIS SOFTWARE.
+//
+
+// Decoding the images found in HOG files (PCX, and IFF BBM / LBM), to preview
+// them in a terminal as small thumbnails, drawn with colored half block
+// characters or as sixel graphics.
+//
+// Only the palette based images the game uses are decoded: 8 bit PCX files
+// with a 256 color palette at the end, and IFF images with up to 8 bit planes
+// (PBM or ILBM, uncompressed or ByteRun1 compressed).
+
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use crate::error::HogError;
+use crate::sniff::ContentType;
+use crate::util::Limits;
+
+// Largest number of pixels an image may have. Anything larger is not an image
+// from the game, and is not worth decoding to preview it.
+const MAX_PIXELS: u64 = 4096 * 4096;
+
+/// Largest file that may hold an image decode() can decode: run length
+/// encoding takes at most two bytes for every pixel, and the rest of the file
+/// (headers, palettes and other chunks) is given 64 KiB. Larger files need
+/// not be read to be decoded.
+pub const MAX_IMAGE_LEN: u64 = 2 * MAX_PIXELS + 64 * 1024;
+
+/// An image, as indexes into its palette.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+
+    /// The colors of the image, as RGB.
+    pub palette: Vec<[u8; 3]>,
+
+    /// The palette index of every pixel, row by row.
+    pub pixels: Vec<u8>,
+}
+
+// Returns an InvalidImage error.
+fn invalid(msg: &str) -> HogError {
+    HogError::InvalidImage(msg.to_string())
+}
+
+// Returns the big endian u16 at "offset" in "data".
+fn be16(data: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([data[offset], data[offset + 1]])
+}
+
+// Returns the little endian u16 at "offset" in "data".
+fn le16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+// Checks that an image of "width" by "height" pixels is not too large to
+// decode, within "limits" (a pixel is a byte of output).
+fn check_size(width: u32, height: u32, limits: &Limits) -> Result<(), HogError> {
+    if width == 0 || height == 0 {
+        return Err(invalid("the image is empty"));
+    }
+
+    let pixels = u64::from(width) * u64::from(height);
+
+    if pixels > MAX_PIXELS {
+        return Err(invalid("the image is too large"));
+    }
+
+    limits
+        .check(pixels, Duration::ZERO)
+        .map_err(HogError::LimitExceeded)
+}
+
+// Checks that decoding an image that started at "start" has not taken longer
+// than "limits" allow.
+fn check_time(start: Instant, limits: &Limits) -> Result<(), HogError> {
+    limits
+        .check(0, start.elapsed())
+        .map_err(HogError::LimitExceeded)
+}
+
+/// Decodes a file of type "content_type" (see sniff::sniff()) as an image,
+/// or returns None if it is not one of the images that can be decoded.
+/// Images with more pixels than "limits" allow bytes, or taking longer to
+/// decode, are a HogError::LimitExceeded.
+pub fn decode(
+    content_type: ContentType,
+    data: &[u8],
+    limits: &Limits,
+) -> Option<Result<Image, HogError>> {
+    match content_type {
+        ContentType::Pcx => Some(decode_pcx(data, limits)),
+        ContentType::Bbm => Some(decode_iff(data, limits)),
+        _ => None,
+    }
+}
+
+/// Decodes a PCX image. Only 8 bit images with a single plane and a 256
+/// color palette (at the end of the file) are supported.
+pub fn decode_pcx(data: &[u8], limits: &Limits) -> Result<Image, HogError> {
+    let start = Instant::now();
+
+    const HEADER_LEN: usize = 128;
+    const PALETTE_LEN: usize = 256 * 3;
+
+    if data.len() < HEADER_LEN + PALETTE_LEN + 1 || data[0] != 0x0a {
+        return Err(invalid("not a PCX image"));
+    }
+
+    if data[1] != 5 || data[3] != 8 || data[65] != 1 {
+        return Err(invalid(
+            "only 8 bit PCX images with a palette are supported",
+        ));
+    }
+
+    let width = u32::from(le16(data, 8)) + 1 - u32::from(le16(data, 4).min(le16(data, 8)));
+    let height = u32::from(le16(data, 10)) + 1 - u32::from(le16(data, 6).min(le16(data, 10)));
+    let line_len = usize::from(le16(data, 66));
+
+    check_size(width, height, limits)?;
+
+    if line_len < width as usize {
+        return Err(invalid("the lines of the image are too short"));
+    }
+
+    let palette_start = data.len() - PALETTE_LEN;
+
+    if data[palette_start - 1] != 0x0c {
+        return Err(invalid("the image has no palette"));
+    }
+
+    let palette = data[palette_start..]
+        .chunks(3)
+        .map(|x| [x[0], x[1], x[2]])
+        .collect();
+
+    // Every line is run length encoded: a byte with the top two bits set
+    // repeats the next byte (the rest of its bits) times.
+    let mut body = data[HEADER_LEN..palette_start - 1].iter().copied();
+    let mut pixels = Vec::with_capacity(width as usize * height as usize);
+    let mut line = Vec::with_capacity(line_len);
+
+    for _ in 0..height {
+        check_time(start, limits)?;
+        line.clear();
+
+        while line.len() < line_len {
+            let byte = body
+                .next()
+                .ok_or_else(|| invalid("the image is cut short"))?;
+
+            if byte >= 0xc0 {
+                let value = body
+                    .next()
+                    .ok_or_else(|| invalid("the image is cut short"))?;
+                line.extend(std::iter::repeat_n(value, usize::from(byte & 0x3f)));
+            } else {
+                line.push(byte);
+            }
+        }
+
+        pixels.extend_from_slice(&line[..width as usize]);
+    }
+
+    Ok(Image {
+        width,
+        height,
+        palette,
+        pixels,
+    })
+}
+
+// Unpacks "len" bytes of ByteRun1 compressed data from "body".
+fn unpack_byterun1(
+    body: &mut impl Iterator<Item = u8>,
+    len: usize,
+    out: &mut Vec<u8>,
+) -> Result<(), HogError> {
+    let end = out.len() + len;
+
+    while out.len() < end {
+        let n = body
+            .next()
+            .ok_or_else(|| invalid("the image is cut short"))? as i8;
+
+        match n {
+            0..=127 => {
+                for _ in 0..=n {
+                    out.push(
+                        body.next()
+                            .ok_or_else(|| invalid("the image is cut short"))?,
+                    );
+                }
+            }
+            -127..=-1 => {
+                let value = body
+                    .next()
+                    .ok_or_else(|| invalid("the image is cut short"))?;
+                out.extend(std::iter::repeat_n(value, (1 - isize::from(n)) as usize));
+            }
+            -128 => {}
+        }
+    }
+
+    // A run can go past the end of the row, which is just dropped.
+    out.truncate(end);
+
+    Ok(())
+}
+
+/// Decodes an IFF image (a BBM or LBM file): either chunky (PBM) or planar
+/// (ILBM), with up to 8 bit planes, uncompressed or ByteRun1 compressed.
+pub fn decode_iff(data: &[u8], limits: &Limits) -> Result<Image, HogError> {
+    let start = Instant::now();
+
+    if data.len() < 12 || data[0..4] != *b"FORM" {
+        return Err(invalid("not an IFF image"));
+    }
+
+    let planar = match &data[8..12] {
+        b"ILBM" => true,
+        b"PBM " => false,
+        _ => return Err(invalid("only PBM and ILBM images are supported")),
+    };
+
+    let mut header = None;
+    let mut palette = Vec::new();
+    let mut body = None;
+    let mut chunks = &data[12..];
+
+    // Chunks are an ID and a big endian length, padded to an even length.
+    while chunks.len() >= 8 {
+        let len = u32::from_be_bytes([chunks[4], chunks[5], chunks[6], chunks[7]]) as usize;
+        let contents = chunks
+            .get(8..8 + len)
+            .ok_or_else(|| invalid("a chunk is cut short"))?;
+
+        match &chunks[0..4] {
+            b"BMHD" if len >= 20 => header = Some(contents),
+            b"CMAP" => {
+                palette = contents
+                    .chunks_exact(3)
+                    .map(|x| [x[0], x[1], x[2]])
+                    .collect()
+            }
+            b"BODY" => body = Some(contents),
+            _ => {}
+        }
+
+        chunks = chunks.get(8 + len + len % 2..).unwrap_or_default();
+    }
+
+    let header = header.ok_or_else(|| invalid("the image has no header"))?;
+    let body = body.ok_or_else(|| invalid("the image has no body"))?;
+    let width = u32::from(be16(header, 0));
+    let height = u32::from(be16(header, 2));
+    let planes = usize::from(header[8]);
+    let masked = header[9] == 1;
+    let compressed = match header[10] {
+        0 => false,
+        1 => true,
+        _ => return Err(invalid("unknown compression")),
+    };
+
+    check_size(width, height, limits)?;
+
+    if palette.is_empty() {
+        return Err(invalid("the image has no palette"));
+    }
+
+    if planes == 0 || planes > 8 || (!planar && planes != 8) {
+        return Err(invalid("only images with up to 8 bit planes are supported"));
+    }
+
+    // Rows of planar images are padded to 16 bits for every plane (and the
+    // mask, if there is one), rows of chunky images to a whole 16 bits.
+    let w = width as usize;
+    let row_len = match planar {
+        true => w.div_ceil(16) * 2 * (planes + usize::from(masked)),
+        false => w + w % 2,
+    };
+
+    let mut rows = Vec::with_capacity(row_len * height as usize);
+    let mut bytes = body.iter().copied();
+
+    for _ in 0..height {
+        check_time(start, limits)?;
+
+        if compressed {
+            unpack_byterun1(&mut bytes, row_len, &mut rows)?;
+        } else {
+            let start = rows.len();
+            rows.extend(bytes.by_ref().take(row_len));
+
+            if rows.len() - start < row_len {
+                return Err(invalid("the image is cut short"));
+            }
+        }
+    }
+
+    let pixels = match planar {
+        false => rows
+            .chunks(row_len)
+            .flat_map(|x| &x[..w])
+            .copied()
+            .collect(),
+        true => {
+            let plane_len = w.div_ceil(16) * 2;
+
+            rows.chunks(row_len)
+                .flat_map(|row| {
+                    (0..w).map(move |x| {
+                        (0..planes).fold(0, |pixel, plane| {
+                            let bit = row[plane * plane_len + x / 8] >> (7 - x % 8) & 1;
+                            pixel | bit << plane
+                        })
+                    })
+                })
+                .collect()
+        }
+    };
+
+    Ok(Image {
+        width,
+        height,
+        palette,
+        pixels,
+    })
+}
+
+impl Image {
+    /// Returns the color of the pixel at "x", "y". Pixels whose index is not
+    /// in the palette are black.
+    pub fn rgb(&self, x: u32, y: u32) -> [u8; 3] {
+        let index = self.pixels[(y * self.width + x) as usize];
+
+        self.palette
+            .get(usize::from(index))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns the image scaled down (keeping its aspect ratio) to fit in
+    /// "max_width" by "max_height" pixels. Images that fit already are
+    /// returned as they are. Pixels are picked rather than blended, so that
+    /// the palette still holds all the colors.
+    pub fn thumbnail(&self, max_width: u32, max_height: u32) -> Image {
+        let scale = f64::max(
+            f64::from(self.width) / f64::from(max_width.max(1)),
+            f64::from(self.height) / f64::from(max_height.max(1)),
+        );
+
+        if scale <= 1.0 {
+            return self.clone();
+        }
+
+        let width = ((f64::from(self.width) / scale) as u32).max(1);
+        let height = ((f64::from(self.height) / scale) as u32).max(1);
+        let mut pixels = Vec::with_capacity(width as usize * height as usize);
+
+        for y in 0..height {
+            let src_y = (u64::from(y) * u64::from(self.height) / u64::from(height)) as u32;
+
+            for x in 0..width {
+                let src_x = (u64::from(x) * u64::from(self.width) / u64::from(width)) as u32;
+                pixels.push(self.pixels[(src_y * self.width + src_x) as usize]);
+            }
+        }
+
+        Image {
+            width,
+            height,
+            palette: self.palette.clone(),
+            pixels,
+        }
+    }
+
+    /// Returns the image as lines of text for a terminal with 24 bit color:
+    /// every character is an upper half block, colored as a pixel of one row
+    /// (its foreground) and the pixel below it (its background).
+    pub fn to_ansi(&self) -> Vec<String> {
+        (0..self.height)
+            .step_by(2)
+            .map(|y| {
+                let mut line = String::new();
+
+                for x in 0..self.width {
+                    let [r, g, b] = self.rgb(x, y);
+                    let _ = write!(line, "\x1b[38;2;{};{};{}m", r, g, b);
+
+                    match y + 1 < self.height {
+                        true => {
+                            let [r, g, b] = self.rgb(x, y + 1);
+                            let _ = write!(line, "\x1b[48;2;{};{};{}m", r, g, b);
+                        }
+                        false => line.push_str("\x1b[49m"),
+                    }
+
+                    line.push('\u{2580}');
+                }
+
+                line.push_str("\x1b[0m");
+                line
+            })
+            .collect()
+    }
+
+    /// Returns the image as sixel graphics, for terminals that can display
+    /// them (such as xterm -ti vt340, mlterm and foot).
+    pub fn to_sixel(&self) -> String {
+        let mut sixel = format!("\x1bPq\"1;1;{};{}", self.width, self.height);
+
+        // Only the colors used are defined. Sixel colors are percentages.
+        let mut used = [false; 256];
+        self.pixels
+            .iter()
+            .for_each(|&x| used[usize::from(x)] = true);
+
+        for (index, [r, g, b]) in self.palette.iter().enumerate().filter(|x| used[x.0]) {
+            let pct = |x: &u8| u32::from(*x) * 100 / 255;
+            let _ = write!(sixel, "#{};2;{};{};{}", index, pct(r), pct(g), pct(b));
+        }
+
+        // Each band of six rows is drawn once per color in it, every time from
+        // the left edge again ("$"), and "-" moves on to the next band.
+        for band in (0..self.height).step_by(6) {
+            let rows = band..(band + 6).min(self.height);
+            let mut colors: Vec<u8> = rows
+                .clone()
+                .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+                .map(|(x, y)| self.pixels[(y * self.width + x) as usize])
+                .collect();
+
+            colors.sort_unstable();
+            colors.dedup();
+
+            for color in colors {
+                let _ = write!(sixel, "#{}", color);
+                let bits = (0..self.width).map(|x| {
+                    rows.clone().fold(0u8, |bits, y| {
+                        let set = self.pixels[(y * self.width + x) as usize] == color;
+                        bits | u8::from(set) << (y - band)
+                    })
+                });
+
+                push_sixel_runs(&mut sixel, bits);
+                sixel.push('$');
+            }
+
+            sixel.push('-');
+        }
+
+        sixel.push_str("\x1b\\");
+        sixel
+    }
+
+    /// Returns the image as an uncompressed 8 bit BMP file, which web
+    /// browsers can display. Palette indexes that are not in the palette are
+    /// black, like rgb() makes them.
+    pub fn to_bmp(&self) -> Vec<u8> {
+        const HEADER_LEN: u32 = 14 + 40 + 256 * 4;
+
+        // Rows are padded to a multiple of 4 bytes, and stored bottom up.
+        let row_len = (self.width + 3) & !3;
+        let pixels_len = row_len * self.height;
+        let mut bmp = Vec::with_capacity((HEADER_LEN + pixels_len) as usize);
+
+        bmp.extend(b"BM");
+        bmp.extend((HEADER_LEN + pixels_len).to_le_bytes());
+        bmp.extend(0u32.to_le_bytes());
+        bmp.extend(HEADER_LEN.to_le_bytes());
+
+        // BITMAPINFOHEADER: its length, the size, 1 plane of 8 bits, no
+        // compression, the pixel data length, 72 DPI and 256 colors.
+        bmp.extend(40u32.to_le_bytes());
+        bmp.extend(self.width.to_le_bytes());
+        bmp.extend(self.height.to_le_bytes());
+        bmp.extend(1u16.to_le_bytes());
+        bmp.extend(8u16.to_le_bytes());
+        bmp.extend(0u32.to_le_bytes());
+        bmp.extend(pixels_len.to_le_bytes());
+        bmp.extend(2835u32.to_le_bytes());
+        bmp.extend(2835u32.to_le_bytes());
+        bmp.extend(256u32.to_le_bytes());
+        bmp.extend(0u32.to_le_bytes());
+
+        for index in 0..256 {
+            let [r, g, b] = self.palette.get(index).copied().unwrap_or_default();
+            bmp.extend([b, g, r, 0]);
+        }
+
+        for y in (0..self.height).rev() {
+            let start = (y * self.width) as usize;
+
+            bmp.extend(&self.pixels[start..start + self.width as usize]);
+            bmp.resize(bmp.len() + (row_len - self.width) as usize, 0);
+        }
+
+        bmp
+    }
+}
+
+// Appends the sixels with the given bits to "sixel", repeats of the same one
+// as a single run ("!count", then the sixel).
+fn push_sixel_runs(sixel: &mut String, bits: impl Iterator<Item = u8>) {
+    let mut run: Option<(u8, usize)> = None;
+    let flush = |sixel: &mut String, run: Option<(u8, usize)>| match run {
+        Some((bits, count)) if count > 3 => {
+            let _ = write!(sixel, "!{}{}", count, char::from(63 + bits));
+        }
+        Some((bits, count)) => {
+            sixel.extend(std::iter::repeat_n(char::from(63 + bits), count));
+        }
+        None => {}
+    };
+
+    for b in bits {
+        run = match run {
+            Some((bits, count)) if bits == b => Some((bits, count + 1)),
+            _ => {
+                flush(sixel, run);
+                Some((b, 1))
+            }
+        };
+    }
+
+    flush(sixel, run);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::LimitExceeded;
+
+    // Returns a 4 by 2 PCX image, with pixels 0..8 and a grey palette.
+    fn pcx() -> Vec<u8> {
+        let mut data = vec![0; 128];
+        data[0] = 0x0a;
+        data[1] = 5;
+        data[2] = 1;
+        data[3] = 8;
+        data[8..10].copy_from_slice(&3u16.to_le_bytes());
+        data[10..12].copy_from_slice(&1u16.to_le_bytes());
+        data[65] = 1;
+        data[66..68].copy_from_slice(&4u16.to_le_bytes());
+
+        // A run of three 1s, then literal bytes.
+        data.extend([0, 0xc3, 1, 4, 5, 6, 0xc1, 0xc7]);
+        data.push(0x0c);
+        data.extend((0..=255).flat_map(|x| [x, x, x]));
+        data
+    }
+
+    #[test]
+    fn test_decode_pcx() {
+        let image = decode_pcx(&pcx(), &Limits::default()).unwrap();
+
+        assert_eq!((image.width, image.height), (4, 2));
+        assert_eq!(image.pixels, [0, 1, 1, 1, 4, 5, 6, 0xc7]);
+        assert_eq!(image.rgb(1, 1), [5, 5, 5]);
+
+        let mut cut = pcx();
+        cut.drain(130..134);
+        assert!(matches!(
+            decode_pcx(&cut, &Limits::default()),
+            Err(HogError::InvalidImage(_))
+        ));
+
+        let mut no_palette = pcx();
+        no_palette.truncate(no_palette.len() - 1);
+        assert!(matches!(
+            decode_pcx(&no_palette, &Limits::default()),
+            Err(HogError::InvalidImage(_))
+        ));
+
+        assert!(matches!(
+            decode_pcx(b"PK", &Limits::default()),
+            Err(HogError::InvalidImage(_))
+        ));
+        assert!(decode(ContentType::Pcx, &pcx(), &Limits::default()).is_some_and(|x| x.is_ok()));
+        assert!(decode(ContentType::Text, &pcx(), &Limits::default()).is_none());
+    }
+
+    #[test]
+    fn test_decode_limits() {
+        // Images with more pixels than the limit allows bytes are refused
+        // before they are decoded.
+        let limits = Limits {
+            max_len: Some(7),
+            max_time: None,
+        };
+        assert!(matches!(
+            decode(ContentType::Pcx, &pcx(), &limits),
+            Some(Err(HogError::LimitExceeded(LimitExceeded::Size(7))))
+        ));
+
+        let limits = Limits {
+            max_len: Some(8),
+            max_time: Some(Duration::ZERO),
+        };
+        assert!(matches!(
+            decode_pcx(&pcx(), &limits),
+            Err(HogError::LimitExceeded(LimitExceeded::Time(_)))
+        ));
+    }
+
+    #[test]
+    fn test_decode_iff() {
+        let chunk = |id: &[u8], contents: &[u8]| {
+            let mut chunk = id.to_vec();
+            chunk.extend((contents.len() as u32).to_be_bytes());
+            chunk.extend(contents);
+            chunk.resize(chunk.len() + contents.len() % 2, 0);
+            chunk
+        };
+        let form = |kind: &[u8], planes: u8, compression: u8, body: &[u8]| {
+            let mut bmhd = [0; 20];
+            bmhd[0..2].copy_from_slice(&3u16.to_be_bytes());
+            bmhd[2..4].copy_from_slice(&2u16.to_be_bytes());
+            bmhd[8] = planes;
+            bmhd[10] = compression;
+
+            let mut contents = kind.to_vec();
+            contents.extend(chunk(b"BMHD", &bmhd));
+            contents.extend(chunk(b"CMAP", &[0, 0, 0, 255, 0, 0, 0, 255, 0]));
+            contents.extend(chunk(b"BODY", body));
+            chunk(b"FORM", &contents)
+        };
+
+        // Chunky rows are padded to an even length.
+        let pbm = form(b"PBM ", 8, 0, &[0, 1, 2, 0, 2, 1, 0, 0]);
+        let image = decode_iff(&pbm, &Limits::default()).unwrap();
+        assert_eq!((image.width, image.height), (3, 2));
+        assert_eq!(image.pixels, [0, 1, 2, 2, 1, 0]);
+        assert_eq!(image.rgb(1, 0), [255, 0, 0]);
+
+        // The same, ByteRun1 compressed: a literal run, then a repeat.
+        let packed = form(b"PBM ", 8, 1, &[2, 0, 1, 2, 0, 0, 1, 2, 1, 0xff, 0]);
+        assert_eq!(
+            decode_iff(&packed, &Limits::default()).unwrap().pixels,
+            image.pixels
+        );
+
+        // Two bit planes, each padded to 16 bits.
+        let ilbm = form(b"ILBM", 2, 0, &[0x40, 0, 0x20, 0, 0x40, 0, 0x80, 0]);
+        assert_eq!(
+            decode_iff(&ilbm, &Limits::default()).unwrap().pixels,
+            image.pixels
+        );
+
+        let mut cut = pbm.clone();
+        cut.truncate(cut.len() - 4);
+        assert!(matches!(
+            decode_iff(&cut, &Limits::default()),
+            Err(HogError::InvalidImage(_))
+        ));
+    }
+
+    #[test]
+    fn test_thumbnail() {
+        let image = Image {
+            width: 8,
+            height: 4,
+            palette: vec![[0, 0, 0], [255, 255, 255]],
+            pixels: (0..32).map(|x| (x % 2) as u8).collect(),
+        };
+
+        let thumbnail = image.thumbnail(4, 4);
+        assert_eq!((thumbnail.width, thumbnail.height), (4, 2));
+        assert_eq!(thumbnail.pixels, [0; 8]);
+        assert_eq!(image.thumbnail(100, 100), image);
+
+        let ansi = thumbnail.to_ansi();
+        assert_eq!(ansi.len(), 1);
+        assert!(ansi[0].starts_with("\x1b[38;2;0;0;0m\x1b[48;2;0;0;0m\u{2580}"));
+        assert!(ansi[0].ends_with("\x1b[0m"));
+
+        assert_eq!(
+            thumbnail.to_sixel(),
+            "\x1bPq\"1;1;4;2#0;2;0;0;0#0!4B$-\x1b\\"
+        );
+    }
+
+    #[test]
+    fn test_to_bmp() {
+        let image = decode_pcx(&pcx(), &Limits::default()).unwrap();
+        let bmp = image.to_bmp();
+        let header_len = 14 + 40 + 256 * 4;
+
+        assert_eq!(&bmp[..2], b"BM");
+        assert_eq!(bmp.len(), header_len + 2 * 4);
+        assert_eq!(bmp[2..6], (bmp.len() as u32).to_le_bytes());
+        assert_eq!(bmp[18..26], [4, 0, 0, 0, 2, 0, 0, 0]);
+
+        // The palette is BGR, and the rows are stored bottom up.
+        assert_eq!(bmp[54 + 5 * 4..54 + 6 * 4], [5, 5, 5, 0]);
+        assert_eq!(bmp[header_len..], [4, 5, 6, 0xc7, 0, 1, 1, 1]);
+
+        // Rows are padded to 4 bytes.
+        let image = Image {
+            width: 3,
+            height: 1,
+            palette: Vec::new(),
+            pixels: vec![1, 2, 3],
+        };
+        assert_eq!(image.to_bmp()[header_len..], [1, 2, 3, 0]);
+        assert_eq!(image.to_bmp()[54..58], [0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,316 @@
+//
+// Copyright (c) 2022-2023 Nathaniel Houghton <nathan@brainwerk.org>
+//
+// Permission to use, copy, modify, and distribute this software for
+// any purpose with or without fee is hereby granted, provided that
+// the above copyright notice and this permission notice appear in all
+// copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL
+// WARRANTIES WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE
+// AUTHOR BE LIABLE FOR ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL
+// DAMAGES OR ANY DAMAGES WHATSOEVER RESULTING FROM LOSS OF USE, DATA
+// OR PROFITS, WHETHER IN AN ACTION OF CONTRACT, NEGLIGENCE OR OTHER
+// TORTIOUS ACTION, ARISING OUT OF OR IN CONNECTION WITH THE USE OR
+// PERFORMANCE OF THIS SOFTWARE.
+//
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use crate::error::HogError;
+use crate::hog::HogFileReader;
+use crate::util::{self, Sha256Writer};
+
+/// First field of the header line of an index map.
+pub const MAP_HEADER: &str = "#hogdump-map";
+
+/// A file listed in a HogIndex.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct IndexEntry {
+    pub name: String,
+
+    /// The offset of the contents of the file in the HOG file.
+    pub offset: u64,
+
+    pub length: u32,
+}
+
+/// The names and locations of the files in a HOG file, so that they can be
+/// found without walking the records of the HOG file.
+///
+/// An index is written as a map file (see write()): a header line with the
+/// length and the digest of the HOG file, then one line per file with its
+/// name, offset and length, all separated by tabs. Backslashes, tabs and line
+/// breaks in names are written as "\\", "\t", "\n" and "\r".
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct HogIndex {
+    /// The length of the HOG file the index is for.
+    pub archive_len: u64,
+
+    /// The digest of the HOG file the index is for (see digest()).
+    pub digest: String,
+
+    pub entries: Vec<IndexEntry>,
+}
+
+/// Returns the digest used to check that an index belongs to a HOG file: the
+/// SHA-256 digest (as a hex string) of the length of the file, and all of its
+/// contents.
+pub fn digest<R: Read + Seek>(file: &mut R) -> io::Result<String> {
+    let len = file.seek(SeekFrom::End(0))?;
+    let mut hasher = Sha256Writer::new(io::sink());
+
+    hasher.write_all(&len.to_le_bytes())?;
+
+    file.seek(SeekFrom::Start(0))?;
+    io::copy(&mut file.take(len), &mut hasher)?;
+
+    Ok(util::to_hex(&hasher.finish().0))
+}
+
+// Escapes the characters of "name" that would break up the lines and fields
+// of a map file.
+fn escape_name(name: &str) -> String {
+    let mut escaped = String::new();
+
+    for c in name.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+// The reverse of escape_name(), or None for an escape it does not write.
+fn unescape_name(name: &str) -> Option<String> {
+    let mut unescaped = String::new();
+    let mut chars = name.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        unescaped.push(match chars.next()? {
+            '\\' => '\\',
+            't' => '\t',
+            'n' => '\n',
+            'r' => '\r',
+            _ => return None,
+        });
+    }
+
+    Some(unescaped)
+}
+
+impl HogIndex {
+    /// Builds the index of a HOG file, by walking all of its records.
+    pub fn build<R: Read + Seek>(hog_file: &mut HogFileReader<R>) -> Result<Self, HogError> {
+        let mut entries = Vec::new();
+        let mut iter = hog_file.records()?;
+
+        while let Some(record) = iter.next() {
+            let record = record?;
+
+            entries.push(IndexEntry {
+                name: record.filename.to_string_lossy().into_owned(),
+                offset: iter.data_offset(),
+                length: record.length,
+            });
+        }
+
+        let file = hog_file.get_mut();
+        let archive_len = file.seek(SeekFrom::End(0)).map_err(HogError::SeekFailure)?;
+        let digest = digest(file).map_err(HogError::ReadFailure)?;
+
+        Ok(Self {
+            archive_len,
+            digest,
+            entries,
+        })
+    }
+
+    /// Parses a map file written by write().
+    pub fn parse(text: &str) -> Result<Self, HogError> {
+        let mut lines = text.lines();
+        let header: Vec<&str> = lines.next().unwrap_or_default().split('\t').collect();
+
+        let (archive_len, digest) = match header[..] {
+            [MAP_HEADER, len, digest] => match len.parse() {
+                Ok(len) => (len, digest.to_string()),
+                Err(_) => return Err(HogError::InvalidIndex("bad HOG file length".to_string())),
+            },
+            _ => return Err(HogError::InvalidIndex("missing header line".to_string())),
+        };
+
+        let mut entries = Vec::new();
+
+        for (i, line) in lines.enumerate() {
+            let bad_line = || HogError::InvalidIndex(format!("bad entry on line {}", i + 2));
+            let fields: Vec<&str> = line.split('\t').collect();
+
+            match fields[..] {
+                [name, offset, length] => entries.push(IndexEntry {
+                    name: unescape_name(name).ok_or_else(bad_line)?,
+                    offset: offset.parse().map_err(|_| bad_line())?,
+                    length: length.parse().map_err(|_| bad_line())?,
+                }),
+                _ => return Err(bad_line()),
+            }
+        }
+
+        Ok(Self {
+            archive_len,
+            digest,
+            entries,
+        })
+    }
+
+    /// Writes the index as a map file to "out".
+    pub fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "{}\t{}\t{}", MAP_HEADER, self.archive_len, self.digest)?;
+
+        for entry in &self.entries {
+            writeln!(
+                out,
+                "{}\t{}\t{}",
+                escape_name(&entry.name),
+                entry.offset,
+                entry.length
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns true if the index is for the HOG file "file".
+    pub fn matches<R: Read + Seek>(&self, file: &mut R) -> io::Result<bool> {
+        if file.seek(SeekFrom::End(0))? != self.archive_len {
+            return Ok(false);
+        }
+
+        Ok(digest(file)? == self.digest)
+    }
+
+    /// Returns the first file named "name", compared without regard to case.
+    pub fn find(&self, name: &str) -> Option<&IndexEntry> {
+        self.entries
+            .iter()
+            .find(|x| x.name.eq_ignore_ascii_case(name))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    fn hog_data() -> Vec<u8> {
+        let mut data = b"DHF".to_vec();
+
+        for (name, contents) in [("a.txt", &b"hello"[..]), ("B.PCX", &b"pcx"[..])] {
+            let mut field = [0; 13];
+            field[..name.len()].copy_from_slice(name.as_bytes());
+
+            data.extend(field);
+            data.extend((contents.len() as u32).to_le_bytes());
+            data.extend(contents);
+        }
+
+        data
+    }
+
+    #[test]
+    fn test_index() {
+        let data = hog_data();
+        let mut hog_file = HogFileReader::from_reader(Cursor::new(data.clone())).unwrap();
+        let index = HogIndex::build(&mut hog_file).unwrap();
+
+        assert_eq!(index.archive_len, data.len() as u64);
+        assert_eq!(
+            index.entries,
+            [
+                IndexEntry {
+                    name: "a.txt".to_string(),
+                    offset: 20,
+                    length: 5,
+                },
+                IndexEntry {
+                    name: "B.PCX".to_string(),
+                    offset: 42,
+                    length: 3,
+                },
+            ]
+        );
+        assert_eq!(index.find("b.pcx"), Some(&index.entries[1]));
+        assert_eq!(index.find("c.txt"), None);
+
+        let mut map = Vec::new();
+        index.write(&mut map).unwrap();
+        let parsed = HogIndex::parse(&String::from_utf8(map).unwrap()).unwrap();
+        assert_eq!(parsed, index);
+
+        assert!(index.matches(&mut Cursor::new(&data)).unwrap());
+
+        let mut changed = data.clone();
+        changed[21] = b'E';
+        assert!(!index.matches(&mut Cursor::new(&changed)).unwrap());
+
+        // Names that would break up the lines and fields are escaped.
+        let mut odd = index.clone();
+        odd.entries[0].name = "a\tb\nc\\d\re".to_string();
+        let mut map = Vec::new();
+        odd.write(&mut map).unwrap();
+        let map = String::from_utf8(map).unwrap();
+        assert_eq!(map.lines().nth(1), Some("a\\tb\\nc\\\\d\\re\t20\t5"));
+        assert_eq!(HogIndex::parse(&map).unwrap(), odd);
+
+        // Changes anywhere in the HOG file are noticed.
+        let mut changed = data.clone();
+        *changed.last_mut().unwrap() ^= 1;
+        assert!(!index.matches(&mut Cursor::new(&changed)).unwrap());
+
+        assert!(HogIndex::parse("a.txt\t20\t5\n").is_err());
+        assert!(HogIndex::parse("#hogdump-map\t10\tab\na\\x\t20\t5\n").is_err());
+        assert!(HogIndex::parse("#hogdump-map\t10\tab\na.txt\t20\n").is_err());
+    }
+
+    #[test]
+    fn test_with_index() {
+        let data = hog_data();
+        let mut hog_file = HogFileReader::from_reader(Cursor::new(data.clone())).unwrap();
+        let index = HogIndex::build(&mut hog_file).unwrap();
+
+        // An index that matches is used as is.
+        let mut hog_file = HogFileReader::from_reader(Cursor::new(data.clone()))
+            .unwrap()
+            .with_index(index.clone())
+            .unwrap();
+        assert_eq!(hog_file.index(), Some(&index));
+
+        let entry = hog_file.find("A.TXT").unwrap().unwrap();
+        assert_eq!(hog_file.read_entry(&entry).unwrap(), b"hello");
+        assert_eq!(hog_file.find("c.txt").unwrap(), None);
+
+        // One for another HOG file is ignored, and the index is built instead.
+        let mut other = index.clone();
+        other.digest = "00".to_string();
+
+        let mut hog_file = HogFileReader::from_reader(Cursor::new(data))
+            .unwrap()
+            .with_index(other)
+            .unwrap();
+        assert_eq!(hog_file.index(), None);
+
+        let entry = hog_file.find("b.pcx").unwrap().unwrap();
+        assert_eq!(hog_file.read_entry(&entry).unwrap(), b"pcx");
+        assert_eq!(hog_file.index(), Some(&index));
+    }
+}
@@ -0,0 +1,313 @@
+//
+// Copyright (c) 2022-2023 Nathaniel Houghton <nathan@brainwerk.org>
+//
+// Permission to use, copy, modify, and distribute this software for
+// any purpose with or without fee is hereby granted, provided that
+// the above copyright notice and this permission notice appear in all
+// copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL
+// WARRANTIES WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE
+// AUTHOR BE LIABLE FOR ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL
+// DAMAGES OR ANY DAMAGES WHATSOEVER RESULTING FROM LOSS OF USE, DATA
+// OR PROFITS, WHETHER IN AN ACTION OF CONTRACT, NEGLIGENCE OR OTHER
+// TORTIOUS ACTION, ARISING OUT OF OR IN CONNECTION WITH THE USE OR
+// PERFORMANCE OF THIS SOFTWARE.
+//
+
+use std::collections::BTreeSet;
+
+use crate::error::HogError;
+
+/// The header of a Descent 1 (.rdl) or Descent 2 (.rl2) level file.
+#[derive(PartialEq, Eq, Debug)]
+pub struct LevelHeader {
+    /// Level file format version. Descent 1 levels are version 1.
+    pub version: u32,
+
+    /// Name of the palette the level uses (such as "groupa.256"), which also
+    /// selects the PIG file its textures come from. Descent 1 levels do not
+    /// have one.
+    pub palette: Option<String>,
+}
+
+impl LevelHeader {
+    /// Returns the name of the PIG file the textures of the level come from,
+    /// if the level names one (the palette name, with a .pig extension).
+    pub fn pig_name(&self) -> Option<String> {
+        let palette = self.palette.as_ref()?;
+        let base = palette.rsplit_once('.').map_or(palette.as_str(), |x| x.0);
+
+        // Match the case of the palette name, since that is how the level
+        // was made.
+        if base.bytes().any(|x| x.is_ascii_lowercase()) {
+            Some(format!("{}.pig", base))
+        } else {
+            Some(format!("{}.PIG", base))
+        }
+    }
+}
+
+/// Most bytes of a level file that are read to check the textures it uses,
+/// which are in the mine data at the start of it.
+pub const MAX_LEVEL_LEN: u64 = 16 * 1024 * 1024;
+
+// Longest palette name that can be stored in a level file.
+const MAX_PALETTE_LEN: usize = 14;
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, HogError> {
+    match data.get(offset..offset + 4) {
+        Some(&[a, b, c, d]) => Ok(u32::from_le_bytes([a, b, c, d])),
+        _ => Err(HogError::InvalidLevel("header is truncated".to_string())),
+    }
+}
+
+/// Parses the header at the start of a level file.
+pub fn parse_level_header(data: &[u8]) -> Result<LevelHeader, HogError> {
+    if !data.starts_with(b"LVLP") {
+        return Err(HogError::InvalidLevel("missing LVLP signature".to_string()));
+    }
+
+    // Signature, version, mine data offset, game data offset.
+    let version = read_u32(data, 4)?;
+    let mut offset = 16;
+
+    // Version 8 added 7 bytes of unused data, and versions before 5 have the
+    // (unused) offset of the hostage text.
+    if version >= 8 {
+        offset += 7;
+    }
+
+    if version < 5 {
+        offset += 4;
+    }
+
+    if version < 2 {
+        return Ok(LevelHeader {
+            version,
+            palette: None,
+        });
+    }
+
+    let rest = data.get(offset..).unwrap_or_default();
+    let end = rest
+        .iter()
+        .take(MAX_PALETTE_LEN)
+        .position(|&x| x == b'\n' || x == 0)
+        .ok_or_else(|| HogError::InvalidLevel("palette name is not terminated".to_string()))?;
+    let palette = std::str::from_utf8(&rest[..end])
+        .map_err(|_| HogError::InvalidLevel("palette name is not valid text".to_string()))?;
+
+    Ok(LevelHeader {
+        version,
+        palette: Some(palette.to_string()),
+    })
+}
+
+// Reads the mine data of a level file, with every read checked against the end
+// of the data.
+struct MineReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl MineReader<'_> {
+    fn bytes(&mut self, len: usize) -> Result<&[u8], HogError> {
+        let bytes = self
+            .offset
+            .checked_add(len)
+            .and_then(|end| self.data.get(self.offset..end))
+            .ok_or_else(|| HogError::InvalidLevel("mine data is truncated".to_string()))?;
+
+        self.offset += len;
+
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, HogError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, HogError> {
+        let bytes = self.bytes(2)?;
+
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+}
+
+// Sides of a segment of a mine, and the bit of the masks in the mine data
+// that says a segment has its special data stored.
+const SIDES: usize = 6;
+const SPECIAL_BIT: u8 = 1 << SIDES;
+
+/// Returns the numbers of the textures that the sides of the mine of a level
+/// file use, both as base textures and as overlays. These are numbers in the
+/// texture table of the game (see pig::HamFile), except in Descent 1 levels,
+/// which use the texture numbers of Descent 1.
+pub fn level_textures(data: &[u8]) -> Result<BTreeSet<u16>, HogError> {
+    let version = parse_level_header(data)?.version;
+    let mut mine = MineReader {
+        data,
+        offset: read_u32(data, 8)? as usize,
+    };
+
+    // Version of the mine data, then the number of vertices and segments.
+    mine.bytes(1)?;
+    let vertices = mine.u16()?;
+    let segments = mine.u16()?;
+
+    mine.bytes(usize::from(vertices) * 12)?;
+
+    let mut textures = BTreeSet::new();
+
+    for _ in 0..segments {
+        let mask = mine.u8()?;
+
+        // The special data comes first in version 5, and after the vertices
+        // in Descent 1 levels. Later versions store it after all of the
+        // segments.
+        if version == 5 && mask & SPECIAL_BIT != 0 {
+            mine.bytes(4)?;
+        }
+
+        // Sides that lead to another segment have no texture, unless there is
+        // a wall on them.
+        let mut children = [false; SIDES];
+
+        for (side, child) in children.iter_mut().enumerate() {
+            if mask & (1 << side) != 0 {
+                *child = mine.u16()? != u16::MAX;
+            }
+        }
+
+        mine.bytes(16)?;
+
+        if version <= 1 && mask & SPECIAL_BIT != 0 {
+            mine.bytes(4)?;
+        }
+
+        // Light of the segment.
+        if version <= 5 {
+            mine.bytes(2)?;
+        }
+
+        let mask = mine.u8()?;
+        let mut walls = [false; SIDES];
+
+        for (side, wall) in walls.iter_mut().enumerate() {
+            if mask & (1 << side) != 0 {
+                *wall = mine.u8()? != u8::MAX;
+            }
+        }
+
+        for side in 0..SIDES {
+            if children[side] && !walls[side] {
+                continue;
+            }
+
+            // The top bit says an overlay texture follows, whose top two bits
+            // are its orientation.
+            let texture = mine.u16()?;
+            textures.insert(texture & 0x7fff);
+
+            if texture & 0x8000 != 0 {
+                let overlay = mine.u16()? & 0x3fff;
+
+                if overlay != 0 {
+                    textures.insert(overlay);
+                }
+            }
+
+            // Texture coordinates and light of each corner.
+            mine.bytes(24)?;
+        }
+    }
+
+    Ok(textures)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn header(version: u32, extra: &[u8]) -> Vec<u8> {
+        let mut data = b"LVLP".to_vec();
+        data.extend(version.to_le_bytes());
+        data.extend(100u32.to_le_bytes());
+        data.extend(200u32.to_le_bytes());
+        data.extend(extra);
+        data
+    }
+
+    #[test]
+    fn test_parse_level_header() {
+        let d1 = parse_level_header(&header(1, &[0; 4])).unwrap();
+        assert_eq!(d1.version, 1);
+        assert_eq!(d1.palette, None);
+        assert_eq!(d1.pig_name(), None);
+
+        let d2 = parse_level_header(&header(8, b"\0\0\0\0\0\0\0GROUPA.256\n....")).unwrap();
+        assert_eq!(d2.version, 8);
+        assert_eq!(d2.palette.as_deref(), Some("GROUPA.256"));
+        assert_eq!(d2.pig_name().as_deref(), Some("GROUPA.PIG"));
+
+        let v3 = parse_level_header(&header(3, b"\0\0\0\0alien1.256\n")).unwrap();
+        assert_eq!(v3.palette.as_deref(), Some("alien1.256"));
+        assert_eq!(v3.pig_name().as_deref(), Some("alien1.pig"));
+
+        assert!(parse_level_header(b"LVLP\x08").is_err());
+        assert!(parse_level_header(b"PSFN\x01\0\0\0").is_err());
+        assert!(parse_level_header(&header(8, b"\0\0\0\0\0\0\0NOT_TERMINATED_AT_ALL")).is_err());
+    }
+
+    #[test]
+    fn test_level_textures() {
+        let mut data = b"LVLP".to_vec();
+        data.extend(8u32.to_le_bytes());
+        data.extend(34u32.to_le_bytes());
+        data.extend(0u32.to_le_bytes());
+        data.extend(b"\0\0\0\0\0\0\0GROUPA.256\n");
+
+        // A mine with no vertices and two segments.
+        data.push(0);
+        data.extend(0u16.to_le_bytes());
+        data.extend(2u16.to_le_bytes());
+
+        for (child, wall) in [(1u16, u8::MAX), (0, 3)] {
+            // Side 0 leads to the other segment, the rest are solid.
+            data.push(1);
+            data.extend(child.to_le_bytes());
+            data.extend([0; 16]);
+            data.push(1);
+            data.push(wall);
+
+            for side in 0..SIDES as u16 {
+                if side == 0 && wall == u8::MAX {
+                    continue;
+                }
+
+                if side == 1 {
+                    data.extend((0x8000 | 5u16).to_le_bytes());
+                    data.extend((0x4000 | 300u16).to_le_bytes());
+                } else {
+                    data.extend((side + 10 * child).to_le_bytes());
+                }
+
+                data.extend([0; 24]);
+            }
+        }
+
+        let textures = level_textures(&data).unwrap();
+        assert_eq!(
+            textures.into_iter().collect::<Vec<_>>(),
+            [0, 2, 3, 4, 5, 12, 13, 14, 15, 300]
+        );
+
+        data.truncate(data.len() - 1);
+        assert!(matches!(
+            level_textures(&data),
+            Err(HogError::InvalidLevel(_))
+        ));
+    }
+}
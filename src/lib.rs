@@ -0,0 +1,67 @@
+//
+// Copyright (c) 2022-2023 Nathaniel Houghton <nathan@brainwerk.org>
+//
+// Permission to use, copy, modify, and distribute this software for
+// any purpose with or without fee is hereby granted, provided that
+// the above copyright notice and this permission notice appear in all
+// copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL
+// WARRANTIES WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE
+// AUTHOR BE LIABLE FOR ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL
+// DAMAGES OR ANY DAMAGES WHATSOEVER RESULTING FROM LOSS OF USE, DATA
+// OR PROFITS, WHETHER IN AN ACTION OF CONTRACT, NEGLIGENCE OR OTHER
+// TORTIOUS ACTION, ARISING OUT OF OR IN CONNECTION WITH THE USE OR
+// PERFORMANCE OF THIS SOFTWARE.
+//
+
+//!
+//! HOG file library
+//!
+//! Reading and writing support for Descent 1 HOG files, used by the hogdump
+//! utility.
+//!
+//! No input, however malformed, makes the library panic: errors are returned
+//! as HogError instead. The lints below keep it that way (tests may still
+//! unwrap).
+//!
+
+#![cfg_attr(
+    not(test),
+    deny(
+        clippy::unwrap_used,
+        clippy::expect_used,
+        clippy::panic,
+        clippy::unreachable,
+        clippy::todo,
+        clippy::unimplemented
+    )
+)]
+
+pub mod bench;
+pub mod compress;
+pub mod convert;
+pub mod demo;
+pub mod diff;
+pub mod error;
+pub mod extract;
+pub mod filter;
+pub mod fixture;
+pub mod hog;
+pub mod image;
+pub mod index;
+pub mod level;
+pub mod listing;
+pub mod manifest;
+pub mod mission;
+pub mod pig;
+pub mod pool;
+pub mod report;
+#[cfg(feature = "s3")]
+pub mod s3;
+pub mod sniff;
+pub mod source;
+pub mod table;
+pub mod util;
+pub mod vfs;
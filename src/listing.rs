@@ -0,0 +1,127 @@
+//
+// Copyright (c) 2022-2023 Nathaniel Houghton <nathan@brainwerk.org>
+//
+// Permission to use, copy, modify, and distribute this software for
+// any purpose with or without fee is hereby granted, provided that
+// the above copyright notice and this permission notice appear in all
+// copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL
+// WARRANTIES WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE
+// AUTHOR BE LIABLE FOR ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL
+// DAMAGES OR ANY DAMAGES WHATSOEVER RESULTING FROM LOSS OF USE, DATA
+// OR PROFITS, WHETHER IN AN ACTION OF CONTRACT, NEGLIGENCE OR OTHER
+// TORTIOUS ACTION, ARISING OUT OF OR IN CONNECTION WITH THE USE OR
+// PERFORMANCE OF THIS SOFTWARE.
+//
+
+// Plain text listings of the files in a HOG file, in the "hogfile.txt" format
+// that older Windows HOG tools exchange: one line per file, in the order of the
+// HOG file, with its name and its length separated by a space.
+
+use std::fs;
+use std::io::{self, ErrorKind, Write};
+use std::path::Path;
+
+use crate::error::HogError;
+
+/// The name legacy tools give a listing.
+pub const LISTING_FILENAME: &str = "hogfile.txt";
+
+/// A file listed in a listing.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ListingEntry {
+    pub name: String,
+    pub length: u32,
+}
+
+/// Reads and parses the listing at "path".
+pub fn load(path: &impl AsRef<Path>) -> Result<Vec<ListingEntry>, HogError> {
+    let text = fs::read_to_string(path).map_err(HogError::OpenInputFailure)?;
+
+    parse(&text)
+}
+
+/// Parses a listing, as written by write() or by the legacy tools.
+///
+/// Lines may end with CRLF or LF, and blank lines are skipped. The length is
+/// the last field of each line, so that names with spaces are read back
+/// whole.
+pub fn parse(text: &str) -> Result<Vec<ListingEntry>, HogError> {
+    let mut entries = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let bad_line = || HogError::InvalidListing(format!("bad entry on line {}", i + 1));
+        let (name, length) = line
+            .rsplit_once(|c: char| c.is_ascii_whitespace())
+            .ok_or_else(bad_line)?;
+
+        entries.push(ListingEntry {
+            name: name.trim_end().to_string(),
+            length: length.parse().map_err(|_| bad_line())?,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Writes "entries" as a listing to "out", with CRLF line endings as the
+/// legacy tools expect. Names that contain a line break cannot be listed.
+pub fn write(entries: &[ListingEntry], out: &mut impl Write) -> io::Result<()> {
+    for entry in entries {
+        if entry.name.contains(['\r', '\n']) {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("\"{}\" cannot be listed", entry.name.escape_debug()),
+            ));
+        }
+
+        write!(out, "{} {}\r\n", entry.name, entry.length)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_listing() {
+        let entries = vec![
+            ListingEntry {
+                name: "descent.txb".to_string(),
+                length: 1234,
+            },
+            ListingEntry {
+                name: "my level.rdl".to_string(),
+                length: 0,
+            },
+        ];
+        let mut out = Vec::new();
+
+        write(&entries, &mut out).unwrap();
+        assert_eq!(out, b"descent.txb 1234\r\nmy level.rdl 0\r\n");
+        assert_eq!(parse(std::str::from_utf8(&out).unwrap()).unwrap(), entries);
+
+        assert_eq!(parse("\n  a.txt\t5  \n\n").unwrap()[0].name, "a.txt");
+        assert!(matches!(parse("a.txt\n"), Err(HogError::InvalidListing(_))));
+        assert!(matches!(
+            parse("a.txt big\n"),
+            Err(HogError::InvalidListing(_))
+        ));
+
+        let bad = [ListingEntry {
+            name: "a\nb".to_string(),
+            length: 1,
+        }];
+        assert!(write(&bad, &mut Vec::new()).is_err());
+    }
+}
@@ -22,20 +22,63 @@
 //! This utility can extract and create Descent 1 HOG files.
 //!
 
-use clap::Parser;
+use clap::{ArgGroup, CommandFactory, Parser, ValueEnum};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fs::{File, OpenOptions};
-use std::io::{BufWriter, ErrorKind};
+use std::io::{self, BufWriter, ErrorKind, Read, Seek, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::num::NonZeroUsize;
+use std::ops::RangeInclusive;
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
 
-mod error;
-mod hog;
-mod util;
+use regex::Regex;
 
-use crate::error::HogError;
-use crate::hog::{HogFileReader, HogFileWriter};
+use hogdump::bench::{self, Benchmark};
+use hogdump::compress::{self, Compression, HogInput};
+use hogdump::convert::{self, EntryConversion};
+use hogdump::demo;
+use hogdump::diff;
+use hogdump::error::{HogError, HogWarning};
+use hogdump::extract::{
+    self, CaseCollision, ConcatSink, ExtractPlan, FrameSink, Organize, OutputTemplate,
+};
+use hogdump::filter::FilterCommand;
+use hogdump::fixture::{self, Corruption};
+use hogdump::hog::{
+    self, Durability, HogFileReader, HogFileWriter, HogRecord, HogRecordIter, NameCase,
+    StandardNamePolicy,
+};
+use hogdump::image;
+use hogdump::index::{self, HogIndex};
+use hogdump::level;
+use hogdump::listing::{self, ListingEntry};
+use hogdump::manifest::{EmbeddedMetadata, Manifest, ManifestEntry, MetadataEntry, METADATA_NAME};
+use hogdump::mission::{Mission, MissionInfo, MAX_MISSION_LEN};
+use hogdump::pig::{self, HamFile, LevelPairing, PigFile, PogFile};
+use hogdump::pool::ThreadPool;
+use hogdump::sniff::{self, ContentType, TypeSummary};
+use hogdump::source::{self, SourceLang};
+use hogdump::table;
+use hogdump::util::{self, Limits, Sha256Reader, Sha256Writer, SparseWriter};
+use hogdump::vfs::HogVfs;
+
+mod browse;
+mod cleanup;
+mod daemon;
+mod output;
+mod serve;
+
+use crate::cleanup::InProgress;
+use crate::output::{Format, Integrity, Reporter, SizeFormat, SkipReason};
+
+// Name of the manifest written by --write-manifest.
+const MANIFEST_FILENAME: &str = "manifest.toml";
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None, arg_required_else_help(true))]
+#[command(group(ArgGroup::new("mode").args(["extract", "create", "test", "repack", "check_assets", "package_mission", "convert_mission", "browse", "serve", "daemon", "map", "export_listing", "mission_info", "diff", "gen_fixture", "bench"])))]
 struct Cli {
     /// Extract the contents of the provided hog file(s)
     #[arg(short = 'x', long)]
@@ -45,244 +88,5726 @@ struct Cli {
     #[arg(short = 'c', long)]
     create: Option<PathBuf>,
 
+    /// Check that all files in the provided hog file(s) can be read
+    #[arg(short = 't', long)]
+    test: bool,
+
+    /// Check that the levels in the provided hog file(s) only reference
+    /// palettes and textures that exist
+    #[arg(long)]
+    check_assets: bool,
+
+    /// Summarize the mission in the provided hog file(s): its name, number of
+    /// levels, and whether it has a briefing, custom textures or music
+    #[arg(long)]
+    mission_info: bool,
+
+    /// Compare the two provided hog files (the old one, then the new one),
+    /// listing the files that were added, removed or changed
+    #[arg(long)]
+    diff: bool,
+
+    /// Also show where the contents of changed files differ, and a text diff
+    /// of changed text files
+    #[arg(long, requires = "diff")]
+    content_diff: bool,
+
+    /// Other hog, pig or ham file the levels may use assets from (repeatable)
+    #[arg(long = "with", value_name = "FILE", requires = "check_assets")]
+    with_files: Vec<PathBuf>,
+
+    /// Browse the contents of the provided hog file interactively
+    #[arg(long)]
+    browse: bool,
+
+    /// Serve the contents of the provided hog file over HTTP
+    #[arg(long)]
+    serve: bool,
+
+    /// Answer JSON-RPC requests to list, extract and hash the files of hog
+    /// files on this Unix socket, keeping them open and indexed between
+    /// requests
+    #[arg(long, value_name = "SOCKET")]
+    daemon: Option<PathBuf>,
+
+    /// Directory the files extracted by --daemon are written to. Extract
+    /// requests give a path relative to it, and cannot write outside of it
+    #[arg(long, value_name = "DIR", default_value = ".", requires = "daemon")]
+    daemon_output_dir: PathBuf,
+
+    /// Write the name, offset and length of each file in the provided hog
+    /// file to stdout, for tools that read the files directly
+    #[arg(long)]
+    map: bool,
+
+    /// Write the name and length of each file in the provided hog file to
+    /// this file ("-" for stdout), in the hogfile.txt format older hog tools
+    /// exchange
+    #[arg(long, value_name = "FILE")]
+    export_listing: Option<PathBuf>,
+
+    /// Port to serve the hog file on
+    #[arg(long, default_value_t = 8080, requires = "serve")]
+    port: u16,
+
+    /// Address to serve the hog file on
+    #[arg(long, default_value = "127.0.0.1", requires = "serve")]
+    bind: IpAddr,
+
+    /// Create a distribution hog file for the mission in this directory
+    #[arg(long, value_name = "DIR")]
+    package_mission: Option<PathBuf>,
+
+    /// Convert the Descent 1 mission in the provided hog file to a Descent 2
+    /// mission, written to this hog file (and a mission file next to it)
+    #[arg(long, value_name = "OUT")]
+    convert_mission: Option<PathBuf>,
+
+    /// Game to convert the mission to
+    #[arg(long, value_enum, default_value_t = GameArg::D2, requires = "convert_mission")]
+    to: GameArg,
+
+    /// Write a synthetic hog file to this file, for testing programs that
+    /// read hog files (the same one every time)
+    #[arg(long, value_name = "OUT", conflicts_with = "file")]
+    gen_fixture: Option<PathBuf>,
+
+    /// Number of files in the --gen-fixture hog file
+    #[arg(long, value_name = "N", default_value_t = 50, value_parser = clap::value_parser!(u32).range(0..=i64::from(fixture::MAX_ENTRIES)), requires = "gen_fixture")]
+    entries: u32,
+
+    /// Break the --gen-fixture hog file in this way (it needs at least one
+    /// file)
+    #[arg(long, value_enum, value_name = "HOW", requires = "gen_fixture")]
+    corrupt: Option<CorruptArg>,
+
+    /// Time listing, extracting and hashing the files of the provided hog
+    /// file (or of a synthetic one, if none is given), for comparing
+    /// performance between releases and machines
+    #[arg(long, conflicts_with = "rate_limit")]
+    bench: bool,
+
+    /// Extract the hog file to --edit-dir, or rebuild it from there if it
+    /// was already extracted
+    #[arg(long, requires = "edit_dir")]
+    repack: bool,
+
+    /// Directory to extract the hog file to when repacking
+    #[arg(long, requires = "repack")]
+    edit_dir: Option<PathBuf>,
+
+    /// Rebuild the hog file when repacking (fail if it was not extracted)
+    #[arg(long, requires = "repack")]
+    finish: bool,
+
+    /// What to do with files that have the same contents as an earlier file
+    #[arg(long, value_enum, default_value_t = DedupeMode::Warn, requires = "create")]
+    dedupe: DedupeMode,
+
+    /// Allow adding multiple files with the same filename to a hog file
+    #[arg(long, requires = "create")]
+    allow_duplicate_names: bool,
+
+    /// Which filenames may be added to a hog file
+    #[arg(long, value_enum, default_value_t = NamePolicyArg::Loose, requires = "create")]
+    name_policy: NamePolicyArg,
+
+    /// Change the case of filenames stored in the hog file
+    #[arg(long, value_enum, default_value_t = StoreCaseArg::Preserve, requires = "create")]
+    store_case: StoreCaseArg,
+
+    /// Replace non-ASCII characters in the stored filenames with their
+    /// closest ASCII equivalents (é becomes e), which the game can display
+    #[arg(long, requires = "create")]
+    transliterate: bool,
+
+    /// After creating the hog file, read it back and check that it contains
+    /// exactly the files that were added
+    #[arg(long, requires = "create")]
+    self_check: bool,
+
+    /// Add a hogdump.json file to the hog file, with the digest of each file
+    /// in it, so that listings can show whether the files are intact
+    #[arg(long, requires = "create", conflicts_with = "from_tar")]
+    embed_metadata: bool,
+
+    /// Refuse to add empty files to the hog file
+    #[arg(long, requires = "create")]
+    refuse_empty: bool,
+
+    /// Allow creating a hog file with no files in it, when no files are given
+    /// (it only has the HOG signature)
+    #[arg(long, requires = "create")]
+    allow_empty: bool,
+
+    /// Pad the hog file so that the contents of each file start at a
+    /// multiple of N bytes
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..), requires = "create")]
+    align: u32,
+
+    /// How much is done to make sure created and rewritten hog files are
+    /// safely on disk before hogdump finishes
+    #[arg(long, value_enum, value_name = "MODE", default_value_t = DurabilityArg::Flush)]
+    durability: DurabilityArg,
+
+    /// Compress the created hog file
+    #[arg(long, value_enum, requires = "create")]
+    compress: Option<CompressArg>,
+
+    /// Format of the created hog file. hogz is not a real HOG file, and is
+    /// only for using hog files outside of the game
+    #[arg(long, value_enum, value_name = "FORMAT", default_value_t = FormatArg::Hog, requires = "create")]
+    format: FormatArg,
+
+    /// Change the case of extracted filenames
+    #[arg(long, value_enum, default_value_t = ExtractCaseArg::Preserve, requires = "extract")]
+    extract_case: ExtractCaseArg,
+
+    /// Sort extracted files into directories (levels, textures, music, ...)
+    #[arg(long, value_enum, requires = "extract")]
+    organize: Option<OrganizeArg>,
+
+    /// Name the extracted files after this template, which can use
+    /// {archive}, {index}, {name}, {stem} and {ext} (like
+    /// "{archive}_{index:03}_{name}")
+    #[arg(long, value_name = "TEMPLATE", value_parser = parse_output_template, requires = "extract")]
+    output_template: Option<OutputTemplate>,
+
+    /// Also write a manifest.toml listing the extracted files in their
+    /// original order
+    #[arg(long, requires = "extract", conflicts_with = "entry")]
+    write_manifest: bool,
+
+    /// Link extracted files with the same contents as an earlier extracted
+    /// file to it, instead of writing another copy
+    #[arg(long, value_enum, requires = "extract", conflicts_with = "entry")]
+    link_dupes: Option<LinkMode>,
+
+    /// What to do with extracted files whose names only differ by case, which
+    /// are the same file on case insensitive filesystems
+    #[arg(long, value_enum, value_name = "POLICY", default_value_t = CaseCollisionArg::Keep, requires = "extract")]
+    case_collisions: CaseCollisionArg,
+
+    /// Don't extract empty files
+    #[arg(long, requires = "extract", conflicts_with_all = ["entry", "write_manifest"])]
+    skip_empty: bool,
+
+    /// Write extracted files sparsely, skipping over blocks of zeros instead
+    /// of writing them
+    #[arg(long, requires = "extract", conflicts_with = "stdout")]
+    sparse: bool,
+
+    /// Extract the files back to back into this single file, instead of into
+    /// a file each, and write where each one is to --index-out
+    #[arg(long, value_name = "FILE", requires_all = ["extract", "index_out"], conflicts_with_all = ["entry", "dry_run", "write_manifest", "link_dupes", "sparse", "verify_on_extract", "organize", "output_template", "glob"])]
+    concat: Option<PathBuf>,
+
+    /// Where to write the map of the files in the --concat file (in the
+    /// format --map uses)
+    #[arg(long, value_name = "FILE", requires = "concat")]
+    index_out: Option<PathBuf>,
+
+    /// Write the files to stdout instead, one after the other, each one as
+    /// the length of its name, its name, its length and its contents (the
+    /// lengths are 32 bit little endian numbers)
+    #[arg(long, requires = "extract", conflicts_with_all = ["entry", "dry_run", "write_manifest", "link_dupes", "sparse", "verify_on_extract", "organize", "output_template", "concat", "filter_cmd"])]
+    stream_all: bool,
+
+    /// Display which files would be extracted, and where to, without
+    /// writing anything
+    #[arg(long, requires = "extract", conflicts_with = "entry")]
+    dry_run: bool,
+
+    /// Check the SHA-256 digest of each extracted file against --digests (or
+    /// the metadata embedded in the HOG file, without it), and warn about or
+    /// fail on files that do not match
+    #[arg(long, value_enum, value_name = "POLICY", requires = "extract", conflicts_with_all = ["entry", "dry_run"])]
+    verify_on_extract: Option<VerifyMode>,
+
+    /// Manifest (written by --write-manifest) with the expected digests for
+    /// --verify-on-extract
+    #[arg(long, value_name = "MANIFEST", requires = "verify_on_extract")]
+    digests: Option<PathBuf>,
+
+    /// Pipe each extracted file through this command (like
+    /// "my-decoder {name}"), and write what it outputs instead. {name} is
+    /// replaced by the name the file is extracted as
+    #[arg(long, value_name = "COMMAND", value_parser = parse_filter_command, requires = "extract", conflicts_with_all = ["write_manifest", "link_dupes", "verify_on_extract", "concat", "emit_source"])]
+    filter_cmd: Option<FilterCommand>,
+
+    /// Pipe each file through this command before adding it, and add what it
+    /// outputs instead. {name} is replaced by the path of the file
+    #[arg(long, value_name = "COMMAND", value_parser = parse_filter_command, requires = "create", conflicts_with = "manifest")]
+    pre_add_cmd: Option<FilterCommand>,
+
+    /// Create the hog file from a manifest written by --write-manifest
+    #[arg(long, requires = "create", conflicts_with = "file")]
+    manifest: Option<PathBuf>,
+
+    /// When listing, also display the first N bytes of each text file
+    #[arg(long, value_name = "N", conflicts_with = "mode")]
+    preview: Option<u64>,
+
+    /// When listing, also display a small thumbnail of each PCX and BBM
+    /// image, drawn with colored characters (ansi, the default) or as sixel
+    /// graphics (--preview-images=sixel)
+    #[arg(long, value_enum, value_name = "FORMAT", num_args = 0..=1, require_equals = true, default_missing_value = "ansi", conflicts_with_all = ["mode", "debug_records", "tree"])]
+    preview_images: Option<ImagePreview>,
+
+    /// Create the hog file from the files in this tar archive ("-" for stdin)
+    #[arg(long, value_name = "TAR", requires = "create", conflicts_with_all = ["file", "manifest"])]
+    from_tar: Option<PathBuf>,
+
+    /// Create the hog file from the files named in this hogfile.txt listing
+    /// (looked for next to it), in the order it lists them
+    #[arg(long, value_name = "LISTING", requires = "create", conflicts_with_all = ["file", "manifest", "from_tar"])]
+    from_listing: Option<PathBuf>,
+
+    /// When listing, report bad records and keep listing the rest of the
+    /// files, instead of stopping
+    #[arg(long, conflicts_with = "mode")]
+    ignore_errors: bool,
+
+    /// When listing, display the raw header of every record in hex, next to
+    /// how it is interpreted, without reading any file contents
+    #[arg(long, conflicts_with_all = ["mode", "preview", "ignore_errors", "with_pig"])]
+    debug_records: bool,
+
+    /// When listing, pair the Descent 2 levels with this PIG file (like
+    /// groupa.pig), and report which of its textures their POG files replace
+    #[arg(long, value_name = "PIG", conflicts_with = "mode")]
+    with_pig: Option<PathBuf>,
+
+    /// When listing, show how many files of each type (levels, textures,
+    /// music, ...) the hog file has, instead of each file
+    #[arg(long, conflicts_with_all = ["mode", "debug_records", "preview"])]
+    tree: bool,
+
+    /// When listing, check each file against its digest in the hogdump.json
+    /// file added by --embed-metadata (otherwise the files are only marked
+    /// as unverified)
+    #[arg(long, conflicts_with_all = ["mode", "debug_records"])]
+    check: bool,
+
+    /// Look up this file in the hog file(s) the way the game does, searching
+    /// them in the order given, and list every copy of it
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["mode", "preview", "debug_records", "name_match"])]
+    resolve: Option<String>,
+
     /// Overwrite files
     #[arg(short = 'o', long)]
     overwrite: bool,
 
+    /// When extracting to a file that already exists, extract to the first
+    /// of "NAME.1", "NAME.2", ... that does not exist instead
+    #[arg(long, requires = "extract", conflicts_with = "overwrite")]
+    auto_suffix: bool,
+
     /// Display more information during processing
     #[arg(short = 'v', long)]
     verbose: bool,
 
+    /// Only extract the named file from the hog file(s)
+    #[arg(short = 'e', long, requires = "extract")]
+    entry: Option<String>,
+
+    /// Write the extracted file to stdout, instead of to disk
+    #[arg(long, requires = "entry")]
+    stdout: bool,
+
+    /// Start extracting at this byte offset into the file
+    #[arg(long, requires = "entry")]
+    offset: Option<u64>,
+
+    /// Only extract the file at this (0 based) position in the hog file(s),
+    /// or a range of them like 3-7 (repeatable)
+    #[arg(long, value_name = "N", value_parser = parse_index_range, requires = "extract", conflicts_with_all = ["entry", "write_manifest"])]
+    index: Vec<RangeInclusive<u64>>,
+
+    /// Only list or extract the files whose names match this regular
+    /// expression
+    #[arg(long = "match", value_name = "REGEX", conflicts_with_all = ["entry", "write_manifest", "create", "test", "repack", "check_assets", "package_mission", "convert_mission", "browse", "serve", "mission_info"])]
+    name_match: Option<Regex>,
+
+    /// Extract at most this many bytes of the file
+    #[arg(long, requires = "entry")]
+    length: Option<u64>,
+
+    /// Write the extracted file as the source code of an array, to embed it
+    /// in a program (to a .h or .rs file named after it, or to stdout)
+    #[arg(
+        long,
+        value_name = "LANG",
+        requires = "entry",
+        conflicts_with = "sparse"
+    )]
+    emit_source: Option<SourceArg>,
+
+    /// When testing, move the hog files that fail into this directory, next
+    /// to a file saying why
+    #[arg(long, value_name = "DIR", requires = "test")]
+    quarantine_dir: Option<PathBuf>,
+
+    /// How to put hog files into the quarantine directory
+    #[arg(long, value_enum, value_name = "MODE", default_value_t = QuarantineMode::Move, requires = "quarantine_dir")]
+    quarantine_mode: QuarantineMode,
+
+    /// Display sizes like "1.4 MiB", instead of in bytes
+    #[arg(short = 'H', long, conflicts_with = "porcelain")]
+    human_readable: bool,
+
+    /// With --human-readable, use powers of 1000 ("1.5 MB") instead of 1024
+    #[arg(long, requires = "human_readable")]
+    si: bool,
+
+    /// Display stable, tab separated output meant for scripts
+    #[arg(long, conflicts_with = "json")]
+    porcelain: bool,
+
+    /// Report errors as JSON objects (on stderr)
+    #[arg(long)]
+    json: bool,
+
+    /// Exit with an error if there were any warnings while reading HOG files
+    /// (such as names the game may not load, or empty files)
+    #[arg(long)]
+    deny_warnings: bool,
+
+    /// Accept HOG files whose signature is in lowercase or comes after a few
+    /// junk bytes (with a warning), in every mode that reads them
+    #[arg(long)]
+    lenient_signature: bool,
+
+    /// Fail on files whose names are paths (containing "/" or "\") or
+    /// otherwise not plain filenames, rather than extracting them with those
+    /// characters replaced by "_", in every mode that reads HOG files
+    #[arg(long)]
+    strict_names: bool,
+
+    /// Skip up to N bytes (at most 4096) of padding that some tools left
+    /// between records, with a warning, in every mode that reads HOG files
+    #[arg(long, value_name = "N", default_value_t = 0, value_parser = clap::value_parser!(u64).range(0..=hog::MAX_SLACK))]
+    tolerate_slack: u64,
+
+    /// Refuse to open HOG files or write extracted files through symbolic links
+    /// in every mode that reads HOG files (for untrusted files in shared
+    /// directories)
+    #[arg(long)]
+    no_follow_symlinks: bool,
+
+    /// Most bytes that decompressing a HOG file, piping a file through
+    /// --filter-cmd or --pre-add-cmd, or decoding an image or text to preview
+    /// it, may produce before it is stopped (files in a --from-tar archive
+    /// larger than this are not added)
+    #[arg(long, value_name = "BYTES")]
+    max_convert_size: Option<u64>,
+
+    /// Most seconds that decompressing a HOG file, piping a file through
+    /// --filter-cmd or --pre-add-cmd, or decoding an image or text to preview
+    /// it, may take before it is stopped
+    #[arg(long, value_name = "SECONDS")]
+    max_convert_time: Option<u64>,
+
+    /// Read and write files at most this many bytes per second (like "500k"
+    /// or "10m"), across all threads, to leave bandwidth for others on
+    /// shared storage
+    #[arg(long, value_name = "RATE", value_parser = parse_rate)]
+    rate_limit: Option<u64>,
+
+    /// Write the JSON summaries to this file instead of to stdout
+    #[arg(long, value_name = "FILE", requires = "json")]
+    report: Option<PathBuf>,
+
+    /// Also operate on the hog files matching this pattern, where "**"
+    /// matches any number of directories (repeatable)
+    #[arg(long, value_name = "PATTERN", conflicts_with_all = ["create", "repack", "package_mission", "convert_mission", "browse", "serve", "map"])]
+    glob: Vec<String>,
+
+    /// Number of threads for processing several hog files at once, for
+    /// writing and hashing the files extracted from a single hog file, and for
+    /// hashing the files to add with --dedupe (defaults to the number of CPU
+    /// cores)
+    #[arg(short = 'j', long)]
+    threads: Option<NonZeroUsize>,
+
     /// The files to operate on (1 or more)
-    #[arg(required = true)]
+    #[arg(required_unless_present_any = ["manifest", "from_tar", "package_mission", "glob", "allow_empty", "daemon", "gen_fixture", "bench", "from_listing"])]
     file: Vec<PathBuf>,
 }
 
-// Summarizes what happened during the extraction process (returned by
-// hog_extract()).
-struct HogExtractInfo {
-    files_processed: u64,
-    files_extracted: u64,
-    files_skipped: u64,
-    bytes_extracted: u64,
+// Parses a --rate-limit argument, a number of bytes per second with an
+// optional k, m or g suffix (for KiB, MiB or GiB).
+fn parse_rate(arg: &str) -> Result<u64, String> {
+    let lower = arg.trim().to_ascii_lowercase();
+    let (number, scale) = match lower.char_indices().last() {
+        Some((i, 'k')) => (&lower[..i], 1 << 10),
+        Some((i, 'm')) => (&lower[..i], 1 << 20),
+        Some((i, 'g')) => (&lower[..i], 1 << 30),
+        _ => (lower.as_str(), 1),
+    };
+
+    let rate = number
+        .parse::<u64>()
+        .ok()
+        .and_then(|x| x.checked_mul(scale))
+        .ok_or_else(|| format!("invalid rate \"{}\"", arg))?;
+
+    if rate == 0 {
+        return Err("the rate must be at least 1 byte per second".to_string());
+    }
+
+    Ok(rate)
 }
 
-impl HogExtractInfo {
-    fn new() -> Self {
-        Self {
-            files_processed: 0,
-            files_extracted: 0,
-            files_skipped: 0,
-            bytes_extracted: 0,
+// Parses an --index argument, either a single index or a range ("3-7").
+fn parse_index_range(arg: &str) -> Result<RangeInclusive<u64>, String> {
+    let parse = |x: &str| {
+        x.trim()
+            .parse::<u64>()
+            .map_err(|e| format!("invalid index \"{}\": {}", x, e))
+    };
+
+    let (start, end) = match arg.split_once('-') {
+        Some((start, end)) if start.trim().is_empty() || end.trim().is_empty() => {
+            return Err(format!("range \"{}\" needs both a start and an end", arg));
         }
+        Some((start, end)) => (parse(start)?, parse(end)?),
+        None => (parse(arg)?, parse(arg)?),
+    };
+
+    if start > end {
+        return Err(format!("range \"{}\" is backwards", arg));
     }
+
+    Ok(start..=end)
 }
 
-// Extracts a single HOG file
-fn hog_extract(path: &impl AsRef<Path>, overwrite: bool) -> Result<HogExtractInfo, HogError> {
-    let mut hog_file = HogFileReader::open(path)?;
-    let mut hog_extract_info = HogExtractInfo::new();
-    let mut iter = hog_file.records()?;
+// Parses an --output-template argument.
+fn parse_output_template(arg: &str) -> Result<OutputTemplate, String> {
+    OutputTemplate::parse(arg).map_err(|e| e.to_string())
+}
 
-    loop {
-        match iter.next() {
-            Some(Ok(hdr)) => {
-                print!(
-                    "  {}: {}: ",
-                    path.as_ref().display(),
-                    hdr.filename.display()
-                );
+// Parses a --filter-cmd or --pre-add-cmd argument.
+fn parse_filter_command(arg: &str) -> Result<FilterCommand, String> {
+    FilterCommand::parse(arg).map_err(|e| e.to_string())
+}
 
-                hog_extract_info.files_processed += 1;
+// How to handle files with identical contents when creating a HOG file.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum DedupeMode {
+    /// Add the file, but display a warning
+    Warn,
+    /// Do not add the file
+    Skip,
+    /// Add the file without checking for duplicates
+    Keep,
+}
 
-                // Create the output file
-                let mut out_f = if overwrite {
-                    let f = File::create(hdr.filename).map_err(HogError::OpenOutputFailure)?;
-                    BufWriter::new(f)
-                } else {
-                    match OpenOptions::new()
-                        .write(true)
-                        .create_new(true)
-                        .open(hdr.filename)
-                    {
-                        Ok(f) => BufWriter::new(f),
-                        Err(e) if e.kind() == ErrorKind::AlreadyExists => {
-                            println!("skipping (already exists)");
-
-                            hog_extract_info.files_skipped += 1;
-
-                            continue;
-                        }
-                        Err(e) => return Err(HogError::OpenOutputFailure(e)),
-                    }
-                };
+// Command line version of hog::StandardNamePolicy.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum NamePolicyArg {
+    /// DOS 8.3 filenames, which the original game can load
+    Dos,
+    /// Any filename shorter than 13 characters
+    Loose,
+}
 
-                iter.copy_cur_file(&mut out_f)?;
+impl From<NamePolicyArg> for StandardNamePolicy {
+    fn from(arg: NamePolicyArg) -> Self {
+        match arg {
+            NamePolicyArg::Dos => StandardNamePolicy::Dos,
+            NamePolicyArg::Loose => StandardNamePolicy::Loose,
+        }
+    }
+}
 
-                println!("wrote {} bytes", hdr.length);
+// How to change the case of filenames stored by --create.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum StoreCaseArg {
+    Upper,
+    Lower,
+    Preserve,
+}
 
-                hog_extract_info.bytes_extracted += u64::from(hdr.length);
-                hog_extract_info.files_extracted += 1;
-            }
-            Some(Err(e)) => {
-                return Err(e);
-            }
-            None => {
-                break;
-            }
+impl From<StoreCaseArg> for NameCase {
+    fn from(arg: StoreCaseArg) -> Self {
+        match arg {
+            StoreCaseArg::Upper => NameCase::Upper,
+            StoreCaseArg::Lower => NameCase::Lower,
+            StoreCaseArg::Preserve => NameCase::Preserve,
         }
     }
+}
 
-    Ok(hog_extract_info)
+// The format of the HOG file to create.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum FormatArg {
+    /// Regular HOG file, as used by the game
+    Hog,
+    /// Non-standard hogdump extension, with every file compressed (the game
+    /// cannot read it)
+    Hogz,
 }
 
-// Summarizes information about the contents of the HOG file. Returned by
-// hog_info().
-struct HogInfoSummary {
-    num_files: u64,
-    num_bytes: u64,
+// Command line version of hog::Durability.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum DurabilityArg {
+    /// Leave writing out the last of the data to closing the file (errors
+    /// doing so are not noticed)
+    None,
+    /// Write out all of the data, and check that it was written
+    Flush,
+    /// Also sync the hog file to disk
+    Fsync,
+    /// Also sync the directory containing the hog file to disk
+    FsyncDir,
 }
 
-impl HogInfoSummary {
-    fn new() -> Self {
-        Self {
-            num_files: 0,
-            num_bytes: 0,
+impl From<DurabilityArg> for Durability {
+    fn from(arg: DurabilityArg) -> Self {
+        match arg {
+            DurabilityArg::None => Durability::None,
+            DurabilityArg::Flush => Durability::Flush,
+            DurabilityArg::Fsync => Durability::Fsync,
+            DurabilityArg::FsyncDir => Durability::FsyncDir,
         }
     }
 }
 
-// Displays information about the HOG file to the screen, such as file contents
-// and file sizes.
-fn hog_info(path: &impl AsRef<Path>, verbose: bool) -> Result<HogInfoSummary, HogError> {
-    let mut hog_file = HogFileReader::open(path)?;
-    let mut hog_info_summary = HogInfoSummary::new();
-    let mut iter = hog_file.records()?;
+// How --preview-images draws images.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ImagePreview {
+    /// Upper half block characters, in 24 bit color
+    Ansi,
+    /// Sixel graphics, for terminals that support them
+    Sixel,
+}
 
-    loop {
-        match iter.next() {
-            Some(Ok(hdr)) => {
-                if verbose {
-                    println!(
-                        "  {}: {}: {} bytes",
-                        path.as_ref().display(),
-                        hdr.filename.display(),
-                        hdr.length
-                    );
-                }
+// Command line version of fixture::Corruption.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum CorruptArg {
+    /// Cut the last file short
+    Truncate,
+    /// Give the file in the middle a filename that is not valid UTF-8
+    #[value(name = "badname")]
+    BadName,
+    /// Give the file in the middle a length that is too large
+    #[value(name = "badlen")]
+    BadLength,
+}
 
-                hog_info_summary.num_files += 1;
-                hog_info_summary.num_bytes += u64::from(hdr.length);
-            }
-            Some(Err(e)) => {
-                return Err(e);
-            }
-            None => {
-                break;
-            }
+impl From<CorruptArg> for Corruption {
+    fn from(arg: CorruptArg) -> Self {
+        match arg {
+            CorruptArg::Truncate => Corruption::Truncate,
+            CorruptArg::BadName => Corruption::BadName,
+            CorruptArg::BadLength => Corruption::BadLength,
         }
     }
+}
 
-    Ok(hog_info_summary)
+// Command line version of compress::Compression.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum CompressArg {
+    Gz,
+    Xz,
 }
 
-// Extracts multiple HOG files, using hog_extract()
-fn extract_hog_files(files: &[impl AsRef<Path>], overwrite: bool) {
-    for file in files {
-        match hog_extract(file, overwrite) {
-            Ok(extract_info) => {
-                println!(
-                    "Processed {} files, extracted {} files ({} bytes), skipped {} files.",
-                    extract_info.files_processed,
-                    extract_info.files_extracted,
-                    extract_info.bytes_extracted,
-                    extract_info.files_skipped
-                );
-            }
-            Err(e) => {
-                eprintln!(
-                    "error while processing HOG file \"{}\": {}",
-                    file.as_ref().display(),
-                    e
-                );
-            }
+impl From<CompressArg> for Compression {
+    fn from(arg: CompressArg) -> Self {
+        match arg {
+            CompressArg::Gz => Compression::Gzip,
+            CompressArg::Xz => Compression::Xz,
         }
     }
 }
 
-// Displays information about multiple HOG files, using hog_info()
-fn display_hog_info(files: &[impl AsRef<Path>], verbose: bool) {
-    for file in files {
-        match hog_info(file, verbose) {
-            Ok(hog_info_summary) => {
-                println!(
-                    "{}: contains {} files ({} bytes).",
-                    file.as_ref().display(),
-                    hog_info_summary.num_files,
-                    hog_info_summary.num_bytes,
-                );
-            }
-            Err(e) => {
-                eprintln!(
-                    "error while processing HOG file \"{}\": {}",
-                    file.as_ref().display(),
-                    e
-                );
-            }
+// How to change the case of filenames written by --extract.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ExtractCaseArg {
+    Lower,
+    Preserve,
+}
+
+impl From<ExtractCaseArg> for NameCase {
+    fn from(arg: ExtractCaseArg) -> Self {
+        match arg {
+            ExtractCaseArg::Lower => NameCase::Lower,
+            ExtractCaseArg::Preserve => NameCase::Preserve,
         }
     }
 }
 
-// Creates a HOG file, adding the list of files provided on the command line.
-fn create_hog_file(out_path: &impl AsRef<Path>, files: &[impl AsRef<Path>], _verbose: bool) {
-    let mut hog_file = match HogFileWriter::create(out_path) {
-        Ok(x) => x,
-        Err(e) => {
-            eprintln!(
-                "error creating output HOG file \"{}\": {}",
-                out_path.as_ref().display(),
-                e
-            );
+// The language to write source code in, with --emit-source.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum SourceArg {
+    C,
+    Rust,
+}
 
-            std::process::exit(1);
+impl From<SourceArg> for SourceLang {
+    fn from(arg: SourceArg) -> Self {
+        match arg {
+            SourceArg::C => SourceLang::C,
+            SourceArg::Rust => SourceLang::Rust,
         }
-    };
+    }
+}
 
-    for file in files {
-        match hog_file.append_file(file) {
-            Ok(length) => {
-                println!(
-                    "{}: added file \"{}\" ({} bytes).",
-                    out_path.as_ref().display(),
-                    file.as_ref().display(),
-                    length,
-                );
-            }
-            Err(e) => {
-                eprintln!(
-                    "error occurred while appending \"{}\" to HOG file \"{}\": {}",
-                    file.as_ref().display(),
-                    out_path.as_ref().display(),
-                    e
-                );
-            }
+// The game to convert a mission to, with --convert-mission.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum GameArg {
+    /// Descent 2
+    D2,
+}
+
+// How to sort extracted files into directories.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OrganizeArg {
+    /// Based on the filename extension
+    ByExtension,
+    /// Based on the type guessed from the file contents
+    ByType,
+}
+
+impl From<OrganizeArg> for Organize {
+    fn from(arg: OrganizeArg) -> Self {
+        match arg {
+            OrganizeArg::ByExtension => Organize::ByExtension,
+            OrganizeArg::ByType => Organize::ByType,
         }
     }
 }
 
-fn main() {
-    let cli = Cli::parse();
+// What to do with extracted files whose names only differ by case.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum CaseCollisionArg {
+    /// Extract the file under its own name anyway (skipping it as an existing
+    /// file on case insensitive filesystems, unless overwriting)
+    Keep,
+    /// Extract the file with a numbered suffix added to its name
+    Suffix,
+    /// Stop extracting with an error
+    Error,
+    /// Replace the earlier file
+    LastWins,
+}
 
-    if cli.extract && cli.create.is_some() {
-        eprintln!("error: --extract and --create are mutually exclusive operations.");
-        std::process::exit(1);
+impl From<CaseCollisionArg> for CaseCollision {
+    fn from(arg: CaseCollisionArg) -> Self {
+        match arg {
+            CaseCollisionArg::Keep => CaseCollision::Keep,
+            CaseCollisionArg::Suffix => CaseCollision::Suffix,
+            CaseCollisionArg::Error => CaseCollision::Error,
+            CaseCollisionArg::LastWins => CaseCollision::LastWins,
+        }
     }
+}
 
-    if cli.extract {
-        extract_hog_files(&cli.file, cli.overwrite);
-    } else if let Some(out_file) = cli.create {
-        create_hog_file(&out_file, &cli.file, cli.verbose);
-    } else {
-        display_hog_info(&cli.file, cli.verbose);
+// Options controlling how files are written when extracting HOG files.
+struct ExtractOptions {
+    overwrite: bool,
+    auto_suffix: bool,
+    write_manifest: bool,
+    link_dupes: Option<LinkDupes>,
+    sparse: bool,
+    verify: Option<Verify>,
+    read: ReadOptions,
+
+    // Command to pipe each extracted file through.
+    filter: Option<FilterCommand>,
+
+    // Which files are extracted, and where to (relative to "dir").
+    plan: extract::ExtractOptions,
+
+    // Directory to extract to (empty for the current directory).
+    dir: PathBuf,
+}
+
+impl ExtractOptions {
+    // Returns the path to extract the file stored as "name" (the current file
+    // of "iter", in the HOG file at "archive") to, relative to "dir". When
+    // organizing the extracted files, the directory the file goes in is
+    // created.
+    fn output_path(
+        &self,
+        archive: &Path,
+        iter: &mut HogRecordIter,
+        name: &Path,
+    ) -> Result<PathBuf, HogError> {
+        let path = self.plan.for_archive(archive).output_path(iter, name)?;
+
+        if self.read.no_follow {
+            util::check_no_symlink_dirs(&self.dir, &path).map_err(HogError::OpenOutputFailure)?;
+        }
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(self.dir.join(dir)).map_err(HogError::OpenOutputFailure)?;
+        }
+
+        Ok(path)
+    }
+}
+
+// Options controlling how HOG files are opened to read the files in them.
+#[derive(Clone, Copy)]
+struct ReadOptions {
+    // Accept signatures that are not quite right.
+    lenient_signature: bool,
+
+    // Refuse to go through symbolic links, to the HOG file or (when
+    // extracting) to where files are extracted to.
+    no_follow: bool,
+
+    // Most bytes of slack between records to skip.
+    tolerate_slack: u64,
+
+    // Fail on names that are not plain filenames.
+    strict_names: bool,
+
+    // Limits on decompressing the HOG file.
+    limits: Limits,
+}
+
+impl From<&Cli> for ReadOptions {
+    fn from(cli: &Cli) -> Self {
+        Self {
+            lenient_signature: cli.lenient_signature,
+            no_follow: cli.no_follow_symlinks,
+            tolerate_slack: cli.tolerate_slack,
+            strict_names: cli.strict_names,
+            limits: convert_limits(cli),
+        }
+    }
+}
+
+// Returns the limits given with --max-convert-size and --max-convert-time.
+fn convert_limits(cli: &Cli) -> Limits {
+    Limits {
+        max_len: cli.max_convert_size,
+        max_time: cli.max_convert_time.map(Duration::from_secs),
+    }
+}
+
+// Options controlling how HOG files are created.
+struct CreateOptions {
+    dedupe: DedupeMode,
+    allow_duplicate_names: bool,
+    name_policy: StandardNamePolicy,
+    store_case: NameCase,
+    transliterate: bool,
+    compress: Option<Compression>,
+    self_check: bool,
+    embed_metadata: bool,
+    allow_empty: bool,
+    align: u32,
+    format: FormatArg,
+    durability: Durability,
+    pre_add: Option<FilterCommand>,
+    limits: Limits,
+}
+
+impl From<&Cli> for CreateOptions {
+    fn from(cli: &Cli) -> Self {
+        Self {
+            dedupe: cli.dedupe,
+            allow_duplicate_names: cli.allow_duplicate_names,
+            name_policy: cli.name_policy.into(),
+            store_case: cli.store_case.into(),
+            transliterate: cli.transliterate,
+            compress: cli.compress.map(Compression::from),
+            self_check: cli.self_check,
+            embed_metadata: cli.embed_metadata,
+            allow_empty: !cli.refuse_empty,
+            align: cli.align,
+            format: cli.format,
+            durability: cli.durability.into(),
+            pre_add: cli
+                .pre_add_cmd
+                .clone()
+                .map(|x| x.with_limits(convert_limits(cli))),
+            limits: convert_limits(cli),
+        }
+    }
+}
+
+impl CreateOptions {
+    // Returns the name the file at "path" is stored as (before its case is
+    // changed).
+    fn hog_filename(&self, path: &impl AsRef<Path>) -> Result<String, HogError> {
+        if self.transliterate {
+            hog::hog_filename_transliterated(path, &self.name_policy)
+        } else {
+            hog::hog_filename(path, &self.name_policy)
+        }
+    }
+}
+
+// Summarizes what happened while creating a HOG file (returned by
+// create_hog_file()).
+struct HogCreateInfo {
+    files_added: u64,
+    files_skipped: u64,
+    files_failed: u64,
+    bytes_added: u64,
+    self_checked: bool,
+    entries: Vec<EntryResult>,
+
+    // Errors adding files, along with the file that failed.
+    errors: Vec<(PathBuf, HogError)>,
+}
+
+impl HogCreateInfo {
+    fn new() -> Self {
+        Self {
+            files_added: 0,
+            files_skipped: 0,
+            files_failed: 0,
+            bytes_added: 0,
+            self_checked: false,
+            entries: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    // Records a file that was added to the HOG file.
+    fn add(&mut self, file: &Path, record: &HogRecord) {
+        self.files_added += 1;
+        self.bytes_added += u64::from(record.length);
+        self.entries.push(EntryResult {
+            name: Some(record.filename.clone()),
+            path: file.to_path_buf(),
+            status: EntryStatus::Added,
+            bytes: record.length.into(),
+        });
+    }
+
+    // Records a file that was left out of the HOG file, because it has the
+    // same contents as an earlier file.
+    fn skip(&mut self, file: &Path) {
+        self.files_skipped += 1;
+        self.entries.push(EntryResult {
+            name: None,
+            path: file.to_path_buf(),
+            status: EntryStatus::Skipped,
+            bytes: 0,
+        });
+    }
+
+    // Records a file that could not be added to the HOG file.
+    fn fail(&mut self, file: &Path, e: HogError) {
+        self.files_failed += 1;
+        self.entries.push(EntryResult {
+            name: None,
+            path: file.to_path_buf(),
+            status: EntryStatus::Failed,
+            bytes: 0,
+        });
+        self.errors.push((file.to_path_buf(), e));
+    }
+}
+
+// What happened to a single file while extracting or creating a HOG file, as
+// listed in the summary displayed with --json.
+struct EntryResult {
+    // Filename stored in the HOG file (unknown for files that were not added).
+    name: Option<PathBuf>,
+
+    // File extracted to, or added from.
+    path: PathBuf,
+
+    status: EntryStatus,
+    bytes: u64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum EntryStatus {
+    Extracted,
+    Overwritten,
+    Skipped,
+    Added,
+    Failed,
+}
+
+impl EntryStatus {
+    fn name(&self) -> &'static str {
+        match self {
+            EntryStatus::Extracted => "extracted",
+            EntryStatus::Overwritten => "overwritten",
+            EntryStatus::Skipped => "skipped",
+            EntryStatus::Added => "added",
+            EntryStatus::Failed => "failed",
+        }
+    }
+}
+
+// An error that stopped the processing of a HOG file, along with the index and
+// header offset of the record being processed when it happened (if any).
+struct ArchiveError {
+    error: HogError,
+    entry_index: Option<u64>,
+    offset: Option<u64>,
+}
+
+impl ArchiveError {
+    // Creates an error that happened while processing the current record of
+    // "iter".
+    fn at(error: HogError, iter: &HogRecordIter) -> Self {
+        Self::at_record(error, iter.record_index(), iter.record_offset())
+    }
+
+    // Creates an error that happened while processing the record with the
+    // given index and header offset.
+    fn at_record(error: HogError, index: u64, offset: u64) -> Self {
+        Self {
+            error,
+            entry_index: Some(index),
+            offset: Some(offset),
+        }
+    }
+}
+
+impl From<HogError> for ArchiveError {
+    fn from(error: HogError) -> Self {
+        Self {
+            error,
+            entry_index: None,
+            offset: None,
+        }
+    }
+}
+
+// How to link extracted files with the same contents together.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum LinkMode {
+    /// Hard links
+    Hardlink,
+    /// Copy-on-write clones (on filesystems that support them)
+    Reflink,
+}
+
+// How --quarantine-dir puts HOG files that fail testing into quarantine.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum QuarantineMode {
+    /// Move the HOG file
+    Move,
+    /// Leave the HOG file in place, and symlink to it
+    Symlink,
+}
+
+// Where --test puts the HOG files that fail, for --quarantine-dir.
+struct Quarantine {
+    dir: PathBuf,
+    mode: QuarantineMode,
+}
+
+impl Quarantine {
+    // Puts the HOG file "path" into quarantine, along with a file recording
+    // why ("error"). The HOG file keeps its name, with a number added after it
+    // if the name is taken already (see util::numbered_path()).
+    // Returns the path of the HOG file in quarantine.
+    fn add(&self, path: &Path, error: &ArchiveError) -> io::Result<PathBuf> {
+        std::fs::create_dir_all(&self.dir)?;
+
+        let taken = |x: &Path| x.symlink_metadata().is_ok() || reason_path(x).exists();
+        let first = self.dir.join(path.file_name().unwrap_or_default());
+        let mut dest = first.clone();
+        let mut n = 1;
+
+        while taken(&dest) {
+            dest = util::numbered_path(&first, n);
+            n += 1;
+        }
+
+        match self.mode {
+            // Moving to another filesystem needs a copy.
+            QuarantineMode::Move => std::fs::rename(path, &dest).or_else(|_| {
+                util::copy_file(path, &dest)?;
+                std::fs::remove_file(path)
+            })?,
+            QuarantineMode::Symlink => symlink(&std::fs::canonicalize(path)?, &dest)?,
+        }
+
+        let mut reason = format!(
+            "{}\n{}: {}\n",
+            path.display(),
+            error.error.name(),
+            error.error
+        );
+
+        if let (Some(index), Some(offset)) = (error.entry_index, error.offset) {
+            reason.push_str(&format!("record {} at offset {}\n", index, offset));
+        }
+
+        std::fs::write(reason_path(&dest), reason)?;
+
+        Ok(dest)
+    }
+}
+
+// Returns the path of the file recording why the HOG file at "path" is in
+// quarantine.
+fn reason_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".reason");
+
+    PathBuf::from(name)
+}
+
+#[cfg(unix)]
+fn symlink(original: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(original, link)
+}
+
+#[cfg(windows)]
+fn symlink(original: &Path, link: &Path) -> io::Result<()> {
+    std::os::windows::fs::symlink_file(original, link)
+}
+
+// What to do with an extracted file that does not have the expected digest.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum VerifyMode {
+    /// Keep the file, and display a warning
+    Warn,
+    /// Remove the file, and stop extracting
+    Fail,
+}
+
+// The expected digests of the extracted files, for --verify-on-extract. They
+// come from the metadata embedded in each HOG file when no manifest is given.
+struct Verify {
+    mode: VerifyMode,
+    digests: Option<Manifest>,
+}
+
+// Keeps track of the files extracted so far (across all HOG files) by their
+// contents, for --link-dupes.
+struct LinkDupes {
+    mode: LinkMode,
+    extracted: Mutex<HashMap<(u32, [u8; 32]), PathBuf>>,
+}
+
+impl LinkDupes {
+    fn new(mode: LinkMode) -> Self {
+        Self {
+            mode,
+            extracted: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Replaces the file just extracted to "path" (with the given length and
+    // digest) with a link to an earlier extracted file with the same contents.
+    // Returns the earlier file, or None if there is none. If linking fails,
+    // the extracted copy is left in place.
+    fn link(&self, path: &Path, length: u32, digest: [u8; 32]) -> Option<PathBuf> {
+        let original = {
+            let mut extracted = self.extracted.lock().unwrap();
+
+            match extracted.get(&(length, digest)) {
+                Some(x) if x != path => x.clone(),
+                Some(_) => return None,
+                None => {
+                    extracted.insert((length, digest), path.to_path_buf());
+                    return None;
+                }
+            }
+        };
+
+        // The link is created next to the file and renamed over it, so that
+        // the file is never missing.
+        let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+        tmp_name.push(".hogdump-link");
+        let tmp_path = path.with_file_name(tmp_name);
+        let _in_progress = InProgress::new(&tmp_path);
+
+        let _ = std::fs::remove_file(&tmp_path);
+
+        let result = match self.mode {
+            LinkMode::Hardlink => std::fs::hard_link(&original, &tmp_path),
+            LinkMode::Reflink => reflink_copy::reflink(&original, &tmp_path),
+        };
+
+        match result.and_then(|_| std::fs::rename(&tmp_path, path)) {
+            Ok(_) => Some(original),
+            Err(_) => {
+                let _ = std::fs::remove_file(&tmp_path);
+                None
+            }
+        }
+    }
+}
+
+// Summarizes what happened during the extraction process (returned by
+// hog_extract()).
+struct HogExtractInfo {
+    files_processed: u64,
+    files_extracted: u64,
+    files_skipped: u64,
+    bytes_extracted: u64,
+    entries: Vec<EntryResult>,
+}
+
+impl HogExtractInfo {
+    fn new() -> Self {
+        Self {
+            files_processed: 0,
+            files_extracted: 0,
+            files_skipped: 0,
+            bytes_extracted: 0,
+            entries: Vec::new(),
+        }
+    }
+}
+
+// A file opened for extraction by open_output_file().
+type OutputFile = (BufWriter<File>, InProgress);
+
+// Opens a file for extraction. If "overwrite" is false and the file already
+// exists, Ok(None) is returned. With "no_follow", a symbolic link at "path" is
+// never written through: it counts as an existing file, or is an error when
+// overwriting.
+//
+// The file is marked as in progress (so it is removed if hogdump is
+// interrupted) until the returned InProgress is dropped.
+fn open_output_file(
+    path: &Path,
+    overwrite: bool,
+    no_follow: bool,
+) -> Result<Option<OutputFile>, HogError> {
+    let mut open_opts = OpenOptions::new();
+
+    if overwrite {
+        open_opts.write(true).create(true).truncate(true);
+    } else {
+        open_opts.write(true).create_new(true);
+    }
+
+    let result = if no_follow {
+        util::open_no_follow(&mut open_opts, path)
+    } else {
+        open_opts.open(path)
+    };
+
+    let f = match result {
+        Ok(f) => f,
+        Err(e) if !overwrite && e.kind() == ErrorKind::AlreadyExists => return Ok(None),
+        Err(e) => return Err(HogError::OpenOutputFailure(e)),
+    };
+
+    Ok(Some((BufWriter::new(f), InProgress::new(path))))
+}
+
+// Opens a file for extraction like open_output_file() does when not
+// overwriting, but if a file already exists at "path", the first of the
+// numbered paths "NAME.1", "NAME.2", ... (see util::numbered_path())
+// that does not exist is opened instead. Returns the number added to the path,
+// if any, along with the file.
+fn open_output_file_suffixed(
+    path: &Path,
+    no_follow: bool,
+) -> Result<(Option<u64>, OutputFile), HogError> {
+    if let Some(f) = open_output_file(path, false, no_follow)? {
+        return Ok((None, f));
+    }
+
+    let mut n = 1;
+
+    loop {
+        if let Some(f) = open_output_file(&util::numbered_path(path, n), false, no_follow)? {
+            return Ok((Some(n), f));
+        }
+
+        n += 1;
+    }
+}
+
+// Returns the writer to extract a file through, to the file opened by
+// open_output_file(). With --sparse, blocks of zeros are skipped over rather
+// than written.
+fn extract_writer(out_f: BufWriter<File>, sparse: bool) -> Box<dyn Write> {
+    if sparse {
+        Box::new(SparseWriter::new(out_f))
+    } else {
+        Box::new(out_f)
+    }
+}
+
+// Returns a handle to the file opened by open_output_file(), so that its
+// modification time can be set with restore_mtime() once it is written, if
+// "mtime" says there is one to set.
+fn mtime_handle(
+    out_f: &BufWriter<File>,
+    mtime: Option<SystemTime>,
+) -> Result<Option<File>, HogError> {
+    match mtime {
+        Some(_) => out_f
+            .get_ref()
+            .try_clone()
+            .map(Some)
+            .map_err(HogError::ExtractFailure),
+        None => Ok(None),
+    }
+}
+
+// Sets the modification time of an extracted file to the one stored for it in
+// the HOG file, if there is one (only hogz files store them). It is set
+// through the handle the file was written through (see mtime_handle()), since
+// reopening the path would follow a symbolic link put there in the meantime.
+fn restore_mtime(handle: Option<File>, mtime: Option<SystemTime>) -> Result<(), HogError> {
+    match (handle, mtime) {
+        (Some(f), Some(mtime)) => f.set_modified(mtime).map_err(HogError::ExtractFailure),
+        _ => Ok(()),
+    }
+}
+
+// Opens the HOG file at "path" to read the files in it, the way "read" says
+// (including how much slack between records is skipped). With a lenient
+// signature, a warning is written to "out" if it is not quite right.
+fn open_hog_file(
+    path: &Path,
+    read: ReadOptions,
+    reporter: &Reporter,
+    out: &mut dyn Write,
+) -> Result<HogFileReader, HogError> {
+    let input = if read.no_follow {
+        HogInput::open_no_follow(&path, &read.limits)
+    } else {
+        HogInput::open_limited(&path, &read.limits)
+    }
+    .map_err(|e| HogError::from_io(e, HogError::OpenHogFailure))?;
+
+    if !read.lenient_signature {
+        let mut hog_file = HogFileReader::from_reader(input)?;
+        hog_file.set_tolerate_slack(read.tolerate_slack);
+        hog_file.set_strict_names(read.strict_names);
+
+        return Ok(hog_file);
+    }
+
+    let mut hog_file = HogFileReader::from_reader_lenient(input)?;
+    hog_file.set_tolerate_slack(read.tolerate_slack);
+    hog_file.set_strict_names(read.strict_names);
+
+    if let Some(warning) = hog_file.signature_warning() {
+        reporter
+            .warning(out, path, &warning)
+            .map_err(HogError::OutputFailure)?;
+    }
+
+    Ok(hog_file)
+}
+
+// Extracts a single HOG file, writing progress information to "out".
+fn hog_extract(
+    path: &Path,
+    opts: &ExtractOptions,
+    reporter: &Reporter,
+    out: &mut dyn Write,
+) -> Result<HogExtractInfo, ArchiveError> {
+    let mut hog_extract_info = HogExtractInfo::new();
+
+    hog_extract_into(path, opts, None, &mut hog_extract_info, reporter, out)?;
+
+    Ok(hog_extract_info)
+}
+
+// Like hog_extract(), but what happened is recorded in "hog_extract_info" as
+// extraction goes on, so that it is still there if extraction fails partway
+// through. With a "pool", the files are written (and hashed) on its threads,
+// while they are read on the calling thread.
+fn hog_extract_into(
+    path: &Path,
+    opts: &ExtractOptions,
+    pool: Option<&ThreadPool>,
+    hog_extract_info: &mut HogExtractInfo,
+    reporter: &Reporter,
+    out: &mut dyn Write,
+) -> Result<(), ArchiveError> {
+    let mut hog_file = open_hog_file(path, opts.read, reporter, out)?;
+    let (plan, plan_error) = plan_extract(path, &mut hog_file, opts)?;
+    let embedded = match &opts.verify {
+        Some(Verify { digests: None, .. }) => embedded_metadata(path, &mut hog_file, reporter, out),
+        _ => None,
+    };
+    let mut ex = Extraction {
+        path,
+        opts,
+        pool: pool.filter(|x| x.threads() > 1),
+        info: hog_extract_info,
+        manifest: Manifest {
+            archive: path
+                .file_name()
+                .map(|x| x.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            entries: Vec::new(),
+        },
+        embedded,
+        reporter,
+        batch: Vec::new(),
+        batch_len: 0,
+    };
+
+    // The planned files are extracted in a second pass over the HOG file.
+    // Anything displayed while reading it waits for the files before it to be
+    // written, so that the output comes in the same order either way.
+    let mut iter = hog_file.records()?;
+    for entry in &plan.entries {
+        // Skip ahead to the record of the planned file.
+        let hdr = loop {
+            let hdr = match iter.next() {
+                Some(Ok(hdr)) => hdr,
+                Some(Err(e)) => return Err(ArchiveError::at(e, &iter)),
+                None => return Err(ArchiveError::at(HogError::UnexpectedEof, &iter)),
+            };
+
+            if iter.slack_before().is_some() || !iter.padding_before().is_empty() {
+                ex.flush(out)?;
+            }
+
+            report_slack(path, &iter, reporter, out)?;
+
+            if iter.record_index() == entry.index {
+                break hdr;
+            }
+        };
+
+        ex.info.files_processed += 1;
+
+        if !entry.extract {
+            ex.flush(out)?;
+
+            reporter
+                .skipped(out, path, &hdr.filename, SkipReason::Empty)
+                .map_err(HogError::OutputFailure)?;
+
+            ex.info.files_skipped += 1;
+            ex.info.entries.push(EntryResult {
+                name: Some(hdr.filename.clone()),
+                path: entry.path.clone(),
+                status: EntryStatus::Skipped,
+                bytes: 0,
+            });
+
+            continue;
+        }
+
+        let out_path = &entry.path;
+
+        // When replacing an earlier file, it is only overwritten if it was
+        // extracted (rather than skipped because it existed).
+        let mut overwrite = opts.overwrite;
+
+        if let Some(earlier) = &entry.collision {
+            ex.flush(out)?;
+
+            if entry.replaces_earlier() {
+                overwrite |= ex
+                    .info
+                    .entries
+                    .iter()
+                    .any(|x| x.path == *earlier && x.status != EntryStatus::Skipped);
+            }
+
+            reporter
+                .case_collision(out, path, &hdr.filename, earlier, out_path)
+                .map_err(HogError::OutputFailure)?;
+        }
+
+        let existed = overwrite && opts.dir.join(out_path).exists();
+
+        if opts.read.no_follow {
+            util::check_no_symlink_dirs(&opts.dir, out_path)
+                .map_err(|e| ArchiveError::at(HogError::OpenOutputFailure(e), &iter))?;
+        }
+
+        // Create the output file (and the directory it goes in, when
+        // organizing the extracted files).
+        if let Some(dir) = out_path.parent() {
+            std::fs::create_dir_all(opts.dir.join(dir))
+                .map_err(|e| ArchiveError::at(HogError::OpenOutputFailure(e), &iter))?;
+        }
+
+        // Files linked by --link-dupes share their contents, so they are
+        // replaced rather than written through when overwriting.
+        if overwrite && opts.link_dupes.is_some() {
+            let _ = std::fs::remove_file(opts.dir.join(out_path));
+        }
+
+        // An earlier file that is meant to be replaced is not kept by
+        // --auto-suffix.
+        let (suffix, out_f) = if opts.auto_suffix && !overwrite {
+            open_output_file_suffixed(&opts.dir.join(out_path), opts.read.no_follow)
+                .map(|(n, f)| (n, Some(f)))
+        } else {
+            open_output_file(&opts.dir.join(out_path), overwrite, opts.read.no_follow)
+                .map(|f| (None, f))
+        }
+        .map_err(|e| ArchiveError::at(e, &iter))?;
+
+        let suffixed = suffix.map(|n| util::numbered_path(out_path, n));
+        let out_path = suffixed.as_ref().unwrap_or(out_path);
+
+        if suffixed.is_some() {
+            ex.flush(out)?;
+
+            reporter
+                .auto_suffixed(out, path, &hdr.filename, out_path)
+                .map_err(HogError::OutputFailure)?;
+        }
+
+        let Some(out_f) = out_f else {
+            ex.flush(out)?;
+
+            reporter
+                .skipped(out, path, &hdr.filename, SkipReason::Exists)
+                .map_err(HogError::OutputFailure)?;
+
+            ex.info.files_skipped += 1;
+            ex.info.entries.push(EntryResult {
+                name: Some(hdr.filename.clone()),
+                path: out_path.clone(),
+                status: EntryStatus::Skipped,
+                bytes: 0,
+            });
+
+            if !opts.write_manifest {
+                continue;
+            }
+
+            // The manifest still has to list the file, so it is read just to
+            // calculate its digest.
+            let mut hasher = Sha256Writer::new(io::sink());
+
+            iter.copy_cur_file(&mut hasher)
+                .map_err(|e| ArchiveError::at(e, &iter))?;
+
+            ex.manifest
+                .entries
+                .push(manifest_entry(&hdr, out_path, &hasher.finish().0));
+
+            continue;
+        };
+
+        let file = ExtractedFile {
+            hdr,
+            index: iter.record_index(),
+            offset: iter.record_offset(),
+            out_path: out_path.clone(),
+            existed,
+        };
+
+        if ex.batches(file.hdr.length) {
+            let mut data = Vec::new();
+
+            iter.copy_cur_file(&mut data)
+                .map_err(|e| ArchiveError::at(e, &iter))?;
+
+            let job = ExtractJob {
+                file,
+                out_f,
+                mtime: iter.mtime(),
+                data,
+            };
+
+            ex.queue(job, out)?;
+
+            continue;
+        }
+
+        ex.flush(out)?;
+
+        let (out_f, _in_progress) = out_f;
+        let mtime = iter.mtime();
+        let name = extracted_name(out_path);
+        let result = write_extracted(out_f, mtime, opts.sparse, |out_f| {
+            copy_cur_file_filtered(&mut iter, name, opts.filter.as_ref(), out_f)
+        });
+
+        ex.finish(file, result, out)?;
+    }
+
+    ex.flush(out)?;
+
+    if let Some(e) = plan_error {
+        return Err(e);
+    }
+
+    if opts.write_manifest {
+        write_manifest(path, &ex.manifest, opts, reporter, out)?;
+    }
+
+    Ok(())
+}
+
+// Files of up to this many bytes are read into memory and written on the
+// threads of the pool by hog_extract_into(), in batches of up to
+// EXTRACT_BATCH_LEN bytes. Larger files are written as they are read.
+const MAX_BATCHED_FILE_LEN: u64 = 4 * 1024 * 1024;
+const EXTRACT_BATCH_LEN: u64 = 64 * 1024 * 1024;
+
+// How writing an extracted file went: its digest and the number of bytes
+// written, or the error that failed just that file (see
+// copy_cur_file_filtered()).
+type WriteResult = Result<([u8; 32], u64), HogError>;
+
+// A file of the HOG file being extracted by hog_extract_into(), and where it
+// is extracted to.
+struct ExtractedFile {
+    hdr: HogRecord,
+    index: u64,
+    offset: u64,
+    out_path: PathBuf,
+    existed: bool,
+}
+
+// A file read into memory, to be written to "out_f" on a thread of the pool.
+struct ExtractJob {
+    file: ExtractedFile,
+    out_f: OutputFile,
+    mtime: Option<SystemTime>,
+    data: Vec<u8>,
+}
+
+// The files of a HOG file being extracted by hog_extract_into(), along with
+// what happened to them so far.
+struct Extraction<'a> {
+    path: &'a Path,
+    opts: &'a ExtractOptions,
+    pool: Option<&'a ThreadPool>,
+    info: &'a mut HogExtractInfo,
+    manifest: Manifest,
+    // The metadata embedded in the HOG file, when its digests are used for
+    // --verify-on-extract.
+    embedded: Option<EmbeddedMetadata>,
+    reporter: &'a Reporter,
+
+    // Files waiting to be written on the threads of the pool, and how many
+    // bytes they take up.
+    batch: Vec<ExtractJob>,
+    batch_len: u64,
+}
+
+impl Extraction<'_> {
+    // Returns true if a file of "length" bytes is written on the threads of the
+    // pool. A file that fails verification stops the extraction, so no files
+    // after it are to be written.
+    fn batches(&self, length: u32) -> bool {
+        let fail = self
+            .opts
+            .verify
+            .as_ref()
+            .is_some_and(|x| x.mode == VerifyMode::Fail);
+
+        self.pool.is_some() && !fail && u64::from(length) <= MAX_BATCHED_FILE_LEN
+    }
+
+    // Adds "job" to the batch, writing the batch once it is large enough.
+    fn queue(&mut self, job: ExtractJob, out: &mut dyn Write) -> Result<(), ArchiveError> {
+        self.batch_len += job.data.len() as u64;
+        self.batch.push(job);
+
+        if self.batch_len >= EXTRACT_BATCH_LEN {
+            self.flush(out)?;
+        }
+
+        Ok(())
+    }
+
+    // Writes the files in the batch on the threads of the pool, then finishes
+    // them in the order they are stored in.
+    fn flush(&mut self, out: &mut dyn Write) -> Result<(), ArchiveError> {
+        let (Some(pool), false) = (self.pool, self.batch.is_empty()) else {
+            return Ok(());
+        };
+
+        let batch = std::mem::take(&mut self.batch);
+        self.batch_len = 0;
+
+        let opts = self.opts;
+        let results = pool.map_into(batch, |job| {
+            let (out_f, _in_progress) = job.out_f;
+            let name = extracted_name(&job.file.out_path);
+            let result = write_extracted(out_f, job.mtime, opts.sparse, |out_f| {
+                copy_data_filtered(&job.data, name, opts.filter.as_ref(), out_f)
+            });
+
+            (job.file, result)
+        });
+
+        for (file, result) in results {
+            self.finish(file, result, out)?;
+        }
+
+        Ok(())
+    }
+
+    // Finishes extracting "file" once write_extracted() is done with it:
+    // verifies it, and records and displays what happened.
+    fn finish(
+        &mut self,
+        file: ExtractedFile,
+        result: Result<WriteResult, HogError>,
+        out: &mut dyn Write,
+    ) -> Result<(), ArchiveError> {
+        let (path, opts, reporter) = (self.path, self.opts, self.reporter);
+        let ExtractedFile {
+            hdr,
+            index,
+            offset,
+            out_path,
+            existed,
+        } = file;
+        let at = |error| ArchiveError::at_record(error, index, offset);
+
+        // The filter failing for one file does not stop the others from being
+        // extracted.
+        let (digest, written) = match result.map_err(at)? {
+            Ok(x) => x,
+            Err(e) => {
+                let _ = std::fs::remove_file(opts.dir.join(&out_path));
+
+                reporter.entry_error(path, &hdr.filename, index, &e);
+
+                self.info.entries.push(EntryResult {
+                    name: Some(hdr.filename),
+                    path: out_path,
+                    status: EntryStatus::Failed,
+                    bytes: 0,
+                });
+
+                return Ok(());
+            }
+        };
+
+        if let Some(verify) = &opts.verify {
+            let name = hdr.filename.to_string_lossy();
+            let expected = match (&verify.digests, &self.embedded) {
+                (Some(digests), _) => digests.sha256(index, &name),
+                (None, Some(metadata)) => metadata.entry(&name).map(|x| x.sha256.as_str()),
+                (None, None) => None,
+            };
+            let actual = util::to_hex(&digest);
+
+            if let Some(expected) = expected.filter(|x| !x.eq_ignore_ascii_case(&actual)) {
+                let fail = verify.mode == VerifyMode::Fail;
+
+                if fail {
+                    let _ = std::fs::remove_file(opts.dir.join(&out_path));
+                }
+
+                reporter
+                    .digest_mismatch(out, path, &hdr.filename, expected, &actual, fail)
+                    .map_err(HogError::OutputFailure)?;
+
+                if fail {
+                    let e = HogError::DigestMismatch(hdr.filename.display().to_string());
+
+                    return Err(at(e));
+                }
+            }
+        }
+
+        if opts.write_manifest {
+            self.manifest
+                .entries
+                .push(manifest_entry(&hdr, &out_path, &digest));
+        }
+
+        reporter
+            .extracted(out, path, &hdr.filename, written)
+            .map_err(HogError::OutputFailure)?;
+
+        let original = opts
+            .link_dupes
+            .as_ref()
+            .and_then(|x| x.link(&opts.dir.join(&out_path), hdr.length, digest));
+
+        if let Some(original) = original {
+            reporter
+                .linked(out, path, &hdr.filename, &original)
+                .map_err(HogError::OutputFailure)?;
+        }
+
+        self.info.bytes_extracted += written;
+        self.info.files_extracted += 1;
+        self.info.entries.push(EntryResult {
+            name: Some(hdr.filename),
+            path: out_path,
+            status: if existed {
+                EntryStatus::Overwritten
+            } else {
+                EntryStatus::Extracted
+            },
+            bytes: written,
+        });
+
+        Ok(())
+    }
+}
+
+// Returns the name a file extracted to "out_path" is given to the filter,
+// which (unlike the stored name) is never a path.
+fn extracted_name(out_path: &Path) -> &Path {
+    Path::new(out_path.file_name().unwrap_or_default())
+}
+
+// Writes an extracted file to "out_f" (opened by open_output_file()) with
+// "copy", which returns what copy_cur_file_filtered() does, then sets its
+// modification time to "mtime". The file is closed when this returns.
+fn write_extracted(
+    out_f: BufWriter<File>,
+    mtime: Option<SystemTime>,
+    sparse: bool,
+    copy: impl FnOnce(&mut dyn Write) -> Result<WriteResult, HogError>,
+) -> Result<WriteResult, HogError> {
+    // The digest is only needed for the manifest and for linking duplicates,
+    // but is cheap to calculate while writing the file anyway.
+    let mtime_f = mtime_handle(&out_f, mtime)?;
+    let mut out_f = extract_writer(out_f, sparse);
+
+    let written = match copy(&mut *out_f)? {
+        Ok(x) => x,
+        Err(e) => return Ok(Err(e)),
+    };
+
+    out_f.flush().map_err(HogError::ExtractFailure)?;
+    restore_mtime(mtime_f, mtime)?;
+
+    Ok(Ok(written))
+}
+
+// Plans which files of "hog_file" are extracted, and where to. Along with the
+// plan, the error that stopped planning is returned (if any), since the files
+// before it can still be extracted.
+fn plan_extract(
+    path: &Path,
+    hog_file: &mut HogFileReader,
+    opts: &ExtractOptions,
+) -> Result<(ExtractPlan, Option<ArchiveError>), ArchiveError> {
+    let mut iter = hog_file.records()?;
+    let (plan, error) = ExtractPlan::new(&mut iter, &opts.plan.for_archive(path));
+
+    let error = match error {
+        Some(e) => Some(ArchiveError::at(e, &iter)),
+
+        // Asking for a file that is not there is an error, like it is for -e.
+        None => plan
+            .missing_index(&opts.plan)
+            .map(|x| HogError::IndexOutOfRange(x, plan.record_count).into()),
+    };
+
+    Ok((plan, error))
+}
+
+// Displays what extracting a HOG file would do (for --dry-run), without
+// writing anything.
+fn hog_plan(
+    path: &Path,
+    opts: &ExtractOptions,
+    reporter: &Reporter,
+    out: &mut dyn Write,
+) -> Result<ExtractPlan, ArchiveError> {
+    let mut hog_file = open_hog_file(path, opts.read, reporter, out)?;
+    let (plan, error) = plan_extract(path, &mut hog_file, opts)?;
+
+    for entry in &plan.entries {
+        if let Some(earlier) = &entry.collision {
+            reporter
+                .case_collision(out, path, &entry.name, earlier, &entry.path)
+                .map_err(HogError::OutputFailure)?;
+        }
+
+        reporter
+            .planned(out, path, entry)
+            .map_err(HogError::OutputFailure)?;
+    }
+
+    match error {
+        Some(e) => Err(e),
+        None => Ok(plan),
+    }
+}
+
+// Returns the manifest entry for a file extracted to "out_path", with the
+// given SHA-256 digest.
+fn manifest_entry(hdr: &HogRecord, out_path: &Path, digest: &[u8; 32]) -> ManifestEntry {
+    ManifestEntry {
+        name: hdr.filename.to_string_lossy().into_owned(),
+        path: out_path.to_string_lossy().into_owned(),
+        length: hdr.length,
+        sha256: util::to_hex(digest),
+    }
+}
+
+// Writes the manifest for an extracted HOG file to manifest.toml, next to the
+// extracted files.
+fn write_manifest(
+    archive: &Path,
+    manifest: &Manifest,
+    opts: &ExtractOptions,
+    reporter: &Reporter,
+    out: &mut dyn Write,
+) -> Result<(), HogError> {
+    let path = opts.dir.join(MANIFEST_FILENAME);
+    let toml = manifest.to_toml()?;
+
+    match open_output_file(&path, opts.overwrite, opts.read.no_follow)? {
+        Some((mut f, _in_progress)) => f
+            .write_all(toml.as_bytes())
+            .and_then(|_| f.flush())
+            .map_err(HogError::ExtractFailure),
+        None => reporter
+            .skipped(
+                out,
+                archive,
+                Path::new(MANIFEST_FILENAME),
+                SkipReason::Exists,
+            )
+            .map_err(HogError::OutputFailure),
+    }
+}
+
+// Copies the current file of "iter" (extracted as "name") to "out_f", piping it
+// through "filter" if there is one. Returns the SHA-256 digest of the file as
+// it is stored in the HOG file (not of what the filter wrote), which is what
+// manifests record, and the number of bytes written to "out_f".
+//
+// The filter failing only fails this file, so that error is returned inside
+// of the Ok, while errors reading the HOG file or writing "out_f" are returned
+// as the Err.
+fn copy_cur_file_filtered<R: Read + Seek>(
+    iter: &mut HogRecordIter<'_, R>,
+    name: &Path,
+    filter: Option<&FilterCommand>,
+    out_f: &mut dyn Write,
+) -> Result<WriteResult, HogError> {
+    let Some(filter) = filter else {
+        let mut out_f = Sha256Writer::new(out_f);
+        let written = iter.copy_cur_file_range(&mut out_f, 0, None)?;
+
+        return Ok(Ok((out_f.finish().0, written)));
+    };
+
+    let mut data = Vec::new();
+    iter.copy_cur_file(&mut data)?;
+
+    copy_data_filtered(&data, name, Some(filter), out_f)
+}
+
+// Like copy_cur_file_filtered(), for a file already read into "data".
+fn copy_data_filtered(
+    data: &[u8],
+    name: &Path,
+    filter: Option<&FilterCommand>,
+    out_f: &mut dyn Write,
+) -> Result<WriteResult, HogError> {
+    let written = match filter {
+        Some(filter) => match filter.run(&name.to_string_lossy(), data, out_f) {
+            Ok(x) => x,
+            Err(HogError::ExtractFailure(e)) => return Err(HogError::ExtractFailure(e)),
+            Err(e) => return Ok(Err(e)),
+        },
+        None => {
+            out_f.write_all(data).map_err(HogError::ExtractFailure)?;
+
+            data.len() as u64
+        }
+    };
+
+    let mut data = data;
+
+    util::sha256(&mut data)
+        .map(|x| Ok((x, written)))
+        .map_err(HogError::ReadFailure)
+}
+
+// Describes which file (and which part of it) to extract with
+// hog_extract_entry().
+struct EntrySelection<'a> {
+    name: &'a str,
+    offset: u64,
+    length: Option<u64>,
+    to_stdout: bool,
+
+    // Write it as source code, rather than as is.
+    source: Option<SourceLang>,
+
+    // Command to pipe it through.
+    filter: Option<&'a FilterCommand>,
+}
+
+impl EntrySelection<'_> {
+    // Copies the selected part of the current file of "iter" (extracted as
+    // "name") to "out_f", through the filter or as source code if that was
+    // asked for. Returns the number of bytes of the file that were copied.
+    fn copy(
+        &self,
+        iter: &mut HogRecordIter,
+        name: &Path,
+        out_f: &mut impl Write,
+    ) -> Result<u64, HogError> {
+        if let Some(filter) = self.filter {
+            let mut data = Vec::new();
+            let copied = iter.copy_cur_file_range(&mut data, self.offset, self.length)?;
+
+            filter.run(&name.to_string_lossy(), &data, out_f)?;
+
+            return Ok(copied);
+        }
+
+        let Some(lang) = self.source else {
+            return iter.copy_cur_file_range(out_f, self.offset, self.length);
+        };
+
+        let mut data = Vec::new();
+        let copied = iter.copy_cur_file_range(&mut data, self.offset, self.length)?;
+
+        source::write_source(out_f, lang, &name.to_string_lossy(), &data)
+            .map_err(HogError::ExtractFailure)?;
+
+        Ok(copied)
+    }
+}
+
+// Extracts a single file from a HOG file, either to disk or to stdout. Progress
+// information is written to stderr when extracting to stdout.
+//
+// Filenames are compared without regard to case, like DOS would.
+fn hog_extract_entry(
+    path: &Path,
+    sel: &EntrySelection,
+    opts: &ExtractOptions,
+    reporter: &Reporter,
+) -> Result<(), ArchiveError> {
+    // Progress information goes to stderr when the file goes to stdout.
+    let mut hog_file = if sel.to_stdout {
+        open_hog_file(path, opts.read, reporter, &mut io::stderr())
+    } else {
+        open_hog_file(path, opts.read, reporter, &mut io::stdout())
+    }?;
+    let mut iter = hog_file.records()?;
+
+    loop {
+        match iter.next() {
+            Some(Ok(hdr)) => {
+                if !hdr
+                    .filename
+                    .to_string_lossy()
+                    .eq_ignore_ascii_case(sel.name)
+                {
+                    continue;
+                }
+
+                if sel.to_stdout {
+                    // The filter is given the name as it would be extracted,
+                    // which is never a path.
+                    let name = PathBuf::from(hog::sanitize_name(&hdr.filename.to_string_lossy()));
+                    let mut out_f = io::stdout().lock();
+                    let written = sel
+                        .copy(&mut iter, &name, &mut out_f)
+                        .and_then(|x| out_f.flush().map(|_| x).map_err(HogError::ExtractFailure))
+                        .map_err(|e| ArchiveError::at(e, &iter))?;
+
+                    reporter
+                        .extracted(&mut io::stderr(), path, &hdr.filename, written)
+                        .map_err(HogError::OutputFailure)?;
+                } else {
+                    let mut out_path = opts
+                        .output_path(path, &mut iter, &hdr.filename)
+                        .map(|x| opts.dir.join(x))
+                        .map_err(|e| ArchiveError::at(e, &iter))?;
+                    let name = PathBuf::from(out_path.file_name().unwrap_or_default());
+
+                    // Source code goes in a file named after the file, with
+                    // the extension of the language after its own.
+                    if let Some(lang) = sel.source {
+                        let mut name = out_path.into_os_string();
+                        name.push(".");
+                        name.push(lang.extension());
+                        out_path = name.into();
+                    }
+
+                    let (suffix, out_f) = if opts.auto_suffix {
+                        open_output_file_suffixed(&out_path, opts.read.no_follow)
+                            .map(|(n, f)| (n, Some(f)))
+                    } else {
+                        open_output_file(&out_path, opts.overwrite, opts.read.no_follow)
+                            .map(|f| (None, f))
+                    }
+                    .map_err(|e| ArchiveError::at(e, &iter))?;
+
+                    if let Some(n) = suffix {
+                        out_path = util::numbered_path(&out_path, n);
+
+                        reporter
+                            .auto_suffixed(&mut io::stdout(), path, &hdr.filename, &out_path)
+                            .map_err(HogError::OutputFailure)?;
+                    }
+
+                    match out_f {
+                        Some((out_f, _in_progress)) => {
+                            let mtime_f = mtime_handle(&out_f, iter.mtime())
+                                .map_err(|e| ArchiveError::at(e, &iter))?;
+                            let mut out_f = extract_writer(out_f, opts.sparse);
+
+                            let written = sel
+                                .copy(&mut iter, &name, &mut out_f)
+                                .and_then(|x| {
+                                    out_f.flush().map(|_| x).map_err(HogError::ExtractFailure)
+                                })
+                                .map_err(|e| ArchiveError::at(e, &iter))?;
+
+                            restore_mtime(mtime_f, iter.mtime())
+                                .map_err(|e| ArchiveError::at(e, &iter))?;
+                            drop(out_f);
+
+                            reporter
+                                .extracted(&mut io::stdout(), path, &hdr.filename, written)
+                                .map_err(HogError::OutputFailure)?;
+                        }
+                        None => {
+                            reporter
+                                .skipped(&mut io::stdout(), path, &hdr.filename, SkipReason::Exists)
+                                .map_err(HogError::OutputFailure)?;
+                        }
+                    }
+                }
+
+                return Ok(());
+            }
+            Some(Err(e)) => {
+                return Err(ArchiveError::at(e, &iter));
+            }
+            None => {
+                return Err(HogError::EntryNotFound(sel.name.to_string()).into());
+            }
+        }
+    }
+}
+
+// Extracts a single file from multiple HOG files, using hog_extract_entry()
+fn extract_hog_entry(
+    files: &[PathBuf],
+    sel: &EntrySelection,
+    opts: &ExtractOptions,
+    reporter: &Reporter,
+) {
+    for file in files {
+        if let Err(e) = hog_extract_entry(file, sel, opts, reporter) {
+            reporter.archive_error(file, &e);
+        }
+    }
+}
+
+// Summarizes information about the contents of the HOG file. Returned by
+// hog_info().
+struct HogInfoSummary {
+    num_files: u64,
+    num_bytes: u64,
+
+    // Records skipped by --ignore-errors.
+    bad_records: u64,
+
+    // Files that do not match the embedded metadata, with --check.
+    mismatches: u64,
+}
+
+impl HogInfoSummary {
+    fn new() -> Self {
+        Self {
+            num_files: 0,
+            num_bytes: 0,
+            bad_records: 0,
+            mismatches: 0,
+        }
+    }
+}
+
+// Options controlling how HOG files are listed.
+struct ListOptions {
+    // Number of bytes of each text file to display.
+    preview: Option<u64>,
+
+    // How to display a thumbnail of each image, if at all.
+    preview_images: Option<ImagePreview>,
+
+    // Report bad records and keep going, rather than stopping at the first
+    // one.
+    ignore_errors: bool,
+
+    // Only list the files whose names match this.
+    name_match: Option<Regex>,
+
+    // Display the raw record headers, instead of the files.
+    debug_records: bool,
+
+    // Check the files against the metadata embedded in the HOG file.
+    check: bool,
+
+    // PIG file to pair the levels with, and its name.
+    pig: Option<(String, PigFile)>,
+
+    // Summarize the types of the files, instead of listing them.
+    tree: bool,
+
+    read: ReadOptions,
+}
+
+// Reads the metadata embedded in the HOG file by --embed-metadata, if there is
+// any. Metadata that cannot be read is warned about and ignored, so that the
+// files can still be listed.
+fn embedded_metadata(
+    path: &Path,
+    hog_file: &mut HogFileReader,
+    reporter: &Reporter,
+    out: &mut dyn Write,
+) -> Option<EmbeddedMetadata> {
+    // Errors finding it are left for the listing to report.
+    let entry = hog_file.find(METADATA_NAME).ok()??;
+    let metadata = hog_file
+        .read_entry(&entry)
+        .and_then(|x| EmbeddedMetadata::parse(&x));
+
+    match metadata {
+        Ok(x) => Some(x),
+        Err(e) => {
+            let warning = HogWarning::UnreadableMetadata(e.to_string());
+            let _ = reporter.warning(out, path, &warning);
+
+            None
+        }
+    }
+}
+
+// Returns true if "name" is the name of a demo recording.
+fn is_demo(name: &Path) -> bool {
+    name.extension()
+        .is_some_and(|x| x.eq_ignore_ascii_case("dem"))
+}
+
+// Returns true if "name" is the name of a Descent 3 table file.
+fn is_table(name: &Path) -> bool {
+    name.extension()
+        .is_some_and(|x| x.eq_ignore_ascii_case("gam"))
+}
+
+// Displays information about the HOG file to "out", such as file contents and
+// file sizes.
+//
+// If "opts.preview" is given, that many bytes of each text file (decoded, for
+// TXB files) are displayed too.
+fn hog_info(
+    path: &Path,
+    opts: &ListOptions,
+    reporter: &Reporter,
+    out: &mut dyn Write,
+) -> Result<HogInfoSummary, ArchiveError> {
+    let mut hog_file = open_hog_file(path, opts.read, reporter, out)?;
+
+    if opts.debug_records {
+        return hog_debug_records(path, &mut hog_file, opts, reporter, out);
+    }
+
+    // The embedded metadata is only looked for when it would be used.
+    let metadata = if opts.check || reporter.lists_entries() {
+        embedded_metadata(path, &mut hog_file, reporter, out)
+    } else {
+        None
+    };
+
+    let mut hog_info_summary = HogInfoSummary::new();
+    let mut levels = Vec::new();
+    let mut pogs = HashMap::new();
+    let mut types = TypeSummary::new();
+    let mut iter = hog_file.records()?;
+
+    loop {
+        match iter.next() {
+            Some(Ok(hdr)) => {
+                report_slack(path, &iter, reporter, out)?;
+
+                if let Some(name_match) = &opts.name_match {
+                    if !name_match.is_match(&hdr.filename.to_string_lossy()) {
+                        continue;
+                    }
+                }
+
+                // Files are only hashed with --check, since that means reading
+                // all of them.
+                let name = hdr.filename.to_string_lossy();
+                let integrity = match &metadata {
+                    Some(metadata) if opts.check => match metadata.entry(&name) {
+                        Some(entry) => {
+                            let mut hasher = Sha256Writer::new(io::sink());
+                            iter.stream_cur_file(&mut hasher)
+                                .map_err(|e| ArchiveError::at(e, &iter))?;
+
+                            if entry.matches(hdr.length.into(), &hasher.finish().0) {
+                                Some(Integrity::Verified)
+                            } else {
+                                Some(Integrity::Mismatch)
+                            }
+                        }
+                        None => None,
+                    },
+                    Some(metadata) => metadata.entry(&name).map(|_| Integrity::Unverified),
+                    None => None,
+                };
+
+                if integrity == Some(Integrity::Mismatch) {
+                    hog_info_summary.mismatches += 1;
+                }
+
+                let content_type = if reporter.shows_content_type()
+                    || opts.preview.is_some()
+                    || opts.preview_images.is_some()
+                    || opts.tree
+                {
+                    Some(
+                        iter.sniff_cur_file()
+                            .map_err(|e| ArchiveError::at(e, &iter))?,
+                    )
+                } else {
+                    None
+                };
+
+                if let (true, Some(content_type)) = (opts.tree, content_type) {
+                    types.add(content_type, hdr.length.into());
+                } else {
+                    reporter
+                        .listed(
+                            out,
+                            path,
+                            &hdr.filename,
+                            hdr.length,
+                            content_type,
+                            integrity,
+                        )
+                        .map_err(HogError::OutputFailure)?;
+                }
+
+                report_warnings(path, &hdr, reporter, out)?;
+
+                if reporter.shows_content_type() && !opts.tree && is_demo(&hdr.filename) {
+                    // Demos that are not too large are read whole, to find
+                    // how long they last.
+                    let info = if u64::from(hdr.length) <= demo::MAX_DEMO_LEN {
+                        let data = iter
+                            .peek_cur_file(hdr.length.into())
+                            .map_err(|e| ArchiveError::at(e, &iter))?;
+
+                        demo::parse_demo(&data, &data).map(|x| demo::DemoInfo {
+                            duration: demo::demo_duration(&data),
+                            ..x
+                        })
+                    } else {
+                        let head = iter
+                            .peek_cur_file(demo::DEMO_HEAD_LEN as u64)
+                            .map_err(|e| ArchiveError::at(e, &iter))?;
+                        let tail = iter
+                            .peek_cur_file_tail(demo::DEMO_TAIL_LEN as u64)
+                            .map_err(|e| ArchiveError::at(e, &iter))?;
+
+                        demo::parse_demo(&head, &tail)
+                    };
+
+                    if let Some(info) = info {
+                        reporter.demo(out, &info).map_err(HogError::OutputFailure)?;
+                    }
+                }
+
+                if reporter.shows_content_type()
+                    && !opts.tree
+                    && is_table(&hdr.filename)
+                    && u64::from(hdr.length) <= table::MAX_TABLE_LEN
+                {
+                    let data = iter
+                        .peek_cur_file(hdr.length.into())
+                        .map_err(|e| ArchiveError::at(e, &iter))?;
+
+                    if let Some(info) = table::parse_table(&data) {
+                        reporter
+                            .table(out, &info)
+                            .map_err(HogError::OutputFailure)?;
+                    }
+                }
+
+                if let (Some(len), Some(ContentType::Text | ContentType::Txb)) =
+                    (opts.preview, content_type)
+                {
+                    let mut data = iter
+                        .peek_cur_file(len)
+                        .map_err(|e| ArchiveError::at(e, &iter))?;
+
+                    if content_type == Some(ContentType::Txb) {
+                        data.iter_mut()
+                            .for_each(|x| *x = sniff::decode_txb_byte(*x));
+                    }
+
+                    reporter
+                        .preview(out, path, &hdr.filename, &String::from_utf8_lossy(&data))
+                        .map_err(HogError::OutputFailure)?;
+                }
+
+                if let (Some(_), Some(content_type)) = (opts.preview_images, content_type) {
+                    preview_image(&mut iter, path, &hdr, content_type, opts, reporter, out)?;
+                }
+
+                if opts.pig.is_some() {
+                    find_pig_assets(path, &mut iter, &hdr, &mut levels, &mut pogs, reporter, out)
+                        .map_err(|e| ArchiveError::at(e, &iter))?;
+                }
+
+                hog_info_summary.num_files += 1;
+                hog_info_summary.num_bytes += u64::from(hdr.length);
+            }
+            Some(Err(e)) if opts.ignore_errors => {
+                // The iterator ends by itself if it cannot go on past the
+                // error.
+                reporter
+                    .bad_record(out, path, &ArchiveError::at(e, &iter))
+                    .map_err(HogError::OutputFailure)?;
+
+                hog_info_summary.bad_records += 1;
+            }
+            Some(Err(e)) => {
+                return Err(ArchiveError::at(e, &iter));
+            }
+            None => {
+                report_slack(path, &iter, reporter, out)?;
+                report_trailing_bytes(path, &iter, reporter, out)?;
+
+                break;
+            }
+        }
+    }
+
+    if opts.tree {
+        reporter
+            .type_tree(out, path, &types)
+            .map_err(HogError::OutputFailure)?;
+    }
+
+    if let Some((pig_name, pig)) = &opts.pig {
+        for (level, uses) in levels {
+            let stem = level.file_stem().unwrap_or_default().to_string_lossy();
+            let pog = pogs
+                .get(&format!("{}.pog", stem.to_ascii_lowercase()))
+                .map(|(name, pog): &(String, PogFile)| (name.as_str(), pog));
+            let pairing = LevelPairing::new(&uses, pig_name, pig, pog);
+
+            reporter
+                .pig_pairing(out, path, &level, pig_name, &pairing)
+                .map_err(HogError::OutputFailure)?;
+        }
+    }
+
+    Ok(hog_info_summary)
+}
+
+// Reads the PIG file given with --with-pig, exiting if it cannot be read.
+fn load_pig(path: &Path, reporter: &Reporter) -> (String, PigFile) {
+    let pig = File::open(path)
+        .map_err(HogError::OpenInputFailure)
+        .and_then(|f| PigFile::read(&mut io::BufReader::new(f)));
+
+    match pig {
+        Ok(pig) => {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+            (name.into_owned(), pig)
+        }
+        Err(e) => {
+            reporter.archive_error(path, &e.into());
+
+            reporter.exit();
+        }
+    }
+}
+
+// Records the current file of "iter" (in the HOG file at "path") for
+// --with-pig if it is a Descent 2 level (in "levels", with the PIG file it
+// uses) or a POG file (in "pogs", by lowercase name). POG files that cannot be
+// read are warned about and left out.
+fn find_pig_assets(
+    path: &Path,
+    iter: &mut HogRecordIter,
+    hdr: &HogRecord,
+    levels: &mut Vec<(PathBuf, String)>,
+    pogs: &mut HashMap<String, (String, PogFile)>,
+    reporter: &Reporter,
+    out: &mut dyn Write,
+) -> Result<(), HogError> {
+    let name = hdr.filename.to_string_lossy();
+
+    if name.to_ascii_lowercase().ends_with(".pog") {
+        let data = iter.peek_cur_file(pig::MAX_POG_HEADER_LEN)?;
+
+        match PogFile::read(&mut data.as_slice()) {
+            Ok(pog) => {
+                pogs.insert(name.to_ascii_lowercase(), (name.into_owned(), pog));
+            }
+            Err(e) => {
+                let warning = HogWarning::UnreadablePog(hdr.filename.clone(), e.to_string());
+
+                reporter
+                    .warning(out, path, &warning)
+                    .map_err(HogError::OutputFailure)?;
+            }
+        }
+    } else if iter.sniff_cur_file()? == ContentType::Rl2 {
+        let data = iter.peek_cur_file(sniff::SNIFF_LEN as u64)?;
+
+        if let Some(pig) = level::parse_level_header(&data)?.pig_name() {
+            levels.push((hdr.filename.clone(), pig));
+        }
+    }
+
+    Ok(())
+}
+
+// Largest thumbnails --preview-images draws, in pixels: ANSI thumbnails take
+// a character for every pixel across, and for every two down.
+const ANSI_THUMBNAIL_SIZE: (u32, u32) = (64, 40);
+const SIXEL_THUMBNAIL_SIZE: (u32, u32) = (192, 120);
+
+// Displays a thumbnail of the current file of "iter" (stored in the HOG file
+// at "path" as "hdr"), if it is an image, for --preview-images. Images that
+// cannot be decoded are left without one, and images that go past the
+// --max-convert-size or --max-convert-time limits are reported.
+fn preview_image(
+    iter: &mut HogRecordIter,
+    path: &Path,
+    hdr: &HogRecord,
+    content_type: ContentType,
+    opts: &ListOptions,
+    reporter: &Reporter,
+    out: &mut dyn Write,
+) -> Result<(), ArchiveError> {
+    let Some(how) = opts.preview_images else {
+        return Ok(());
+    };
+
+    // Files too large to be an image that can be decoded are not read.
+    if !matches!(content_type, ContentType::Pcx | ContentType::Bbm)
+        || u64::from(hdr.length) > image::MAX_IMAGE_LEN
+    {
+        return Ok(());
+    }
+
+    let data = iter
+        .peek_cur_file(hdr.length.into())
+        .map_err(|e| ArchiveError::at(e, &*iter))?;
+
+    let image = match image::decode(content_type, &data, &opts.read.limits) {
+        Some(Ok(x)) => x,
+        Some(Err(e @ HogError::LimitExceeded(_))) => {
+            reporter.entry_error(path, &hdr.filename, iter.record_index(), &e);
+
+            return Ok(());
+        }
+        _ => return Ok(()),
+    };
+
+    let lines = match how {
+        ImagePreview::Ansi => {
+            let (width, height) = ANSI_THUMBNAIL_SIZE;
+            image.thumbnail(width, height).to_ansi()
+        }
+        ImagePreview::Sixel => {
+            let (width, height) = SIXEL_THUMBNAIL_SIZE;
+            vec![image.thumbnail(width, height).to_sixel()]
+        }
+    };
+
+    reporter
+        .image_preview(out, path, &hdr.filename, &lines)
+        .map_err(|e| HogError::OutputFailure(e).into())
+}
+
+// Reports the warnings about a file listed or tested in the HOG file at
+// "path".
+fn report_warnings(
+    path: &Path,
+    hdr: &HogRecord,
+    reporter: &Reporter,
+    out: &mut dyn Write,
+) -> Result<(), HogError> {
+    for warning in hdr.warnings() {
+        // The metadata written by --embed-metadata is not for the game, so
+        // its name does not have to be one the game can load.
+        if matches!(&warning, HogWarning::SuspiciousName(name) if name == Path::new(METADATA_NAME))
+        {
+            continue;
+        }
+
+        reporter
+            .warning(out, path, &warning)
+            .map_err(HogError::OutputFailure)?;
+    }
+
+    Ok(())
+}
+
+// Reports the slack and the padding records skipped right before the record
+// "iter" just read from the HOG file at "path" (or before its end), if any.
+fn report_slack<R: Read + Seek>(
+    path: &Path,
+    iter: &HogRecordIter<R>,
+    reporter: &Reporter,
+    out: &mut dyn Write,
+) -> Result<(), HogError> {
+    if let Some((offset, len)) = iter.slack_before() {
+        reporter
+            .warning(out, path, &HogWarning::Slack(offset, len))
+            .map_err(HogError::OutputFailure)?;
+    }
+
+    for &(offset, len) in iter.padding_before() {
+        reporter
+            .warning(out, path, &HogWarning::Padding(offset, len))
+            .map_err(HogError::OutputFailure)?;
+    }
+
+    Ok(())
+}
+
+// Reports the bytes left over at the end of the HOG file at "path", once
+// "iter" has gone through all of its records.
+fn report_trailing_bytes<R: Read + Seek>(
+    path: &Path,
+    iter: &HogRecordIter<R>,
+    reporter: &Reporter,
+    out: &mut dyn Write,
+) -> Result<(), HogError> {
+    if iter.trailing_bytes() > 0 {
+        reporter
+            .warning(out, path, &HogWarning::TrailingBytes(iter.trailing_bytes()))
+            .map_err(HogError::OutputFailure)?;
+    }
+
+    Ok(())
+}
+
+// Displays the raw record headers of the HOG file to "out", for --debug-records.
+// The listing carries on past records with bad filenames, which are counted as
+// bad records.
+fn hog_debug_records(
+    path: &Path,
+    hog_file: &mut HogFileReader,
+    opts: &ListOptions,
+    reporter: &Reporter,
+    out: &mut dyn Write,
+) -> Result<HogInfoSummary, ArchiveError> {
+    let mut hog_info_summary = HogInfoSummary::new();
+    let mut index = 0;
+    let mut offset = 3;
+
+    for header in hog_file.record_headers()? {
+        let header = header.map_err(|error| ArchiveError {
+            error,
+            entry_index: Some(index),
+            offset: Some(offset),
+        })?;
+        offset = header.offset + hog::HDR_LEN as u64 + u64::from(header.length());
+
+        if header.is_padding() {
+            reporter
+                .record_header(out, path, None, &header)
+                .map_err(HogError::OutputFailure)?;
+
+            continue;
+        }
+
+        let name_matches = match (header.record(), &opts.name_match) {
+            (Ok(record), Some(name_match)) => {
+                name_match.is_match(&record.filename.to_string_lossy())
+            }
+            _ => true,
+        };
+
+        if name_matches {
+            reporter
+                .record_header(out, path, Some(index), &header)
+                .map_err(HogError::OutputFailure)?;
+
+            if header.record().is_ok() {
+                hog_info_summary.num_files += 1;
+                hog_info_summary.num_bytes += u64::from(header.length());
+            } else {
+                hog_info_summary.bad_records += 1;
+            }
+        }
+
+        index += 1;
+    }
+
+    Ok(hog_info_summary)
+}
+
+// Summarizes what happened while testing a HOG file (filled in by hog_test()).
+struct HogTestInfo {
+    summary: HogInfoSummary,
+    entries: Vec<TestedEntry>,
+}
+
+impl HogTestInfo {
+    fn new() -> Self {
+        Self {
+            summary: HogInfoSummary::new(),
+            entries: Vec::new(),
+        }
+    }
+}
+
+// A record read while testing a HOG file. "name" and "length" are None if the
+// record header itself could not be read, "problem" is the error that was
+// found, if any.
+struct TestedEntry {
+    index: u64,
+    offset: u64,
+    name: Option<PathBuf>,
+    length: Option<u32>,
+    problem: Option<&'static str>,
+}
+
+// Reads every file in the HOG file (without saving it), to check that the HOG
+// file is complete and readable. Progress is written to "out", and what was
+// found is added to "info".
+fn hog_test(
+    path: &Path,
+    read: ReadOptions,
+    info: &mut HogTestInfo,
+    reporter: &Reporter,
+    out: &mut dyn Write,
+) -> Result<(), ArchiveError> {
+    let mut hog_file = open_hog_file(path, read, reporter, out)?;
+    let mut iter = hog_file.records()?;
+
+    loop {
+        match iter.next() {
+            Some(Ok(hdr)) => {
+                report_slack(path, &iter, reporter, out)?;
+
+                let mut entry = TestedEntry {
+                    index: iter.record_index(),
+                    offset: iter.record_offset(),
+                    name: Some(hdr.filename.clone()),
+                    length: Some(hdr.length),
+                    problem: None,
+                };
+
+                if let Err(e) = iter.check_cur_file() {
+                    entry.problem = Some(e.name());
+                    info.entries.push(entry);
+
+                    return Err(ArchiveError::at(e, &iter));
+                }
+
+                info.entries.push(entry);
+
+                reporter
+                    .tested(out, path, &hdr.filename, hdr.length)
+                    .map_err(HogError::OutputFailure)?;
+
+                report_warnings(path, &hdr, reporter, out)?;
+
+                info.summary.num_files += 1;
+                info.summary.num_bytes += u64::from(hdr.length);
+            }
+            Some(Err(e)) => {
+                info.entries.push(TestedEntry {
+                    index: iter.record_index(),
+                    offset: iter.record_offset(),
+                    name: None,
+                    length: None,
+                    problem: Some(e.name()),
+                });
+
+                return Err(ArchiveError::at(e, &iter));
+            }
+            None => {
+                report_slack(path, &iter, reporter, out)?;
+                report_trailing_bytes(path, &iter, reporter, out)?;
+
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Runs "work" on each of the HOG files using the thread pool, then calls "done"
+// with each result, in the same order as "files".
+//
+// When only a single thread is doing the work, output is written directly to
+// stdout as it is produced. Otherwise, the output of each HOG file is buffered
+// and displayed all at once, so the output of different HOG files is never
+// interleaved.
+fn process_hog_files<T, W, D>(pool: &ThreadPool, files: &[PathBuf], work: W, mut done: D)
+where
+    T: Send,
+    W: Fn(&Path, &mut dyn Write) -> T + Sync,
+    D: FnMut(&Path, T),
+{
+    if pool.threads() == 1 || files.len() <= 1 {
+        for file in files {
+            let result = work(file, &mut io::stdout().lock());
+
+            done(file, result);
+        }
+    } else {
+        let results = pool.map(files, |file| {
+            let mut buf = Vec::new();
+            let result = work(file, &mut buf);
+
+            (buf, result)
+        });
+
+        for (file, (buf, result)) in files.iter().zip(results) {
+            // Nothing useful can be done if stdout is gone, processing has
+            // already finished.
+            let _ = io::stdout().lock().write_all(&buf);
+
+            done(file, result);
+        }
+    }
+}
+
+// Totals across all of the HOG files processed, displayed when more than one
+// HOG file was given.
+struct HogTotals {
+    archives: u64,
+    files: u64,
+    bytes: u64,
+    errors: u64,
+}
+
+impl HogTotals {
+    fn new() -> Self {
+        Self {
+            archives: 0,
+            files: 0,
+            bytes: 0,
+            errors: 0,
+        }
+    }
+
+    // Adds a HOG file that was processed successfully.
+    fn add(&mut self, files: u64, bytes: u64) {
+        self.archives += 1;
+        self.files += files;
+        self.bytes += bytes;
+    }
+
+    // Adds a HOG file that could not be processed.
+    fn add_error(&mut self) {
+        self.archives += 1;
+        self.errors += 1;
+    }
+}
+
+// Extracts the files of the HOG file at "path" back to back into the file at
+// "concat_path" (for --concat), and writes a map of where each one is to
+// "index_path". The map is a HogIndex of the concatenated file rather than
+// of the HOG file, so its header has the length and digest of the
+// concatenated file.
+fn hog_concat(
+    path: &Path,
+    concat_path: &Path,
+    index_path: &Path,
+    opts: &ExtractOptions,
+    reporter: &Reporter,
+) -> Result<(), ArchiveError> {
+    // Unlike extracted files, existing output files are not skipped, as
+    // there would be nothing left to do.
+    let open = |path: &Path| {
+        open_output_file(path, opts.overwrite, opts.read.no_follow)?.ok_or_else(|| {
+            HogError::OpenOutputFailure(io::Error::new(
+                ErrorKind::AlreadyExists,
+                format!("\"{}\" already exists", path.display()),
+            ))
+        })
+    };
+
+    let mut hog_file = open_hog_file(path, opts.read, reporter, &mut io::stdout())?;
+    let plan_opts = opts.plan.for_archive(path);
+    let (out_f, _in_progress) = open(concat_path)?;
+    let mut sink = ConcatSink::new(out_f);
+
+    let plan = extract::extract_all(&mut hog_file, &plan_opts, &mut sink)?;
+
+    if let Some(index) = plan.missing_index(&plan_opts) {
+        return Err(HogError::IndexOutOfRange(index, plan.record_count).into());
+    }
+
+    let (mut out_f, entries) = sink.finish();
+    out_f.flush().map_err(HogError::ExtractFailure)?;
+    drop(out_f);
+
+    let (files, bytes) = plan.extracted();
+    let digest = File::open(concat_path)
+        .and_then(|mut f| index::digest(&mut f))
+        .map_err(HogError::ReadFailure)?;
+    let index = HogIndex {
+        archive_len: bytes,
+        digest,
+        entries,
+    };
+
+    let (mut index_f, _index_in_progress) = open(index_path)?;
+    index
+        .write(&mut index_f)
+        .and_then(|_| index_f.flush())
+        .map_err(HogError::ExtractFailure)?;
+
+    reporter.concatenated(path, concat_path, index_path, files, bytes);
+
+    Ok(())
+}
+
+// Writes the files of the HOG file at "path" chosen by "opts" to "out" as a
+// stream of frames, for --stream-all.
+fn hog_stream(
+    path: &Path,
+    opts: &ExtractOptions,
+    out: &mut dyn Write,
+    reporter: &Reporter,
+) -> Result<(), ArchiveError> {
+    // Warnings go to stderr, rather than into the stream.
+    let mut hog_file = open_hog_file(path, opts.read, reporter, &mut io::stderr())?;
+    let plan_opts = opts.plan.for_archive(path);
+    let mut sink = FrameSink::new(BufWriter::new(out));
+
+    let plan = extract::extract_all(&mut hog_file, &plan_opts, &mut sink)?;
+
+    if let Some(index) = plan.missing_index(&plan_opts) {
+        return Err(HogError::IndexOutOfRange(index, plan.record_count).into());
+    }
+
+    sink.finish().flush().map_err(HogError::OutputFailure)?;
+
+    let (files, bytes) = plan.extracted();
+
+    reporter.streamed(path, files, bytes);
+
+    Ok(())
+}
+
+// Extracts multiple HOG files, using hog_extract()
+fn extract_hog_files(
+    pool: &ThreadPool,
+    files: &[PathBuf],
+    opts: &ExtractOptions,
+    reporter: &Reporter,
+) {
+    let mut totals = HogTotals::new();
+
+    process_hog_files(
+        pool,
+        files,
+        |file, out| {
+            let mut extract_info = HogExtractInfo::new();
+            // A single HOG file has the whole pool to itself.
+            let pool = (files.len() == 1).then_some(pool);
+            let result = hog_extract_into(file, opts, pool, &mut extract_info, reporter, out);
+
+            (extract_info, result)
+        },
+        |file, (extract_info, result)| match result {
+            Ok(()) => {
+                reporter.extract_summary(file, &extract_info, None);
+
+                // Like when listing and testing, the total counts every file
+                // processed, extracted or not.
+                totals.add(extract_info.files_processed, extract_info.bytes_extracted);
+            }
+            Err(e) => {
+                reporter.archive_error(file, &e);
+                reporter.extract_summary(file, &extract_info, Some(&e));
+
+                totals.add_error();
+            }
+        },
+    );
+
+    if files.len() > 1 {
+        reporter.totals(&totals);
+    }
+}
+
+// Displays what extracting multiple HOG files would do, using hog_plan().
+fn plan_hog_files(
+    pool: &ThreadPool,
+    files: &[PathBuf],
+    opts: &ExtractOptions,
+    reporter: &Reporter,
+) {
+    let mut totals = HogTotals::new();
+
+    process_hog_files(
+        pool,
+        files,
+        |file, out| hog_plan(file, opts, reporter, out),
+        |file, result| match result {
+            Ok(plan) => {
+                reporter.plan_summary(file, &plan);
+
+                let (files, bytes) = plan.extracted();
+
+                totals.add(files, bytes);
+            }
+            Err(e) => {
+                reporter.archive_error(file, &e);
+
+                totals.add_error();
+            }
+        },
+    );
+
+    if files.len() > 1 {
+        reporter.totals(&totals);
+    }
+}
+
+// Writes the map of the HOG file for --map to "out" (see HogIndex::write()).
+// Only the files whose names match "name_match" are included, if it is given.
+// Warnings go to stderr, rather than into the map.
+fn hog_map(
+    path: &Path,
+    name_match: Option<&Regex>,
+    read: ReadOptions,
+    reporter: &Reporter,
+    out: &mut dyn Write,
+) -> Result<(), ArchiveError> {
+    let mut hog_file = open_hog_file(path, read, reporter, &mut io::stderr())?;
+
+    // Compressed HOG files are decompressed into memory, so the offsets would
+    // not be offsets in the file on disk.
+    if let HogInput::Memory(_) | HogInput::Hogz(..) = hog_file.get_ref() {
+        return Err(HogError::NotMappable.into());
+    }
+
+    let mut index = HogIndex::build(&mut hog_file)?;
+
+    if let Some(name_match) = name_match {
+        index.entries.retain(|x| name_match.is_match(&x.name));
+    }
+
+    let mut out = BufWriter::new(out);
+
+    index
+        .write(&mut out)
+        .and_then(|_| out.flush())
+        .map_err(HogError::OutputFailure)?;
+
+    Ok(())
+}
+
+// Writes a hogfile.txt listing of the HOG file at "path" to "listing_path" (or
+// stdout, if it is "-") for --export-listing. Only the files whose names match
+// "name_match" are listed, if it is given.
+fn export_listing(
+    path: &Path,
+    listing_path: &Path,
+    name_match: Option<&Regex>,
+    opts: &ExtractOptions,
+    reporter: &Reporter,
+) -> Result<(), ArchiveError> {
+    // Warnings go to stderr when the listing goes to stdout.
+    let mut hog_file = if listing_path == Path::new("-") {
+        open_hog_file(path, opts.read, reporter, &mut io::stderr())
+    } else {
+        open_hog_file(path, opts.read, reporter, &mut io::stdout())
+    }?;
+    let mut entries = Vec::new();
+    let mut iter = hog_file.records()?;
+
+    while let Some(record) = iter.next() {
+        let record = record.map_err(|e| ArchiveError::at(e, &iter))?;
+        let name = record.filename.to_string_lossy().into_owned();
+
+        if name_match.is_none_or(|x| x.is_match(&name)) {
+            entries.push(ListingEntry {
+                name,
+                length: record.length,
+            });
+        }
+    }
+
+    let write = |out: &mut dyn Write| {
+        let mut out = BufWriter::new(out);
+
+        listing::write(&entries, &mut out).and_then(|_| out.flush())
+    };
+
+    if listing_path == Path::new("-") {
+        write(&mut io::stdout()).map_err(HogError::OutputFailure)?;
+    } else {
+        let (mut out_f, _in_progress) =
+            open_output_file(listing_path, opts.overwrite, opts.read.no_follow)?.ok_or_else(
+                || {
+                    HogError::OpenOutputFailure(io::Error::new(
+                        ErrorKind::AlreadyExists,
+                        format!("\"{}\" already exists", listing_path.display()),
+                    ))
+                },
+            )?;
+
+        write(&mut out_f).map_err(HogError::OutputFailure)?;
+        reporter.listing_exported(path, listing_path, entries.len() as u64);
+    }
+
+    Ok(())
+}
+
+// Looks up "name" across the HOG files for --resolve, searching them in order,
+// and reports every copy of it. Exits with an error if it is not found.
+fn resolve_hog_file(files: &[PathBuf], name: &str, reporter: &Reporter) {
+    let mut vfs = HogVfs::new();
+
+    for file in files {
+        if let Err(e) = vfs.add(file) {
+            reporter.archive_error(file, &e.into());
+        }
+    }
+
+    let resolutions = vfs.resolve_all(name);
+
+    if resolutions.is_empty() {
+        reporter.unresolved(name);
+
+        reporter.exit();
+    }
+
+    for (i, resolution) in resolutions.iter().enumerate() {
+        reporter.resolved(resolution, i > 0);
+    }
+}
+
+// Displays information about multiple HOG files, using hog_info(). Returns
+// false if any of the files did not match the embedded metadata (only checked
+// with --check).
+fn display_hog_info(
+    pool: &ThreadPool,
+    files: &[PathBuf],
+    opts: &ListOptions,
+    reporter: &Reporter,
+) -> bool {
+    let mut totals = HogTotals::new();
+    let mut intact = true;
+
+    process_hog_files(
+        pool,
+        files,
+        |file, out| hog_info(file, opts, reporter, out),
+        |file, result| match result {
+            Ok(hog_info_summary) => {
+                reporter.list_summary(file, &hog_info_summary);
+
+                intact &= hog_info_summary.mismatches == 0;
+
+                totals.add(hog_info_summary.num_files, hog_info_summary.num_bytes);
+            }
+            Err(e) => {
+                reporter.archive_error(file, &e);
+
+                totals.add_error();
+            }
+        },
+    );
+
+    if files.len() > 1 {
+        reporter.totals(&totals);
+    }
+
+    intact
+}
+
+// Tests multiple HOG files, using hog_test(). Returns true if no problems were
+// found. The HOG files that fail are put into "quarantine", if given.
+fn test_hog_files(
+    pool: &ThreadPool,
+    files: &[PathBuf],
+    quarantine: Option<&Quarantine>,
+    read: ReadOptions,
+    reporter: &Reporter,
+) -> bool {
+    let mut totals = HogTotals::new();
+
+    process_hog_files(
+        pool,
+        files,
+        |file, out| {
+            let mut test_info = HogTestInfo::new();
+            let result = hog_test(file, read, &mut test_info, reporter, out);
+
+            (test_info, result)
+        },
+        |file, (test_info, result)| match result {
+            Ok(()) => {
+                reporter.test_summary(file, &test_info, None);
+
+                totals.add(test_info.summary.num_files, test_info.summary.num_bytes);
+            }
+            Err(e) => {
+                reporter.archive_error(file, &e);
+                reporter.test_summary(file, &test_info, Some(&e));
+
+                if let Some(quarantine) = quarantine {
+                    match quarantine.add(file, &e) {
+                        Ok(dest) => reporter.quarantined(file, &dest),
+                        Err(e) => {
+                            reporter.archive_error(file, &HogError::QuarantineFailure(e).into())
+                        }
+                    }
+                }
+
+                totals.add_error();
+            }
+        },
+    );
+
+    if files.len() > 1 {
+        reporter.totals(&totals);
+    }
+
+    totals.errors == 0
+}
+
+// Summarizes the results of checking the assets used by the levels in a HOG
+// file. Returned by hog_check_assets().
+struct AssetCheckInfo {
+    levels_checked: u64,
+    missing: u64,
+}
+
+// The assets levels can use, for hog_check_assets() to check them against.
+#[derive(Clone, Default)]
+struct Assets {
+    // The (lowercase) names of the files providing them.
+    names: HashSet<String>,
+
+    // The texture table of the first HAM file found, and the PIG files found
+    // (by lowercase name). The textures of levels are only checked with both.
+    ham: Option<HamFile>,
+    pigs: HashMap<String, PigFile>,
+
+    // The POG files found, by lowercase name.
+    pogs: HashMap<String, PogFile>,
+}
+
+impl Assets {
+    // Adds the current file of "iter" (stored as "hdr") to the assets, reading
+    // it if it is a HAM, PIG or POG file. Like when listing with --with-pig,
+    // a POG file that cannot be read is left out, and the warning about it is
+    // returned.
+    fn add(
+        &mut self,
+        iter: &mut HogRecordIter,
+        hdr: &HogRecord,
+    ) -> Result<Option<HogWarning>, HogError> {
+        let name = hdr.filename.to_string_lossy().to_ascii_lowercase();
+        let mut warning = None;
+
+        if name.ends_with(".ham") && self.ham.is_none() {
+            let data = iter.peek_cur_file(pig::MAX_HAM_HEADER_LEN)?;
+
+            self.ham = Some(HamFile::read(&mut data.as_slice())?);
+        } else if name.ends_with(".pig") {
+            let data = iter.peek_cur_file(pig::MAX_PIG_HEADER_LEN)?;
+
+            self.pigs
+                .insert(name.clone(), PigFile::read(&mut data.as_slice())?);
+        } else if name.ends_with(".pog") {
+            let data = iter.peek_cur_file(pig::MAX_POG_HEADER_LEN)?;
+
+            match PogFile::read(&mut data.as_slice()) {
+                Ok(pog) => {
+                    self.pogs.insert(name.clone(), pog);
+                }
+                Err(e) => {
+                    warning = Some(HogWarning::UnreadablePog(
+                        hdr.filename.clone(),
+                        e.to_string(),
+                    ));
+                }
+            }
+        }
+
+        self.names.insert(name);
+
+        Ok(warning)
+    }
+}
+
+// Returns the assets that "files" provide for levels to use: the files
+// themselves, and the files inside those that are HOG files (opened the way
+// "read" says).
+fn available_assets(
+    files: &[PathBuf],
+    read: ReadOptions,
+    reporter: &Reporter,
+) -> Result<Assets, (PathBuf, HogError)> {
+    let mut assets = Assets::default();
+
+    for file in files {
+        let name = file
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_ascii_lowercase();
+
+        // PIG and HAM files are read from the start, to find their bitmaps
+        // and textures.
+        let read_file = |len: u64| {
+            File::open(file)
+                .and_then(|f| {
+                    let mut data = Vec::new();
+                    f.take(len).read_to_end(&mut data).map(|_| data)
+                })
+                .map_err(|e| (file.clone(), HogError::OpenInputFailure(e)))
+        };
+
+        if name.ends_with(".pig") {
+            let data = read_file(pig::MAX_PIG_HEADER_LEN)?;
+            let pig = PigFile::read(&mut data.as_slice()).map_err(|e| (file.clone(), e))?;
+
+            assets.pigs.insert(name.clone(), pig);
+        } else if name.ends_with(".ham") && assets.ham.is_none() {
+            let data = read_file(pig::MAX_HAM_HEADER_LEN)?;
+            let ham = HamFile::read(&mut data.as_slice()).map_err(|e| (file.clone(), e))?;
+
+            assets.ham = Some(ham);
+        }
+
+        assets.names.insert(name);
+
+        // PIG files (and anything else that is not a HOG file) only provide
+        // themselves.
+        let mut hog_file = match open_hog_file(file, read, reporter, &mut io::stdout()) {
+            Ok(x) => x,
+            Err(HogError::InvalidSignature) => continue,
+            Err(e) => return Err((file.clone(), e)),
+        };
+
+        let mut iter = hog_file.records().map_err(|e| (file.clone(), e))?;
+
+        while let Some(hdr) = iter.next() {
+            let hdr = hdr.map_err(|e| (file.clone(), e))?;
+
+            let warning = assets.add(&mut iter, &hdr).map_err(|e| (file.clone(), e))?;
+
+            if let Some(warning) = warning {
+                reporter
+                    .warning(&mut io::stdout(), file, &warning)
+                    .map_err(|e| (file.clone(), HogError::OutputFailure(e)))?;
+            }
+        }
+    }
+
+    Ok(assets)
+}
+
+// Checks that the palette and PIG file named by each level in the HOG file are
+// available, either in the HOG file itself or in "other_assets". With a HAM
+// file and the PIG file of a Descent 2 level among them, the textures its mine
+// uses are checked too: each one has to be in the texture table of the HAM
+// file, and show a bitmap that the PIG file (or the POG file of the level)
+// has.
+fn hog_check_assets(
+    path: &Path,
+    other_assets: &Assets,
+    read: ReadOptions,
+    reporter: &Reporter,
+    out: &mut dyn Write,
+) -> Result<AssetCheckInfo, ArchiveError> {
+    let mut hog_file = open_hog_file(path, read, reporter, out)?;
+    let mut assets = other_assets.clone();
+    let mut levels = Vec::new();
+    let mut iter = hog_file.records()?;
+
+    loop {
+        match iter.next() {
+            Some(Ok(hdr)) => {
+                let warning = assets
+                    .add(&mut iter, &hdr)
+                    .map_err(|e| ArchiveError::at(e, &iter))?;
+
+                if let Some(warning) = warning {
+                    reporter
+                        .warning(out, path, &warning)
+                        .map_err(HogError::OutputFailure)?;
+                }
+
+                if !matches!(
+                    iter.sniff_cur_file(),
+                    Ok(ContentType::Rdl | ContentType::Rl2)
+                ) {
+                    continue;
+                }
+
+                let data = iter
+                    .peek_cur_file(level::MAX_LEVEL_LEN)
+                    .map_err(|e| ArchiveError::at(e, &iter))?;
+                let level =
+                    level::parse_level_header(&data).map_err(|e| ArchiveError::at(e, &iter))?;
+
+                // Only Descent 2 levels use the texture table of a HAM file.
+                let textures = match level.pig_name() {
+                    Some(_) => {
+                        level::level_textures(&data).map_err(|e| ArchiveError::at(e, &iter))?
+                    }
+                    None => BTreeSet::new(),
+                };
+
+                levels.push((hdr.filename, level, textures));
+            }
+            Some(Err(e)) => {
+                return Err(ArchiveError::at(e, &iter));
+            }
+            None => {
+                break;
+            }
+        }
+    }
+
+    let mut info = AssetCheckInfo {
+        levels_checked: 0,
+        missing: 0,
+    };
+
+    for (name, level, textures) in levels {
+        info.levels_checked += 1;
+
+        let mut missing: Vec<(&'static str, String)> = [
+            ("palette", level.palette.clone()),
+            ("pig", level.pig_name()),
+        ]
+        .into_iter()
+        .filter_map(|(kind, asset)| Some((kind, asset?)))
+        .filter(|(_, asset)| !assets.names.contains(&asset.to_ascii_lowercase()))
+        .collect();
+
+        let pig = level
+            .pig_name()
+            .and_then(|x| assets.pigs.get(&x.to_ascii_lowercase()));
+        let stem = name.file_stem().unwrap_or_default().to_string_lossy();
+        let pog = assets
+            .pogs
+            .get(&format!("{}.pog", stem.to_ascii_lowercase()));
+
+        if let (Some(ham), Some(pig)) = (&assets.ham, pig) {
+            let mut bitmaps = BTreeSet::new();
+
+            for texture in textures {
+                match ham.bitmap(texture) {
+                    Some(bitmap) => {
+                        if pig.bitmap(bitmap).is_none() && !pog.is_some_and(|x| x.replaces(bitmap))
+                        {
+                            bitmaps.insert(bitmap);
+                        }
+                    }
+                    None => missing.push(("texture", texture.to_string())),
+                }
+            }
+
+            missing.extend(bitmaps.into_iter().map(|x| ("bitmap", x.to_string())));
+        }
+
+        for (kind, asset) in missing {
+            reporter
+                .missing_asset(out, path, &name, kind, &asset)
+                .map_err(HogError::OutputFailure)?;
+
+            info.missing += 1;
+        }
+    }
+
+    Ok(info)
+}
+
+// Checks the assets used by the levels in multiple HOG files, using
+// hog_check_assets(). Returns true if nothing was missing.
+fn check_hog_assets(
+    pool: &ThreadPool,
+    files: &[PathBuf],
+    with_files: &[PathBuf],
+    read: ReadOptions,
+    reporter: &Reporter,
+) -> bool {
+    let other_assets = match available_assets(with_files, read, reporter) {
+        Ok(x) => x,
+        Err((file, e)) => {
+            reporter.archive_error(&file, &e.into());
+
+            return false;
+        }
+    };
+
+    let mut ok = true;
+
+    process_hog_files(
+        pool,
+        files,
+        |file, out| hog_check_assets(file, &other_assets, read, reporter, out),
+        |file, result| match result {
+            Ok(info) => {
+                reporter.check_summary(file, info.levels_checked, info.missing);
+
+                ok &= info.missing == 0;
+            }
+            Err(e) => {
+                reporter.archive_error(file, &e);
+
+                ok = false;
+            }
+        },
+    );
+
+    ok
+}
+
+// Summarizes the mission in the HOG file at "path", reading its mission file
+// from the HOG file, or from next to it.
+fn hog_mission_info(
+    path: &Path,
+    read: ReadOptions,
+    reporter: &Reporter,
+    out: &mut dyn Write,
+) -> Result<MissionInfo, ArchiveError> {
+    let mut hog_file = open_hog_file(path, read, reporter, out)?;
+    let mut iter = hog_file.records()?;
+    let mut records = Vec::new();
+    let mut mission_text = None;
+
+    while let Some(hdr) = iter.next() {
+        let hdr = hdr.map_err(|e| ArchiveError::at(e, &iter))?;
+        let ext = hdr
+            .filename
+            .extension()
+            .map(|x| x.to_string_lossy().to_ascii_lowercase());
+
+        if mission_text.is_none() && matches!(ext.as_deref(), Some("msn" | "mn2")) {
+            let data = iter
+                .peek_cur_file(MAX_MISSION_LEN)
+                .map_err(|e| ArchiveError::at(e, &iter))?;
+
+            mission_text = Some(String::from_utf8_lossy(&data).into_owned());
+        }
+
+        records.push(hdr);
+    }
+
+    if mission_text.is_none() {
+        let beside = ["msn", "mn2", "MSN", "MN2"]
+            .into_iter()
+            .map(|x| path.with_extension(x))
+            .find(|x| x.is_file());
+
+        if let Some(beside) = beside {
+            let text = std::fs::read(beside).map_err(HogError::OpenInputFailure)?;
+
+            mission_text = Some(String::from_utf8_lossy(&text).into_owned());
+        }
+    }
+
+    let mission = mission_text.map(|x| Mission::parse(&x)).transpose()?;
+    let mut info = MissionInfo::new(mission.as_ref());
+
+    for hdr in records {
+        info.add_file(&hdr.filename.to_string_lossy(), hdr.length);
+    }
+
+    Ok(info)
+}
+
+// Summarizes the missions in multiple HOG files, using hog_mission_info().
+// Returns true if all of them could be read.
+fn mission_info(
+    pool: &ThreadPool,
+    files: &[PathBuf],
+    read: ReadOptions,
+    reporter: &Reporter,
+) -> bool {
+    let mut ok = true;
+
+    process_hog_files(
+        pool,
+        files,
+        |file, out| hog_mission_info(file, read, reporter, out),
+        |file, result| match result {
+            Ok(info) => reporter.mission_info(file, &info),
+            Err(e) => {
+                reporter.archive_error(file, &e);
+
+                ok = false;
+            }
+        },
+    );
+
+    ok
+}
+
+// Compares two HOG files for --diff. Errors found while comparing them are
+// reported for the old HOG file. Returns false if there were any errors.
+fn hog_diff(old: &Path, new: &Path, content: bool, read: ReadOptions, reporter: &Reporter) -> bool {
+    let open = |path: &Path| {
+        open_hog_file(path, read, reporter, &mut io::stdout())
+            .map_err(|e| reporter.archive_error(path, &e.into()))
+    };
+
+    let (mut old_file, mut new_file) = match (open(old), open(new)) {
+        (Ok(old_file), Ok(new_file)) => (old_file, new_file),
+        _ => return false,
+    };
+
+    match diff::diff_archives(&mut old_file, &mut new_file, content) {
+        Ok(diffs) => {
+            reporter.diff(old, new, &diffs);
+
+            true
+        }
+        Err(e) => {
+            reporter.archive_error(old, &e.into());
+
+            false
+        }
+    }
+}
+
+// Extensions of files that go along with a level, and are packaged with it if
+// they have the same base name: custom textures, robots, lighting, and music.
+const LEVEL_COMPANION_EXTENSIONS: &[&str] = &["pog", "hxm", "lgt", "clr", "hmp"];
+
+// Finds the mission file (.msn or .mn2) in "dir". There must be exactly one.
+fn find_mission_file(dir: &Path) -> Result<PathBuf, HogError> {
+    let mut found = Vec::new();
+
+    for entry in std::fs::read_dir(dir).map_err(HogError::OpenInputFailure)? {
+        let path = entry.map_err(HogError::OpenInputFailure)?.path();
+        let ext = path
+            .extension()
+            .map(|x| x.to_string_lossy().to_ascii_lowercase());
+
+        if matches!(ext.as_deref(), Some("msn" | "mn2")) && path.is_file() {
+            found.push(path);
+        }
+    }
+
+    match found.len() {
+        1 => Ok(found.pop().unwrap()),
+        0 => Err(HogError::InvalidMission(format!(
+            "no .msn or .mn2 file in \"{}\"",
+            dir.display()
+        ))),
+        _ => Err(HogError::InvalidMission(format!(
+            "more than one .msn or .mn2 file in \"{}\"",
+            dir.display()
+        ))),
+    }
+}
+
+// Returns the files that make up the mission in "dir", in the order they go
+// in the HOG file: the briefing and ending, then each level followed by the
+// files that go along with it.
+//
+// Every level the mission lists must exist. Names are looked up without
+// regard to case, like DOS would.
+fn mission_files(dir: &Path, mission_path: &Path) -> Result<Vec<PathBuf>, HogError> {
+    let text = std::fs::read_to_string(mission_path).map_err(HogError::OpenInputFailure)?;
+    let mission = Mission::parse(&text)?;
+    let base = mission_path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+
+    let mut on_disk = HashMap::new();
+
+    for file in list_files(dir).map_err(HogError::OpenInputFailure)? {
+        if let Some(name) = file.file_name() {
+            on_disk.insert(name.to_string_lossy().to_ascii_lowercase(), file.clone());
+        }
+    }
+
+    let find = |name: &str| on_disk.get(&name.to_ascii_lowercase()).cloned();
+    let mut files = Vec::new();
+
+    // Descent 1 missions do not name their briefing, it goes by the name of
+    // the mission.
+    let briefing = mission.briefing.clone().or_else(|| {
+        [format!("{}.txb", base), format!("{}.tex", base)]
+            .into_iter()
+            .find(|x| find(x).is_some())
+    });
+
+    for name in briefing.iter().chain(&mission.ending) {
+        match find(name) {
+            Some(file) => files.push(file),
+            None => return Err(HogError::InvalidMission(format!("\"{}\" is missing", name))),
+        }
+    }
+
+    let levels = mission
+        .levels
+        .iter()
+        .chain(mission.secret_levels.iter().map(|x| &x.0));
+
+    for level in levels {
+        let level_file = find(level)
+            .ok_or_else(|| HogError::InvalidMission(format!("level \"{}\" is missing", level)))?;
+        let level_base = level.rsplit_once('.').map_or(level.as_str(), |x| x.0);
+
+        files.push(level_file.clone());
+
+        for ext in LEVEL_COMPANION_EXTENSIONS {
+            if let Some(file) = find(&format!("{}.{}", level_base, ext)) {
+                files.push(file);
+            }
+        }
+
+        // A custom palette, if the level uses one that is included.
+        let header = File::open(&level_file)
+            .and_then(|f| {
+                let mut data = Vec::new();
+                f.take(sniff::SNIFF_LEN as u64).read_to_end(&mut data)?;
+                Ok(data)
+            })
+            .map_err(HogError::OpenInputFailure)?;
+
+        if let Some(palette) = level::parse_level_header(&header)?.palette {
+            if let Some(file) = find(&palette) {
+                files.push(file);
+            }
+        }
+    }
+
+    // Levels can share companion files (such as a palette).
+    let mut seen = HashSet::new();
+    files.retain(|x| seen.insert(x.clone()));
+
+    Ok(files)
+}
+
+// Checks that "file" can be stored in a distribution HOG file, and returns the
+// name to store it as (its own name, which must be a DOS filename).
+fn check_mission_file(file: &Path) -> Result<String, HogError> {
+    let name = hog::hog_filename(&file, &StandardNamePolicy::Dos)?;
+    let len = std::fs::metadata(file)
+        .map_err(HogError::OpenInputFailure)?
+        .len();
+
+    if len == 0 {
+        return Err(HogError::InvalidMission(format!("\"{}\" is empty", name)));
+    }
+
+    if len > u32::MAX.into() {
+        return Err(HogError::FileTooLarge(len));
+    }
+
+    Ok(name)
+}
+
+// Creates a distribution HOG file (named after the mission, in the current
+// directory) from the mission file in "dir" and the files it uses. Files in
+// "dir" that are not used by the mission are reported, and left out.
+fn package_mission(pool: &ThreadPool, dir: &Path, opts: &CreateOptions, reporter: &Reporter) {
+    let mission_path = match find_mission_file(dir) {
+        Ok(x) => x,
+        Err(e) => {
+            reporter.create_error(dir, &e);
+
+            reporter.exit();
+        }
+    };
+
+    let mut out_path = PathBuf::from(mission_path.file_name().unwrap_or_default());
+    out_path.set_extension("hog");
+
+    let files = match mission_files(dir, &mission_path) {
+        Ok(x) => x,
+        Err(e) => {
+            reporter.create_error(&out_path, &e);
+
+            reporter.exit();
+        }
+    };
+
+    let mut names = Vec::with_capacity(files.len());
+    let mut ok = true;
+
+    for file in &files {
+        match check_mission_file(file) {
+            Ok(name) => names.push(name),
+            Err(e) => {
+                reporter.append_error(&out_path, file, &e);
+
+                ok = false;
+            }
+        }
+    }
+
+    if !ok {
+        reporter.exit();
+    }
+
+    create_hog_file(pool, &out_path, &files, Some(&names), None, opts, reporter);
+
+    if let Ok(all_files) = list_files(dir) {
+        for file in all_files {
+            if file != mission_path && !files.contains(&file) {
+                reporter.unreferenced(&out_path, &file);
+            }
+        }
+    }
+}
+
+// Returns the Descent 1 mission file of the mission in "archive", and its
+// contents. Missions usually have it next to the HOG file (with the same base
+// name), but some have it inside of the HOG file.
+fn find_d1_mission(archive: &Path) -> Result<(PathBuf, String), HogError> {
+    let mut hog_file = HogFileReader::open(&archive)?;
+    let mut iter = hog_file.records()?;
+
+    while let Some(hdr) = iter.next() {
+        let hdr = hdr?;
+
+        if convert::convert_entry(&hdr.filename.to_string_lossy()) == EntryConversion::MissionFile {
+            let data = iter.peek_cur_file(hdr.length.into())?;
+
+            return Ok((hdr.filename, String::from_utf8_lossy(&data).into_owned()));
+        }
+    }
+
+    for ext in ["msn", "MSN"] {
+        let path = archive.with_extension(ext);
+
+        if path.is_file() {
+            let text = std::fs::read(&path).map_err(HogError::OpenInputFailure)?;
+
+            return Ok((path, String::from_utf8_lossy(&text).into_owned()));
+        }
+    }
+
+    Err(HogError::InvalidMission(format!(
+        "no .msn file in \"{}\" or next to it",
+        archive.display()
+    )))
+}
+
+// Converts the Descent 1 mission in "archive" to a Descent 2 mission, written
+// to "out_path" (along with a .mn2 mission file next to it), following the
+// rules in convert. What remains to be done by hand is reported.
+fn convert_d1_mission(
+    archive: &Path,
+    out_path: &Path,
+    overwrite: bool,
+    opts: &CreateOptions,
+    reporter: &Reporter,
+) {
+    let mission = find_d1_mission(archive).and_then(|(path, text)| {
+        Mission::parse(&text)
+            .map_err(|e| HogError::InvalidMission(format!("\"{}\": {}", path.display(), e)))
+    });
+    let mission = match mission {
+        Ok(x) => convert::convert_mission(&x),
+        Err(e) => {
+            reporter.create_error(out_path, &e);
+
+            reporter.exit();
+        }
+    };
+
+    // Descent 2 finds the mission file by the name of the HOG file. It is
+    // checked first, so that nothing is written if it already exists.
+    let mission_path = out_path.with_extension("mn2");
+
+    if !overwrite && mission_path.exists() {
+        let e = io::Error::new(ErrorKind::AlreadyExists, "use -o to overwrite it");
+
+        reporter.create_error(&mission_path, &HogError::OpenOutputFailure(e));
+
+        reporter.exit();
+    }
+
+    let mut hog_in = match HogFileReader::open(&archive) {
+        Ok(x) => x,
+        Err(e) => {
+            reporter.create_error(out_path, &e);
+
+            reporter.exit();
+        }
+    };
+    let (mut hog_file, in_progress) = start_hog_file(out_path, opts, reporter);
+    let mut create_info = HogCreateInfo::new();
+    let mut manual_steps = Vec::new();
+
+    let records = hog_in.iter_with_data(None).unwrap_or_else(|e| {
+        reporter.create_error(out_path, &e);
+
+        reporter.exit();
+    });
+
+    for entry in records {
+        let (record, data) = match entry {
+            Ok(x) => x,
+            Err(e) => {
+                reporter.create_error(out_path, &e);
+
+                reporter.exit();
+            }
+        };
+        let name = record.filename.to_string_lossy();
+
+        match convert::convert_entry(&name) {
+            EntryConversion::MissionFile => continue,
+            EntryConversion::Copy => {}
+            EntryConversion::Level { rl2_name } => manual_steps.push((
+                record.filename.clone(),
+                format!(
+                    "convert it to a Descent 2 level named \"{}\" in a level editor",
+                    rl2_name
+                ),
+            )),
+            EntryConversion::Briefing => manual_steps.push((
+                record.filename.clone(),
+                "check the briefing screens, Descent 2 shows them differently".to_string(),
+            )),
+        }
+
+        match hog_file.append_reader(&record.filename, data.len() as u64, &mut data.as_slice()) {
+            Ok(added) => {
+                create_info.add(&record.filename, &added);
+
+                reporter.added(out_path, &record.filename, &added, create_info.bytes_added);
+            }
+            Err(e) => {
+                reporter.append_error(out_path, &record.filename, &e);
+
+                create_info.fail(&record.filename, e);
+            }
+        }
+    }
+
+    let finished = finish_hog_file(hog_file, in_progress, out_path, None, reporter);
+    reporter.create_summary(out_path, &create_info);
+
+    if !finished {
+        reporter.exit();
+    }
+
+    let written = open_output_file(&mission_path, true, false).and_then(|f| {
+        let (mut f, _in_progress) = f.unwrap();
+
+        f.write_all(mission.to_text().as_bytes())
+            .and_then(|_| f.flush())
+            .map_err(HogError::ExtractFailure)
+    });
+
+    if let Err(e) = written {
+        reporter.create_error(&mission_path, &e);
+
+        reporter.exit();
+    }
+
+    reporter.mission_written(out_path, &mission_path);
+
+    for (file, step) in &manual_steps {
+        reporter.manual_step(out_path, file, step);
+    }
+
+    if create_info.files_failed > 0 {
+        reporter.exit();
+    }
+}
+
+// Finds files with the same contents as an earlier file in "files". Returns a
+// list with an entry per file, containing the index of the earlier file with
+// the same contents (if any).
+//
+// Files that cannot be read are never considered duplicates, the error is
+// reported when the file is added to the HOG file instead.
+fn find_duplicates(pool: &ThreadPool, files: &[PathBuf]) -> Vec<Option<usize>> {
+    let digests = pool.map(files, |file| {
+        File::open(file).and_then(|mut f| util::sha256(&mut f)).ok()
+    });
+    let mut seen = HashMap::new();
+    let mut duplicates = Vec::with_capacity(files.len());
+
+    for (i, digest) in digests.into_iter().enumerate() {
+        let duplicate = match digest {
+            Some(digest) => match seen.get(&digest) {
+                Some(&original) => Some(original),
+                None => {
+                    seen.insert(digest, i);
+                    None
+                }
+            },
+            None => None,
+        };
+
+        duplicates.push(duplicate);
+    }
+
+    duplicates
+}
+
+// Checks that no two of the files would be stored with the same filename in a
+// HOG file (ignoring case, since DOS does too). Every conflict is reported, and
+// true is returned if there were none.
+//
+// Files with filenames that cannot be stored in a HOG file are ignored, they
+// fail when adding them to the HOG file instead.
+fn check_duplicate_names(
+    out_path: &Path,
+    files: &[&PathBuf],
+    opts: &CreateOptions,
+    reporter: &Reporter,
+) -> bool {
+    let mut seen: HashMap<String, &PathBuf> = HashMap::new();
+    let mut ok = true;
+
+    for &file in files {
+        let name = match opts.hog_filename(file) {
+            Ok(name) => name,
+            Err(_) => continue,
+        };
+
+        match seen.get(&name.to_ascii_lowercase()) {
+            Some(original) => {
+                let e = HogError::DuplicateHogFilename(name, original.display().to_string());
+
+                reporter.append_error(out_path, file, &e);
+
+                ok = false;
+            }
+            None => {
+                seen.insert(name.to_ascii_lowercase(), file);
+            }
+        }
+    }
+
+    ok
+}
+
+// Creates a HOG file, adding the list of files provided on the command line.
+//
+// If "stored_names" is given, each file is stored under the corresponding name
+// exactly, without checking for duplicates (this is used to recreate a HOG
+// file from a manifest). If "manifest" is given as well, each file must have
+// the length and contents its manifest entry gives, or creating the HOG file
+// fails.
+fn create_hog_file(
+    pool: &ThreadPool,
+    out_path: &Path,
+    files: &[PathBuf],
+    stored_names: Option<&[String]>,
+    manifest: Option<&[ManifestEntry]>,
+    opts: &CreateOptions,
+    reporter: &Reporter,
+) -> HogCreateInfo {
+    let duplicates = match opts.dedupe {
+        _ if stored_names.is_some() => vec![None; files.len()],
+        DedupeMode::Keep => vec![None; files.len()],
+        DedupeMode::Warn | DedupeMode::Skip => find_duplicates(pool, files),
+    };
+
+    if !opts.allow_duplicate_names && stored_names.is_none() {
+        let added: Vec<&PathBuf> = files
+            .iter()
+            .zip(&duplicates)
+            .filter(|(_, duplicate)| !(opts.dedupe == DedupeMode::Skip && duplicate.is_some()))
+            .map(|(file, _)| file)
+            .collect();
+
+        if !check_duplicate_names(out_path, &added, opts, reporter) {
+            reporter.exit();
+        }
+    }
+
+    // The embedded metadata would clash with a file of the same name.
+    if opts.embed_metadata {
+        let clash = files.iter().find(|x| {
+            opts.hog_filename(x)
+                .is_ok_and(|x| x.eq_ignore_ascii_case(METADATA_NAME))
+        });
+
+        if let Some(file) = clash {
+            let e = HogError::DuplicateHogFilename(
+                METADATA_NAME.to_string(),
+                "--embed-metadata".to_string(),
+            );
+
+            reporter.append_error(out_path, file, &e);
+
+            reporter.exit();
+        }
+    }
+
+    let (mut hog_file, in_progress) = start_hog_file(out_path, opts, reporter);
+    let mut create_info = HogCreateInfo::new();
+    let mut expected = Vec::new();
+    let mut metadata = EmbeddedMetadata::default();
+
+    for (i, (file, duplicate)) in files.iter().zip(duplicates).enumerate() {
+        if let Some(original) = duplicate {
+            let skip = opts.dedupe == DedupeMode::Skip;
+
+            reporter.duplicate(out_path, file, &files[original], skip);
+
+            if skip {
+                create_info.skip(file);
+
+                continue;
+            }
+        }
+
+        // Files piped through --pre-add-cmd are added from memory, since
+        // their length is not known until the command is done.
+        let filtered = match opts.pre_add.as_ref().map(|x| pre_add_filter(x, file)) {
+            Some(Ok(data)) => Some(data),
+            Some(Err(e)) => {
+                reporter.append_error(out_path, file, &e);
+
+                create_info.fail(file, e);
+
+                continue;
+            }
+            None => None,
+        };
+
+        // Files from a manifest are hashed as they are added, to check them
+        // against it.
+        let mut read_digest = None;
+
+        let result = match (&filtered, stored_names) {
+            (Some(data), Some(names)) => {
+                hog_file.append_reader_as(&names[i], data.len() as u64, &mut data.as_slice())
+            }
+            (Some(data), None) => {
+                hog_file.append_reader(file, data.len() as u64, &mut data.as_slice())
+            }
+            (None, Some(names)) if manifest.is_some() => {
+                append_file_hashed(&mut hog_file, file, &names[i]).map(|(record, digest)| {
+                    read_digest = Some(digest);
+                    record
+                })
+            }
+            (None, Some(names)) => hog_file.append_file_as(file, &names[i]),
+            (None, None) => hog_file.append_file(file),
+        };
+
+        let mismatch = match (&result, manifest, read_digest) {
+            (Ok(record), Some(manifest), Some(digest)) => {
+                check_manifest_entry(&manifest[i], record, &digest).err()
+            }
+            _ => None,
+        };
+
+        if let Some(e) = mismatch {
+            reporter.append_error(out_path, file, &e);
+
+            let _ = std::fs::remove_file(out_path);
+
+            reporter.exit();
+        }
+
+        if let (Ok(_), Some(_)) = (&result, &filtered) {
+            if let Ok(mtime) = std::fs::metadata(file).and_then(|x| x.modified()) {
+                hog_file.set_mtime(mtime);
+            }
+        }
+
+        match result {
+            Ok(record) => {
+                create_info.add(file, &record);
+
+                if opts.transliterate && stored_names.is_none() {
+                    report_transliteration(out_path, file, &record, reporter);
+                }
+
+                reporter.added(out_path, file, &record, create_info.bytes_added);
+
+                if opts.self_check || opts.embed_metadata {
+                    let digest = match (&filtered, read_digest) {
+                        (Some(data), _) => util::sha256(&mut data.as_slice()),
+                        (None, Some(digest)) => Ok(digest),
+                        (None, None) => File::open(file).and_then(|mut f| util::sha256(&mut f)),
+                    };
+                    let digest = digest.unwrap_or_else(|e| {
+                        reporter.append_error(out_path, file, &HogError::ReadFailure(e));
+
+                        let _ = std::fs::remove_file(out_path);
+
+                        reporter.exit();
+                    });
+
+                    metadata.entries.push(MetadataEntry {
+                        name: record.filename.to_string_lossy().into_owned(),
+                        length: record.length,
+                        sha256: util::to_hex(&digest),
+                    });
+                    expected.push(ExpectedEntry::new(record, digest));
+                }
+            }
+            Err(e) => {
+                reporter.append_error(out_path, file, &e);
+
+                create_info.fail(file, e);
+            }
+        }
+    }
+
+    if opts.embed_metadata {
+        let appended = metadata.to_json().and_then(|json| {
+            let json = json.into_bytes();
+
+            hog_file
+                .append_reader_as(METADATA_NAME, json.len() as u64, &mut json.as_slice())
+                .map(|record| (record, json))
+        });
+
+        match appended {
+            Ok((record, json)) => {
+                let digest = util::sha256(&mut json.as_slice()).unwrap();
+
+                expected.push(ExpectedEntry::new(record, digest));
+            }
+            Err(e) => {
+                reporter.append_error(out_path, Path::new(METADATA_NAME), &e);
+
+                let _ = std::fs::remove_file(out_path);
+
+                reporter.exit();
+            }
+        }
+    }
+
+    let expected = opts.self_check.then_some(expected.as_slice());
+
+    let finished = finish_hog_file(hog_file, in_progress, out_path, expected, reporter);
+
+    create_info.self_checked = opts.self_check && finished;
+    reporter.create_summary(out_path, &create_info);
+
+    if !finished {
+        reporter.exit();
+    }
+
+    create_info
+}
+
+// Appends the file at "path" to "hog_file" as "stored_name", like
+// HogFileWriter::append_file_as(), also returning the SHA-256 digest of what
+// was read from it.
+fn append_file_hashed(
+    hog_file: &mut HogFileWriter,
+    path: &Path,
+    stored_name: &str,
+) -> Result<(HogRecord, [u8; 32]), HogError> {
+    let in_file = File::open(path).map_err(HogError::OpenInputFailure)?;
+    let metadata = in_file.metadata().map_err(HogError::AppendToHogFailure)?;
+    let mut reader = Sha256Reader::new(io::BufReader::new(in_file));
+
+    let record = hog_file.append_reader_as(stored_name, metadata.len(), &mut reader)?;
+
+    if let Ok(mtime) = metadata.modified() {
+        hog_file.set_mtime(mtime);
+    }
+
+    Ok((record, reader.finish().0))
+}
+
+// Checks that a file added to a HOG file (as "record", with contents "digest")
+// is the one "entry" of a manifest describes.
+fn check_manifest_entry(
+    entry: &ManifestEntry,
+    record: &HogRecord,
+    digest: &[u8; 32],
+) -> Result<(), HogError> {
+    if record.length != entry.length || !util::to_hex(digest).eq_ignore_ascii_case(&entry.sha256) {
+        return Err(HogError::DigestMismatch(entry.name.clone()));
+    }
+
+    Ok(())
+}
+
+// Reads the file at "path" and pipes it through "filter" (for --pre-add-cmd),
+// returning what it outputs.
+fn pre_add_filter(filter: &FilterCommand, path: &Path) -> Result<Vec<u8>, HogError> {
+    let input = std::fs::read(path).map_err(HogError::OpenInputFailure)?;
+    let mut output = Vec::new();
+
+    filter.run(&path.to_string_lossy(), &input, &mut output)?;
+
+    Ok(output)
+}
+
+// Writes a fixture HOG file (see fixture::generate()) to "out_path".
+fn gen_fixture(
+    out_path: &Path,
+    entries: u32,
+    corruption: Option<Corruption>,
+    overwrite: bool,
+    reporter: &Reporter,
+) {
+    let fixture = match fixture::generate(entries, corruption) {
+        Ok(x) => x,
+        Err(e) => {
+            reporter.create_error(out_path, &e);
+
+            reporter.exit();
+        }
+    };
+    let mut opts = OpenOptions::new();
+
+    opts.write(true);
+
+    if overwrite {
+        opts.create(true).truncate(true);
+    } else {
+        opts.create_new(true);
+    }
+
+    let result = opts
+        .open(out_path)
+        .map_err(HogError::OpenOutputFailure)
+        .and_then(|mut f| {
+            f.write_all(&fixture.data)
+                .and_then(|_| f.flush())
+                .map_err(HogError::OutputFailure)
+        });
+
+    if let Err(e) = result {
+        reporter.create_error(out_path, &e);
+
+        reporter.exit();
+    }
+
+    let corrupted = corruption.zip(fixture.corrupted);
+
+    reporter.fixture(out_path, entries, corrupted);
+}
+
+// Name the synthetic HOG file that --bench runs on without one is reported as.
+const BENCH_FIXTURE_NAME: &str = "(synthetic)";
+
+// How long each --bench benchmark is run for, at least.
+const BENCH_TIME: Duration = Duration::from_secs(1);
+
+// Runs every benchmark on the HOG file at "path" for --bench, or on a fixture
+// with as many files as a fixture can have if there is none. Returns false if
+// the HOG file (opened the way "read" says) could not be read.
+fn run_benchmarks(path: Option<&Path>, read: ReadOptions, reporter: &Reporter) -> bool {
+    let result = match path {
+        Some(path) => open_hog_file(path, read, reporter, &mut io::stdout())
+            .and_then(|mut f| bench_hog_file(&mut f, path, reporter)),
+        None => fixture::generate(fixture::MAX_ENTRIES, None)
+            .and_then(|x| HogFileReader::from_reader(io::Cursor::new(x.data)))
+            .and_then(|mut f| bench_hog_file(&mut f, Path::new(BENCH_FIXTURE_NAME), reporter)),
+    };
+
+    if let Err(e) = result {
+        reporter.archive_error(path.unwrap_or(Path::new(BENCH_FIXTURE_NAME)), &e.into());
+
+        return false;
+    }
+
+    true
+}
+
+// Runs every benchmark on "hog_file" (the HOG file "archive"), reporting how
+// long each one took.
+fn bench_hog_file<R: Read + Seek>(
+    hog_file: &mut HogFileReader<R>,
+    archive: &Path,
+    reporter: &Reporter,
+) -> Result<(), HogError> {
+    for benchmark in Benchmark::ALL {
+        let result = bench::run(hog_file, benchmark, BENCH_TIME)?;
+
+        reporter.bench(archive, &result);
+    }
+
+    Ok(())
+}
+
+// Reports the name "file" was stored as, if it had to be transliterated.
+fn report_transliteration(out_path: &Path, file: &Path, record: &HogRecord, reporter: &Reporter) {
+    let name = file.file_name().unwrap_or_default();
+
+    if !name.to_string_lossy().is_ascii() {
+        reporter.transliterated(out_path, file, &record.filename);
+    }
+}
+
+// Creates the HOG file for create_hog_file() and friends. It is removed if
+// hogdump is interrupted, until it is passed to finish_hog_file().
+fn start_hog_file(
+    out_path: &Path,
+    opts: &CreateOptions,
+    reporter: &Reporter,
+) -> (HogFileWriter, InProgress) {
+    let hog_file = match opts.format {
+        FormatArg::Hog => HogFileWriter::create_compressed(&out_path, opts.compress),
+        FormatArg::Hogz => HogFileWriter::create_hogz(&out_path),
+    };
+
+    let mut hog_file = match hog_file {
+        Ok(x) => x,
+        Err(e) => {
+            reporter.create_error(out_path, &e);
+
+            reporter.exit();
+        }
+    };
+
+    let in_progress = InProgress::new(out_path);
+
+    hog_file.set_name_policy(opts.name_policy);
+    hog_file.set_name_case(opts.store_case);
+    hog_file.set_transliterate(opts.transliterate);
+    hog_file.set_allow_empty(opts.allow_empty);
+    hog_file.set_align(opts.align);
+    hog_file.set_durability(opts.durability);
+
+    (hog_file, in_progress)
+}
+
+// Finishes writing a HOG file created by start_hog_file(). If "expected" is
+// given, the HOG file is then read back and checked against it. If either
+// fails, the HOG file is removed, and false is returned so that the caller can
+// report the create summary before exiting.
+fn finish_hog_file(
+    hog_file: HogFileWriter,
+    in_progress: InProgress,
+    out_path: &Path,
+    expected: Option<&[ExpectedEntry]>,
+    reporter: &Reporter,
+) -> bool {
+    // Make sure all of the HOG file is written before it is no longer removed
+    // when interrupted.
+    let result = hog_file.finish().and_then(|_| match expected {
+        Some(expected) => self_check(out_path, expected),
+        None => Ok(()),
+    });
+
+    if let Err(e) = result {
+        reporter.create_error(out_path, &e);
+
+        let _ = std::fs::remove_file(out_path);
+
+        return false;
+    }
+
+    if let Some(expected) = expected {
+        reporter.self_checked(out_path, expected.len() as u64);
+    }
+
+    drop(in_progress);
+
+    true
+}
+
+// A file that was added to a HOG file, as it should be found when reading the
+// HOG file back (for --self-check).
+struct ExpectedEntry {
+    record: HogRecord,
+    digest: [u8; 32],
+}
+
+impl ExpectedEntry {
+    fn new(record: HogRecord, digest: [u8; 32]) -> Self {
+        Self { record, digest }
+    }
+}
+
+// Reads back the HOG file at "out_path", and checks that it contains exactly
+// the "expected" files, in the same order, with the same names, lengths and
+// contents.
+fn self_check(out_path: &Path, expected: &[ExpectedEntry]) -> Result<(), HogError> {
+    let mut hog_file = HogFileReader::open(&out_path)?;
+    let mut iter = hog_file.records()?;
+    let mut count = 0;
+
+    while let Some(hdr) = iter.next() {
+        let hdr = hdr?;
+        let fail = |msg: String| Err(HogError::SelfCheckFailed(msg));
+
+        let want = match expected.get(count) {
+            Some(x) => &x.record,
+            None => return fail(format!("unexpected file \"{}\"", hdr)),
+        };
+
+        if hdr.filename != want.filename {
+            return fail(format!(
+                "file {} is named \"{}\", expected \"{}\"",
+                count + 1,
+                hdr,
+                want
+            ));
+        }
+
+        if hdr.length != want.length {
+            return fail(format!(
+                "\"{}\" is {} bytes, expected {}",
+                hdr, hdr.length, want.length
+            ));
+        }
+
+        let mut hasher = Sha256Writer::new(io::sink());
+
+        iter.copy_cur_file(&mut hasher)?;
+
+        if hasher.finish().0 != expected[count].digest {
+            return fail(format!(
+                "contents of \"{}\" do not match the file that was added",
+                hdr
+            ));
+        }
+
+        count += 1;
+    }
+
+    if count != expected.len() {
+        return Err(HogError::SelfCheckFailed(format!(
+            "found {} files, expected {}",
+            count,
+            expected.len()
+        )));
+    }
+
+    Ok(())
+}
+
+// Most bytes reserved up front for a file read out of a tar archive, however
+// large its tar header says it is.
+const MAX_TAR_RESERVE: u64 = 1 << 20;
+
+// Returns an error if a file of "size" bytes in a tar archive cannot be
+// stored in a HOG file, or is larger than --max-convert-size allows.
+fn check_tar_member_size(size: u64, limits: &Limits) -> Result<(), HogError> {
+    if size > u64::from(u32::MAX) {
+        return Err(HogError::FileTooLarge(size));
+    }
+
+    limits
+        .check(size, Duration::ZERO)
+        .map_err(HogError::LimitExceeded)
+}
+
+// Creates a HOG file from the regular files in a tar archive read from
+// "tar_path" (or stdin, if it is "-"), in the order they appear in the tar
+// archive. Directories in the tar archive are flattened, only the base name
+// of each file is stored.
+//
+// Since the tar archive can only be read once, each file is read into memory
+// to check for duplicates before it is added.
+fn create_hog_file_from_tar(
+    out_path: &Path,
+    tar_path: &Path,
+    opts: &CreateOptions,
+    reporter: &Reporter,
+) -> HogCreateInfo {
+    let input: Box<dyn Read> = if tar_path == Path::new("-") {
+        Box::new(io::stdin().lock())
+    } else {
+        match File::open(tar_path) {
+            Ok(f) => Box::new(io::BufReader::new(f)),
+            Err(e) => {
+                reporter.append_error(out_path, tar_path, &HogError::OpenInputFailure(e));
+
+                reporter.exit();
+            }
+        }
+    };
+
+    let (mut hog_file, in_progress) = start_hog_file(out_path, opts, reporter);
+    let mut create_info = HogCreateInfo::new();
+    let mut seen_digests: HashMap<[u8; 32], PathBuf> = HashMap::new();
+    let mut seen_names: HashMap<String, PathBuf> = HashMap::new();
+    let mut expected = Vec::new();
+    let mut archive = tar::Archive::new(input);
+
+    // Errors reading the tar archive itself leave the HOG file incomplete.
+    let fail = |e: io::Error| -> ! {
+        reporter.append_error(out_path, tar_path, &HogError::ReadFailure(e));
+
+        let _ = std::fs::remove_file(out_path);
+
+        reporter.exit();
+    };
+
+    let entries = archive.entries().unwrap_or_else(|e| fail(e));
+
+    for entry in entries {
+        let mut entry = entry.unwrap_or_else(|e| fail(e));
+
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+
+        let member = entry.path().unwrap_or_else(|e| fail(e)).into_owned();
+        let size = entry.header().size().unwrap_or_else(|e| fail(e));
+
+        // Check the size the tar header claims before reading the file into
+        // memory, rather than finding out it is too large afterwards.
+        if let Err(e) = check_tar_member_size(size, &opts.limits) {
+            reporter.append_error(out_path, &member, &e);
+            create_info.fail(&member, e);
+
+            continue;
+        }
+
+        let mut data = Vec::with_capacity(size.min(MAX_TAR_RESERVE) as usize);
+
+        entry
+            .by_ref()
+            .take(size)
+            .read_to_end(&mut data)
+            .unwrap_or_else(|e| fail(e));
+
+        if let Some(filter) = &opts.pre_add {
+            let mut output = Vec::new();
+
+            if let Err(e) = filter.run(&member.to_string_lossy(), &data, &mut output) {
+                reporter.append_error(out_path, &member, &e);
+                create_info.fail(&member, e);
+
+                continue;
+            }
+
+            data = output;
+        }
+
+        if opts.dedupe != DedupeMode::Keep {
+            let digest = util::sha256(&mut data.as_slice()).unwrap_or_else(|e| fail(e));
+
+            match seen_digests.get(&digest) {
+                Some(original) => {
+                    let skip = opts.dedupe == DedupeMode::Skip;
+
+                    reporter.duplicate(out_path, &member, original, skip);
+
+                    if skip {
+                        create_info.skip(&member);
+
+                        continue;
+                    }
+                }
+                None => {
+                    seen_digests.insert(digest, member.clone());
+                }
+            }
+        }
+
+        if !opts.allow_duplicate_names {
+            if let Ok(name) = opts.hog_filename(&member) {
+                if let Some(original) = seen_names.get(&name.to_ascii_lowercase()) {
+                    let e = HogError::DuplicateHogFilename(name, original.display().to_string());
+
+                    reporter.append_error(out_path, &member, &e);
+                    create_info.fail(&member, e);
+
+                    continue;
+                }
+
+                seen_names.insert(name.to_ascii_lowercase(), member.clone());
+            }
+        }
+
+        match hog_file.append_reader(&member, data.len() as u64, &mut data.as_slice()) {
+            Ok(record) => {
+                create_info.add(&member, &record);
+
+                if opts.transliterate {
+                    report_transliteration(out_path, &member, &record, reporter);
+                }
+
+                reporter.added(out_path, &member, &record, create_info.bytes_added);
+
+                if opts.self_check {
+                    let digest = util::sha256(&mut data.as_slice()).unwrap_or_else(|e| fail(e));
+
+                    expected.push(ExpectedEntry::new(record, digest));
+                }
+            }
+            Err(e) => {
+                reporter.append_error(out_path, &member, &e);
+
+                create_info.fail(&member, e);
+            }
+        }
+    }
+
+    let expected = opts.self_check.then_some(expected.as_slice());
+
+    let finished = finish_hog_file(hog_file, in_progress, out_path, expected, reporter);
+
+    create_info.self_checked = opts.self_check && finished;
+    reporter.create_summary(out_path, &create_info);
+
+    if !finished {
+        reporter.exit();
+    }
+
+    create_info
+}
+
+// Returns the files listed in a manifest, and the names to store them as.
+fn manifest_files(manifest_path: &Path) -> Result<(Vec<PathBuf>, Vec<String>), HogError> {
+    let manifest = Manifest::load(&manifest_path)?;
+
+    Ok(manifest_paths(manifest_path, &manifest.entries))
+}
+
+// Returns the files the entries of the manifest at "manifest_path" were
+// extracted to, and the names they were stored as.
+fn manifest_paths(manifest_path: &Path, entries: &[ManifestEntry]) -> (Vec<PathBuf>, Vec<String>) {
+    // Paths in the manifest are relative to the manifest itself.
+    let dir = manifest_path.parent().unwrap_or(Path::new(""));
+
+    entries
+        .iter()
+        .map(|x| (dir.join(&x.path), x.name.clone()))
+        .unzip()
+}
+
+// Returns the files named in a hogfile.txt listing (which are next to it), and
+// the names to store them as. The lengths in the listing are not checked, as
+// the files may well have been edited since it was written.
+fn listing_files(listing_path: &Path) -> Result<(Vec<PathBuf>, Vec<String>), HogError> {
+    let entries = listing::load(&listing_path)?;
+    let dir = listing_path.parent().unwrap_or(Path::new(""));
+
+    Ok(entries
+        .into_iter()
+        .map(|x| (dir.join(&x.name), x.name))
+        .unzip())
+}
+
+// Returns all of the files in "dir" and its subdirectories, sorted by path
+// (with util::sort_paths(), so that the order does not depend on the
+// filesystem or the locale).
+fn list_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            files.extend(list_files(&path)?);
+        } else {
+            files.push(path);
+        }
+    }
+
+    util::sort_paths(&mut files);
+
+    Ok(files)
+}
+
+// Extracts a HOG file into "opts.dir" (along with a manifest) so that it can
+// be edited, or if that was already done (or "finish" is true), rebuilds the
+// HOG file from the files there.
+//
+// When rebuilding, files listed in the manifest keep their original order and
+// names, files that were removed are left out, and new files are added at the
+// end. The new HOG file is written next to the original, and only replaces it
+// if every file was added.
+fn repack_hog_file(
+    pool: &ThreadPool,
+    hog_path: &Path,
+    finish: bool,
+    opts: &ExtractOptions,
+    create_opts: &CreateOptions,
+    reporter: &Reporter,
+) {
+    let manifest_path = opts.dir.join(MANIFEST_FILENAME);
+
+    if !finish && !manifest_path.exists() {
+        let result = std::fs::create_dir_all(&opts.dir)
+            .map_err(|e| HogError::OpenOutputFailure(e).into())
+            .and_then(|_| hog_extract(hog_path, opts, reporter, &mut io::stdout()));
+
+        match result {
+            Ok(extract_info) => {
+                reporter.extract_summary(hog_path, &extract_info, None);
+                reporter.repack_extracted(hog_path, &opts.dir);
+            }
+            Err(e) => {
+                reporter.archive_error(hog_path, &e);
+
+                reporter.exit();
+            }
+        }
+
+        return;
+    }
+
+    let (listed_files, listed_names) = match manifest_files(&manifest_path) {
+        Ok(x) => x,
+        Err(e) => {
+            reporter.archive_error(hog_path, &e.into());
+
+            reporter.exit();
+        }
+    };
+
+    let mut files = Vec::new();
+    let mut names = Vec::new();
+
+    for (file, name) in listed_files.iter().zip(listed_names) {
+        if file.exists() {
+            files.push(file.clone());
+            names.push(name);
+        } else {
+            reporter.removed(hog_path, file);
+        }
+    }
+
+    let all_files = match list_files(&opts.dir) {
+        Ok(x) => x,
+        Err(e) => {
+            reporter.append_error(hog_path, &opts.dir, &HogError::OpenInputFailure(e));
+
+            reporter.exit();
+        }
+    };
+
+    let mut ok = true;
+
+    for file in all_files {
+        if file == manifest_path || listed_files.contains(&file) {
+            continue;
+        }
+
+        let name = create_opts
+            .hog_filename(&file)
+            .map(|x| create_opts.store_case.apply(&x))
+            .and_then(
+                |name| match names.iter().position(|x| x.eq_ignore_ascii_case(&name)) {
+                    Some(i) => Err(HogError::DuplicateHogFilename(
+                        name,
+                        files[i].display().to_string(),
+                    )),
+                    None => Ok(name),
+                },
+            );
+
+        match name {
+            Ok(name) => {
+                files.push(file);
+                names.push(name);
+            }
+            Err(e) => {
+                reporter.append_error(hog_path, &file, &e);
+
+                ok = false;
+            }
+        }
+    }
+
+    if !ok {
+        reporter.exit();
+    }
+
+    // Keep the HOG file compressed the same way it was (or as a hogz file).
+    let mut signature = [0; 3];
+    let hogz = File::open(hog_path)
+        .and_then(|mut f| f.read_exact(&mut signature))
+        .is_ok()
+        && signature == hog::HOGZ_SIGNATURE;
+
+    let create_opts = &CreateOptions {
+        compress: Compression::detect_file(&hog_path).ok().flatten(),
+        format: if hogz {
+            FormatArg::Hogz
+        } else {
+            FormatArg::Hog
+        },
+        pre_add: None,
+        ..*create_opts
+    };
+
+    let mut tmp_name = hog_path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = hog_path.with_file_name(tmp_name);
+
+    let create_info = create_hog_file(
+        pool,
+        &tmp_path,
+        &files,
+        Some(&names),
+        None,
+        create_opts,
+        reporter,
+    );
+
+    if create_info.files_failed > 0 {
+        let _ = std::fs::remove_file(&tmp_path);
+
+        reporter.exit();
+    }
+
+    // The directory was synced for the temporary file, the rename needs it
+    // synced again.
+    let result = std::fs::rename(&tmp_path, hog_path).and_then(|_| match create_opts.durability {
+        Durability::FsyncDir => util::sync_parent_dir(hog_path),
+        _ => Ok(()),
+    });
+
+    if let Err(e) = result {
+        reporter.create_error(hog_path, &HogError::OutputFailure(e));
+
+        let _ = std::fs::remove_file(&tmp_path);
+
+        reporter.exit();
+    }
+}
+
+// Returns "files", followed by the files matching each of the --glob
+// "patterns". Patterns that cannot be searched are reported, while patterns
+// that match nothing, and directories that cannot be searched, are warned
+// about.
+fn glob_files(files: &[PathBuf], patterns: &[String], reporter: &Reporter) -> Vec<PathBuf> {
+    let mut all = files.to_vec();
+
+    for pattern in patterns {
+        match util::glob(pattern) {
+            Ok(matches) => {
+                for (dir, e) in matches.unreadable {
+                    let warning = HogWarning::UnreadableDirectory(dir, e.to_string());
+                    let _ = reporter.warning(&mut io::stdout(), Path::new(pattern), &warning);
+                }
+
+                if matches.paths.is_empty() {
+                    let warning = HogWarning::NoMatches;
+                    let _ = reporter.warning(&mut io::stdout(), Path::new(pattern), &warning);
+                }
+
+                all.extend(matches.paths);
+            }
+            Err(e) => {
+                reporter.archive_error(Path::new(pattern), &HogError::OpenHogFailure(e).into())
+            }
+        }
+    }
+
+    all
+}
+
+// Replaces each ZIP archive in "files" with the HOG files inside of it, so that
+// mission downloads can be used without unzipping them first. ZIP archives
+// that cannot be read, or do not contain any HOG files, are reported and left
+// out.
+fn expand_zip_files(files: &[PathBuf], reporter: &Reporter) -> Vec<PathBuf> {
+    let mut expanded = Vec::with_capacity(files.len());
+
+    for file in files {
+        match compress::zip_hog_members(file) {
+            Ok(None) => expanded.push(file.clone()),
+            Ok(Some(members)) if members.is_empty() => {
+                reporter.archive_error(file, &HogError::NoHogInZip.into());
+            }
+            Ok(Some(members)) => expanded.extend(members),
+            Err(e) => reporter.archive_error(file, &HogError::OpenHogFailure(e).into()),
+        }
+    }
+
+    expanded
+}
+
+// Exits after a command line error (or after displaying --help or --version).
+// Clap exits with status 2 for errors, but that means a damaged file here (see
+// Severity), so they exit with 1 instead.
+fn usage_error(e: clap::Error) -> ! {
+    let _ = e.print();
+
+    std::process::exit(if e.use_stderr() { 1 } else { 0 });
+}
+
+fn main() {
+    let cli = Cli::try_parse().unwrap_or_else(|e| usage_error(e));
+
+    // There is no file to break in an empty fixture.
+    if cli.corrupt.is_some() && cli.entries == 0 {
+        usage_error(Cli::command().error(
+            clap::error::ErrorKind::ArgumentConflict,
+            "--corrupt cannot be used with --entries 0",
+        ));
+    }
+
+    if cli.repack && cli.file.len() > 1 {
+        usage_error(Cli::command().error(
+            clap::error::ErrorKind::ArgumentConflict,
+            "--repack can only be used with a single hog file",
+        ));
+    }
+
+    if cli.convert_mission.is_some() && cli.file.len() > 1 {
+        usage_error(Cli::command().error(
+            clap::error::ErrorKind::ArgumentConflict,
+            "--convert-mission can only be used with a single hog file",
+        ));
+    }
+
+    if cli.browse && cli.file.len() > 1 {
+        usage_error(Cli::command().error(
+            clap::error::ErrorKind::ArgumentConflict,
+            "--browse can only be used with a single hog file",
+        ));
+    }
+
+    if cli.serve && cli.file.len() > 1 {
+        usage_error(Cli::command().error(
+            clap::error::ErrorKind::ArgumentConflict,
+            "--serve can only be used with a single hog file",
+        ));
+    }
+
+    if cli.concat.is_some() && cli.file.len() > 1 {
+        usage_error(Cli::command().error(
+            clap::error::ErrorKind::ArgumentConflict,
+            "--concat can only be used with a single hog file",
+        ));
+    }
+
+    if cli.stream_all && cli.file.len() > 1 {
+        usage_error(Cli::command().error(
+            clap::error::ErrorKind::ArgumentConflict,
+            "--stream-all can only be used with a single hog file",
+        ));
+    }
+
+    if cli.bench && cli.file.len() > 1 {
+        usage_error(Cli::command().error(
+            clap::error::ErrorKind::ArgumentConflict,
+            "--bench can only be used with a single hog file",
+        ));
+    }
+
+    if cli.diff && cli.file.len() != 2 {
+        usage_error(Cli::command().error(
+            clap::error::ErrorKind::WrongNumberOfValues,
+            "--diff needs exactly two hog files",
+        ));
+    }
+
+    if cli.map && cli.file.len() > 1 {
+        usage_error(Cli::command().error(
+            clap::error::ErrorKind::ArgumentConflict,
+            "--map can only be used with a single hog file",
+        ));
+    }
+
+    if cli.export_listing.is_some() && cli.file.len() > 1 {
+        usage_error(Cli::command().error(
+            clap::error::ErrorKind::ArgumentConflict,
+            "--export-listing can only be used with a single hog file",
+        ));
+    }
+
+    if let Some(rate) = cli.rate_limit {
+        util::set_rate_limit(rate);
+    }
+
+    cleanup::install_handler();
+
+    let pool = match cli.threads {
+        Some(threads) => ThreadPool::with_threads(threads),
+        None => ThreadPool::new(),
+    };
+
+    let format = if cli.porcelain {
+        Format::Porcelain
+    } else if cli.json {
+        Format::Json
+    } else {
+        Format::Human
+    };
+
+    let sizes = match (cli.human_readable, cli.si) {
+        (false, _) => SizeFormat::Bytes,
+        (true, false) => SizeFormat::Binary,
+        (true, true) => SizeFormat::Si,
+    };
+
+    let data_on_stdout = cli.stdout || cli.stream_all;
+    let mut reporter = Reporter::new(format, cli.verbose, data_on_stdout).with_sizes(sizes);
+
+    if let Some(report_path) = &cli.report {
+        match File::create(report_path) {
+            Ok(f) => reporter = reporter.with_report(f),
+            Err(e) => {
+                usage_error(Cli::command().error(
+                    clap::error::ErrorKind::Io,
+                    format!("cannot create report \"{}\": {}", report_path.display(), e),
+                ));
+            }
+        }
+    }
+
+    if !data_on_stdout {
+        // Nothing useful can be done if stdout is gone.
+        let _ = reporter.begin(&mut io::stdout());
+    }
+
+    // Files given to --create are added as is, ZIP archives are only looked
+    // into when reading HOG files.
+    let hog_files = if cli.create.is_some()
+        || cli.repack
+        || cli.package_mission.is_some()
+        || cli.convert_mission.is_some()
+    {
+        cli.file.clone()
+    } else {
+        expand_zip_files(&glob_files(&cli.file, &cli.glob, &reporter), &reporter)
+    };
+
+    // Each HOG file would overwrite the manifest of the one before it.
+    if cli.write_manifest && hog_files.len() > 1 {
+        usage_error(Cli::command().error(
+            clap::error::ErrorKind::ArgumentConflict,
+            "--write-manifest can only be used with a single hog file",
+        ));
+    }
+
+    // Files in hogz files are compressed on their own, and not aligned.
+    if cli.format == FormatArg::Hogz && (cli.compress.is_some() || cli.align > 1) {
+        usage_error(Cli::command().error(
+            clap::error::ErrorKind::ArgumentConflict,
+            "--format hogz cannot be used with --compress or --align",
+        ));
+    }
+
+    // A replaced file could already be the original that later duplicates
+    // get linked to.
+    if cli.link_dupes.is_some() && cli.case_collisions == CaseCollisionArg::LastWins {
+        usage_error(Cli::command().error(
+            clap::error::ErrorKind::ArgumentConflict,
+            "--link-dupes cannot be used with --case-collisions last-wins",
+        ));
+    }
+
+    // The digests only cover the files of a single HOG file.
+    if cli.digests.is_some() && hog_files.len() > 1 {
+        usage_error(Cli::command().error(
+            clap::error::ErrorKind::ArgumentConflict,
+            "--digests can only be used with a single hog file",
+        ));
+    }
+
+    let verify = match (cli.verify_on_extract, &cli.digests) {
+        (Some(mode), Some(digests)) => match Manifest::load(digests) {
+            Ok(digests) => Some(Verify {
+                mode,
+                digests: Some(digests),
+            }),
+            Err(e) => {
+                reporter.archive_error(digests, &e.into());
+
+                reporter.exit();
+            }
+        },
+        (Some(mode), None) => Some(Verify {
+            mode,
+            digests: None,
+        }),
+        _ => None,
+    };
+
+    let extract_opts = ExtractOptions {
+        overwrite: cli.overwrite,
+        auto_suffix: cli.auto_suffix,
+        write_manifest: cli.write_manifest,
+        link_dupes: cli.link_dupes.map(LinkDupes::new),
+        sparse: cli.sparse,
+        verify,
+        read: ReadOptions::from(&cli),
+        filter: cli
+            .filter_cmd
+            .clone()
+            .map(|x| x.with_limits(convert_limits(&cli))),
+        plan: extract::ExtractOptions {
+            case: cli.extract_case.into(),
+            organize: cli.organize.map(Organize::from),
+            case_collisions: cli.case_collisions.into(),
+            skip_empty: cli.skip_empty,
+            indexes: cli.index.clone(),
+            name_match: cli.name_match.clone(),
+            template: cli.output_template.clone(),
+            archive: String::new(),
+        },
+        dir: PathBuf::new(),
+    };
+
+    if let Some(name) = &cli.entry {
+        let sel = EntrySelection {
+            name,
+            offset: cli.offset.unwrap_or(0),
+            length: cli.length,
+            to_stdout: cli.stdout,
+            source: cli.emit_source.map(SourceLang::from),
+            filter: extract_opts.filter.as_ref(),
+        };
+
+        extract_hog_entry(&hog_files, &sel, &extract_opts, &reporter);
+    } else if let (Some(concat_path), Some(index_path)) = (&cli.concat, &cli.index_out) {
+        let result = hog_concat(
+            &hog_files[0],
+            concat_path,
+            index_path,
+            &extract_opts,
+            &reporter,
+        );
+
+        if let Err(e) = result {
+            reporter.archive_error(&hog_files[0], &e);
+
+            reporter.exit();
+        }
+    } else if cli.stream_all {
+        if let Err(e) = hog_stream(&hog_files[0], &extract_opts, &mut io::stdout(), &reporter) {
+            reporter.archive_error(&hog_files[0], &e);
+
+            reporter.exit();
+        }
+    } else if cli.extract && cli.dry_run {
+        plan_hog_files(&pool, &hog_files, &extract_opts, &reporter);
+    } else if cli.extract {
+        extract_hog_files(&pool, &hog_files, &extract_opts, &reporter);
+    } else if cli.browse {
+        if let Err(e) = browse::browse(&cli.file[0], &extract_opts) {
+            reporter.archive_error(&cli.file[0], &e.into());
+
+            reporter.exit();
+        }
+    } else if cli.serve {
+        let addr = SocketAddr::new(cli.bind, cli.port);
+
+        if let Err(e) = serve::serve(&cli.file[0], addr, convert_limits(&cli), &reporter) {
+            reporter.archive_error(&cli.file[0], &e.into());
+
+            reporter.exit();
+        }
+    } else if cli.diff {
+        if !hog_diff(
+            &hog_files[0],
+            &hog_files[1],
+            cli.content_diff,
+            extract_opts.read,
+            &reporter,
+        ) {
+            reporter.exit();
+        }
+    } else if let Some(socket) = &cli.daemon {
+        if let Err(e) = daemon::daemon(
+            socket,
+            &cli.daemon_output_dir,
+            convert_limits(&cli),
+            &reporter,
+        ) {
+            reporter.archive_error(socket, &e.into());
+
+            reporter.exit();
+        }
+    } else if cli.map {
+        if let Err(e) = hog_map(
+            &hog_files[0],
+            cli.name_match.as_ref(),
+            extract_opts.read,
+            &reporter,
+            &mut io::stdout(),
+        ) {
+            reporter.archive_error(&hog_files[0], &e);
+
+            reporter.exit();
+        }
+    } else if let Some(listing_path) = &cli.export_listing {
+        let result = export_listing(
+            &hog_files[0],
+            listing_path,
+            cli.name_match.as_ref(),
+            &extract_opts,
+            &reporter,
+        );
+
+        if let Err(e) = result {
+            reporter.archive_error(&hog_files[0], &e);
+
+            reporter.exit();
+        }
+    } else if let Some(dir) = &cli.package_mission {
+        package_mission(&pool, dir, &CreateOptions::from(&cli), &reporter);
+    } else if let Some(out_file) = &cli.convert_mission {
+        let GameArg::D2 = cli.to;
+
+        convert_d1_mission(
+            &cli.file[0],
+            out_file,
+            cli.overwrite,
+            &CreateOptions::from(&cli),
+            &reporter,
+        );
+    } else if let Some(out_file) = &cli.gen_fixture {
+        let corruption = cli.corrupt.map(Corruption::from);
+
+        gen_fixture(out_file, cli.entries, corruption, cli.overwrite, &reporter);
+    } else if cli.bench {
+        if !run_benchmarks(
+            hog_files.first().map(|x| x.as_path()),
+            extract_opts.read,
+            &reporter,
+        ) {
+            reporter.exit();
+        }
+    } else if cli.mission_info {
+        if !mission_info(&pool, &hog_files, extract_opts.read, &reporter) {
+            reporter.exit();
+        }
+    } else if cli.check_assets {
+        if !check_hog_assets(
+            &pool,
+            &hog_files,
+            &cli.with_files,
+            extract_opts.read,
+            &reporter,
+        ) {
+            reporter.exit();
+        }
+    } else if cli.test {
+        let quarantine = cli.quarantine_dir.as_ref().map(|dir| Quarantine {
+            dir: dir.clone(),
+            mode: cli.quarantine_mode,
+        });
+
+        if !test_hog_files(
+            &pool,
+            &hog_files,
+            quarantine.as_ref(),
+            ReadOptions::from(&cli),
+            &reporter,
+        ) {
+            reporter.exit();
+        }
+    } else if let Some(out_file) = &cli.create {
+        let create_opts = CreateOptions::from(&cli);
+
+        // The files of a manifest are checked against it as they are added.
+        let listed = match (&cli.manifest, &cli.from_listing) {
+            (Some(manifest_path), _) => {
+                let listed = Manifest::load(&manifest_path).map(|manifest| {
+                    let (files, names) = manifest_paths(manifest_path, &manifest.entries);
+
+                    (files, names, Some(manifest.entries))
+                });
+
+                Some((manifest_path, listed))
+            }
+            (_, Some(listing_path)) => {
+                let listed = listing_files(listing_path).map(|(files, names)| (files, names, None));
+
+                Some((listing_path, listed))
+            }
+            _ => None,
+        };
+
+        match listed {
+            Some((listed_path, listed)) => {
+                let (files, names, manifest) = match listed {
+                    Ok(x) => x,
+                    Err(e) => {
+                        reporter.append_error(out_file, listed_path, &e);
+
+                        reporter.exit();
+                    }
+                };
+
+                create_hog_file(
+                    &pool,
+                    out_file,
+                    &files,
+                    Some(&names),
+                    manifest.as_deref(),
+                    &create_opts,
+                    &reporter,
+                );
+            }
+            None => match &cli.from_tar {
+                Some(tar_path) => {
+                    create_hog_file_from_tar(out_file, tar_path, &create_opts, &reporter);
+                }
+                None => {
+                    create_hog_file(
+                        &pool,
+                        out_file,
+                        &cli.file,
+                        None,
+                        None,
+                        &create_opts,
+                        &reporter,
+                    );
+                }
+            },
+        }
+    } else if let Some(edit_dir) = &cli.edit_dir {
+        let extract_opts = ExtractOptions {
+            write_manifest: true,
+            link_dupes: None,
+            verify: None,
+            plan: extract::ExtractOptions {
+                case: extract_opts.plan.case,
+                organize: extract_opts.plan.organize,
+                ..Default::default()
+            },
+            dir: edit_dir.clone(),
+            ..extract_opts
+        };
+
+        repack_hog_file(
+            &pool,
+            &cli.file[0],
+            cli.finish,
+            &extract_opts,
+            &CreateOptions::from(&cli),
+            &reporter,
+        );
+    } else if let Some(name) = &cli.resolve {
+        resolve_hog_file(&hog_files, name, &reporter);
+    } else {
+        let list_opts = ListOptions {
+            preview: cli.preview,
+            preview_images: cli.preview_images,
+            ignore_errors: cli.ignore_errors,
+            name_match: cli.name_match.clone(),
+            debug_records: cli.debug_records,
+            check: cli.check,
+            pig: cli.with_pig.as_deref().map(|x| load_pig(x, &reporter)),
+            tree: cli.tree,
+            read: ReadOptions::from(&cli),
+        };
+
+        if !display_hog_info(&pool, &hog_files, &list_opts, &reporter) {
+            reporter.exit();
+        }
+    }
+
+    if cli.deny_warnings && reporter.warning_count() > 0 {
+        eprintln!(
+            "error: {} warnings, and --deny-warnings was given",
+            reporter.warning_count()
+        );
+
+        reporter.exit();
+    }
+
+    let status = reporter.finish(false);
+
+    if status != 0 {
+        std::process::exit(status);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_filtered_digest() {
+        let fixture = fixture::generate(1, None).unwrap();
+        let mut reader = HogFileReader::from_reader(Cursor::new(fixture.data.clone())).unwrap();
+        let mut iter = reader.records().unwrap();
+        let hdr = iter.next().unwrap().unwrap();
+        let filter = FilterCommand::parse("tr a-z A-Z").unwrap();
+        let mut out = Vec::new();
+
+        let (digest, written) =
+            copy_cur_file_filtered(&mut iter, &hdr.filename, Some(&filter), &mut out)
+                .unwrap()
+                .unwrap();
+        let (_, contents) = fixture::entry(0);
+
+        assert_eq!(out, contents.to_ascii_uppercase());
+        assert_eq!(written, contents.len() as u64);
+        assert_eq!(digest, util::sha256(&mut contents.as_slice()).unwrap());
+
+        // What the filter wrote is counted, rather than the stored length.
+        let mut reader = HogFileReader::from_reader(Cursor::new(fixture.data)).unwrap();
+        let mut iter = reader.records().unwrap();
+        let hdr = iter.next().unwrap().unwrap();
+        let filter = FilterCommand::parse("head -c 3").unwrap();
+        let mut out = Vec::new();
+
+        let (_, written) =
+            copy_cur_file_filtered(&mut iter, &hdr.filename, Some(&filter), &mut out)
+                .unwrap()
+                .unwrap();
+        assert_eq!(written, 3);
+        assert_eq!(out, contents[..3]);
+
+        // A failing filter only fails the file it failed for.
+        let mut reader =
+            HogFileReader::from_reader(Cursor::new(fixture::generate(2, None).unwrap().data))
+                .unwrap();
+        let mut iter = reader.records().unwrap();
+        let hdr = iter.next().unwrap().unwrap();
+        let filter = FilterCommand::parse("false").unwrap();
+
+        let copied =
+            copy_cur_file_filtered(&mut iter, &hdr.filename, Some(&filter), &mut io::sink());
+        assert!(matches!(copied, Ok(Err(HogError::FilterFailed(..)))));
+        assert!(iter.next().unwrap().is_ok());
+
+        // The digests of filtered files would not describe what was written.
+        let args = ["hogdump", "-x", "--filter-cmd", "cat", "a.hog"];
+        assert!(Cli::try_parse_from(args).is_ok());
+
+        for extra in [
+            &["--write-manifest"][..],
+            &["--verify-on-extract=warn", "--digests=m"],
+        ] {
+            assert!(Cli::try_parse_from(args.iter().chain(extra)).is_err());
+        }
+    }
+
+    #[test]
+    fn test_manifest_check() {
+        let dir = std::env::temp_dir().join(format!("hogdump-{}-manifest", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let file = dir.join("a.txt");
+        std::fs::write(&file, b"elephant").unwrap();
+
+        let mut hog_file = HogFileWriter::create(&dir.join("a.hog")).unwrap();
+        let (record, digest) = append_file_hashed(&mut hog_file, &file, "A.TXT").unwrap();
+        hog_file.finish().unwrap();
+
+        assert_eq!(record.filename, Path::new("A.TXT"));
+        assert_eq!(digest, util::sha256(&mut b"elephant".as_slice()).unwrap());
+
+        let entry = ManifestEntry {
+            name: "A.TXT".to_string(),
+            path: "a.txt".to_string(),
+            length: 8,
+            sha256: util::to_hex(&digest).to_uppercase(),
+        };
+        assert!(check_manifest_entry(&entry, &record, &digest).is_ok());
+
+        let changed = util::sha256(&mut b"elephaNt".as_slice()).unwrap();
+        let e = check_manifest_entry(&entry, &record, &changed).unwrap_err();
+        assert!(matches!(e, HogError::DigestMismatch(name) if name == "A.TXT"));
+
+        let shorter = ManifestEntry { length: 7, ..entry };
+        assert!(check_manifest_entry(&shorter, &record, &digest).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_filter_name() {
+        let dir = std::env::temp_dir().join(format!("hogdump-{}-filter-name", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut data = b"DHF".to_vec();
+        let mut field = [0; 13];
+        field[..8].copy_from_slice(b"../x.txt");
+        data.extend(field);
+        data.extend(1u32.to_le_bytes());
+        data.push(b'x');
+
+        let archive = dir.join("a.hog");
+        std::fs::write(&archive, data).unwrap();
+
+        // The filter is given the name as extracted, not the stored path.
+        let opts = ExtractOptions {
+            overwrite: false,
+            auto_suffix: false,
+            write_manifest: false,
+            link_dupes: None,
+            sparse: false,
+            verify: None,
+            read: ReadOptions {
+                lenient_signature: false,
+                no_follow: false,
+                tolerate_slack: 0,
+                strict_names: false,
+                limits: Limits::default(),
+            },
+            filter: Some(FilterCommand::parse("echo {name}").unwrap()),
+            plan: extract::ExtractOptions::default(),
+            dir: dir.join("out"),
+        };
+        let reporter = Reporter::new(Format::Human, false, false);
+
+        assert!(hog_extract(&archive, &opts, &reporter, &mut io::sink()).is_ok());
+
+        let written = std::fs::read_to_string(dir.join("out").join(".._x.txt")).unwrap();
+        assert_eq!(written, ".._x.txt\n");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_verify_embedded_digests() {
+        let dir = std::env::temp_dir().join(format!("hogdump-{}-verify", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // The metadata lists the digest of "elephant", but the file holds
+        // something else.
+        let metadata = EmbeddedMetadata {
+            entries: vec![MetadataEntry {
+                name: "A.TXT".to_string(),
+                length: 8,
+                sha256: util::to_hex(&util::sha256(&mut b"elephant".as_slice()).unwrap()),
+            }],
+        };
+        let json = metadata.to_json().unwrap();
+
+        let archive = dir.join("a.hog");
+        let mut hog_file = HogFileWriter::create(&archive).unwrap();
+        hog_file
+            .append_reader_as("A.TXT", 8, &mut b"elephaNt".as_slice())
+            .unwrap();
+        hog_file
+            .append_reader_as(METADATA_NAME, json.len() as u64, &mut json.as_bytes())
+            .unwrap();
+        hog_file.finish().unwrap();
+
+        let opts = |mode| ExtractOptions {
+            overwrite: true,
+            auto_suffix: false,
+            write_manifest: false,
+            link_dupes: None,
+            sparse: false,
+            verify: Some(Verify {
+                mode,
+                digests: None,
+            }),
+            read: ReadOptions {
+                lenient_signature: false,
+                no_follow: false,
+                tolerate_slack: 0,
+                strict_names: false,
+                limits: Limits::default(),
+            },
+            filter: None,
+            plan: extract::ExtractOptions::default(),
+            dir: dir.join("out"),
+        };
+        let reporter = Reporter::new(Format::Human, false, false);
+
+        let e = hog_extract(
+            &archive,
+            &opts(VerifyMode::Fail),
+            &reporter,
+            &mut io::sink(),
+        );
+        assert!(matches!(e, Err(e) if matches!(e.error, HogError::DigestMismatch(_))));
+        assert!(!dir.join("out").join("A.TXT").exists());
+
+        // With "warn", the file is kept.
+        let opts = opts(VerifyMode::Warn);
+        assert!(hog_extract(&archive, &opts, &reporter, &mut io::sink()).is_ok());
+        assert!(dir.join("out").join("A.TXT").exists());
+
+        // Without --digests, each HOG file is checked against its own metadata.
+        let args = [
+            "hogdump",
+            "-x",
+            "--verify-on-extract=warn",
+            "a.hog",
+            "b.hog",
+        ];
+        assert!(Cli::try_parse_from(args).is_ok());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_rate() {
+        assert_eq!(parse_rate("1"), Ok(1));
+        assert_eq!(parse_rate("500"), Ok(500));
+        assert_eq!(parse_rate("500k"), Ok(500 << 10));
+        assert_eq!(parse_rate(" 10M "), Ok(10 << 20));
+        assert_eq!(parse_rate("2g"), Ok(2 << 30));
+        assert!(parse_rate("0").is_err());
+        assert!(parse_rate("0k").is_err());
+        assert!(parse_rate("").is_err());
+        assert!(parse_rate("k").is_err());
+        assert!(parse_rate("-1").is_err());
+        assert!(parse_rate("1.5m").is_err());
+        assert!(parse_rate("10t").is_err());
+        assert!(parse_rate("18446744073709551615").is_ok());
+        assert!(parse_rate("18446744073709551615k").is_err());
+        assert!(parse_rate("18446744073709551616").is_err());
+    }
+
+    #[test]
+    fn test_parse_index_range() {
+        assert_eq!(parse_index_range("0"), Ok(0..=0));
+        assert_eq!(parse_index_range("12"), Ok(12..=12));
+        assert_eq!(parse_index_range("3-7"), Ok(3..=7));
+        assert_eq!(parse_index_range(" 3 - 7 "), Ok(3..=7));
+        assert_eq!(parse_index_range("4-4"), Ok(4..=4));
+        assert_eq!(
+            parse_index_range("0-18446744073709551615"),
+            Ok(0..=u64::MAX)
+        );
+
+        // Open ranges are not supported, both ends must be given.
+        for arg in ["3-", "-7", "-", " - "] {
+            assert_eq!(
+                parse_index_range(arg),
+                Err(format!("range \"{}\" needs both a start and an end", arg))
+            );
+        }
+
+        assert_eq!(
+            parse_index_range("7-3"),
+            Err("range \"7-3\" is backwards".to_string())
+        );
+
+        for arg in [
+            "",
+            "x",
+            "1.5",
+            "3-7-9",
+            "18446744073709551616",
+            "0-18446744073709551616",
+            "18446744073709551616-0",
+        ] {
+            assert!(parse_index_range(arg)
+                .unwrap_err()
+                .starts_with("invalid index"));
+        }
+    }
+
+    #[test]
+    fn test_tar_member_size() {
+        let limits = Limits {
+            max_len: Some(100),
+            max_time: None,
+        };
+
+        assert!(check_tar_member_size(0, &Limits::default()).is_ok());
+        assert!(check_tar_member_size(u32::MAX.into(), &Limits::default()).is_ok());
+        assert!(matches!(
+            check_tar_member_size(1 << 33, &Limits::default()),
+            Err(HogError::FileTooLarge(x)) if x == 1 << 33
+        ));
+        assert!(check_tar_member_size(100, &limits).is_ok());
+        assert!(matches!(
+            check_tar_member_size(101, &limits),
+            Err(HogError::LimitExceeded(
+                hogdump::error::LimitExceeded::Size(100)
+            ))
+        ));
     }
 }
@@ -0,0 +1,216 @@
+//
+// Copyright (c) 2022-2023 Nathaniel Houghton <nathan@brainwerk.org>
+//
+// Permission to use, copy, modify, and distribute this software for
+// any purpose with or without fee is hereby granted, provided that
+// the above copyright notice and this permission notice appear in all
+// copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL
+// WARRANTIES WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE
+// AUTHOR BE LIABLE FOR ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL
+// DAMAGES OR ANY DAMAGES WHATSOEVER RESULTING FROM LOSS OF USE, DATA
+// OR PROFITS, WHETHER IN AN ACTION OF CONTRACT, NEGLIGENCE OR OTHER
+// TORTIOUS ACTION, ARISING OUT OF OR IN CONNECTION WITH THE USE OR
+// PERFORMANCE OF THIS SOFTWARE.
+//
+
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::HogError;
+use crate::util;
+
+/// Lists the files of a HOG file in their original order, along with where
+/// each one was extracted to. Written when extracting a HOG file, so that the
+/// HOG file can be recreated exactly (the order of the files matters to the
+/// game).
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Default)]
+pub struct Manifest {
+    /// Filename of the HOG file the files were extracted from.
+    pub archive: String,
+
+    #[serde(rename = "entry", default)]
+    pub entries: Vec<ManifestEntry>,
+}
+
+/// A single file in a Manifest.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub struct ManifestEntry {
+    /// Filename as stored in the HOG file.
+    pub name: String,
+
+    /// Where the file was extracted to, relative to the manifest.
+    pub path: String,
+
+    pub length: u32,
+
+    /// SHA-256 digest of the file contents, as a hex string.
+    pub sha256: String,
+}
+
+impl Manifest {
+    /// Reads a manifest from a TOML file.
+    pub fn load(path: &impl AsRef<Path>) -> Result<Self, HogError> {
+        let text = fs::read_to_string(path).map_err(HogError::OpenInputFailure)?;
+
+        Self::parse(&text)
+    }
+
+    /// Parses a manifest from TOML text.
+    pub fn parse(text: &str) -> Result<Self, HogError> {
+        toml::from_str(text).map_err(|e| HogError::InvalidManifest(e.message().to_string()))
+    }
+
+    /// Returns the SHA-256 digest (as a hex string) listed for the file stored
+    /// as "name" at "index" in the HOG file, if any.
+    pub fn sha256(&self, index: u64, name: &str) -> Option<&str> {
+        let entry = self.entries.get(usize::try_from(index).ok()?)?;
+
+        (entry.name == name).then_some(entry.sha256.as_str())
+    }
+
+    /// Formats the manifest as TOML.
+    pub fn to_toml(&self) -> Result<String, HogError> {
+        toml::to_string(self).map_err(|e| HogError::InvalidManifest(e.to_string()))
+    }
+}
+
+/// Name of the file hogdump embeds in the HOG files it creates (with
+/// --embed-metadata), listing the digest of every other file in them.
+pub const METADATA_NAME: &str = "hogdump.json";
+
+/// The contents of an embedded METADATA_NAME file. Unlike a Manifest, this
+/// travels with the HOG file, so its files can be checked without anything
+/// else.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Default)]
+pub struct EmbeddedMetadata {
+    pub entries: Vec<MetadataEntry>,
+}
+
+/// A single file in EmbeddedMetadata.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+pub struct MetadataEntry {
+    /// Filename as stored in the HOG file.
+    pub name: String,
+
+    pub length: u32,
+
+    /// SHA-256 digest of the file contents, as a hex string.
+    pub sha256: String,
+}
+
+impl EmbeddedMetadata {
+    /// Parses embedded metadata from the JSON contents of a METADATA_NAME
+    /// file.
+    pub fn parse(data: &[u8]) -> Result<Self, HogError> {
+        serde_json::from_slice(data).map_err(|e| HogError::InvalidManifest(e.to_string()))
+    }
+
+    /// Formats the metadata as JSON.
+    pub fn to_json(&self) -> Result<String, HogError> {
+        serde_json::to_string_pretty(self).map_err(|e| HogError::InvalidManifest(e.to_string()))
+    }
+
+    /// Returns the entry for the file stored as "name" (compared without
+    /// regard to case, like DOS would), if it is listed.
+    pub fn entry(&self, name: &str) -> Option<&MetadataEntry> {
+        self.entries
+            .iter()
+            .find(|x| x.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Checks "data" against the length and digest listed for the file stored
+    /// as "name". Returns None if the file is not listed.
+    pub fn verify(&self, name: &str, data: &[u8]) -> Option<bool> {
+        let entry = self.entry(name)?;
+
+        Some(entry.matches(data.len() as u64, &Sha256::digest(data).into()))
+    }
+}
+
+impl MetadataEntry {
+    /// Returns true if a file of "length" bytes, with the SHA-256 digest
+    /// "digest", is the file listed.
+    pub fn matches(&self, length: u64, digest: &[u8; 32]) -> bool {
+        length == u64::from(self.length) && self.sha256.eq_ignore_ascii_case(&util::to_hex(digest))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_manifest_round_trip() {
+        let manifest = Manifest {
+            archive: "descent.hog".to_string(),
+            entries: vec![
+                ManifestEntry {
+                    name: "LEVEL01.RDL".to_string(),
+                    path: "levels/level01.rdl".to_string(),
+                    length: 29712,
+                    sha256: "00ff".to_string(),
+                },
+                ManifestEntry {
+                    name: "briefing.txb".to_string(),
+                    path: "briefing.txb".to_string(),
+                    length: 0,
+                    sha256: "ab".to_string(),
+                },
+            ],
+        };
+
+        let text = manifest.to_toml().unwrap();
+        let parsed = Manifest::parse(&text).unwrap();
+
+        assert!(text.contains("[[entry]]"));
+        assert_eq!(parsed, manifest);
+
+        assert_eq!(parsed.sha256(1, "briefing.txb"), Some("ab"));
+        assert_eq!(parsed.sha256(1, "LEVEL01.RDL"), None);
+        assert_eq!(parsed.sha256(2, "briefing.txb"), None);
+    }
+
+    #[test]
+    fn test_invalid_manifest() {
+        assert!(matches!(
+            Manifest::parse("archive = 5"),
+            Err(HogError::InvalidManifest(_))
+        ));
+        assert!(matches!(
+            Manifest::parse("archive = \"a.hog\"\n[[entry]]\nname = \"A.TXT\"\n"),
+            Err(HogError::InvalidManifest(_))
+        ));
+    }
+
+    #[test]
+    fn test_embedded_metadata() {
+        // SHA-256 of "abc".
+        let abc = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        let metadata = EmbeddedMetadata {
+            entries: vec![MetadataEntry {
+                name: "ABC.TXT".to_string(),
+                length: 3,
+                sha256: abc.to_string(),
+            }],
+        };
+
+        let parsed = EmbeddedMetadata::parse(metadata.to_json().unwrap().as_bytes()).unwrap();
+        assert_eq!(parsed, metadata);
+
+        assert_eq!(parsed.verify("abc.txt", b"abc"), Some(true));
+        assert_eq!(parsed.verify("abc.txt", b"abd"), Some(false));
+        assert_eq!(parsed.verify("abc.txt", b"abcd"), Some(false));
+        assert_eq!(parsed.verify("other.txt", b"abc"), None);
+
+        assert!(matches!(
+            EmbeddedMetadata::parse(b"{\"entries\": 5}"),
+            Err(HogError::InvalidManifest(_))
+        ));
+    }
+}
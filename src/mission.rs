@@ -0,0 +1,298 @@
+//
+// Copyright (c) 2022-2023 Nathaniel Houghton <nathan@brainwerk.org>
+//
+// Permission to use, copy, modify, and distribute this software for
+// any purpose with or without fee is hereby granted, provided that
+// the above copyright notice and this permission notice appear in all
+// copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL
+// WARRANTIES WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE
+// AUTHOR BE LIABLE FOR ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL
+// DAMAGES OR ANY DAMAGES WHATSOEVER RESULTING FROM LOSS OF USE, DATA
+// OR PROFITS, WHETHER IN AN ACTION OF CONTRACT, NEGLIGENCE OR OTHER
+// TORTIOUS ACTION, ARISING OUT OF OR IN CONNECTION WITH THE USE OR
+// PERFORMANCE OF THIS SOFTWARE.
+//
+
+use std::path::Path;
+
+use crate::error::HogError;
+use crate::sniff::Category;
+
+/// Largest mission file that is read. Mission files are a few hundred bytes of
+/// text, so anything past this is not part of one.
+pub const MAX_MISSION_LEN: u64 = 64 * 1024;
+
+/// A mission file (.msn for Descent 1, .mn2 for Descent 2), which tells the
+/// game which levels make up a mission.
+#[derive(PartialEq, Eq, Debug, Default)]
+pub struct Mission {
+    pub name: Option<String>,
+
+    /// Level filenames, in the order they are played.
+    pub levels: Vec<String>,
+
+    /// Secret level filenames, along with the level they are reached from.
+    pub secret_levels: Vec<(String, u32)>,
+
+    /// Briefing and ending text files, if the mission names them.
+    pub briefing: Option<String>,
+    pub ending: Option<String>,
+}
+
+// Splits a "key = value" line.
+fn key_value(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once('=')?;
+
+    Some((key.trim(), value.trim()))
+}
+
+// Parses the count in a "num_levels = N" style line.
+fn parse_count(key: &str, value: &str, line_num: usize) -> Result<usize, HogError> {
+    value.parse().map_err(|_| {
+        HogError::InvalidMission(format!(
+            "line {}: {} is not a number: \"{}\"",
+            line_num, key, value
+        ))
+    })
+}
+
+impl Mission {
+    /// Parses the text of a mission file.
+    ///
+    /// Comments (starting with ";") and unknown keys are ignored.
+    pub fn parse(text: &str) -> Result<Self, HogError> {
+        let mut mission = Mission::default();
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(i, x)| (i + 1, x.split(';').next().unwrap_or_default().trim()))
+            .filter(|(_, x)| !x.is_empty());
+
+        while let Some((line_num, line)) = lines.next() {
+            let (key, value) = match key_value(line) {
+                Some(x) => x,
+                None => continue,
+            };
+
+            match key.to_ascii_lowercase().as_str() {
+                "name" => mission.name = Some(value.to_string()),
+                "briefing" => mission.briefing = Some(value.to_string()),
+                "ending" => mission.ending = Some(value.to_string()),
+                "num_levels" => {
+                    for _ in 0..parse_count(key, value, line_num)? {
+                        let (_, level) = lines.next().ok_or_else(|| {
+                            HogError::InvalidMission("mission file ends in the level list".into())
+                        })?;
+
+                        mission.levels.push(level.to_string());
+                    }
+                }
+                "num_secrets" => {
+                    for _ in 0..parse_count(key, value, line_num)? {
+                        let (line_num, secret) = lines.next().ok_or_else(|| {
+                            HogError::InvalidMission(
+                                "mission file ends in the secret level list".into(),
+                            )
+                        })?;
+
+                        let parsed = secret.split_once(',').and_then(|(name, from)| {
+                            Some((name.trim(), from.trim().parse().ok()?))
+                        });
+
+                        match parsed {
+                            Some((name, from)) => {
+                                mission.secret_levels.push((name.to_string(), from))
+                            }
+                            None => {
+                                return Err(HogError::InvalidMission(format!(
+                                    "line {}: expected \"level,number\": \"{}\"",
+                                    line_num, secret
+                                )))
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(mission)
+    }
+
+    /// Returns the text of a mission file describing this mission, which
+    /// parse() reads back the same way.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        let mut line = |x: &str| {
+            text.push_str(x);
+            text.push_str("\r\n");
+        };
+
+        if let Some(name) = &self.name {
+            line(&format!("name = {}", name));
+        }
+
+        line("type = normal");
+
+        if let Some(briefing) = &self.briefing {
+            line(&format!("briefing = {}", briefing));
+        }
+
+        if let Some(ending) = &self.ending {
+            line(&format!("ending = {}", ending));
+        }
+
+        line(&format!("num_levels = {}", self.levels.len()));
+        self.levels.iter().for_each(|x| line(x));
+
+        if !self.secret_levels.is_empty() {
+            line(&format!("num_secrets = {}", self.secret_levels.len()));
+
+            for (level, from) in &self.secret_levels {
+                line(&format!("{},{}", level, from));
+            }
+        }
+
+        text
+    }
+}
+
+/// A summary of a mission, from its mission file and the files in its HOG
+/// file (added with add_file()), such as a mission archive would list.
+#[derive(PartialEq, Eq, Debug, Default)]
+pub struct MissionInfo {
+    pub name: Option<String>,
+
+    /// Number of levels and secret levels. Without a mission file, these are
+    /// the number of level files in the HOG file.
+    pub levels: usize,
+    pub secret_levels: usize,
+
+    /// Whether the HOG file has the briefing the mission file names (or any
+    /// briefing, if it does not name one).
+    pub briefing: bool,
+
+    /// Whether the HOG file has custom textures (.pog, .dtx or .pig files).
+    pub custom_textures: bool,
+
+    /// Whether the HOG file has custom music (.hmp, .hmq or .mid files).
+    pub custom_music: bool,
+
+    /// Number of files in the HOG file, and their total length.
+    pub files: u64,
+    pub bytes: u64,
+
+    // Whether a mission file was given, and the briefing it names.
+    has_mission: bool,
+    briefing_name: Option<String>,
+}
+
+impl MissionInfo {
+    /// Starts the summary of a mission, with its mission file (if there is
+    /// one).
+    pub fn new(mission: Option<&Mission>) -> Self {
+        match mission {
+            Some(mission) => MissionInfo {
+                name: mission.name.clone(),
+                levels: mission.levels.len(),
+                secret_levels: mission.secret_levels.len(),
+                has_mission: true,
+                briefing_name: mission.briefing.clone(),
+                ..Default::default()
+            },
+            None => MissionInfo::default(),
+        }
+    }
+
+    /// Adds a file found in the HOG file of the mission.
+    pub fn add_file(&mut self, name: &str, length: u32) {
+        let ext = Path::new(name)
+            .extension()
+            .map(|x| x.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+
+        self.files += 1;
+        self.bytes += u64::from(length);
+
+        match &self.briefing_name {
+            Some(briefing) => self.briefing |= name.eq_ignore_ascii_case(briefing),
+            None => self.briefing |= matches!(ext.as_str(), "txb" | "tex"),
+        }
+
+        self.custom_textures |= matches!(ext.as_str(), "pog" | "dtx" | "pig");
+        self.custom_music |= Category::from_extension(&ext) == Category::Music;
+
+        if !self.has_mission {
+            match ext.as_str() {
+                "rdl" | "rl2" if name.to_ascii_lowercase().starts_with("levels") => {
+                    self.secret_levels += 1
+                }
+                "rdl" | "rl2" => self.levels += 1,
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_mission() {
+        let text = "name = My Mission ; a comment\r\n\
+                    type = normal\r\n\
+                    briefing = mymiss.txb\n\
+                    num_levels = 2\n\
+                    level01.rl2\n\
+                    \n\
+                    level02.rl2\n\
+                    num_secrets = 1\n\
+                    levels1.rl2,2\n";
+
+        let mission = Mission::parse(text).unwrap();
+
+        assert_eq!(mission.name.as_deref(), Some("My Mission"));
+        assert_eq!(mission.briefing.as_deref(), Some("mymiss.txb"));
+        assert_eq!(mission.ending, None);
+        assert_eq!(mission.levels, ["level01.rl2", "level02.rl2"]);
+        assert_eq!(mission.secret_levels, [("levels1.rl2".to_string(), 2)]);
+
+        assert_eq!(Mission::parse(&mission.to_text()).unwrap(), mission);
+
+        assert!(Mission::parse("num_levels = 2\nlevel01.rdl\n").is_err());
+        assert!(Mission::parse("num_levels = two\n").is_err());
+        assert!(Mission::parse("num_secrets = 1\nlevels1.rdl\n").is_err());
+    }
+
+    #[test]
+    fn test_mission_info() {
+        let mission = Mission::parse(
+            "name = My Mission\nbriefing = MYMISS.TXB\nnum_levels = 2\nlevel01.rl2\nlevel02.rl2\n",
+        )
+        .unwrap();
+        let mut info = MissionInfo::new(Some(&mission));
+
+        for (name, length) in [("level01.rl2", 10), ("mymiss.txb", 2), ("game01.hmp", 3)] {
+            info.add_file(name, length);
+        }
+
+        assert_eq!(info.name.as_deref(), Some("My Mission"));
+        assert_eq!((info.levels, info.secret_levels), (2, 0));
+        assert!(info.briefing && info.custom_music && !info.custom_textures);
+        assert_eq!((info.files, info.bytes), (3, 15));
+
+        // Without a mission file, the level files are counted instead.
+        let mut info = MissionInfo::new(None);
+
+        for name in ["level01.rdl", "level02.rdl", "levels1.rdl", "custom.dtx"] {
+            info.add_file(name, 1);
+        }
+
+        assert_eq!((info.levels, info.secret_levels), (2, 1));
+        assert!(!info.briefing && !info.custom_music && info.custom_textures);
+    }
+}
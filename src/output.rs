@@ -0,0 +1,2527 @@
+//
+// Copyright (c) 2022-2023 Nathaniel Houghton <nathan@brainwerk.org>
+//
+// Permission to use, copy, modify, and distribute this software for
+// any purpose with or without fee is hereby granted, provided that
+// the above copyright notice and this permission notice appear in all
+// copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL
+// WARRANTIES WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE
+// AUTHOR BE LIABLE FOR ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL
+// DAMAGES OR ANY DAMAGES WHATSOEVER RESULTING FROM LOSS OF USE, DATA
+// OR PROFITS, WHETHER IN AN ACTION OF CONTRACT, NEGLIGENCE OR OTHER
+// TORTIOUS ACTION, ARISING OUT OF OR IN CONNECTION WITH THE USE OR
+// PERFORMANCE OF THIS SOFTWARE.
+//
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, LineWriter, Write};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+use hogdump::bench::BenchResult;
+use hogdump::demo::DemoInfo;
+use hogdump::diff::{Change, EntryDiff};
+use hogdump::error::{HogError, HogWarning};
+use hogdump::extract::{ExtractPlan, PlannedEntry};
+use hogdump::fixture::Corruption;
+use hogdump::hog::{EscapedName, HogRecord, RecordHeader, NAME_LEN};
+use hogdump::manifest::METADATA_NAME;
+use hogdump::mission::MissionInfo;
+use hogdump::pig::LevelPairing;
+use hogdump::report::{
+    self, AutoSuffixReport, DiffEntryReport, EntryReport, ErrorReport, ListedEntryReport,
+    MissingAssetReport, RunOutcome, Severity, Summary, TestEntryReport, TestErrorReport,
+    WarningReport,
+};
+use hogdump::sniff::{ContentType, TypeSummary};
+use hogdump::table::TableInfo;
+use hogdump::util;
+use hogdump::vfs::Resolution;
+
+use crate::{
+    ArchiveError, EntryResult, EntryStatus, HogCreateInfo, HogExtractInfo, HogInfoSummary,
+    HogTestInfo, HogTotals,
+};
+
+// Version of the porcelain output format. This must be bumped whenever the
+// porcelain output changes in a way that could break existing scripts.
+const PORCELAIN_VERSION: u32 = 1;
+
+// How results are displayed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    // Human readable text. This is free to change between releases.
+    Human,
+
+    // Stable, tab separated output meant to be parsed by scripts.
+    Porcelain,
+
+    // Human readable text, but with errors reported as JSON objects.
+    Json,
+}
+
+// Why a file was not extracted.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    // The output file already exists.
+    Exists,
+
+    // The file is empty, and --skip-empty was given.
+    Empty,
+}
+
+impl SkipReason {
+    // Returns the reason as displayed in porcelain output.
+    fn name(&self) -> &'static str {
+        match self {
+            SkipReason::Exists => "exists",
+            SkipReason::Empty => "empty",
+        }
+    }
+
+    // Returns the reason as displayed in human readable output.
+    fn description(&self) -> &'static str {
+        match self {
+            SkipReason::Exists => "already exists",
+            SkipReason::Empty => "empty",
+        }
+    }
+}
+
+// How sizes are displayed in the human readable output. Porcelain and JSON
+// output always use plain byte counts.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SizeFormat {
+    // Byte counts ("1468006 bytes").
+    Bytes,
+
+    // Powers of 1024 ("1.4 MiB").
+    Binary,
+
+    // Powers of 1000 ("1.5 MB").
+    Si,
+}
+
+// Whether a listed file matches what the metadata embedded in the HOG file
+// (by --embed-metadata) says about it.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Integrity {
+    // Checked with --check, and it matches.
+    Verified,
+
+    // Listed in the metadata, but not checked.
+    Unverified,
+
+    // Checked with --check, and it does not match.
+    Mismatch,
+}
+
+impl Integrity {
+    fn name(&self) -> &'static str {
+        match self {
+            Integrity::Verified => "verified",
+            Integrity::Unverified => "unverified",
+            Integrity::Mismatch => "mismatch",
+        }
+    }
+}
+
+// Displays the results of each operation, in the selected format.
+//
+// Per-file progress is written to the provided writer (which may be a buffer,
+// when processing HOG files in parallel). Summaries and errors are written
+// directly to stdout/stderr.
+//
+// When extracted file contents are written to stdout ("data_on_stdout"),
+// everything that would normally go to stdout goes to stderr instead.
+pub struct Reporter {
+    format: Format,
+    verbose: bool,
+    data_on_stdout: bool,
+
+    // Where the output goes instead of stdout, with --report.
+    report: Option<Mutex<LineWriter<File>>>,
+
+    sizes: SizeFormat,
+
+    // Number of warnings reported so far, for --deny-warnings.
+    warnings: AtomicU64,
+
+    // The problems reported so far, for the exit status.
+    outcome: Mutex<RunOutcome>,
+}
+
+// Escapes a porcelain field, so that it never contains a tab or a newline.
+fn escape(field: &str) -> String {
+    let mut escaped = String::with_capacity(field.len());
+
+    for c in field.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+fn escape_path(path: &Path) -> String {
+    escape(&path.to_string_lossy())
+}
+
+// Writes an error to stderr as a single line JSON object (see
+// report::ErrorReport).
+fn json_error(
+    archive: &Path,
+    file: Option<&Path>,
+    e: &HogError,
+    entry_index: Option<u64>,
+    offset: Option<u64>,
+) {
+    let error = error_report(archive, file, e, entry_index, offset);
+
+    eprintln!("{}", report::to_json(&error));
+}
+
+// Returns the report of an error, written by json_error() and included in the
+// --json summaries.
+fn error_report(
+    archive: &Path,
+    file: Option<&Path>,
+    e: &HogError,
+    entry_index: Option<u64>,
+    offset: Option<u64>,
+) -> ErrorReport {
+    ErrorReport::new(
+        &archive.to_string_lossy(),
+        file.map(|x| x.to_string_lossy()).as_deref(),
+        e,
+        entry_index,
+        offset,
+    )
+}
+
+// The remediation for a HOG file that ends early.
+const TRUNCATED: &str = "the HOG file is truncated: the files before this record can still be \
+                         extracted, the rest need a complete copy of the HOG file";
+
+// Returns what can be done about an error found while testing a HOG file, for
+// the --test --json report.
+fn remediation(e: &HogError) -> Option<&'static str> {
+    match e {
+        HogError::UnexpectedEof => Some(TRUNCATED),
+        HogError::ReadFailure(e) if e.kind() == io::ErrorKind::UnexpectedEof => Some(TRUNCATED),
+        HogError::InvalidFilename | HogError::ReadHeaderError(_) => Some(
+            "the record header is damaged: use --ignore-errors or --debug-records to \
+             inspect the records from this offset on",
+        ),
+        HogError::InvalidSignature | HogError::TooShort(_) | HogError::SignatureReadFailure(_) => {
+            Some("the file is not a HOG file, or its start is damaged")
+        }
+        HogError::ReadFailure(_) | HogError::SeekFailure(_) | HogError::OpenHogFailure(_) => {
+            Some("the HOG file could not be read: check the storage it is on")
+        }
+        _ => None,
+    }
+}
+
+// Returns the list of files in a --json summary.
+fn entry_reports(entries: &[EntryResult]) -> Vec<EntryReport> {
+    entries
+        .iter()
+        .map(|x| EntryReport {
+            name: x.name.as_ref().map(|x| x.to_string_lossy().into_owned()),
+            path: x.path.to_string_lossy().into_owned(),
+            status: x.status.name(),
+            bytes: x.bytes,
+        })
+        .collect()
+}
+
+impl Reporter {
+    pub fn new(format: Format, verbose: bool, data_on_stdout: bool) -> Self {
+        Self {
+            format,
+            verbose,
+            data_on_stdout,
+            report: None,
+            sizes: SizeFormat::Bytes,
+            warnings: AtomicU64::new(0),
+            outcome: Mutex::new(RunOutcome::default()),
+        }
+    }
+
+    /// Displays sizes in the human readable output as "sizes".
+    pub fn with_sizes(self, sizes: SizeFormat) -> Self {
+        Self { sizes, ..self }
+    }
+
+    /// Formats a size for the human readable output.
+    fn size(&self, bytes: u64) -> String {
+        match self.sizes {
+            SizeFormat::Bytes => format!("{} bytes", bytes),
+            SizeFormat::Binary => util::human_size(bytes, false),
+            SizeFormat::Si => util::human_size(bytes, true),
+        }
+    }
+
+    /// Writes the output to "file" instead of to stdout (errors still go to
+    /// stderr).
+    pub fn with_report(self, file: File) -> Self {
+        Self {
+            report: Some(Mutex::new(LineWriter::new(file))),
+            ..self
+        }
+    }
+
+    // Writes a line of output to stdout, or to stderr if stdout is being used
+    // for extracted file contents (or to the --report file, if there is one).
+    fn line(&self, args: fmt::Arguments) {
+        if let Some(report) = &self.report {
+            let mut report = report.lock().unwrap();
+
+            if let Err(e) = writeln!(report, "{}", args) {
+                eprintln!("error: failed to write report: {}", e);
+
+                std::process::exit(1);
+            }
+        } else if self.data_on_stdout {
+            eprintln!("{}", args);
+        } else {
+            println!("{}", args);
+        }
+    }
+
+    // Writes a --json summary document.
+    fn summary(&self, summary: &Summary) {
+        self.line(format_args!("{}", report::to_json(summary)));
+    }
+
+    /// Writes anything that needs to come before all other output.
+    pub fn begin(&self, out: &mut dyn Write) -> io::Result<()> {
+        match self.format {
+            Format::Human | Format::Json => Ok(()),
+            Format::Porcelain => writeln!(out, "hogdump-porcelain\t{}", PORCELAIN_VERSION),
+        }
+    }
+
+    /// Returns true if listings include the guessed type of each file, which
+    /// means that the start of each file has to be read.
+    pub fn shows_content_type(&self) -> bool {
+        match self.format {
+            Format::Human | Format::Json => self.verbose,
+            Format::Porcelain => false,
+        }
+    }
+
+    /// Returns true if listings report each file, rather than just the
+    /// totals.
+    pub fn lists_entries(&self) -> bool {
+        match self.format {
+            Format::Human | Format::Json => self.verbose,
+            Format::Porcelain => true,
+        }
+    }
+
+    /// Writes the summary of the types of files in a HOG file (for --tree):
+    /// each category, with the content types of the files in it.
+    pub fn type_tree(
+        &self,
+        out: &mut dyn Write,
+        archive: &Path,
+        types: &TypeSummary,
+    ) -> io::Result<()> {
+        let categories = types.categories();
+
+        match self.format {
+            Format::Human | Format::Json => {
+                writeln!(out, "{}:", archive.display())?;
+
+                for category in &categories {
+                    writeln!(
+                        out,
+                        "  {}: {} files, {}",
+                        category.category.name(),
+                        category.files,
+                        self.size(category.bytes)
+                    )?;
+
+                    for &(content_type, files, bytes) in &category.types {
+                        writeln!(
+                            out,
+                            "    {}: {} files, {}",
+                            content_type,
+                            files,
+                            self.size(bytes)
+                        )?;
+                    }
+                }
+
+                Ok(())
+            }
+            Format::Porcelain => {
+                for category in &categories {
+                    for &(content_type, files, bytes) in &category.types {
+                        writeln!(
+                            out,
+                            "tree\t{}\t{}\t{}\t{}\t{}",
+                            escape_path(archive),
+                            category.category.dir_name(),
+                            content_type,
+                            files,
+                            bytes
+                        )?;
+                    }
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Reports a file found while listing a HOG file. "content_type" is only
+    /// used if shows_content_type() is true. "integrity" is given for the
+    /// files listed in the metadata embedded in the HOG file, if there is any.
+    ///
+    /// In human readable and JSON mode, this is only displayed in verbose mode
+    /// (or for files that do not match their digests).
+    pub fn listed(
+        &self,
+        out: &mut dyn Write,
+        archive: &Path,
+        name: &Path,
+        length: u32,
+        content_type: Option<ContentType>,
+        integrity: Option<Integrity>,
+    ) -> io::Result<()> {
+        let mismatch = integrity == Some(Integrity::Mismatch);
+
+        match self.format {
+            Format::Json if self.verbose || mismatch => {
+                let entry = ListedEntryReport {
+                    archive: archive.to_string_lossy().into_owned(),
+                    name: name.to_string_lossy().into_owned(),
+                    length,
+                    content_type: content_type.filter(|_| self.verbose).map(|x| x.name()),
+                    integrity: integrity.map(|x| x.name()),
+                };
+
+                writeln!(out, "{}", report::to_json(&entry))
+            }
+            Format::Human if self.verbose => writeln!(
+                out,
+                "  {}: {}: {} ({}){}",
+                archive.display(),
+                EscapedName(name),
+                self.size(u64::from(length)),
+                content_type.unwrap_or(ContentType::Unknown),
+                integrity.map_or(String::new(), |x| format!(" [{}]", x.name()))
+            ),
+            // Files that do not match are always worth mentioning.
+            Format::Human if mismatch => writeln!(
+                out,
+                "  {}: {}: does not match its digest in {}",
+                archive.display(),
+                EscapedName(name),
+                METADATA_NAME
+            ),
+            Format::Human | Format::Json => Ok(()),
+            Format::Porcelain => {
+                writeln!(
+                    out,
+                    "entry\t{}\t{}\t{}",
+                    escape_path(archive),
+                    escape_path(name),
+                    length
+                )?;
+
+                match integrity {
+                    Some(integrity) => writeln!(
+                        out,
+                        "integrity\t{}\t{}\t{}",
+                        escape_path(archive),
+                        escape_path(name),
+                        integrity.name()
+                    ),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+
+    /// Displays the start of a text file found while listing a HOG file.
+    pub fn preview(
+        &self,
+        out: &mut dyn Write,
+        archive: &Path,
+        name: &Path,
+        text: &str,
+    ) -> io::Result<()> {
+        match self.format {
+            Format::Human | Format::Json => {
+                // In verbose mode, the file was just listed already.
+                if !self.verbose {
+                    writeln!(out, "  {}: {}:", archive.display(), EscapedName(name))?;
+                }
+
+                for line in text.lines() {
+                    writeln!(out, "    | {}", line)?;
+                }
+
+                Ok(())
+            }
+            Format::Porcelain => writeln!(
+                out,
+                "preview\t{}\t{}\t{}",
+                escape_path(archive),
+                escape_path(name),
+                escape(text)
+            ),
+        }
+    }
+
+    /// Displays a thumbnail of an image while listing a HOG file (for
+    /// --preview-images), as the lines of text that draw it. There is no
+    /// porcelain output for it.
+    pub fn image_preview(
+        &self,
+        out: &mut dyn Write,
+        archive: &Path,
+        name: &Path,
+        lines: &[String],
+    ) -> io::Result<()> {
+        match self.format {
+            Format::Human | Format::Json => {
+                // In verbose mode, the file was just listed already.
+                if !self.verbose {
+                    writeln!(out, "  {}: {}:", archive.display(), EscapedName(name))?;
+                }
+
+                for line in lines {
+                    writeln!(out, "    {}", line)?;
+                }
+
+                Ok(())
+            }
+            Format::Porcelain => Ok(()),
+        }
+    }
+
+    /// Displays what was found about a demo recording while listing a HOG
+    /// file (only in verbose listings, right after the demo was listed).
+    pub fn demo(&self, out: &mut dyn Write, info: &DemoInfo) -> io::Result<()> {
+        writeln!(out, "    | {}", info)
+    }
+
+    /// Displays how many definitions of each kind a Descent 3 table file
+    /// holds (only in verbose listings, right after the file was listed).
+    pub fn table(&self, out: &mut dyn Write, info: &TableInfo) -> io::Result<()> {
+        writeln!(out, "    | {}", info)
+    }
+
+    /// Displays a record header for --debug-records: the raw bytes, and how
+    /// they are interpreted. "index" is the position of the file in the HOG
+    /// file (None for padding records).
+    pub fn record_header(
+        &self,
+        out: &mut dyn Write,
+        archive: &Path,
+        index: Option<u64>,
+        header: &RecordHeader,
+    ) -> io::Result<()> {
+        let hex = |bytes: &[u8]| {
+            bytes
+                .iter()
+                .map(|x| format!("{:02x}", x))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let record = header.record();
+        let status = if header.truncated {
+            "truncated"
+        } else if header.is_padding() {
+            "padding"
+        } else if record.is_err() {
+            "bad-name"
+        } else {
+            "ok"
+        };
+
+        match self.format {
+            Format::Human | Format::Json => {
+                let mut meaning = match (index, &record) {
+                    (None, _) => format!("padding, {} bytes", header.length()),
+                    (Some(index), Ok(record)) => format!(
+                        "record {}: \"{}\", {} bytes",
+                        index,
+                        record,
+                        header.length()
+                    ),
+                    (Some(index), Err(e)) => {
+                        format!("record {}: {}, {} bytes", index, e, header.length())
+                    }
+                };
+
+                if header.truncated {
+                    meaning.push_str(" (runs past the end of the HOG file)");
+                }
+
+                writeln!(
+                    out,
+                    "  {}: {:>10}: {} | {}  {}",
+                    archive.display(),
+                    header.offset,
+                    hex(&header.bytes[..NAME_LEN]),
+                    hex(&header.bytes[NAME_LEN..]),
+                    meaning
+                )
+            }
+            Format::Porcelain => writeln!(
+                out,
+                "record-header\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                escape_path(archive),
+                header.offset,
+                util::to_hex(&header.bytes),
+                index.map(|x| x.to_string()).unwrap_or_default(),
+                record.map(|x| escape_path(&x.filename)).unwrap_or_default(),
+                header.length(),
+                status
+            ),
+        }
+    }
+
+    /// Reports a record of a HOG file that could not be read while listing
+    /// it, when listing continues anyway.
+    pub fn bad_record(
+        &self,
+        out: &mut dyn Write,
+        archive: &Path,
+        e: &ArchiveError,
+    ) -> io::Result<()> {
+        let index = e.entry_index.unwrap_or_default();
+        let offset = e.offset.unwrap_or_default();
+
+        self.count(Severity::Corrupt, 1);
+
+        match self.format {
+            Format::Human => writeln!(
+                out,
+                "  {}: bad record {} at offset {}: {}",
+                archive.display(),
+                index,
+                offset,
+                e.error
+            ),
+            Format::Json => {
+                json_error(archive, None, &e.error, e.entry_index, e.offset);
+
+                Ok(())
+            }
+            Format::Porcelain => writeln!(
+                out,
+                "bad-record\t{}\t{}\t{}\t{}",
+                escape_path(archive),
+                index,
+                offset,
+                escape(&e.error.to_string())
+            ),
+        }
+    }
+
+    /// Reports that the files of a HOG file were written to stdout by
+    /// --stream-all.
+    pub fn streamed(&self, archive: &Path, files: u64, bytes: u64) {
+        match self.format {
+            Format::Human => self.line(format_args!(
+                "{}: streamed {} files ({}).",
+                archive.display(),
+                files,
+                self.size(bytes)
+            )),
+            Format::Porcelain => self.line(format_args!(
+                "streamed\t{}\t{}\t{}",
+                escape_path(archive),
+                files,
+                bytes
+            )),
+            Format::Json => self.summary(&Summary::Stream {
+                archive: archive.to_string_lossy().into_owned(),
+                files,
+                bytes,
+            }),
+        }
+    }
+
+    /// Reports how long a benchmark took on a HOG file (see bench::run()).
+    pub fn bench(&self, archive: &Path, result: &BenchResult) {
+        match self.format {
+            Format::Human => self.line(format_args!(
+                "{}: {}: {} files ({}) in {:.3} ms, {}/s (median of {} rounds, fastest {:.3} ms).",
+                archive.display(),
+                result.benchmark.name(),
+                result.files,
+                self.size(result.bytes),
+                result.median.as_secs_f64() * 1000.0,
+                self.size(result.bytes_per_sec() as u64),
+                result.rounds,
+                result.fastest.as_secs_f64() * 1000.0
+            )),
+            Format::Porcelain => self.line(format_args!(
+                "bench\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                escape_path(archive),
+                result.benchmark.name(),
+                result.rounds,
+                result.files,
+                result.bytes,
+                result.median.as_nanos(),
+                result.fastest.as_nanos()
+            )),
+            Format::Json => self.summary(&Summary::Bench {
+                archive: archive.to_string_lossy().into_owned(),
+                benchmark: result.benchmark.name(),
+                rounds: result.rounds,
+                files: result.files,
+                bytes: result.bytes,
+                fastest_secs: result.fastest.as_secs_f64(),
+                median_secs: result.median.as_secs_f64(),
+                bytes_per_sec: result.bytes_per_sec(),
+            }),
+        }
+    }
+
+    /// Reports that the files of a HOG file were extracted into a single
+    /// file by --concat, with a map of them in "index".
+    pub fn concatenated(&self, archive: &Path, path: &Path, index: &Path, files: u64, bytes: u64) {
+        match self.format {
+            Format::Human => self.line(format_args!(
+                "{}: wrote {} files ({}) to \"{}\", mapped in \"{}\".",
+                archive.display(),
+                files,
+                self.size(bytes),
+                path.display(),
+                index.display()
+            )),
+            Format::Porcelain => self.line(format_args!(
+                "concat\t{}\t{}\t{}\t{}\t{}",
+                escape_path(archive),
+                escape_path(path),
+                escape_path(index),
+                files,
+                bytes
+            )),
+            Format::Json => self.summary(&Summary::Concat {
+                archive: archive.to_string_lossy().into_owned(),
+                path: path.to_string_lossy().into_owned(),
+                index: index.to_string_lossy().into_owned(),
+                files,
+                bytes,
+            }),
+        }
+    }
+
+    /// Reports that a listing of the files of a HOG file was written to
+    /// "path" by --export-listing.
+    pub fn listing_exported(&self, archive: &Path, path: &Path, files: u64) {
+        match self.format {
+            Format::Human => self.line(format_args!(
+                "{}: listed {} files in \"{}\".",
+                archive.display(),
+                files,
+                path.display()
+            )),
+            Format::Porcelain => self.line(format_args!(
+                "export-listing\t{}\t{}\t{}",
+                escape_path(archive),
+                escape_path(path),
+                files
+            )),
+            Format::Json => self.summary(&Summary::ExportListing {
+                archive: archive.to_string_lossy().into_owned(),
+                path: path.to_string_lossy().into_owned(),
+                files,
+            }),
+        }
+    }
+
+    /// Reports the totals for a listed HOG file.
+    pub fn list_summary(&self, archive: &Path, summary: &HogInfoSummary) {
+        self.count(Severity::Corrupt, summary.mismatches);
+
+        if summary.mismatches > 0 && self.format == Format::Human {
+            self.line(format_args!(
+                "{}: {} files do not match their digests in {}.",
+                archive.display(),
+                summary.mismatches,
+                METADATA_NAME
+            ));
+        }
+
+        match self.format {
+            Format::Json => self.summary(&Summary::List {
+                archive: archive.to_string_lossy().into_owned(),
+                files: summary.num_files,
+                bytes: summary.num_bytes,
+                bad_records: summary.bad_records,
+                mismatches: summary.mismatches,
+            }),
+            Format::Human if summary.bad_records > 0 => self.line(format_args!(
+                "{}: contains {} files ({}), skipped {} bad records.",
+                archive.display(),
+                summary.num_files,
+                self.size(summary.num_bytes),
+                summary.bad_records
+            )),
+            Format::Human => self.line(format_args!(
+                "{}: contains {} files ({}).",
+                archive.display(),
+                summary.num_files,
+                self.size(summary.num_bytes),
+            )),
+            Format::Porcelain => self.line(format_args!(
+                "list-summary\t{}\t{}\t{}",
+                escape_path(archive),
+                summary.num_files,
+                summary.num_bytes
+            )),
+        }
+    }
+
+    /// Reports a file that --dry-run would extract (or skip).
+    pub fn planned(
+        &self,
+        out: &mut dyn Write,
+        archive: &Path,
+        entry: &PlannedEntry,
+    ) -> io::Result<()> {
+        match self.format {
+            // The plan summary lists the files instead.
+            Format::Json => Ok(()),
+            Format::Human if entry.extract => writeln!(
+                out,
+                "  {}: {}: would extract to \"{}\" ({})",
+                archive.display(),
+                EscapedName(&entry.name),
+                EscapedName(&entry.path),
+                self.size(u64::from(entry.length))
+            ),
+            Format::Human => writeln!(
+                out,
+                "  {}: {}: would skip ({})",
+                archive.display(),
+                EscapedName(&entry.name),
+                SkipReason::Empty.description()
+            ),
+            Format::Porcelain => writeln!(
+                out,
+                "planned\t{}\t{}\t{}\t{}\t{}",
+                escape_path(archive),
+                escape_path(&entry.name),
+                escape_path(&entry.path),
+                entry.length,
+                if entry.extract {
+                    "extract"
+                } else {
+                    SkipReason::Empty.name()
+                }
+            ),
+        }
+    }
+
+    /// Reports the totals for a HOG file planned by --dry-run.
+    pub fn plan_summary(&self, archive: &Path, plan: &ExtractPlan) {
+        let (files, bytes) = plan.extracted();
+        let skipped = plan.entries.len() as u64 - files;
+
+        match self.format {
+            Format::Json => self.summary(&Summary::Plan {
+                archive: archive.to_string_lossy().into_owned(),
+                files,
+                bytes,
+                files_skipped: skipped,
+                entries: plan
+                    .entries
+                    .iter()
+                    .map(|x| EntryReport {
+                        name: Some(x.name.to_string_lossy().into_owned()),
+                        path: x.path.to_string_lossy().into_owned(),
+                        status: if x.extract { "extract" } else { "skip" },
+                        bytes: x.length.into(),
+                    })
+                    .collect(),
+            }),
+            Format::Human => self.line(format_args!(
+                "{}: would extract {} files ({}), skip {} files.",
+                archive.display(),
+                files,
+                self.size(bytes),
+                skipped
+            )),
+            Format::Porcelain => self.line(format_args!(
+                "plan-summary\t{}\t{}\t{}\t{}",
+                escape_path(archive),
+                files,
+                bytes,
+                skipped
+            )),
+        }
+    }
+
+    /// Reports the totals across all HOG files processed.
+    pub fn totals(&self, totals: &HogTotals) {
+        match self.format {
+            Format::Json => self.summary(&Summary::Total {
+                archives: totals.archives,
+                files: totals.files,
+                bytes: totals.bytes,
+                errors: totals.errors,
+            }),
+            Format::Human => self.line(format_args!(
+                "Total: {} HOG files, {} files ({}), {} errors.",
+                totals.archives,
+                totals.files,
+                self.size(totals.bytes),
+                totals.errors
+            )),
+            Format::Porcelain => self.line(format_args!(
+                "total\t{}\t{}\t{}\t{}",
+                totals.archives, totals.files, totals.bytes, totals.errors
+            )),
+        }
+    }
+
+    /// Reports a file that was extracted from a HOG file.
+    pub fn extracted(
+        &self,
+        out: &mut dyn Write,
+        archive: &Path,
+        name: &Path,
+        length: u64,
+    ) -> io::Result<()> {
+        match self.format {
+            Format::Human => writeln!(
+                out,
+                "  {}: {}: wrote {}",
+                archive.display(),
+                EscapedName(name),
+                self.size(length)
+            ),
+            Format::Json => Ok(()),
+            Format::Porcelain => writeln!(
+                out,
+                "extracted\t{}\t{}\t{}",
+                escape_path(archive),
+                escape_path(name),
+                length
+            ),
+        }
+    }
+
+    /// Reports an extracted file that was linked to an earlier extracted file
+    /// with the same contents.
+    pub fn linked(
+        &self,
+        out: &mut dyn Write,
+        archive: &Path,
+        name: &Path,
+        original: &Path,
+    ) -> io::Result<()> {
+        match self.format {
+            Format::Human => writeln!(
+                out,
+                "  {}: {}: linked to \"{}\" (same contents)",
+                archive.display(),
+                EscapedName(name),
+                EscapedName(original)
+            ),
+            Format::Json => Ok(()),
+            Format::Porcelain => writeln!(
+                out,
+                "linked\t{}\t{}\t{}",
+                escape_path(archive),
+                escape_path(name),
+                escape_path(original)
+            ),
+        }
+    }
+
+    /// Reports an extracted file whose SHA-256 digest is not the one it was
+    /// expected to have. "removed" is true if the file was removed again (and
+    /// extraction stops with an error, reported separately).
+    pub fn digest_mismatch(
+        &self,
+        out: &mut dyn Write,
+        archive: &Path,
+        name: &Path,
+        expected: &str,
+        actual: &str,
+        removed: bool,
+    ) -> io::Result<()> {
+        if !removed {
+            self.count(Severity::Corrupt, 1);
+        }
+
+        match self.format {
+            Format::Human | Format::Json if removed => Ok(()),
+            Format::Human | Format::Json => self.warning(
+                out,
+                archive,
+                &HogWarning::DigestMismatch(name.into(), expected.into(), actual.into()),
+            ),
+            Format::Porcelain => writeln!(
+                out,
+                "digest-mismatch\t{}\t{}\t{}\t{}\t{}",
+                escape_path(archive),
+                escape_path(name),
+                expected,
+                actual,
+                if removed { "removed" } else { "kept" }
+            ),
+        }
+    }
+
+    /// Reports a file whose name only differs by case from an earlier file
+    /// ("earlier"), which is extracted to "resolved" (its own path, when it is
+    /// kept).
+    pub fn case_collision(
+        &self,
+        out: &mut dyn Write,
+        archive: &Path,
+        name: &Path,
+        earlier: &Path,
+        resolved: &Path,
+    ) -> io::Result<()> {
+        let action = if earlier == resolved {
+            "last-wins"
+        } else if earlier.to_string_lossy().to_lowercase()
+            == resolved.to_string_lossy().to_lowercase()
+        {
+            "keep"
+        } else {
+            "suffix"
+        };
+
+        match self.format {
+            Format::Human if action == "last-wins" => writeln!(
+                out,
+                "  {}: {}: same file as \"{}\" on case insensitive filesystems, replacing it",
+                archive.display(),
+                EscapedName(name),
+                EscapedName(earlier)
+            ),
+            Format::Human if action == "keep" => writeln!(
+                out,
+                "  {}: {}: same file as \"{}\" on case insensitive filesystems",
+                archive.display(),
+                EscapedName(name),
+                EscapedName(earlier)
+            ),
+            Format::Human => writeln!(
+                out,
+                "  {}: {}: same file as \"{}\" on case insensitive filesystems, extracting as \"{}\"",
+                archive.display(),
+                EscapedName(name),
+                EscapedName(earlier),
+                EscapedName(resolved)
+            ),
+            Format::Json => Ok(()),
+            Format::Porcelain => writeln!(
+                out,
+                "case-collision\t{}\t{}\t{}\t{}\t{}",
+                escape_path(archive),
+                escape_path(name),
+                escape_path(earlier),
+                action,
+                escape_path(resolved)
+            ),
+        }
+    }
+
+    /// Reports a file that was extracted to "resolved" (with --auto-suffix),
+    /// because a file already existed where it would have been extracted to.
+    pub fn auto_suffixed(
+        &self,
+        out: &mut dyn Write,
+        archive: &Path,
+        name: &Path,
+        resolved: &Path,
+    ) -> io::Result<()> {
+        match self.format {
+            Format::Human => writeln!(
+                out,
+                "  {}: {}: a file with this name already exists, extracting as \"{}\"",
+                archive.display(),
+                EscapedName(name),
+                EscapedName(resolved)
+            ),
+            Format::Json => {
+                let suffixed = AutoSuffixReport {
+                    archive: archive.to_string_lossy().into_owned(),
+                    name: name.to_string_lossy().into_owned(),
+                    extracted_as: resolved.to_string_lossy().into_owned(),
+                };
+
+                writeln!(out, "{}", report::to_json(&suffixed))
+            }
+            Format::Porcelain => writeln!(
+                out,
+                "auto-suffix\t{}\t{}\t{}",
+                escape_path(archive),
+                escape_path(name),
+                escape_path(resolved)
+            ),
+        }
+    }
+
+    /// Reports a file that was not extracted, and why.
+    pub fn skipped(
+        &self,
+        out: &mut dyn Write,
+        archive: &Path,
+        name: &Path,
+        reason: SkipReason,
+    ) -> io::Result<()> {
+        match self.format {
+            Format::Human => writeln!(
+                out,
+                "  {}: {}: skipping ({})",
+                archive.display(),
+                EscapedName(name),
+                reason.description()
+            ),
+            Format::Json => Ok(()),
+            Format::Porcelain => writeln!(
+                out,
+                "skipped\t{}\t{}\t{}",
+                escape_path(archive),
+                escape_path(name),
+                reason.name()
+            ),
+        }
+    }
+
+    /// Reports what happened while extracting a HOG file. "error" is the
+    /// error that stopped the extraction, if any.
+    ///
+    /// With --json, this is a JSON document listing every file. Otherwise,
+    /// nothing is displayed if there was an error (it was reported already).
+    pub fn extract_summary(
+        &self,
+        archive: &Path,
+        info: &HogExtractInfo,
+        error: Option<&ArchiveError>,
+    ) {
+        match self.format {
+            Format::Json => {
+                let with_status = |status| -> Vec<String> {
+                    info.entries
+                        .iter()
+                        .filter(|x| x.status == status)
+                        .filter_map(|x| x.name.as_ref().map(|x| x.to_string_lossy().into_owned()))
+                        .collect()
+                };
+
+                self.summary(&Summary::Extract {
+                    archive: archive.to_string_lossy().into_owned(),
+                    files_processed: info.files_processed,
+                    files_extracted: info.files_extracted,
+                    bytes_extracted: info.bytes_extracted,
+                    files_skipped: info.files_skipped,
+                    entries: entry_reports(&info.entries),
+                    skipped: with_status(EntryStatus::Skipped),
+                    overwritten: with_status(EntryStatus::Overwritten),
+                    errors: error
+                        .iter()
+                        .map(|e| error_report(archive, None, &e.error, e.entry_index, e.offset))
+                        .collect(),
+                });
+            }
+            _ if error.is_some() => {}
+            Format::Human => self.line(format_args!(
+                "Processed {} files, extracted {} files ({}), skipped {} files.",
+                info.files_processed,
+                info.files_extracted,
+                self.size(info.bytes_extracted),
+                info.files_skipped
+            )),
+            Format::Porcelain => self.line(format_args!(
+                "extract-summary\t{}\t{}\t{}\t{}\t{}",
+                escape_path(archive),
+                info.files_processed,
+                info.files_extracted,
+                info.bytes_extracted,
+                info.files_skipped
+            )),
+        }
+    }
+
+    /// Reports a file that was read successfully while testing a HOG file.
+    pub fn tested(
+        &self,
+        out: &mut dyn Write,
+        archive: &Path,
+        name: &Path,
+        length: u32,
+    ) -> io::Result<()> {
+        match self.format {
+            Format::Human => writeln!(
+                out,
+                "  {}: {}: OK ({})",
+                archive.display(),
+                EscapedName(name),
+                self.size(u64::from(length))
+            ),
+            Format::Porcelain => writeln!(
+                out,
+                "tested\t{}\t{}\t{}",
+                escape_path(archive),
+                escape_path(name),
+                length
+            ),
+            Format::Json => Ok(()),
+        }
+    }
+
+    /// Reports the results of testing a HOG file. "error" is the problem that
+    /// stopped the test, if any.
+    ///
+    /// With --json, this is a JSON document listing every record that was
+    /// read. Otherwise, nothing is displayed if there was an error (it was
+    /// reported already).
+    pub fn test_summary(&self, archive: &Path, info: &HogTestInfo, error: Option<&ArchiveError>) {
+        match self.format {
+            Format::Json => {
+                let entries = info
+                    .entries
+                    .iter()
+                    .map(|x| TestEntryReport {
+                        index: x.index,
+                        offset: x.offset,
+                        name: x.name.as_ref().map(|x| x.to_string_lossy().into_owned()),
+                        length: x.length,
+                        status: if x.problem.is_some() { "failed" } else { "ok" },
+                        problem: x.problem,
+                    })
+                    .collect();
+
+                let errors = error
+                    .iter()
+                    .map(|e| TestErrorReport {
+                        error: error_report(archive, None, &e.error, e.entry_index, e.offset),
+                        remediation: remediation(&e.error),
+                    })
+                    .collect();
+
+                self.summary(&Summary::Test {
+                    archive: archive.to_string_lossy().into_owned(),
+                    status: if error.is_some() { "damaged" } else { "ok" },
+                    files_tested: info.summary.num_files,
+                    bytes_tested: info.summary.num_bytes,
+                    entries,
+                    errors,
+                });
+            }
+            _ if error.is_some() => {}
+            Format::Human => self.line(format_args!(
+                "{}: no errors detected in {} files ({}).",
+                archive.display(),
+                info.summary.num_files,
+                self.size(info.summary.num_bytes),
+            )),
+            Format::Porcelain => self.line(format_args!(
+                "test-summary\t{}\t{}\t{}",
+                escape_path(archive),
+                info.summary.num_files,
+                info.summary.num_bytes
+            )),
+        }
+    }
+
+    /// Reports a HOG file that failed testing, and was put into quarantine as
+    /// "dest".
+    pub fn quarantined(&self, archive: &Path, dest: &Path) {
+        match self.format {
+            Format::Human => self.line(format_args!(
+                "{}: put into quarantine as \"{}\".",
+                archive.display(),
+                dest.display()
+            )),
+            Format::Porcelain => self.line(format_args!(
+                "quarantined\t{}\t{}",
+                escape_path(archive),
+                escape_path(dest)
+            )),
+            Format::Json => self.summary(&Summary::Quarantine {
+                archive: archive.to_string_lossy().into_owned(),
+                path: dest.to_string_lossy().into_owned(),
+            }),
+        }
+    }
+
+    /// Reports that the name of "file" had non-ASCII characters, which were
+    /// replaced to store it as "name".
+    ///
+    /// In JSON mode nothing is displayed, the entries of the create summary
+    /// already have both names.
+    pub fn transliterated(&self, archive: &Path, file: &Path, name: &Path) {
+        match self.format {
+            Format::Human => self.line(format_args!(
+                "{}: storing \"{}\" as \"{}\".",
+                archive.display(),
+                file.display(),
+                EscapedName(name)
+            )),
+            Format::Porcelain => self.line(format_args!(
+                "transliterated\t{}\t{}\t{}",
+                escape_path(archive),
+                escape_path(file),
+                escape_path(name)
+            )),
+            Format::Json => {}
+        }
+    }
+
+    /// Reports a file that was added to a new HOG file, as "record". "total"
+    /// is the number of bytes added to the HOG file so far.
+    pub fn added(&self, archive: &Path, file: &Path, record: &HogRecord, total: u64) {
+        match self.format {
+            Format::Human if self.verbose => self.line(format_args!(
+                "{}: added file \"{}\" as \"{}\" ({}, {} total).",
+                archive.display(),
+                file.display(),
+                record,
+                self.size(u64::from(record.length)),
+                self.size(total)
+            )),
+            Format::Human => self.line(format_args!(
+                "{}: added file \"{}\" ({}).",
+                archive.display(),
+                file.display(),
+                self.size(u64::from(record.length)),
+            )),
+            Format::Json => {}
+            Format::Porcelain => self.line(format_args!(
+                "added\t{}\t{}\t{}",
+                escape_path(archive),
+                escape_path(file),
+                record.length
+            )),
+        }
+    }
+
+    /// Reports the totals for a new HOG file.
+    ///
+    /// In human readable mode, this is only displayed in verbose mode.
+    pub fn create_summary(&self, archive: &Path, info: &HogCreateInfo) {
+        match self.format {
+            Format::Json => {
+                let skipped: Vec<String> = info
+                    .entries
+                    .iter()
+                    .filter(|x| x.status == EntryStatus::Skipped)
+                    .map(|x| x.path.to_string_lossy().into_owned())
+                    .collect();
+
+                self.summary(&Summary::Create {
+                    archive: archive.to_string_lossy().into_owned(),
+                    files_added: info.files_added,
+                    bytes_added: info.bytes_added,
+                    files_skipped: info.files_skipped,
+                    files_failed: info.files_failed,
+                    self_checked: info.self_checked,
+                    entries: entry_reports(&info.entries),
+                    skipped,
+                    errors: info
+                        .errors
+                        .iter()
+                        .map(|(file, e)| error_report(archive, Some(file), e, None, None))
+                        .collect(),
+                });
+            }
+            Format::Human if self.verbose => self.line(format_args!(
+                "{}: added {} files ({}), skipped {} files, {} files failed.",
+                archive.display(),
+                info.files_added,
+                self.size(info.bytes_added),
+                info.files_skipped,
+                info.files_failed
+            )),
+            Format::Human => {}
+            Format::Porcelain => self.line(format_args!(
+                "create-summary\t{}\t{}\t{}\t{}\t{}",
+                escape_path(archive),
+                info.files_added,
+                info.bytes_added,
+                info.files_skipped,
+                info.files_failed
+            )),
+        }
+    }
+
+    pub fn self_checked(&self, archive: &Path, files: u64) {
+        match self.format {
+            Format::Human => self.line(format_args!(
+                "{}: self-check passed ({} files).",
+                archive.display(),
+                files
+            )),
+            Format::Json => {}
+            Format::Porcelain => self.line(format_args!(
+                "self-check\t{}\t{}",
+                escape_path(archive),
+                files
+            )),
+        }
+    }
+
+    /// Reports a file with the same contents as a file that was already added
+    /// to a new HOG file. "skipped" is true if the file was not added.
+    pub fn duplicate(&self, archive: &Path, file: &Path, original: &Path, skipped: bool) {
+        match self.format {
+            Format::Json if skipped => {}
+            Format::Human if skipped => self.line(format_args!(
+                "{}: skipped file \"{}\" (same contents as \"{}\").",
+                archive.display(),
+                file.display(),
+                original.display()
+            )),
+            Format::Human | Format::Json => {
+                let _ = self.warning(
+                    &mut io::stdout(),
+                    archive,
+                    &HogWarning::DuplicateContents(file.into(), original.into()),
+                );
+            }
+            Format::Porcelain => self.line(format_args!(
+                "duplicate\t{}\t{}\t{}\t{}",
+                escape_path(archive),
+                escape_path(file),
+                escape_path(original),
+                if skipped { "skipped" } else { "added" }
+            )),
+        }
+    }
+
+    /// Reports an asset used by a level that could not be found. "kind" is
+    /// the type of asset ("palette", "pig", "texture" or "bitmap", for which
+    /// "asset" is the number).
+    pub fn missing_asset(
+        &self,
+        out: &mut dyn Write,
+        archive: &Path,
+        level: &Path,
+        kind: &'static str,
+        asset: &str,
+    ) -> io::Result<()> {
+        match self.format {
+            Format::Json => {
+                let missing = MissingAssetReport {
+                    archive: archive.to_string_lossy().into_owned(),
+                    level: level.to_string_lossy().into_owned(),
+                    kind,
+                    asset: asset.to_string(),
+                };
+
+                writeln!(out, "{}", report::to_json(&missing))
+            }
+            Format::Human => writeln!(
+                out,
+                "  {}: {}: missing {} \"{}\"",
+                archive.display(),
+                EscapedName(level),
+                kind,
+                EscapedName(Path::new(asset))
+            ),
+            Format::Porcelain => writeln!(
+                out,
+                "missing\t{}\t{}\t{}\t{}",
+                escape_path(archive),
+                escape_path(level),
+                kind,
+                escape(asset)
+            ),
+        }
+    }
+
+    /// Reports how a level found while listing a HOG file pairs up with the
+    /// PIG file given with --with-pig ("pig_name"), and which of its textures
+    /// the POG file of the level replaces.
+    pub fn pig_pairing(
+        &self,
+        out: &mut dyn Write,
+        archive: &Path,
+        level: &Path,
+        pig_name: &str,
+        pairing: &LevelPairing,
+    ) -> io::Result<()> {
+        let pog = pairing.pog.as_deref().unwrap_or_default();
+
+        match self.format {
+            Format::Human | Format::Json => {
+                let replaced = match &pairing.pog {
+                    _ if !pairing.matches => format!(", not {}", EscapedName(Path::new(pig_name))),
+                    None => ", no custom textures".to_string(),
+                    Some(_) if pairing.unresolved() > 0 => format!(
+                        ", {} replaces {} of its textures ({} not in it)",
+                        EscapedName(Path::new(pog)),
+                        pairing.overrides.len(),
+                        pairing.unresolved()
+                    ),
+                    Some(_) => format!(
+                        ", {} replaces {} of its textures",
+                        EscapedName(Path::new(pog)),
+                        pairing.overrides.len()
+                    ),
+                };
+
+                writeln!(
+                    out,
+                    "  {}: {}: uses {}{}",
+                    archive.display(),
+                    EscapedName(level),
+                    EscapedName(Path::new(&pairing.pig)),
+                    replaced
+                )?;
+
+                for texture in &pairing.overrides {
+                    let replaces = match &texture.replaces {
+                        Some(x) => EscapedName(Path::new(x)).to_string(),
+                        None => format!("(not in {})", EscapedName(Path::new(pig_name))),
+                    };
+
+                    writeln!(
+                        out,
+                        "    | #{} {} <- {}",
+                        texture.index,
+                        replaces,
+                        EscapedName(Path::new(&texture.name))
+                    )?;
+                }
+
+                Ok(())
+            }
+            Format::Porcelain => {
+                writeln!(
+                    out,
+                    "pig-level\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                    escape_path(archive),
+                    escape_path(level),
+                    escape(&pairing.pig),
+                    if pairing.matches { "yes" } else { "no" },
+                    escape(pog),
+                    pairing.overrides.len(),
+                    pairing.unresolved()
+                )?;
+
+                for texture in &pairing.overrides {
+                    writeln!(
+                        out,
+                        "pig-texture\t{}\t{}\t{}\t{}\t{}",
+                        escape_path(archive),
+                        escape_path(level),
+                        texture.index,
+                        escape(&texture.name),
+                        escape(texture.replaces.as_deref().unwrap_or_default())
+                    )?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Reports the summary of the mission in a HOG file.
+    pub fn mission_info(&self, archive: &Path, info: &MissionInfo) {
+        let yes_no = |x: bool| if x { "yes" } else { "no" };
+
+        match self.format {
+            Format::Human => {
+                self.line(format_args!(
+                    "{}: {}",
+                    archive.display(),
+                    EscapedName(Path::new(
+                        info.name.as_deref().unwrap_or("(no mission file)")
+                    ))
+                ));
+                self.line(format_args!(
+                    "  levels: {} ({} secret)",
+                    info.levels, info.secret_levels
+                ));
+                self.line(format_args!("  briefing: {}", yes_no(info.briefing)));
+                self.line(format_args!(
+                    "  custom textures: {}",
+                    yes_no(info.custom_textures)
+                ));
+                self.line(format_args!(
+                    "  custom music: {}",
+                    yes_no(info.custom_music)
+                ));
+                self.line(format_args!(
+                    "  contains {} files ({}).",
+                    info.files,
+                    self.size(info.bytes)
+                ));
+            }
+            Format::Porcelain => self.line(format_args!(
+                "mission-info\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                escape_path(archive),
+                escape(info.name.as_deref().unwrap_or_default()),
+                info.levels,
+                info.secret_levels,
+                yes_no(info.briefing),
+                yes_no(info.custom_textures),
+                yes_no(info.custom_music),
+                info.files,
+                info.bytes
+            )),
+            Format::Json => self.summary(&Summary::MissionInfo {
+                archive: archive.to_string_lossy().into_owned(),
+                name: info.name.clone(),
+                levels: info.levels,
+                secret_levels: info.secret_levels,
+                briefing: info.briefing,
+                custom_textures: info.custom_textures,
+                custom_music: info.custom_music,
+                files: info.files,
+                bytes: info.bytes,
+            }),
+        }
+    }
+
+    /// Reports the files that differ between the HOG files "old" and "new".
+    pub fn diff(&self, old: &Path, new: &Path, diffs: &[EntryDiff]) {
+        let count = |change| diffs.iter().filter(|x| x.change == change).count();
+        let (added, removed, changed) = (
+            count(Change::Added),
+            count(Change::Removed),
+            count(Change::Changed),
+        );
+
+        match self.format {
+            Format::Human => {
+                for diff in diffs {
+                    let lengths = match (diff.old_length, diff.new_length) {
+                        (Some(old_len), Some(new_len)) => format!(
+                            "{} -> {}",
+                            self.size(old_len.into()),
+                            self.size(new_len.into())
+                        ),
+                        (Some(len), None) | (None, Some(len)) => self.size(len.into()),
+                        (None, None) => String::new(),
+                    };
+
+                    self.line(format_args!(
+                        "  {}: {} ({})",
+                        EscapedName(Path::new(&diff.name)),
+                        diff.change.name(),
+                        lengths
+                    ));
+
+                    if let Some(content) = &diff.content {
+                        self.line(format_args!(
+                            "    first difference at offset {}, {} bytes differ",
+                            content.first_offset, content.differing_bytes
+                        ));
+
+                        for line in content.text_diff.iter().flat_map(|x| x.lines()) {
+                            self.line(format_args!("    {}", EscapedName(Path::new(line))));
+                        }
+                    }
+                }
+
+                self.line(format_args!(
+                    "{} -> {}: {} added, {} removed, {} changed.",
+                    old.display(),
+                    new.display(),
+                    added,
+                    removed,
+                    changed
+                ));
+            }
+            Format::Porcelain => {
+                let length = |x: Option<u32>| x.map(|x| x.to_string()).unwrap_or_default();
+
+                for diff in diffs {
+                    self.line(format_args!(
+                        "diff\t{}\t{}\t{}\t{}\t{}\t{}",
+                        escape_path(old),
+                        escape_path(new),
+                        escape(&diff.name),
+                        diff.change.name(),
+                        length(diff.old_length),
+                        length(diff.new_length)
+                    ));
+
+                    if let Some(content) = &diff.content {
+                        self.line(format_args!(
+                            "content-diff\t{}\t{}\t{}\t{}\t{}",
+                            escape_path(old),
+                            escape_path(new),
+                            escape(&diff.name),
+                            content.first_offset,
+                            content.differing_bytes
+                        ));
+                    }
+                }
+
+                self.line(format_args!(
+                    "diff-summary\t{}\t{}\t{}\t{}\t{}",
+                    escape_path(old),
+                    escape_path(new),
+                    added,
+                    removed,
+                    changed
+                ));
+            }
+            Format::Json => {
+                let entries = diffs
+                    .iter()
+                    .map(|x| DiffEntryReport {
+                        name: x.name.clone(),
+                        change: x.change.name(),
+                        old_length: x.old_length,
+                        new_length: x.new_length,
+                        first_offset: x.content.as_ref().map(|x| x.first_offset),
+                        differing_bytes: x.content.as_ref().map(|x| x.differing_bytes),
+                        text_diff: x.content.as_ref().and_then(|x| x.text_diff.clone()),
+                    })
+                    .collect();
+
+                self.summary(&Summary::Diff {
+                    old: old.to_string_lossy().into_owned(),
+                    new: new.to_string_lossy().into_owned(),
+                    added,
+                    removed,
+                    changed,
+                    entries,
+                });
+            }
+        }
+    }
+
+    /// Reports the results of checking the assets used by the levels in a
+    /// HOG file.
+    pub fn check_summary(&self, archive: &Path, levels: u64, missing: u64) {
+        match self.format {
+            Format::Json => self.summary(&Summary::CheckAssets {
+                archive: archive.to_string_lossy().into_owned(),
+                levels,
+                missing,
+            }),
+            Format::Human => self.line(format_args!(
+                "{}: checked {} levels, {} missing assets.",
+                archive.display(),
+                levels,
+                missing
+            )),
+            Format::Porcelain => self.line(format_args!(
+                "check-summary\t{}\t{}\t{}",
+                escape_path(archive),
+                levels,
+                missing
+            )),
+        }
+    }
+
+    /// Reports a fixture HOG file that was generated with "entries" files,
+    /// and how and where it was broken, if it was.
+    pub fn fixture(
+        &self,
+        archive: &Path,
+        entries: u32,
+        corrupted: Option<(Corruption, (u32, u64))>,
+    ) {
+        match self.format {
+            Format::Human | Format::Json => {
+                let how = match corrupted {
+                    Some((corruption, (index, offset))) => format!(
+                        ", with {} in record {} (at offset {})",
+                        match corruption {
+                            Corruption::Truncate => "the contents cut short",
+                            Corruption::BadName => "a bad name",
+                            Corruption::BadLength => "a bad length",
+                        },
+                        index,
+                        offset
+                    ),
+                    None => String::new(),
+                };
+
+                self.line(format_args!(
+                    "{}: generated {} files{}.",
+                    archive.display(),
+                    entries,
+                    how
+                ))
+            }
+            Format::Porcelain => {
+                let (how, index, offset) = match corrupted {
+                    Some((corruption, (index, offset))) => (
+                        match corruption {
+                            Corruption::Truncate => "truncate",
+                            Corruption::BadName => "badname",
+                            Corruption::BadLength => "badlen",
+                        },
+                        index.to_string(),
+                        offset.to_string(),
+                    ),
+                    None => ("", String::new(), String::new()),
+                };
+
+                self.line(format_args!(
+                    "fixture\t{}\t{}\t{}\t{}\t{}",
+                    escape_path(archive),
+                    entries,
+                    how,
+                    index,
+                    offset
+                ))
+            }
+        }
+    }
+
+    /// Reports a file that was left out of a mission HOG file, because the
+    /// mission does not use it.
+    pub fn unreferenced(&self, archive: &Path, file: &Path) {
+        match self.format {
+            Format::Human | Format::Json => self.line(format_args!(
+                "{}: left out \"{}\" (not used by the mission).",
+                archive.display(),
+                file.display()
+            )),
+            Format::Porcelain => self.line(format_args!(
+                "unreferenced\t{}\t{}",
+                escape_path(archive),
+                escape_path(file)
+            )),
+        }
+    }
+
+    /// Reports the mission file written for a converted mission.
+    pub fn mission_written(&self, archive: &Path, path: &Path) {
+        match self.format {
+            Format::Human | Format::Json => self.line(format_args!(
+                "{}: wrote mission file \"{}\".",
+                archive.display(),
+                path.display()
+            )),
+            Format::Porcelain => self.line(format_args!(
+                "mission-file\t{}\t{}",
+                escape_path(archive),
+                escape_path(path)
+            )),
+        }
+    }
+
+    /// Reports work that remains to be done by hand on "file" of a converted
+    /// mission.
+    pub fn manual_step(&self, archive: &Path, file: &Path, step: &str) {
+        match self.format {
+            Format::Human | Format::Json => self.line(format_args!(
+                "{}: to do: {}: {}.",
+                archive.display(),
+                file.display(),
+                step
+            )),
+            Format::Porcelain => self.line(format_args!(
+                "to-do\t{}\t{}\t{}",
+                escape_path(archive),
+                escape_path(file),
+                escape(step)
+            )),
+        }
+    }
+
+    /// Reports that a HOG file was extracted to "dir" for repacking.
+    pub fn repack_extracted(&self, archive: &Path, dir: &Path) {
+        match self.format {
+            Format::Human | Format::Json => self.line(format_args!(
+                "{}: extracted to \"{}\", edit the files there and run the same command again to rebuild it.",
+                archive.display(),
+                dir.display()
+            )),
+            Format::Porcelain => self.line(format_args!(
+                "repack-extracted\t{}\t{}",
+                escape_path(archive),
+                escape_path(dir)
+            )),
+        }
+    }
+
+    /// Reports a file that is left out when repacking a HOG file, because it
+    /// was removed from the edit directory.
+    pub fn removed(&self, archive: &Path, file: &Path) {
+        match self.format {
+            Format::Human | Format::Json => self.line(format_args!(
+                "{}: leaving out \"{}\" (it was removed).",
+                archive.display(),
+                file.display()
+            )),
+            Format::Porcelain => self.line(format_args!(
+                "removed\t{}\t{}",
+                escape_path(archive),
+                escape_path(file)
+            )),
+        }
+    }
+
+    /// Reports the address a HOG file is served on by --serve.
+    pub fn serving(&self, archive: &Path, addr: &SocketAddr) {
+        match self.format {
+            Format::Human | Format::Json => self.line(format_args!(
+                "{}: serving on http://{}/ (press Ctrl-C to stop).",
+                archive.display(),
+                addr
+            )),
+            Format::Porcelain => {
+                self.line(format_args!("serving\t{}\t{}", escape_path(archive), addr))
+            }
+        }
+    }
+
+    /// Reports the socket the daemon is answering requests on.
+    pub fn daemon_listening(&self, socket: &Path) {
+        match self.format {
+            Format::Human | Format::Json => self.line(format_args!(
+                "{}: answering requests (press Ctrl-C to stop).",
+                socket.display()
+            )),
+            Format::Porcelain => self.line(format_args!("daemon\t{}", escape_path(socket))),
+        }
+    }
+
+    /// Reports a copy of a file looked up with --resolve. "shadowed" is true
+    /// for the copies that an earlier HOG file takes precedence over.
+    pub fn resolved(&self, resolution: &Resolution, shadowed: bool) {
+        match self.format {
+            Format::Human | Format::Json => self.line(format_args!(
+                "{}: {}: {} (index {}, {}){}",
+                resolution.requested_name,
+                resolution.archive.display(),
+                EscapedName(Path::new(&resolution.stored_name)),
+                resolution.index,
+                self.size(u64::from(resolution.length)),
+                if shadowed { ", shadowed" } else { "" }
+            )),
+            Format::Porcelain => self.line(format_args!(
+                "resolved\t{}\t{}\t{}\t{}\t{}\t{}",
+                escape(&resolution.requested_name),
+                escape_path(&resolution.archive),
+                resolution.index,
+                escape(&resolution.stored_name),
+                resolution.length,
+                if shadowed { "shadowed" } else { "used" }
+            )),
+        }
+    }
+
+    /// Reports a file looked up with --resolve that is in none of the HOG
+    /// files.
+    pub fn unresolved(&self, name: &str) {
+        match self.format {
+            Format::Human | Format::Json => self.line(format_args!("{}: not found.", name)),
+            Format::Porcelain => self.line(format_args!("unresolved\t{}", escape(name))),
+        }
+    }
+
+    /// Reports an error that stopped the processing of a HOG file (with the
+    /// record it happened at, in JSON mode).
+    pub fn archive_error(&self, archive: &Path, e: &ArchiveError) {
+        self.count(Severity::of(&e.error), 1);
+
+        match self.format {
+            Format::Human => eprintln!(
+                "error while processing HOG file \"{}\": {}",
+                archive.display(),
+                e.error
+            ),
+            Format::Porcelain => self.porcelain_error(archive, None, &e.error),
+            Format::Json => json_error(archive, None, &e.error, e.entry_index, e.offset),
+        }
+    }
+
+    /// Reports a warning about a HOG file. Warnings go to stderr, except in
+    /// porcelain mode, where (like errors) they are part of the regular
+    /// output.
+    pub fn warning(
+        &self,
+        out: &mut dyn Write,
+        archive: &Path,
+        warning: &HogWarning,
+    ) -> io::Result<()> {
+        self.warnings.fetch_add(1, Ordering::Relaxed);
+
+        match self.format {
+            Format::Human => {
+                eprintln!("warning: {}: {}", archive.display(), warning);
+
+                Ok(())
+            }
+            Format::Porcelain => writeln!(
+                out,
+                "warning\t{}\t{}\t{}",
+                escape_path(archive),
+                warning.name(),
+                escape(&warning.to_string())
+            ),
+            Format::Json => {
+                let warning = WarningReport::new(&archive.to_string_lossy(), warning);
+
+                eprintln!("{}", report::to_json(&warning));
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns the number of warnings reported so far.
+    pub fn warning_count(&self) -> u64 {
+        self.warnings.load(Ordering::Relaxed)
+    }
+
+    /// Reports an error creating a new HOG file.
+    pub fn create_error(&self, archive: &Path, e: &HogError) {
+        self.count(Severity::of(e), 1);
+
+        match self.format {
+            Format::Human => eprintln!(
+                "error creating output HOG file \"{}\": {}",
+                archive.display(),
+                e
+            ),
+            Format::Porcelain => self.porcelain_error(archive, None, e),
+            Format::Json => json_error(archive, None, e, None, None),
+        }
+    }
+
+    /// Reports an error with a single file of a HOG file (such as
+    /// --filter-cmd failing for it) that the other files are still processed
+    /// after.
+    pub fn entry_error(&self, archive: &Path, name: &Path, index: u64, e: &HogError) {
+        self.count(Severity::of_entry(e), 1);
+
+        match self.format {
+            Format::Human => eprintln!(
+                "error while processing \"{}\" in HOG file \"{}\": {}",
+                EscapedName(name),
+                archive.display(),
+                e
+            ),
+            Format::Porcelain => self.porcelain_error(archive, Some(name), e),
+            Format::Json => json_error(archive, Some(name), e, Some(index), None),
+        }
+    }
+
+    /// Reports an error adding a file to a new HOG file.
+    pub fn append_error(&self, archive: &Path, file: &Path, e: &HogError) {
+        self.count(Severity::of_entry(e), 1);
+
+        match self.format {
+            Format::Human => eprintln!(
+                "error occurred while appending \"{}\" to HOG file \"{}\": {}",
+                file.display(),
+                archive.display(),
+                e
+            ),
+            Format::Porcelain => self.porcelain_error(archive, Some(file), e),
+            Format::Json => json_error(archive, Some(file), e, None, None),
+        }
+    }
+
+    // Counts "count" problems of severity "severity" towards the exit status.
+    fn count(&self, severity: Severity, count: u64) {
+        self.outcome
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .add(severity, count);
+    }
+
+    /// Returns the status to exit with for the problems reported so far, and
+    /// reports it first if it is not 0. "stopped" is true if hogdump stops
+    /// because of a failure, so the status is 1 at least.
+    pub fn finish(&self, stopped: bool) -> i32 {
+        let outcome = self
+            .outcome
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        let mut severity = outcome.severity();
+
+        if stopped {
+            severity = severity.max(Severity::Failed);
+        }
+
+        if severity == Severity::Ok {
+            return 0;
+        }
+
+        match self.format {
+            Format::Human => {}
+            Format::Porcelain => self.line(format_args!(
+                "outcome\t{}\t{}\t{}\t{}\t{}\t{}",
+                severity.name(),
+                severity.exit_code(),
+                outcome.corrupt,
+                outcome.policy,
+                outcome.failed,
+                outcome.partial
+            )),
+            Format::Json => self.summary(&Summary::Outcome {
+                severity,
+                exit_code: severity.exit_code(),
+                corrupt: outcome.corrupt,
+                policy: outcome.policy,
+                failed: outcome.failed,
+                partial: outcome.partial,
+            }),
+        }
+
+        severity.exit_code()
+    }
+
+    /// Exits after a failure that stops hogdump (see finish()).
+    pub fn exit(&self) -> ! {
+        std::process::exit(self.finish(true))
+    }
+
+    // In porcelain mode errors are part of the regular output, so that
+    // scripts only have to read a single stream. The message itself is not
+    // covered by the stability guarantee.
+    fn porcelain_error(&self, archive: &Path, file: Option<&Path>, e: &HogError) {
+        self.line(format_args!(
+            "error\t{}\t{}\t{}",
+            escape_path(archive),
+            file.map(escape_path).unwrap_or_default(),
+            escape(&e.to_string())
+        ));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    use hogdump::bench::Benchmark;
+    use hogdump::diff::ContentDiff;
+    use hogdump::pig::TextureOverride;
+
+    use crate::HogExtractInfo;
+
+    // Runs "f" with a porcelain Reporter, and returns the lines it wrote: the
+    // ones written to the writer it is given first, then the ones written to
+    // stdout (sent to a --report file named after "test" here).
+    fn porcelain(test: &str, f: impl FnOnce(&Reporter, &mut Vec<u8>)) -> Vec<String> {
+        let path =
+            std::env::temp_dir().join(format!("hogdump-{}-porcelain-{}", std::process::id(), test));
+        let reporter = Reporter::new(Format::Porcelain, false, false)
+            .with_report(File::create(&path).unwrap());
+        let mut out = Vec::new();
+
+        f(&reporter, &mut out);
+        drop(reporter);
+
+        let report = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        [out, report]
+            .iter()
+            .flat_map(|x| {
+                String::from_utf8_lossy(x)
+                    .lines()
+                    .map(str::to_string)
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    // Returns the record types of the porcelain output listed in the README.
+    fn readme_records() -> Vec<String> {
+        let readme = include_str!("../README.md");
+        let start = readme.find("## Porcelain Output").unwrap();
+        let end = start
+            + readme[start..]
+                .find("\n## ")
+                .unwrap_or(readme.len() - start);
+
+        readme[start..end]
+            .lines()
+            .filter_map(|x| x.strip_prefix("| `"))
+            .filter_map(|x| x.split('`').next())
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_porcelain() {
+        let a = Path::new("a.hog");
+        let b = Path::new("b.hog");
+        let name = Path::new("A.TXT");
+        let other = Path::new("B.TXT");
+        let level = Path::new("LEVEL01.RDL");
+        let planned = PlannedEntry {
+            index: 0,
+            name: name.into(),
+            length: 5,
+            path: "out/A.TXT".into(),
+            extract: true,
+            collision: None,
+        };
+        let mut header = RecordHeader {
+            offset: 3,
+            bytes: [0; hogdump::hog::HDR_LEN],
+            truncated: false,
+        };
+        header.bytes[..5].copy_from_slice(b"A.TXT");
+        header.bytes[NAME_LEN] = 5;
+        let empty = HogError::EmptyFile;
+        let eof = ArchiveError {
+            error: HogError::UnexpectedEof,
+            entry_index: Some(2),
+            offset: Some(40),
+        };
+
+        let mut lines = porcelain("list", |r, out| {
+            let mut types = TypeSummary::new();
+            types.add(ContentType::Txb, 5);
+            let summary = HogInfoSummary {
+                num_files: 1,
+                num_bytes: 5,
+                ..HogInfoSummary::new()
+            };
+
+            r.begin(out).unwrap();
+            r.listed(out, a, name, 5, None, Some(Integrity::Verified))
+                .unwrap();
+            r.preview(out, a, name, "hi").unwrap();
+            r.type_tree(out, a, &types).unwrap();
+            r.bad_record(out, a, &eof).unwrap();
+            r.record_header(out, a, Some(0), &header).unwrap();
+            r.warning(out, a, &HogWarning::EmptyFile(other.into()))
+                .unwrap();
+            r.list_summary(a, &summary);
+        });
+
+        lines.extend(porcelain("extract", |r, out| {
+            let info = HogExtractInfo {
+                files_processed: 2,
+                files_extracted: 1,
+                files_skipped: 1,
+                bytes_extracted: 5,
+                entries: Vec::new(),
+            };
+            let plan = ExtractPlan {
+                entries: vec![planned.clone()],
+                record_count: 1,
+            };
+
+            r.extracted(out, a, name, 5).unwrap();
+            r.skipped(out, a, other, SkipReason::Exists).unwrap();
+            r.linked(out, a, other, name).unwrap();
+            r.case_collision(out, a, Path::new("a.txt"), name, Path::new("a.txt.1"))
+                .unwrap();
+            r.case_collision(out, a, Path::new("a.txt"), name, name)
+                .unwrap();
+            r.case_collision(out, a, Path::new("a.txt"), name, Path::new("a.txt"))
+                .unwrap();
+            r.auto_suffixed(out, a, name, Path::new("A.TXT.1")).unwrap();
+            r.digest_mismatch(out, a, name, "00", "ff", false).unwrap();
+            r.planned(out, a, &planned).unwrap();
+            r.extract_summary(a, &info, None);
+            r.concatenated(a, Path::new("all.bin"), Path::new("all.map"), 1, 5);
+            r.listing_exported(a, Path::new("list.txt"), 1);
+            r.streamed(a, 1, 5);
+            r.plan_summary(a, &plan);
+        }));
+
+        lines.extend(porcelain("test", |r, out| {
+            let mut info = HogTestInfo::new();
+            info.summary.num_files = 1;
+            info.summary.num_bytes = 5;
+
+            r.tested(out, a, name, 5).unwrap();
+            r.test_summary(a, &info, None);
+            r.quarantined(a, Path::new("q/a.hog"));
+            r.archive_error(a, &eof);
+        }));
+
+        lines.extend(porcelain("create", |r, _| {
+            let mut info = HogCreateInfo::new();
+            info.files_added = 1;
+            info.bytes_added = 5;
+            info.files_skipped = 1;
+            info.files_failed = 1;
+            let record = HogRecord {
+                filename: name.into(),
+                length: 5,
+            };
+
+            r.transliterated(a, Path::new("\u{e9}.txt"), Path::new("e.txt"));
+            r.added(a, name, &record, 5);
+            r.duplicate(a, other, name, true);
+            r.append_error(a, other, &empty);
+            r.self_checked(a, 1);
+            r.create_summary(a, &info);
+            r.fixture(a, 3, Some((Corruption::Truncate, (1, 40))));
+            r.fixture(a, 3, None);
+            r.repack_extracted(a, Path::new("a.hog.d"));
+            r.removed(a, other);
+            r.finish(false);
+        }));
+
+        lines.extend(porcelain("mission", |r, out| {
+            let pairing = LevelPairing {
+                pig: "GROUPA.PIG".into(),
+                matches: true,
+                pog: Some("LEVEL01.POG".into()),
+                overrides: vec![
+                    TextureOverride {
+                        index: 1,
+                        name: "rock".into(),
+                        replaces: Some("rock".into()),
+                    },
+                    TextureOverride {
+                        index: 2,
+                        name: "new".into(),
+                        replaces: None,
+                    },
+                ],
+            };
+            let mut info = MissionInfo::new(None);
+            info.add_file("LEVEL01.RDL", 5);
+
+            r.missing_asset(out, a, level, "palette", "GROUPA.256")
+                .unwrap();
+            r.pig_pairing(out, a, level, "groupa.pig", &pairing)
+                .unwrap();
+            r.check_summary(a, 1, 1);
+            r.mission_info(a, &info);
+            r.unreferenced(a, other);
+            r.mission_written(a, Path::new("a.mn2"));
+            r.manual_step(a, other, "convert it");
+        }));
+
+        lines.extend(porcelain("other", |r, _| {
+            let diffs = [
+                EntryDiff {
+                    name: "A.TXT".into(),
+                    change: Change::Changed,
+                    old_length: Some(5),
+                    new_length: Some(6),
+                    content: Some(ContentDiff {
+                        first_offset: 2,
+                        differing_bytes: 4,
+                        text_diff: None,
+                    }),
+                },
+                EntryDiff {
+                    name: "B.TXT".into(),
+                    change: Change::Added,
+                    old_length: None,
+                    new_length: Some(7),
+                    content: None,
+                },
+            ];
+            let resolution = Resolution {
+                archive: a.into(),
+                index: 0,
+                stored_name: "A.TXT".into(),
+                requested_name: "a.txt".into(),
+                length: 5,
+            };
+            let result = BenchResult {
+                benchmark: Benchmark::Hash,
+                rounds: 3,
+                files: 1,
+                bytes: 5,
+                fastest: Duration::from_millis(1),
+                median: Duration::from_millis(2),
+            };
+
+            r.diff(a, b, &diffs);
+            r.resolved(&resolution, false);
+            r.resolved(&resolution, true);
+            r.unresolved("c.txt");
+            r.bench(a, &result);
+            r.totals(&HogTotals {
+                archives: 2,
+                files: 2,
+                bytes: 10,
+                errors: 1,
+            });
+            r.serving(a, &"127.0.0.1:8080".parse().unwrap());
+            r.daemon_listening(Path::new("hogdump.sock"));
+        }));
+
+        let expected = [
+            "hogdump-porcelain\t1".to_string(),
+            "entry\ta.hog\tA.TXT\t5".into(),
+            "integrity\ta.hog\tA.TXT\tverified".into(),
+            "preview\ta.hog\tA.TXT\thi".into(),
+            "tree\ta.hog\ttext\tTXB\t1\t5".into(),
+            format!("bad-record\ta.hog\t2\t40\t{}", eof.error),
+            "record-header\ta.hog\t3\t412e545854000000000000000005000000\t0\tA.TXT\t5\tok".into(),
+            format!(
+                "warning\ta.hog\tEmptyFile\t{}",
+                HogWarning::EmptyFile(other.into())
+            ),
+            "list-summary\ta.hog\t1\t5".into(),
+            "extracted\ta.hog\tA.TXT\t5".into(),
+            "skipped\ta.hog\tB.TXT\texists".into(),
+            "linked\ta.hog\tB.TXT\tA.TXT".into(),
+            "case-collision\ta.hog\ta.txt\tA.TXT\tsuffix\ta.txt.1".into(),
+            "case-collision\ta.hog\ta.txt\tA.TXT\tlast-wins\tA.TXT".into(),
+            "case-collision\ta.hog\ta.txt\tA.TXT\tkeep\ta.txt".into(),
+            "auto-suffix\ta.hog\tA.TXT\tA.TXT.1".into(),
+            "digest-mismatch\ta.hog\tA.TXT\t00\tff\tkept".into(),
+            "planned\ta.hog\tA.TXT\tout/A.TXT\t5\textract".into(),
+            "extract-summary\ta.hog\t2\t1\t5\t1".into(),
+            "concat\ta.hog\tall.bin\tall.map\t1\t5".into(),
+            "export-listing\ta.hog\tlist.txt\t1".into(),
+            "streamed\ta.hog\t1\t5".into(),
+            "plan-summary\ta.hog\t1\t5\t0".into(),
+            "tested\ta.hog\tA.TXT\t5".into(),
+            "test-summary\ta.hog\t1\t5".into(),
+            "quarantined\ta.hog\tq/a.hog".into(),
+            format!("error\ta.hog\t\t{}", eof.error),
+            "transliterated\ta.hog\t\u{e9}.txt\te.txt".into(),
+            "added\ta.hog\tA.TXT\t5".into(),
+            "duplicate\ta.hog\tB.TXT\tA.TXT\tskipped".into(),
+            format!("error\ta.hog\tB.TXT\t{}", empty),
+            "self-check\ta.hog\t1".into(),
+            "create-summary\ta.hog\t1\t5\t1\t1".into(),
+            "fixture\ta.hog\t3\ttruncate\t1\t40".into(),
+            "fixture\ta.hog\t3\t\t\t".into(),
+            "repack-extracted\ta.hog\ta.hog.d".into(),
+            "removed\ta.hog\tB.TXT".into(),
+            "outcome\tpolicy\t3\t0\t1\t0\t0".into(),
+            "missing\ta.hog\tLEVEL01.RDL\tpalette\tGROUPA.256".into(),
+            "pig-level\ta.hog\tLEVEL01.RDL\tGROUPA.PIG\tyes\tLEVEL01.POG\t2\t1".into(),
+            "pig-texture\ta.hog\tLEVEL01.RDL\t1\trock\trock".into(),
+            "pig-texture\ta.hog\tLEVEL01.RDL\t2\tnew\t".into(),
+            "check-summary\ta.hog\t1\t1".into(),
+            "mission-info\ta.hog\t\t1\t0\tno\tno\tno\t1\t5".into(),
+            "unreferenced\ta.hog\tB.TXT".into(),
+            "mission-file\ta.hog\ta.mn2".into(),
+            "to-do\ta.hog\tB.TXT\tconvert it".into(),
+            "diff\ta.hog\tb.hog\tA.TXT\tchanged\t5\t6".into(),
+            "content-diff\ta.hog\tb.hog\tA.TXT\t2\t4".into(),
+            "diff\ta.hog\tb.hog\tB.TXT\tadded\t\t7".into(),
+            "diff-summary\ta.hog\tb.hog\t1\t0\t1".into(),
+            "resolved\ta.txt\ta.hog\t0\tA.TXT\t5\tused".into(),
+            "resolved\ta.txt\ta.hog\t0\tA.TXT\t5\tshadowed".into(),
+            "unresolved\tc.txt".into(),
+            "bench\ta.hog\thash\t3\t1\t5\t2000000\t1000000".into(),
+            "total\t2\t2\t10\t1".into(),
+            "serving\ta.hog\t127.0.0.1:8080".into(),
+            "daemon\thogdump.sock".into(),
+        ];
+
+        assert_eq!(lines, expected);
+
+        // The record types covered above are the ones the README documents.
+        let mut covered: Vec<String> = expected[1..]
+            .iter()
+            .filter_map(|x| x.split('\t').next())
+            .map(str::to_string)
+            .collect();
+        let mut documented = readme_records();
+        covered.sort();
+        covered.dedup();
+        documented.sort();
+
+        assert_eq!(covered, documented);
+    }
+
+    #[test]
+    fn test_porcelain_escape() {
+        let a = Path::new("a\\b\t.hog");
+        let name = Path::new("x\ny\rz");
+
+        let lines = porcelain("escape", |r, out| {
+            r.listed(out, a, name, 5, None, None).unwrap();
+            r.preview(out, a, name, "one\ttwo\r\nthree\\").unwrap();
+            r.extracted(out, a, name, 5).unwrap();
+            r.unresolved("c\td.txt");
+            r.append_error(
+                a,
+                name,
+                &HogError::DuplicateHogFilename("p\tq".into(), "r".into()),
+            );
+        });
+
+        assert_eq!(
+            lines,
+            [
+                "entry\ta\\\\b\\t.hog\tx\\ny\\rz\t5".to_string(),
+                "preview\ta\\\\b\\t.hog\tx\\ny\\rz\tone\\ttwo\\r\\nthree\\\\".into(),
+                "extracted\ta\\\\b\\t.hog\tx\\ny\\rz\t5".into(),
+                "unresolved\tc\\td.txt".into(),
+                format!(
+                    "error\ta\\\\b\\t.hog\tx\\ny\\rz\t{}",
+                    escape(&HogError::DuplicateHogFilename("p\tq".into(), "r".into()).to_string())
+                ),
+            ]
+        );
+
+        // Escaping never changes the number of fields.
+        for line in &lines[..3] {
+            assert_eq!(line.split('\t').count(), 4);
+        }
+    }
+
+    #[test]
+    fn test_auto_suffixed_json() {
+        let reporter = Reporter::new(Format::Json, false, false);
+        let mut out = Vec::new();
+
+        reporter
+            .auto_suffixed(
+                &mut out,
+                Path::new("a.hog"),
+                Path::new("A.TXT"),
+                Path::new("out/A.TXT.1"),
+            )
+            .unwrap();
+
+        let line = String::from_utf8(out).unwrap();
+        assert!(line.contains("\"extracted_as\":\"out/A.TXT.1\""));
+        assert!(line.contains("\"name\":\"A.TXT\""));
+        assert!(line.ends_with('\n'));
+    }
+
+    #[test]
+    fn test_finish() {
+        let io_error = || HogError::ReadFailure(io::Error::other("x"));
+        let archive = Path::new("a.hog");
+        let file = Path::new("a.txt");
+
+        let reporter = Reporter::new(Format::Human, false, false);
+        assert_eq!(reporter.finish(false), 0);
+        assert_eq!(reporter.finish(true), 1);
+
+        // A file that failed is a partial failure, unless the HOG file is
+        // corrupt or a policy refused it.
+        reporter.append_error(archive, file, &io_error());
+        assert_eq!(reporter.finish(false), 4);
+        assert_eq!(reporter.finish(true), 1);
+
+        reporter.archive_error(archive, &ArchiveError::from(io_error()));
+        assert_eq!(reporter.finish(false), 1);
+
+        reporter.append_error(archive, file, &HogError::EmptyFile);
+        assert_eq!(reporter.finish(false), 3);
+
+        reporter.entry_error(archive, file, 0, &HogError::UnexpectedEof);
+        assert_eq!(reporter.finish(false), 2);
+        assert_eq!(reporter.finish(true), 2);
+    }
+}
@@ -0,0 +1,494 @@
+//
+// Copyright (c) 2022-2023 Nathaniel Houghton <nathan@brainwerk.org>
+//
+// Permission to use, copy, modify, and distribute this software for
+// any purpose with or without fee is hereby granted, provided that
+// the above copyright notice and this permission notice appear in all
+// copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL
+// WARRANTIES WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE
+// AUTHOR BE LIABLE FOR ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL
+// DAMAGES OR ANY DAMAGES WHATSOEVER RESULTING FROM LOSS OF USE, DATA
+// OR PROFITS, WHETHER IN AN ACTION OF CONTRACT, NEGLIGENCE OR OTHER
+// TORTIOUS ACTION, ARISING OUT OF OR IN CONNECTION WITH THE USE OR
+// PERFORMANCE OF THIS SOFTWARE.
+//
+
+use std::io::Read;
+
+use crate::error::HogError;
+
+// Signatures and versions of Descent 2 PIG files, and of the POG files that
+// replace some of their textures for a level.
+const PIG_SIGNATURE: &[u8; 4] = b"PPIG";
+const PIG_VERSION: u32 = 2;
+const POG_SIGNATURE: &[u8; 4] = b"DPOG";
+const POG_VERSION: u32 = 1;
+
+// Signature of Descent 2 HAM files, and the versions of them that are known
+// (version 2 has the offset of the sounds after the version).
+const HAM_SIGNATURE: &[u8; 4] = b"HAM!";
+const HAM_VERSIONS: [u32; 2] = [2, 3];
+
+// Size of the header describing each bitmap, in PIG and POG files alike.
+const BITMAP_HEADER_LEN: usize = 18;
+
+// Set in the flags of a bitmap that is a frame of an animation, along with the
+// frame number.
+const FLAG_ANIMATED: u8 = 0x40;
+const FRAME_MASK: u8 = 0x3f;
+
+// Most bitmaps and textures the game can load. Counts past these are from
+// damaged files.
+const MAX_BITMAPS: usize = 2620;
+const MAX_TEXTURES: usize = 1200;
+
+/// Most bytes at the start of a PIG file that PigFile::read() reads: the
+/// signature, version and count, then the header of each bitmap.
+pub const MAX_PIG_HEADER_LEN: u64 = (12 + MAX_BITMAPS * BITMAP_HEADER_LEN) as u64;
+
+/// Most bytes at the start of a HAM file that HamFile::read() reads: the
+/// signature and version (and the offset of the sounds, in version 2), then
+/// the count and the texture table.
+pub const MAX_HAM_HEADER_LEN: u64 = (16 + MAX_TEXTURES * 2) as u64;
+
+/// Most bytes at the start of a POG file that PogFile::read() reads: the
+/// signature, version and count, then the number and the header of each
+/// bitmap. The bitmaps themselves come after them.
+pub const MAX_POG_HEADER_LEN: u64 = (12 + MAX_BITMAPS * (2 + BITMAP_HEADER_LEN)) as u64;
+
+/// The header of a bitmap in a PIG or POG file.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Bitmap {
+    /// Name of the bitmap, with "#" and the frame number after it for frames
+    /// of animations (like "door01#3").
+    pub name: String,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Bitmap {
+    fn parse(data: &[u8]) -> Bitmap {
+        let name_len = data[..8].iter().position(|&x| x == 0).unwrap_or(8);
+        let mut name = String::from_utf8_lossy(&data[..name_len]).into_owned();
+        let flags = data[8];
+
+        if flags & FLAG_ANIMATED != 0 {
+            name.push_str(&format!("#{}", flags & FRAME_MASK));
+        }
+
+        Bitmap {
+            name,
+            width: u16::from(data[9]) + (u16::from(data[11] & 0x0f) << 8),
+            height: u16::from(data[10]) + (u16::from(data[11] & 0xf0) << 4),
+        }
+    }
+}
+
+// Reads the signature, version and count at the start of a PIG or POG file.
+fn read_start(
+    reader: &mut impl Read,
+    signature: &[u8; 4],
+    version: u32,
+    invalid: impl Fn(String) -> HogError,
+) -> Result<usize, HogError> {
+    let mut start = [0; 12];
+
+    reader
+        .read_exact(&mut start)
+        .map_err(|_| invalid("header is truncated".to_string()))?;
+
+    if &start[..4] != signature {
+        return Err(invalid(format!(
+            "missing {} signature",
+            String::from_utf8_lossy(signature)
+        )));
+    }
+
+    let found = u32::from_le_bytes([start[4], start[5], start[6], start[7]]);
+
+    if found != version {
+        return Err(invalid(format!("unknown version {}", found)));
+    }
+
+    let count = u32::from_le_bytes([start[8], start[9], start[10], start[11]]) as usize;
+
+    if count > MAX_BITMAPS {
+        return Err(invalid(format!("too many bitmaps ({})", count)));
+    }
+
+    Ok(count)
+}
+
+// Reads "count" bitmap headers.
+fn read_bitmaps(
+    reader: &mut impl Read,
+    count: usize,
+    invalid: impl Fn(String) -> HogError,
+) -> Result<Vec<Bitmap>, HogError> {
+    let mut data = vec![0; count * BITMAP_HEADER_LEN];
+
+    reader
+        .read_exact(&mut data)
+        .map_err(|_| invalid("bitmap headers are truncated".to_string()))?;
+
+    Ok(data.chunks(BITMAP_HEADER_LEN).map(Bitmap::parse).collect())
+}
+
+/// The bitmaps in a Descent 2 PIG file (like groupa.pig), which hold the
+/// textures of the levels that use it. Only the headers are read, not the
+/// bitmaps themselves.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PigFile {
+    bitmaps: Vec<Bitmap>,
+}
+
+impl PigFile {
+    /// Reads the bitmap headers from the start of a PIG file.
+    pub fn read(reader: &mut impl Read) -> Result<Self, HogError> {
+        let count = read_start(reader, PIG_SIGNATURE, PIG_VERSION, HogError::InvalidPig)?;
+        let bitmaps = read_bitmaps(reader, count, HogError::InvalidPig)?;
+
+        Ok(Self { bitmaps })
+    }
+
+    /// Returns the bitmap with the number (as used by POG files, starting from
+    /// 1) "index".
+    pub fn bitmap(&self, index: u16) -> Option<&Bitmap> {
+        self.bitmaps.get(usize::from(index).checked_sub(1)?)
+    }
+
+    /// Returns the number of bitmaps in the PIG file.
+    pub fn len(&self) -> usize {
+        self.bitmaps.len()
+    }
+
+    /// Returns true if the PIG file has no bitmaps.
+    pub fn is_empty(&self) -> bool {
+        self.bitmaps.is_empty()
+    }
+}
+
+/// The custom textures of a Descent 2 level, stored in a POG file named after
+/// it. Each one replaces a bitmap of the PIG file the level uses, by number.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PogFile {
+    /// The bitmaps in the POG file, along with the number of the bitmap in
+    /// the PIG file that each one replaces.
+    pub replacements: Vec<(u16, Bitmap)>,
+}
+
+impl PogFile {
+    /// Reads the headers of the bitmaps in a POG file.
+    pub fn read(reader: &mut impl Read) -> Result<Self, HogError> {
+        let count = read_start(reader, POG_SIGNATURE, POG_VERSION, HogError::InvalidPog)?;
+        let mut indexes = vec![0; count * 2];
+
+        reader
+            .read_exact(&mut indexes)
+            .map_err(|_| HogError::InvalidPog("bitmap numbers are truncated".to_string()))?;
+
+        let bitmaps = read_bitmaps(reader, count, HogError::InvalidPog)?;
+        let replacements = indexes
+            .chunks(2)
+            .map(|x| u16::from_le_bytes([x[0], x[1]]))
+            .zip(bitmaps)
+            .collect();
+
+        Ok(Self { replacements })
+    }
+
+    /// Returns true if the POG file replaces the bitmap with the number
+    /// "index" (see PigFile::bitmap()).
+    pub fn replaces(&self, index: u16) -> bool {
+        self.replacements.iter().any(|x| x.0 == index)
+    }
+}
+
+/// The texture table of a Descent 2 HAM file (like descent2.ham). Levels refer
+/// to textures by their number in it, and each one shows a bitmap of the PIG
+/// file the level uses. Only the table is read, not the rest of the file.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct HamFile {
+    textures: Vec<u16>,
+}
+
+impl HamFile {
+    /// Reads the texture table from the start of a HAM file.
+    pub fn read(reader: &mut impl Read) -> Result<Self, HogError> {
+        let truncated = |_| HogError::InvalidHam("header is truncated".to_string());
+        let mut start = [0; 8];
+
+        reader.read_exact(&mut start).map_err(truncated)?;
+
+        if &start[..4] != HAM_SIGNATURE {
+            return Err(HogError::InvalidHam("missing HAM! signature".to_string()));
+        }
+
+        let version = u32::from_le_bytes([start[4], start[5], start[6], start[7]]);
+
+        if !HAM_VERSIONS.contains(&version) {
+            return Err(HogError::InvalidHam(format!("unknown version {}", version)));
+        }
+
+        let mut count = [0; 4];
+
+        if version < 3 {
+            reader.read_exact(&mut count).map_err(truncated)?;
+        }
+
+        reader.read_exact(&mut count).map_err(truncated)?;
+
+        let count = u32::from_le_bytes(count) as usize;
+
+        if count > MAX_TEXTURES {
+            return Err(HogError::InvalidHam(format!(
+                "too many textures ({})",
+                count
+            )));
+        }
+
+        let mut data = vec![0; count * 2];
+
+        reader
+            .read_exact(&mut data)
+            .map_err(|_| HogError::InvalidHam("texture table is truncated".to_string()))?;
+
+        let textures = data
+            .chunks(2)
+            .map(|x| u16::from_le_bytes([x[0], x[1]]))
+            .collect();
+
+        Ok(Self { textures })
+    }
+
+    /// Returns the number of the bitmap (see PigFile::bitmap()) that the
+    /// texture with the number "texture" shows, if there is such a texture.
+    pub fn bitmap(&self, texture: u16) -> Option<u16> {
+        self.textures.get(usize::from(texture)).copied()
+    }
+
+    /// Returns the number of textures in the HAM file.
+    pub fn len(&self) -> usize {
+        self.textures.len()
+    }
+
+    /// Returns true if the HAM file has no textures.
+    pub fn is_empty(&self) -> bool {
+        self.textures.is_empty()
+    }
+}
+
+/// Where a custom texture of a level comes from, when paired with a PIG file.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TextureOverride {
+    /// Number of the bitmap in the PIG file that is replaced.
+    pub index: u16,
+
+    /// Name of the replacement bitmap in the POG file.
+    pub name: String,
+
+    /// Name of the bitmap it replaces in the PIG file, or None if the PIG
+    /// file has no bitmap with that number (so the replacement is not used).
+    pub replaces: Option<String>,
+}
+
+/// Pairs the custom textures in "pog" with the bitmaps of "pig" that they
+/// replace.
+pub fn pair_textures(pig: &PigFile, pog: &PogFile) -> Vec<TextureOverride> {
+    pog.replacements
+        .iter()
+        .map(|(index, bitmap)| TextureOverride {
+            index: *index,
+            name: bitmap.name.clone(),
+            replaces: pig.bitmap(*index).map(|x| x.name.clone()),
+        })
+        .collect()
+}
+
+/// How a Descent 2 level pairs up with a PIG file.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct LevelPairing {
+    /// The PIG file the level uses (as named by the level).
+    pub pig: String,
+
+    /// Whether that is the PIG file it was paired with (the names are compared
+    /// without regard to case).
+    pub matches: bool,
+
+    /// The POG file with the custom textures of the level, if there is one.
+    pub pog: Option<String>,
+
+    /// The textures replaced by the POG file. This is empty if the level uses
+    /// another PIG file, since the bitmap numbers are then meaningless.
+    pub overrides: Vec<TextureOverride>,
+}
+
+impl LevelPairing {
+    /// Pairs a level that uses the PIG file "uses" with the PIG file
+    /// "pig_name" (whose bitmaps are "pig"), given the POG file found for the
+    /// level.
+    pub fn new(uses: &str, pig_name: &str, pig: &PigFile, pog: Option<(&str, &PogFile)>) -> Self {
+        let matches = uses.eq_ignore_ascii_case(pig_name);
+        let overrides = match pog {
+            Some((_, pog)) if matches => pair_textures(pig, pog),
+            _ => Vec::new(),
+        };
+
+        Self {
+            pig: uses.to_string(),
+            matches,
+            pog: pog.map(|x| x.0.to_string()),
+            overrides,
+        }
+    }
+
+    /// Returns the number of custom textures that replace no bitmap of the
+    /// PIG file.
+    pub fn unresolved(&self) -> usize {
+        self.overrides
+            .iter()
+            .filter(|x| x.replaces.is_none())
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn bitmap_header(name: &[u8], flags: u8, width: u16, height: u16) -> Vec<u8> {
+        let mut data = [0; BITMAP_HEADER_LEN].to_vec();
+        data[..name.len()].copy_from_slice(name);
+        data[8] = flags;
+        data[9] = width as u8;
+        data[10] = height as u8;
+        data[11] = ((width >> 8) as u8 & 0x0f) | ((height >> 4) as u8 & 0xf0);
+        data
+    }
+
+    fn pig_data() -> Vec<u8> {
+        let mut data = b"PPIG".to_vec();
+        data.extend(2u32.to_le_bytes());
+        data.extend(3u32.to_le_bytes());
+        data.extend(bitmap_header(b"rock083", 0, 64, 64));
+        data.extend(bitmap_header(b"door01", FLAG_ANIMATED | 2, 64, 64));
+        data.extend(bitmap_header(b"sky", 0, 320, 512));
+        data
+    }
+
+    #[test]
+    fn test_pig() {
+        let pig = PigFile::read(&mut pig_data().as_slice()).unwrap();
+
+        assert_eq!(pig.len(), 3);
+        assert_eq!(pig.bitmap(0), None);
+        assert_eq!(pig.bitmap(1).unwrap().name, "rock083");
+        assert_eq!(pig.bitmap(2).unwrap().name, "door01#2");
+        assert_eq!(
+            pig.bitmap(3),
+            Some(&Bitmap {
+                name: "sky".to_string(),
+                width: 320,
+                height: 512
+            })
+        );
+        assert_eq!(pig.bitmap(4), None);
+
+        let mut truncated = pig_data();
+        truncated.truncate(30);
+        assert!(matches!(
+            PigFile::read(&mut truncated.as_slice()),
+            Err(HogError::InvalidPig(_))
+        ));
+
+        assert!(matches!(
+            PigFile::read(&mut &b"DPOG\x01\0\0\0\0\0\0\0"[..]),
+            Err(HogError::InvalidPig(_))
+        ));
+    }
+
+    #[test]
+    fn test_ham() {
+        let mut data = b"HAM!".to_vec();
+        data.extend(3u32.to_le_bytes());
+        data.extend(2u32.to_le_bytes());
+        data.extend(7u16.to_le_bytes());
+        data.extend(1u16.to_le_bytes());
+
+        let ham = HamFile::read(&mut data.as_slice()).unwrap();
+        assert_eq!(ham.len(), 2);
+        assert_eq!(ham.bitmap(0), Some(7));
+        assert_eq!(ham.bitmap(1), Some(1));
+        assert_eq!(ham.bitmap(2), None);
+
+        // Version 2 has the offset of the sounds before the count.
+        let mut old = b"HAM!".to_vec();
+        old.extend(2u32.to_le_bytes());
+        old.extend(1000u32.to_le_bytes());
+        old.extend(&data[8..]);
+        assert_eq!(HamFile::read(&mut old.as_slice()).unwrap(), ham);
+
+        data.truncate(15);
+        assert!(matches!(
+            HamFile::read(&mut data.as_slice()),
+            Err(HogError::InvalidHam(_))
+        ));
+
+        assert!(matches!(
+            HamFile::read(&mut &b"HAM!\x09\0\0\0"[..]),
+            Err(HogError::InvalidHam(_))
+        ));
+    }
+
+    #[test]
+    fn test_pair_textures() {
+        let pig = PigFile::read(&mut pig_data().as_slice()).unwrap();
+
+        let mut data = b"DPOG".to_vec();
+        data.extend(1u32.to_le_bytes());
+        data.extend(2u32.to_le_bytes());
+        data.extend(2u16.to_le_bytes());
+        data.extend(900u16.to_le_bytes());
+        data.extend(bitmap_header(b"mydoor", FLAG_ANIMATED | 2, 64, 64));
+        data.extend(bitmap_header(b"extra", 0, 64, 64));
+
+        let pog = PogFile::read(&mut data.as_slice()).unwrap();
+        assert!(pog.replaces(2));
+        assert!(!pog.replaces(1));
+
+        assert_eq!(
+            pair_textures(&pig, &pog),
+            [
+                TextureOverride {
+                    index: 2,
+                    name: "mydoor#2".to_string(),
+                    replaces: Some("door01#2".to_string()),
+                },
+                TextureOverride {
+                    index: 900,
+                    name: "extra".to_string(),
+                    replaces: None,
+                },
+            ]
+        );
+
+        let pairing = LevelPairing::new("GROUPA.PIG", "groupa.pig", &pig, Some(("a.pog", &pog)));
+        assert!(pairing.matches);
+        assert_eq!(pairing.overrides.len(), 2);
+        assert_eq!(pairing.unresolved(), 1);
+
+        let pairing = LevelPairing::new("alien1.pig", "groupa.pig", &pig, Some(("a.pog", &pog)));
+        assert!(!pairing.matches);
+        assert_eq!(pairing.pog.as_deref(), Some("a.pog"));
+        assert!(pairing.overrides.is_empty());
+
+        data.truncate(data.len() - 1);
+        assert!(matches!(
+            PogFile::read(&mut data.as_slice()),
+            Err(HogError::InvalidPog(_))
+        ));
+    }
+}
@@ -0,0 +1,161 @@
+//
+// Copyright (c) 2022-2023 Nathaniel Houghton <nathan@brainwerk.org>
+//
+// Permission to use, copy, modify, and distribute this software for
+// any purpose with or without fee is hereby granted, provided that
+// the above copyright notice and this permission notice appear in all
+// copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL
+// WARRANTIES WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE
+// AUTHOR BE LIABLE FOR ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL
+// DAMAGES OR ANY DAMAGES WHATSOEVER RESULTING FROM LOSS OF USE, DATA
+// OR PROFITS, WHETHER IN AN ACTION OF CONTRACT, NEGLIGENCE OR OTHER
+// TORTIOUS ACTION, ARISING OUT OF OR IN CONNECTION WITH THE USE OR
+// PERFORMANCE OF THIS SOFTWARE.
+//
+
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+
+// A simple thread pool shared by all of the parallel parts of hogdump. Work is
+// handed out one item at a time, so a few large HOG files do not leave the
+// other worker threads idle.
+pub struct ThreadPool {
+    threads: NonZeroUsize,
+}
+
+impl ThreadPool {
+    /// Creates a thread pool with one thread per available CPU core.
+    ///
+    /// If the number of cores cannot be determined, a single thread is used.
+    pub fn new() -> Self {
+        let threads = thread::available_parallelism().unwrap_or(NonZeroUsize::MIN);
+
+        Self { threads }
+    }
+
+    /// Creates a thread pool with exactly "threads" threads.
+    pub fn with_threads(threads: NonZeroUsize) -> Self {
+        Self { threads }
+    }
+
+    /// Returns the number of threads used by the pool.
+    pub fn threads(&self) -> usize {
+        self.threads.get()
+    }
+
+    /// Calls "f" on every element of "items", returning the results in the
+    /// same order as "items".
+    ///
+    /// If the pool only has a single thread (or there is only a single item),
+    /// the work is done on the calling thread.
+    pub fn map<T, R, F>(&self, items: &[T], f: F) -> Vec<R>
+    where
+        T: Sync,
+        R: Send,
+        F: Fn(&T) -> R + Sync,
+    {
+        let workers = std::cmp::min(self.threads(), items.len());
+
+        if workers <= 1 {
+            return items.iter().map(f).collect();
+        }
+
+        let next = AtomicUsize::new(0);
+        let results: Mutex<Vec<Option<R>>> =
+            Mutex::new(std::iter::repeat_with(|| None).take(items.len()).collect());
+
+        thread::scope(|s| {
+            for _ in 0..workers {
+                s.spawn(|| loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+
+                    if i >= items.len() {
+                        break;
+                    }
+
+                    let result = f(&items[i]);
+
+                    results.lock().unwrap_or_else(PoisonError::into_inner)[i] = Some(result);
+                });
+            }
+        });
+
+        // Every item has a result by now: thread::scope() only returns once
+        // all the threads are done, and passes on their panics.
+        results
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    /// Like map(), but hands each element of "items" to "f" by value.
+    pub fn map_into<T, R, F>(&self, items: Vec<T>, f: F) -> Vec<R>
+    where
+        T: Send,
+        R: Send,
+        F: Fn(T) -> R + Sync,
+    {
+        let items: Vec<Mutex<Option<T>>> = items.into_iter().map(|x| Mutex::new(Some(x))).collect();
+
+        // Each element is taken exactly once, so every one has a result.
+        self.map(&items, |x| {
+            x.lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .take()
+                .map(&f)
+        })
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+}
+
+impl Default for ThreadPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_map_preserves_order() {
+        let items: Vec<u64> = (0..100).collect();
+
+        for threads in [1, 2, 7] {
+            let pool = ThreadPool::with_threads(NonZeroUsize::new(threads).unwrap());
+            let result = pool.map(&items, |x| x * 2);
+
+            assert_eq!(result, items.iter().map(|x| x * 2).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_map_into_preserves_order() {
+        let items: Vec<String> = (0..100).map(|x| x.to_string()).collect();
+        let pool = ThreadPool::with_threads(NonZeroUsize::new(4).unwrap());
+        let result = pool.map_into(items.clone(), |x| x + "!");
+
+        assert_eq!(
+            result,
+            items.iter().map(|x| format!("{x}!")).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_map_empty() {
+        let items: Vec<u64> = Vec::new();
+        let result = ThreadPool::new().map(&items, |x| x + 1);
+
+        assert!(result.is_empty());
+    }
+}
@@ -0,0 +1,499 @@
+//
+// Copyright (c) 2022-2023 Nathaniel Houghton <nathan@brainwerk.org>
+//
+// Permission to use, copy, modify, and distribute this software for
+// any purpose with or without fee is hereby granted, provided that
+// the above copyright notice and this permission notice appear in all
+// copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL
+// WARRANTIES WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE
+// AUTHOR BE LIABLE FOR ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL
+// DAMAGES OR ANY DAMAGES WHATSOEVER RESULTING FROM LOSS OF USE, DATA
+// OR PROFITS, WHETHER IN AN ACTION OF CONTRACT, NEGLIGENCE OR OTHER
+// TORTIOUS ACTION, ARISING OUT OF OR IN CONNECTION WITH THE USE OR
+// PERFORMANCE OF THIS SOFTWARE.
+//
+
+// The JSON documents hogdump writes with --json.
+//
+// Every document written on its own starts with the version of the schema it
+// follows (SCHEMA_VERSION), which is only bumped when a document changes in a
+// way that could break the programs reading it. Adding fields or documents
+// does not bump it. Objects nested in a document (such as the errors listed in
+// a summary) do not repeat the version.
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::error::{HogError, HogErrorKind, HogWarning};
+
+/// Version of the schema of the JSON documents.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Returns "document" as a JSON object, with its schema_version added. The
+/// keys are in alphabetical order.
+///
+/// The documents here are all objects. Anything else is returned without a
+/// schema_version (or as null, if it cannot be represented in JSON).
+pub fn to_json(document: &impl Serialize) -> Value {
+    let mut value = serde_json::to_value(document).unwrap_or_default();
+
+    if let Value::Object(map) = &mut value {
+        map.insert("schema_version".to_string(), SCHEMA_VERSION.into());
+    }
+
+    value
+}
+
+/// An error. "error" is the (stable) error name, the other fields are None if
+/// they do not apply.
+#[derive(Serialize, Clone, Debug)]
+pub struct ErrorReport {
+    pub error: &'static str,
+    pub message: String,
+    pub archive: String,
+
+    /// The input file being added when creating a HOG file.
+    pub file: Option<String>,
+
+    /// Index and header offset of the record being processed.
+    pub entry_index: Option<u64>,
+    pub offset: Option<u64>,
+}
+
+impl ErrorReport {
+    pub fn new(
+        archive: &str,
+        file: Option<&str>,
+        e: &HogError,
+        entry_index: Option<u64>,
+        offset: Option<u64>,
+    ) -> Self {
+        Self {
+            error: e.name(),
+            message: e.to_string(),
+            archive: archive.to_string(),
+            file: file.map(str::to_string),
+            entry_index,
+            offset,
+        }
+    }
+}
+
+/// An error found while testing a HOG file, with what can be done about it.
+#[derive(Serialize, Clone, Debug)]
+pub struct TestErrorReport {
+    #[serde(flatten)]
+    pub error: ErrorReport,
+    pub remediation: Option<&'static str>,
+}
+
+/// A warning. "warning" is the (stable) warning name.
+#[derive(Serialize, Clone, Debug)]
+pub struct WarningReport {
+    pub warning: &'static str,
+    pub message: String,
+    pub archive: String,
+}
+
+impl WarningReport {
+    pub fn new(archive: &str, warning: &HogWarning) -> Self {
+        Self {
+            warning: warning.name(),
+            message: warning.to_string(),
+            archive: archive.to_string(),
+        }
+    }
+}
+
+/// An asset used by a level that could not be found, written for each one
+/// by --check-assets.
+#[derive(Serialize, Clone, Debug)]
+pub struct MissingAssetReport {
+    pub archive: String,
+    pub level: String,
+
+    /// The type of asset ("palette", "pig", "texture" or "bitmap").
+    pub kind: &'static str,
+    pub asset: String,
+}
+
+/// A file extracted under another name by --auto-suffix, because a file
+/// already existed where it would have been extracted to.
+#[derive(Serialize, Clone, Debug)]
+pub struct AutoSuffixReport {
+    pub archive: String,
+    pub name: String,
+
+    /// The path the file was extracted to instead.
+    pub extracted_as: String,
+}
+
+/// A file found while listing a HOG file, written for each one with --json
+/// in verbose mode (and for the files that do not match their digests).
+#[derive(Serialize, Clone, Debug)]
+pub struct ListedEntryReport {
+    pub archive: String,
+    pub name: String,
+    pub length: u32,
+
+    /// The type guessed from the contents of the file, in verbose mode.
+    pub content_type: Option<&'static str>,
+
+    /// Whether the file matches its digest in the embedded metadata, if it is
+    /// listed there.
+    pub integrity: Option<&'static str>,
+}
+
+/// A file extracted from or added to a HOG file.
+#[derive(Serialize, Clone, Debug)]
+pub struct EntryReport {
+    /// Name in the HOG file, if it is known.
+    pub name: Option<String>,
+
+    /// Path on disk.
+    pub path: String,
+    pub status: &'static str,
+    pub bytes: u64,
+}
+
+/// A record read while testing a HOG file.
+#[derive(Serialize, Clone, Debug)]
+pub struct TestEntryReport {
+    pub index: u64,
+
+    /// Offset of the record header.
+    pub offset: u64,
+
+    /// Name and length of the file, None if the header could not be read.
+    pub name: Option<String>,
+    pub length: Option<u32>,
+    pub status: &'static str,
+
+    /// Name of the error found, if the record failed.
+    pub problem: Option<&'static str>,
+}
+
+/// A file that differs between two HOG files.
+#[derive(Serialize, Clone, Debug)]
+pub struct DiffEntryReport {
+    pub name: String,
+    pub change: &'static str,
+    pub old_length: Option<u32>,
+    pub new_length: Option<u32>,
+    pub first_offset: Option<u64>,
+    pub differing_bytes: Option<u64>,
+    pub text_diff: Option<String>,
+}
+
+/// A summary document, written once a HOG file (or all of them) is done. The
+/// "operation" field says which one it is.
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "operation", rename_all = "kebab-case")]
+pub enum Summary {
+    List {
+        archive: String,
+        files: u64,
+        bytes: u64,
+        bad_records: u64,
+        mismatches: u64,
+    },
+    Extract {
+        archive: String,
+        files_processed: u64,
+        files_extracted: u64,
+        bytes_extracted: u64,
+        files_skipped: u64,
+        entries: Vec<EntryReport>,
+        skipped: Vec<String>,
+        overwritten: Vec<String>,
+        errors: Vec<ErrorReport>,
+    },
+    Plan {
+        archive: String,
+        files: u64,
+        bytes: u64,
+        files_skipped: u64,
+        entries: Vec<EntryReport>,
+    },
+    Stream {
+        archive: String,
+        files: u64,
+        bytes: u64,
+    },
+    Concat {
+        archive: String,
+        path: String,
+        index: String,
+        files: u64,
+        bytes: u64,
+    },
+    ExportListing {
+        archive: String,
+        path: String,
+        files: u64,
+    },
+    Create {
+        archive: String,
+        files_added: u64,
+        bytes_added: u64,
+        files_skipped: u64,
+        files_failed: u64,
+        self_checked: bool,
+        entries: Vec<EntryReport>,
+        skipped: Vec<String>,
+        errors: Vec<ErrorReport>,
+    },
+    Test {
+        archive: String,
+        status: &'static str,
+        files_tested: u64,
+        bytes_tested: u64,
+        entries: Vec<TestEntryReport>,
+        errors: Vec<TestErrorReport>,
+    },
+    Quarantine {
+        archive: String,
+        path: String,
+    },
+    CheckAssets {
+        archive: String,
+        levels: u64,
+        missing: u64,
+    },
+    MissionInfo {
+        archive: String,
+        name: Option<String>,
+        levels: usize,
+        secret_levels: usize,
+        briefing: bool,
+        custom_textures: bool,
+        custom_music: bool,
+        files: u64,
+        bytes: u64,
+    },
+    Diff {
+        old: String,
+        new: String,
+        added: usize,
+        removed: usize,
+        changed: usize,
+        entries: Vec<DiffEntryReport>,
+    },
+    Bench {
+        archive: String,
+        benchmark: &'static str,
+        rounds: u32,
+        files: u64,
+        bytes: u64,
+        fastest_secs: f64,
+        median_secs: f64,
+        bytes_per_sec: f64,
+    },
+    Total {
+        archives: u64,
+        files: u64,
+        bytes: u64,
+        errors: u64,
+    },
+    Outcome {
+        severity: Severity,
+        exit_code: i32,
+        corrupt: u64,
+        policy: u64,
+        failed: u64,
+        partial: u64,
+    },
+}
+
+/// How bad a problem is, which decides the exit status of hogdump. The
+/// variants are in order, from the least severe to the most, and the most
+/// severe problem of a run is the one its exit status reports. The specific
+/// classes come before the generic Failed one, as they tell more.
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+    /// Nothing went wrong.
+    Ok,
+
+    /// Some of the files could not be handled (such as a file that failed to
+    /// convert, or could not be added), but the rest were.
+    Partial,
+
+    /// Something else failed, such as reading or writing a file.
+    Failed,
+
+    /// Something was refused by a rule that was chosen, such as the name
+    /// policy or a data limit.
+    Policy,
+
+    /// A HOG file (or another input file) is damaged.
+    Corrupt,
+}
+
+impl Severity {
+    /// Returns the severity of an error that stopped what was being done.
+    pub fn of(e: &HogError) -> Self {
+        match e.kind() {
+            HogErrorKind::Corrupt => Severity::Corrupt,
+            HogErrorKind::Policy => Severity::Policy,
+            _ => Severity::Failed,
+        }
+    }
+
+    /// Returns the severity of an error with a single file, after which the
+    /// other files were still handled.
+    pub fn of_entry(e: &HogError) -> Self {
+        match Self::of(e) {
+            Severity::Failed => Severity::Partial,
+            severity => severity,
+        }
+    }
+
+    /// Returns the name of the severity, as the --json and porcelain outcome
+    /// report it.
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Ok => "ok",
+            Severity::Partial => "partial",
+            Severity::Failed => "failed",
+            Severity::Policy => "policy",
+            Severity::Corrupt => "corrupt",
+        }
+    }
+
+    /// Returns the exit status hogdump exits with.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Severity::Ok => 0,
+            Severity::Failed => 1,
+            Severity::Corrupt => 2,
+            Severity::Policy => 3,
+            Severity::Partial => 4,
+        }
+    }
+}
+
+/// The problems found during a run, counted by severity.
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct RunOutcome {
+    pub corrupt: u64,
+    pub policy: u64,
+    pub failed: u64,
+    pub partial: u64,
+}
+
+impl RunOutcome {
+    /// Counts "count" problems of severity "severity".
+    pub fn add(&mut self, severity: Severity, count: u64) {
+        match severity {
+            Severity::Ok => {}
+            Severity::Partial => self.partial += count,
+            Severity::Failed => self.failed += count,
+            Severity::Policy => self.policy += count,
+            Severity::Corrupt => self.corrupt += count,
+        }
+    }
+
+    /// Returns the severity of the worst problem found.
+    pub fn severity(&self) -> Severity {
+        [
+            (self.corrupt, Severity::Corrupt),
+            (self.policy, Severity::Policy),
+            (self.failed, Severity::Failed),
+            (self.partial, Severity::Partial),
+        ]
+        .into_iter()
+        .find(|x| x.0 > 0)
+        .map_or(Severity::Ok, |x| x.1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_to_json() {
+        let summary = Summary::Total {
+            archives: 2,
+            files: 10,
+            bytes: 100,
+            errors: 1,
+        };
+
+        assert_eq!(
+            to_json(&summary).to_string(),
+            r#"{"archives":2,"bytes":100,"errors":1,"files":10,"operation":"total","schema_version":1}"#
+        );
+
+        let error = TestErrorReport {
+            error: ErrorReport::new("a.hog", None, &HogError::UnexpectedEof, Some(1), Some(20)),
+            remediation: None,
+        };
+        let value = to_json(&error);
+
+        assert_eq!(value["error"], "UnexpectedEof");
+        assert_eq!(value["entry_index"], 1);
+        assert_eq!(value["file"], Value::Null);
+        assert_eq!(value["remediation"], Value::Null);
+        assert_eq!(value["schema_version"], SCHEMA_VERSION);
+
+        let mission = Summary::MissionInfo {
+            archive: "m.hog".to_string(),
+            name: None,
+            levels: 1,
+            secret_levels: 0,
+            briefing: false,
+            custom_textures: false,
+            custom_music: false,
+            files: 1,
+            bytes: 8,
+        };
+
+        assert_eq!(to_json(&mission)["operation"], "mission-info");
+    }
+
+    #[test]
+    fn test_severity() {
+        let mut outcome = RunOutcome::default();
+        assert_eq!(outcome.severity(), Severity::Ok);
+
+        let filter = HogError::FilterFailed("false".to_string(), "exit status: 1".to_string());
+        assert_eq!(Severity::of(&filter), Severity::Failed);
+        assert_eq!(Severity::of_entry(&filter), Severity::Partial);
+        assert_eq!(
+            Severity::of_entry(&HogError::UnexpectedEof),
+            Severity::Corrupt
+        );
+
+        outcome.add(Severity::of_entry(&filter), 1);
+        assert_eq!(outcome.severity().exit_code(), 4);
+
+        outcome.add(Severity::Failed, 1);
+        assert_eq!(outcome.severity(), Severity::Failed);
+
+        outcome.add(
+            Severity::of(&HogError::InvalidDosFilename("a+b".to_string())),
+            1,
+        );
+        assert_eq!(outcome.severity().exit_code(), 3);
+
+        outcome.add(Severity::Corrupt, 2);
+        assert_eq!(outcome.severity().exit_code(), 2);
+        assert_eq!(outcome.corrupt, 2);
+
+        let value = to_json(&Summary::Outcome {
+            severity: outcome.severity(),
+            exit_code: 2,
+            corrupt: 2,
+            policy: 1,
+            failed: 1,
+            partial: 1,
+        });
+        assert_eq!(value["operation"], "outcome");
+        assert_eq!(value["severity"], "corrupt");
+    }
+}
@@ -0,0 +1,518 @@
+//
+// Copyright (c) 2022-2023 Nathaniel Houghton <nathan@brainwerk.org>
+//
+// Permission to use, copy, modify, and distribute this software for
+// any purpose with or without fee is hereby granted, provided that
+// the above copyright notice and this permission notice appear in all
+// copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL
+// WARRANTIES WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE
+// AUTHOR BE LIABLE FOR ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL
+// DAMAGES OR ANY DAMAGES WHATSOEVER RESULTING FROM LOSS OF USE, DATA
+// OR PROFITS, WHETHER IN AN ACTION OF CONTRACT, NEGLIGENCE OR OTHER
+// TORTIOUS ACTION, ARISING OUT OF OR IN CONNECTION WITH THE USE OR
+// PERFORMANCE OF THIS SOFTWARE.
+//
+
+use std::io::{self, BufRead, BufReader, Read, Seek, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use hogdump::error::HogError;
+use hogdump::hog::HogFileReader;
+use hogdump::image;
+use hogdump::index::IndexEntry;
+use hogdump::sniff::{self, ContentType};
+use hogdump::util::Limits;
+
+use crate::output::Reporter;
+
+// How long a client may take to send its request, or to take the response,
+// before it is dropped. Requests are answered one at a time, so a client that
+// stalls would otherwise hold up all the others.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Longest request line or header line accepted.
+const MAX_LINE_LEN: u64 = 8192;
+
+// Most header lines accepted in a request.
+const MAX_HEADERS: usize = 100;
+
+// A file in the HOG file being served. Only where it is is kept: its contents
+// are read from the HOG file when they are asked for.
+struct Entry {
+    index: IndexEntry,
+    content_type: ContentType,
+}
+
+// A response to a request.
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn new(status: &'static str, content_type: &'static str, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status,
+            content_type,
+            body: body.into(),
+        }
+    }
+
+    fn not_found(message: &str) -> Self {
+        Self::new("404 Not Found", "text/plain", format!("{}\n", message))
+    }
+}
+
+// Escapes text to be included in HTML.
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+// Percent encodes everything other than the characters that are always safe
+// in a URL path.
+fn encode_url(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+
+    for &x in text.as_bytes() {
+        if x.is_ascii_alphanumeric() || b"-._~".contains(&x) {
+            encoded.push(x as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", x));
+        }
+    }
+
+    encoded
+}
+
+// Decodes a percent encoded URL path. Returns None if it is malformed.
+fn decode_url(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(decoded).ok()
+}
+
+// Returns the MIME type to serve a file of the given type as.
+fn mime_type(content_type: ContentType) -> &'static str {
+    match content_type {
+        ContentType::Text => "text/plain",
+        ContentType::Pcx => "image/x-pcx",
+        ContentType::Bbm => "image/x-ilbm",
+        ContentType::Hmp => "audio/x-hmp",
+        _ => "application/octet-stream",
+    }
+}
+
+// Returns true if files of the given type have a preview: the decoded text of
+// TXB files, and images (as BMP files).
+fn has_preview(content_type: ContentType) -> bool {
+    matches!(
+        content_type,
+        ContentType::Txb | ContentType::Pcx | ContentType::Bbm
+    )
+}
+
+fn index_page(archive: &Path, entries: &[Entry]) -> String {
+    let title = escape_html(&archive.display().to_string());
+    let mut page = format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{0}</title></head>\n<body>\n<h1>{0}</h1>\n<table>\n<tr><th>Name</th><th>Length</th><th>Type</th><th></th></tr>\n",
+        title
+    );
+
+    for entry in entries {
+        let url = encode_url(&entry.index.name);
+        let preview = match has_preview(entry.content_type) {
+            true => format!("<a href=\"/preview/{}\">preview</a>", url),
+            false => String::new(),
+        };
+
+        page.push_str(&format!(
+            "<tr><td><a href=\"/files/{}\">{}</a></td><td align=\"right\">{}</td><td>{}</td><td>{}</td></tr>\n",
+            url,
+            escape_html(&entry.index.name),
+            entry.index.length,
+            entry.content_type,
+            preview
+        ));
+    }
+
+    page.push_str("</table>\n</body>\n</html>\n");
+    page
+}
+
+// Reads a line of a request into "line", failing if it is longer than
+// MAX_LINE_LEN. Returns the number of bytes read (0 at the end of the
+// request).
+fn read_line(reader: &mut impl BufRead, line: &mut String) -> io::Result<usize> {
+    let len = reader.take(MAX_LINE_LEN).read_line(line)?;
+
+    if len as u64 == MAX_LINE_LEN && !line.ends_with('\n') {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "request line too long",
+        ));
+    }
+
+    Ok(len)
+}
+
+// Reads a request, returning its method and target, or None if it is not a
+// request that can be answered.
+fn read_request(reader: &mut impl BufRead) -> io::Result<Option<(String, String)>> {
+    let mut request_line = String::new();
+
+    if read_line(reader, &mut request_line).is_err() {
+        return Ok(None);
+    }
+
+    // Skip the headers, none of them are needed.
+    for _ in 0..=MAX_HEADERS {
+        let mut line = String::new();
+
+        match read_line(reader, &mut line) {
+            Ok(0) => break,
+            Ok(_) if line.trim_end().is_empty() => break,
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => return Ok(None),
+            Err(e) => return Err(e),
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+
+    Ok(match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => Some((method.to_string(), target.to_string())),
+        _ => None,
+    })
+}
+
+// Answers a request for "target" (a request with another method than GET is
+// refused). Previews going past "limits" are refused, and images that cannot
+// be decoded are reported as such.
+fn respond<R: Read + Seek>(
+    method: &str,
+    target: &str,
+    archive: &Path,
+    hog_file: &mut HogFileReader<R>,
+    entries: &[Entry],
+    limits: &Limits,
+) -> Response {
+    if method != "GET" {
+        return Response::new(
+            "405 Method Not Allowed",
+            "text/plain",
+            "only GET is supported\n",
+        );
+    }
+
+    let path = target.split('?').next().unwrap_or_default();
+
+    if path == "/" {
+        return Response::new(
+            "200 OK",
+            "text/html; charset=utf-8",
+            index_page(archive, entries),
+        );
+    }
+
+    let (preview, name) = if let Some(name) = path.strip_prefix("/files/") {
+        (false, name)
+    } else if let Some(name) = path.strip_prefix("/preview/") {
+        (true, name)
+    } else {
+        return Response::not_found("not found");
+    };
+
+    let entry =
+        match decode_url(name).and_then(|name| entries.iter().find(|x| x.index.name == name)) {
+            Some(x) => x,
+            None => return Response::not_found("not found"),
+        };
+
+    if preview && !has_preview(entry.content_type) {
+        return Response::not_found("no preview available for this file");
+    }
+
+    let start = Instant::now();
+
+    let data = match hog_file.read_entry(&entry.index) {
+        Ok(x) => x,
+        Err(e) => {
+            return Response::new(
+                "500 Internal Server Error",
+                "text/plain",
+                format!("{}\n", e),
+            )
+        }
+    };
+
+    if preview {
+        if let Err(e) = limits.check(data.len() as u64, start.elapsed()) {
+            let e = HogError::LimitExceeded(e);
+
+            return Response::new("403 Forbidden", "text/plain", format!("{}\n", e));
+        }
+
+        if entry.content_type == ContentType::Txb {
+            let text: Vec<u8> = data.iter().map(|&x| sniff::decode_txb_byte(x)).collect();
+
+            return Response::new("200 OK", "text/plain", text);
+        }
+
+        match image::decode(entry.content_type, &data, limits) {
+            Some(Ok(image)) => Response::new("200 OK", "image/bmp", image.to_bmp()),
+            Some(Err(e @ HogError::LimitExceeded(_))) => {
+                Response::new("403 Forbidden", "text/plain", format!("{}\n", e))
+            }
+            Some(Err(e)) => {
+                Response::new("422 Unprocessable Entity", "text/plain", format!("{}\n", e))
+            }
+            None => Response::not_found("no preview available for this file"),
+        }
+    } else {
+        Response::new("200 OK", mime_type(entry.content_type), data)
+    }
+}
+
+fn write_response(stream: &mut TcpStream, response: &Response) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.0 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.content_type,
+        response.body.len()
+    )?;
+    stream.write_all(&response.body)?;
+    stream.flush()
+}
+
+// Handles a single request. Only GET requests are supported, and connections
+// are closed after every response.
+fn handle<R: Read + Seek>(
+    mut stream: TcpStream,
+    archive: &Path,
+    hog_file: &mut HogFileReader<R>,
+    entries: &[Entry],
+    limits: &Limits,
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let response = match read_request(&mut reader)? {
+        Some((method, target)) => respond(&method, &target, archive, hog_file, entries, limits),
+        None => Response::new("400 Bad Request", "text/plain", "bad request\n"),
+    };
+
+    write_response(&mut stream, &response)
+}
+
+// Returns the files of "hog_file", with their types.
+fn read_entries<R: Read + Seek>(hog_file: &mut HogFileReader<R>) -> Result<Vec<Entry>, HogError> {
+    let mut entries = Vec::new();
+    let mut iter = hog_file.records()?;
+
+    while let Some(record) = iter.next() {
+        let record = record?;
+        let start = iter.peek_cur_file(sniff::SNIFF_LEN as u64)?;
+
+        entries.push(Entry {
+            index: IndexEntry {
+                name: record.filename.to_string_lossy().into_owned(),
+                offset: iter.data_offset(),
+                length: record.length,
+            },
+            content_type: sniff::sniff(&start),
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Serves the files in a HOG file over HTTP at "addr", until interrupted.
+/// Decompressing the HOG file, and the previews of files, are bounded by
+/// "limits".
+pub fn serve(
+    archive: &Path,
+    addr: SocketAddr,
+    limits: Limits,
+    reporter: &Reporter,
+) -> Result<(), HogError> {
+    let mut hog_file = HogFileReader::open_limited(&archive, &limits)?;
+    let entries = read_entries(&mut hog_file)?;
+
+    let listener = TcpListener::bind(addr).map_err(HogError::ListenFailure)?;
+    let addr = listener.local_addr().map_err(HogError::ListenFailure)?;
+
+    reporter.serving(archive, &addr);
+
+    // A client going away mid-request should not stop the server.
+    for stream in listener.incoming().flatten() {
+        let _ = handle(stream, archive, &mut hog_file, &entries, &limits);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use hogdump::fixture;
+    use std::io::Cursor;
+
+    fn request(text: &str) -> Option<(String, String)> {
+        read_request(&mut Cursor::new(text.as_bytes())).unwrap()
+    }
+
+    #[test]
+    fn test_read_request() {
+        assert_eq!(
+            request("GET /files/a.txt HTTP/1.0\r\nHost: x\r\n\r\n"),
+            Some(("GET".to_string(), "/files/a.txt".to_string()))
+        );
+        assert_eq!(request("GET\r\n\r\n"), None);
+        assert_eq!(request(""), None);
+
+        // Lines that never end are cut off, rather than read into memory.
+        let long = format!(
+            "GET /{} HTTP/1.0\r\n\r\n",
+            "a".repeat(MAX_LINE_LEN as usize)
+        );
+        assert_eq!(request(&long), None);
+
+        let header = format!("GET / HTTP/1.0\r\nX: {}", "a".repeat(MAX_LINE_LEN as usize));
+        assert_eq!(request(&header), None);
+    }
+
+    #[test]
+    fn test_respond() {
+        let mut hog_file =
+            HogFileReader::from_reader(Cursor::new(fixture::generate(4, None).unwrap().data))
+                .unwrap();
+        let entries = read_entries(&mut hog_file).unwrap();
+        let archive = Path::new("f.hog");
+        let no_limits = Limits::default();
+        let mut get = |method: &str, target: &str| {
+            respond(method, target, archive, &mut hog_file, &entries, &no_limits)
+        };
+
+        let index = get("GET", "/");
+        assert_eq!(index.status, "200 OK");
+        assert!(String::from_utf8(index.body)
+            .unwrap()
+            .contains("/files/file0001.txt"));
+
+        let file = get("GET", "/files/file0003.bin?x=1");
+        assert_eq!(file.status, "200 OK");
+        assert_eq!(file.body, fixture::entry(3).1);
+
+        assert_eq!(
+            get("GET", "/files/file%30000.txt").body,
+            fixture::entry(0).1
+        );
+        assert_eq!(get("GET", "/files/nothere.txt").status, "404 Not Found");
+        assert_eq!(get("GET", "/files/bad%zz").status, "404 Not Found");
+        assert_eq!(get("GET", "/preview/file0000.txt").status, "404 Not Found");
+        assert_eq!(get("GET", "/other").status, "404 Not Found");
+        assert_eq!(get("POST", "/").status, "405 Method Not Allowed");
+    }
+
+    #[test]
+    fn test_preview_image() {
+        // A HOG file with a 1 by 1 PCX image, and a file that only looks like
+        // one.
+        let mut pcx = vec![0; 128];
+        pcx[..4].copy_from_slice(&[0x0a, 5, 1, 8]);
+        pcx[65] = 1;
+        pcx[66] = 1;
+        pcx.extend([7, 0x0c]);
+        pcx.extend([0; 768]);
+
+        let mut data = b"DHF".to_vec();
+        for (name, contents) in [(&b"a.pcx"[..], &pcx[..]), (b"b.pcx", b"\x0a\x05")] {
+            let mut field = [0; 13];
+            field[..name.len()].copy_from_slice(name);
+            data.extend(field);
+            data.extend((contents.len() as u32).to_le_bytes());
+            data.extend(contents);
+        }
+
+        let mut hog_file = HogFileReader::from_reader(Cursor::new(data)).unwrap();
+        let mut entries = read_entries(&mut hog_file).unwrap();
+        entries[1].content_type = ContentType::Pcx;
+
+        let archive = Path::new("f.hog");
+        let no_limits = Limits::default();
+        let mut get =
+            |target: &str| respond("GET", target, archive, &mut hog_file, &entries, &no_limits);
+
+        let preview = get("/preview/a.pcx");
+        assert_eq!(preview.status, "200 OK");
+        assert_eq!(preview.content_type, "image/bmp");
+        assert_eq!(&preview.body[..2], b"BM");
+
+        let broken = get("/preview/b.pcx");
+        assert_eq!(broken.status, "422 Unprocessable Entity");
+    }
+
+    #[test]
+    fn test_preview_limits() {
+        let mut hog_file =
+            HogFileReader::from_reader(Cursor::new(fixture::generate(1, None).unwrap().data))
+                .unwrap();
+        let mut entries = read_entries(&mut hog_file).unwrap();
+        let archive = Path::new("f.hog");
+        let len = fixture::entry(0).1.len() as u64;
+
+        entries[0].content_type = ContentType::Txb;
+
+        for (max_len, status) in [(len, "200 OK"), (len - 1, "403 Forbidden")] {
+            let limits = Limits {
+                max_len: Some(max_len),
+                max_time: None,
+            };
+            let preview = respond(
+                "GET",
+                "/preview/file0000.txt",
+                archive,
+                &mut hog_file,
+                &entries,
+                &limits,
+            );
+
+            assert_eq!(preview.status, status);
+        }
+    }
+}
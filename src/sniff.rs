@@ -0,0 +1,420 @@
+//
+// Copyright (c) 2022-2023 Nathaniel Houghton <nathan@brainwerk.org>
+//
+// Permission to use, copy, modify, and distribute this software for
+// any purpose with or without fee is hereby granted, provided that
+// the above copyright notice and this permission notice appear in all
+// copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL
+// WARRANTIES WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE
+// AUTHOR BE LIABLE FOR ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL
+// DAMAGES OR ANY DAMAGES WHATSOEVER RESULTING FROM LOSS OF USE, DATA
+// OR PROFITS, WHETHER IN AN ACTION OF CONTRACT, NEGLIGENCE OR OTHER
+// TORTIOUS ACTION, ARISING OUT OF OR IN CONNECTION WITH THE USE OR
+// PERFORMANCE OF THIS SOFTWARE.
+//
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// The number of bytes at the start of a file that sniff() looks at.
+pub const SNIFF_LEN: usize = 512;
+
+/// The type of a file stored in a HOG file, as guessed from its contents.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum ContentType {
+    /// PCX image
+    Pcx,
+    /// IFF image (BBM / LBM)
+    Bbm,
+    /// Descent 1 level
+    Rdl,
+    /// Descent 2 level
+    Rl2,
+    /// Polygon model
+    Pof,
+    /// Encoded text (briefings, credits, etc.)
+    Txb,
+    /// Plain text
+    Text,
+    /// HMI MIDI music
+    Hmp,
+    /// Font
+    Fnt,
+    /// Raw 8 bit unsigned PCM sound
+    Pcm,
+    Unknown,
+}
+
+impl ContentType {
+    /// Returns a short name for the content type.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ContentType::Pcx => "PCX",
+            ContentType::Bbm => "BBM",
+            ContentType::Rdl => "RDL",
+            ContentType::Rl2 => "RL2",
+            ContentType::Pof => "POF",
+            ContentType::Txb => "TXB",
+            ContentType::Text => "text",
+            ContentType::Hmp => "HMP",
+            ContentType::Fnt => "FNT",
+            ContentType::Pcm => "PCM",
+            ContentType::Unknown => "unknown",
+        }
+    }
+}
+
+/// A broad grouping of the files found in HOG files, used to sort extracted
+/// files into directories.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Category {
+    Levels,
+    Textures,
+    Models,
+    Music,
+    Sounds,
+    Fonts,
+    Text,
+    Other,
+}
+
+impl Category {
+    /// Returns the category of a file, based on its extension (ignoring case).
+    pub fn from_extension(ext: &str) -> Self {
+        match ext.to_ascii_lowercase().as_str() {
+            "rdl" | "rl2" | "sdl" => Category::Levels,
+            "pcx" | "bbm" | "lbm" | "256" | "pig" => Category::Textures,
+            "pof" => Category::Models,
+            "hmp" | "hmq" | "mid" => Category::Music,
+            "raw" | "dig" | "s11" | "s22" => Category::Sounds,
+            "fnt" => Category::Fonts,
+            "txb" | "txt" | "msn" | "mn2" | "ctb" | "tex" => Category::Text,
+            _ => Category::Other,
+        }
+    }
+
+    /// Returns the name of the directory files in this category go in.
+    pub fn dir_name(&self) -> &'static str {
+        match self {
+            Category::Levels => "levels",
+            Category::Textures => "textures",
+            Category::Models => "models",
+            Category::Music => "music",
+            Category::Sounds => "sounds",
+            Category::Fonts => "fonts",
+            Category::Text => "text",
+            Category::Other => "other",
+        }
+    }
+
+    /// Returns the name of the category, for display.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Category::Levels => "Levels",
+            Category::Textures => "Textures",
+            Category::Models => "Models",
+            Category::Music => "Music",
+            Category::Sounds => "Sounds",
+            Category::Fonts => "Fonts",
+            Category::Text => "Text",
+            Category::Other => "Unknown",
+        }
+    }
+}
+
+/// The number of files of each content type in a HOG file, and how many bytes
+/// they take up, grouped by category.
+#[derive(Default, Debug)]
+pub struct TypeSummary {
+    types: BTreeMap<(Category, ContentType), (u64, u64)>,
+}
+
+/// The files of one category in a TypeSummary.
+#[derive(PartialEq, Eq, Debug)]
+pub struct CategorySummary {
+    pub category: Category,
+    pub files: u64,
+    pub bytes: u64,
+
+    /// The content types of the files, with the number of files and bytes of
+    /// each one.
+    pub types: Vec<(ContentType, u64, u64)>,
+}
+
+impl TypeSummary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts a file of type "content_type" that is "length" bytes long.
+    pub fn add(&mut self, content_type: ContentType, length: u64) {
+        let (files, bytes) = self
+            .types
+            .entry((content_type.category(), content_type))
+            .or_default();
+
+        *files += 1;
+        *bytes += length;
+    }
+
+    /// Returns the categories that have files in them, in a fixed order
+    /// (levels first, unknown files last).
+    pub fn categories(&self) -> Vec<CategorySummary> {
+        let mut categories: Vec<CategorySummary> = Vec::new();
+
+        for (&(category, content_type), &(files, bytes)) in &self.types {
+            match categories.last_mut() {
+                Some(x) if x.category == category => {
+                    x.files += files;
+                    x.bytes += bytes;
+                    x.types.push((content_type, files, bytes));
+                }
+                _ => categories.push(CategorySummary {
+                    category,
+                    files,
+                    bytes,
+                    types: vec![(content_type, files, bytes)],
+                }),
+            }
+        }
+
+        categories
+    }
+}
+
+impl ContentType {
+    /// Returns the category that files of this type belong to.
+    pub fn category(&self) -> Category {
+        match self {
+            ContentType::Pcx | ContentType::Bbm => Category::Textures,
+            ContentType::Rdl | ContentType::Rl2 => Category::Levels,
+            ContentType::Pof => Category::Models,
+            ContentType::Txb | ContentType::Text => Category::Text,
+            ContentType::Hmp => Category::Music,
+            ContentType::Fnt => Category::Fonts,
+            ContentType::Pcm => Category::Sounds,
+            ContentType::Unknown => Category::Other,
+        }
+    }
+}
+
+impl fmt::Display for ContentType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Decodes a single byte of a TXB file. Newlines are stored as is, every
+/// other byte is bit rotated and xor'ed.
+pub fn decode_txb_byte(c: u8) -> u8 {
+    if c == b'\n' {
+        c
+    } else {
+        c.rotate_left(2) ^ 0xa7
+    }
+}
+
+// Returns true if "c" is something that is expected to show up in a text file.
+fn is_text_byte(c: u8) -> bool {
+    c.is_ascii_graphic() || c == b' ' || c == b'\n' || c == b'\r' || c == b'\t'
+}
+
+// Returns true if nearly all of "data" is text. A few odd bytes are allowed,
+// since Descent text files sometimes contain them.
+fn is_mostly_text(data: impl Iterator<Item = u8>) -> bool {
+    let (mut total, mut text) = (0, 0);
+
+    for c in data {
+        total += 1;
+
+        if is_text_byte(c) {
+            text += 1;
+        }
+    }
+
+    total > 0 && text * 100 >= total * 95
+}
+
+// Sound effects are stored as raw unsigned 8 bit samples. There is no header,
+// but the samples are centered around 0x80, and neighboring samples tend to be
+// close together.
+fn is_pcm(data: &[u8]) -> bool {
+    if data.len() < 256 {
+        return false;
+    }
+
+    let center: u64 = data.iter().map(|&x| u64::from(x.abs_diff(0x80))).sum();
+    let steps: u64 = data
+        .windows(2)
+        .map(|x| u64::from(x[0].abs_diff(x[1])))
+        .sum();
+    let len = data.len() as u64;
+
+    center / len < 48 && steps / (len - 1) < 16
+}
+
+/// Guesses the type of a file from the first bytes of its contents (up to
+/// SNIFF_LEN bytes).
+pub fn sniff(data: &[u8]) -> ContentType {
+    if data.len() >= 8 && data[0..4] == *b"LVLP" {
+        let version = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+
+        return if version == 1 {
+            ContentType::Rdl
+        } else {
+            ContentType::Rl2
+        };
+    }
+
+    if data.starts_with(b"HMIMIDIP") {
+        return ContentType::Hmp;
+    }
+
+    if data.starts_with(b"PSFN") {
+        return ContentType::Fnt;
+    }
+
+    if data.starts_with(b"PSPO") {
+        return ContentType::Pof;
+    }
+
+    if data.len() >= 12
+        && data[0..4] == *b"FORM"
+        && (data[8..12] == *b"PBM " || data[8..12] == *b"ILBM")
+    {
+        return ContentType::Bbm;
+    }
+
+    // Manufacturer, version, RLE encoding, bits per pixel.
+    if data.len() >= 128
+        && data[0] == 0x0a
+        && [0, 2, 3, 4, 5].contains(&data[1])
+        && data[2] == 1
+        && [1, 2, 4, 8].contains(&data[3])
+    {
+        return ContentType::Pcx;
+    }
+
+    if is_mostly_text(data.iter().copied()) {
+        return ContentType::Text;
+    }
+
+    if is_mostly_text(data.iter().map(|&x| decode_txb_byte(x))) {
+        return ContentType::Txb;
+    }
+
+    if is_pcm(data) {
+        return ContentType::Pcm;
+    }
+
+    ContentType::Unknown
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Encodes text the same way TXB files are.
+    fn encode_txb(text: &[u8]) -> Vec<u8> {
+        text.iter()
+            .map(|&c| {
+                if c == b'\n' {
+                    c
+                } else {
+                    (c ^ 0xa7).rotate_right(2)
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sniff() {
+        let mut pcx = vec![0x0a, 5, 1, 8];
+        pcx.resize(200, 0);
+        assert_eq!(sniff(&pcx), ContentType::Pcx);
+
+        assert_eq!(sniff(b"LVLP\x01\x00\x00\x00rest"), ContentType::Rdl);
+        assert_eq!(sniff(b"LVLP\x08\x00\x00\x00rest"), ContentType::Rl2);
+        assert_eq!(sniff(b"HMIMIDIP013195"), ContentType::Hmp);
+        assert_eq!(sniff(b"PSFN\x00\x10"), ContentType::Fnt);
+        assert_eq!(sniff(b"PSPO\x06\x00"), ContentType::Pof);
+        assert_eq!(sniff(b"FORM\x00\x00\x10\x00PBM BMHD"), ContentType::Bbm);
+        assert_eq!(
+            sniff(b"name = My Mission\nnum_levels = 1\n"),
+            ContentType::Text
+        );
+
+        let txb = encode_txb(b"$S1\nWelcome to the briefing, Material Defender.\n");
+        assert_eq!(sniff(&txb), ContentType::Txb);
+
+        let pcm: Vec<u8> = (0..1000)
+            .map(|i| (128.0 + 40.0 * (i as f64 / 10.0).sin()) as u8)
+            .collect();
+        assert_eq!(sniff(&pcm), ContentType::Pcm);
+
+        assert_eq!(sniff(&[]), ContentType::Unknown);
+        assert_eq!(
+            sniff(&[0xff, 0x00, 0x13, 0x99, 0x00, 0xfe]),
+            ContentType::Unknown
+        );
+    }
+
+    #[test]
+    fn test_category() {
+        assert_eq!(Category::from_extension("RDL"), Category::Levels);
+        assert_eq!(Category::from_extension("pcx"), Category::Textures);
+        assert_eq!(Category::from_extension("hmp"), Category::Music);
+        assert_eq!(Category::from_extension("Txb"), Category::Text);
+        assert_eq!(Category::from_extension("bin"), Category::Other);
+        assert_eq!(Category::from_extension(""), Category::Other);
+
+        assert_eq!(ContentType::Rl2.category(), Category::Levels);
+        assert_eq!(ContentType::Unknown.category().dir_name(), "other");
+    }
+
+    #[test]
+    fn test_type_summary() {
+        let mut summary = TypeSummary::new();
+        summary.add(ContentType::Unknown, 5);
+        summary.add(ContentType::Pcx, 100);
+        summary.add(ContentType::Rl2, 1000);
+        summary.add(ContentType::Bbm, 50);
+        summary.add(ContentType::Pcx, 20);
+
+        assert_eq!(
+            summary.categories(),
+            [
+                CategorySummary {
+                    category: Category::Levels,
+                    files: 1,
+                    bytes: 1000,
+                    types: vec![(ContentType::Rl2, 1, 1000)],
+                },
+                CategorySummary {
+                    category: Category::Textures,
+                    files: 3,
+                    bytes: 170,
+                    types: vec![(ContentType::Pcx, 2, 120), (ContentType::Bbm, 1, 50)],
+                },
+                CategorySummary {
+                    category: Category::Other,
+                    files: 1,
+                    bytes: 5,
+                    types: vec![(ContentType::Unknown, 1, 5)],
+                },
+            ]
+        );
+        assert!(TypeSummary::new().categories().is_empty());
+    }
+
+    #[test]
+    fn test_decode_txb_byte() {
+        let encoded = encode_txb(b"Descent\n");
+        let decoded: Vec<u8> = encoded.into_iter().map(decode_txb_byte).collect();
+
+        assert_eq!(decoded, b"Descent\n");
+    }
+}
@@ -0,0 +1,170 @@
+//
+// Copyright (c) 2022-2023 Nathaniel Houghton <nathan@brainwerk.org>
+//
+// Permission to use, copy, modify, and distribute this software for
+// any purpose with or without fee is hereby granted, provided that
+// the above copyright notice and this permission notice appear in all
+// copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL
+// WARRANTIES WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE
+// AUTHOR BE LIABLE FOR ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL
+// DAMAGES OR ANY DAMAGES WHATSOEVER RESULTING FROM LOSS OF USE, DATA
+// OR PROFITS, WHETHER IN AN ACTION OF CONTRACT, NEGLIGENCE OR OTHER
+// TORTIOUS ACTION, ARISING OUT OF OR IN CONNECTION WITH THE USE OR
+// PERFORMANCE OF THIS SOFTWARE.
+//
+
+use std::io::{self, Write};
+
+// Number of bytes on each line of the array.
+const BYTES_PER_LINE: usize = 12;
+
+// Keywords of C (up to C99), which cannot be used as the names of arrays. The
+// ones like _Bool are left out: an array name that starts with an underscore
+// always has a digit or another underscore after it.
+const C_KEYWORDS: &[&str] = &[
+    "auto", "break", "case", "char", "const", "continue", "default", "do", "double", "else",
+    "enum", "extern", "float", "for", "goto", "if", "inline", "int", "long", "register",
+    "restrict", "return", "short", "signed", "sizeof", "static", "struct", "switch", "typedef",
+    "union", "unsigned", "void", "volatile", "while",
+];
+
+/// Languages that file contents can be written out as source code for.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SourceLang {
+    /// A `static const unsigned char` array.
+    C,
+    /// A `pub static` `[u8; N]` array.
+    Rust,
+}
+
+impl SourceLang {
+    /// Returns the extension of source files in the language.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            SourceLang::C => "h",
+            SourceLang::Rust => "rs",
+        }
+    }
+}
+
+/// Returns the name of the array holding the contents of the file "name":
+/// the name with everything other than ASCII letters and digits replaced by
+/// underscores (like `xxd -i` does), in uppercase for Rust statics. Names that
+/// are C keywords get an underscore added to them.
+pub fn array_name(lang: SourceLang, name: &str) -> String {
+    let mut ident: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+
+    if !ident.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        ident.insert(0, '_');
+    }
+
+    match lang {
+        SourceLang::C if C_KEYWORDS.contains(&ident.as_str()) => ident + "_",
+        SourceLang::C => ident,
+        SourceLang::Rust => ident.to_ascii_uppercase(),
+    }
+}
+
+/// Writes "data" as the source code of an array named after the file "name"
+/// (see array_name()), which can be embedded into a program.
+///
+/// For C, the length of the file follows as a constant with "_len" added to
+/// the name, like `xxd -i` writes it. C arrays cannot be empty, so an empty
+/// file is an array of a single 0 byte, with a length of 0.
+pub fn write_source(
+    out: &mut impl Write,
+    lang: SourceLang,
+    name: &str,
+    data: &[u8],
+) -> io::Result<()> {
+    let ident = array_name(lang, name);
+
+    match lang {
+        SourceLang::C => writeln!(
+            out,
+            "static const unsigned char {}[{}] = {{",
+            ident,
+            data.len().max(1)
+        )?,
+        SourceLang::Rust => writeln!(out, "pub static {}: [u8; {}] = [", ident, data.len())?,
+    }
+
+    let bytes: &[u8] = match lang {
+        SourceLang::C if data.is_empty() => &[0],
+        _ => data,
+    };
+
+    for line in bytes.chunks(BYTES_PER_LINE) {
+        let bytes: Vec<String> = line.iter().map(|x| format!("0x{:02x},", x)).collect();
+
+        writeln!(out, "    {}", bytes.join(" "))?;
+    }
+
+    match lang {
+        SourceLang::C => {
+            writeln!(out, "}};")?;
+            writeln!(
+                out,
+                "static const unsigned int {}_len = {};",
+                ident,
+                data.len()
+            )
+        }
+        SourceLang::Rust => writeln!(out, "];"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_array_name() {
+        assert_eq!(array_name(SourceLang::C, "level01.rdl"), "level01_rdl");
+        assert_eq!(array_name(SourceLang::Rust, "level01.rdl"), "LEVEL01_RDL");
+        assert_eq!(array_name(SourceLang::C, "1st-try.txt"), "_1st_try_txt");
+        assert_eq!(array_name(SourceLang::C, "int"), "int_");
+        assert_eq!(array_name(SourceLang::C, "for.txt"), "for_txt");
+        assert_eq!(array_name(SourceLang::Rust, "int"), "INT");
+    }
+
+    #[test]
+    fn test_write_source() {
+        let mut c = Vec::new();
+        write_source(&mut c, SourceLang::C, "a.txt", b"hi").unwrap();
+        assert_eq!(
+            String::from_utf8(c).unwrap(),
+            "static const unsigned char a_txt[2] = {\n    0x68, 0x69,\n};\nstatic const unsigned int a_txt_len = 2;\n"
+        );
+
+        let mut rust = Vec::new();
+        write_source(&mut rust, SourceLang::Rust, "b.bin", &[7; 13]).unwrap();
+        assert_eq!(
+            String::from_utf8(rust).unwrap(),
+            format!(
+                "pub static B_BIN: [u8; 13] = [\n    {}\n    0x07,\n];\n",
+                ["0x07,"; 12].join(" ")
+            )
+        );
+
+        let mut empty = Vec::new();
+        write_source(&mut empty, SourceLang::Rust, "e", b"").unwrap();
+        assert_eq!(
+            String::from_utf8(empty).unwrap(),
+            "pub static E: [u8; 0] = [\n];\n"
+        );
+
+        let mut empty = Vec::new();
+        write_source(&mut empty, SourceLang::C, "e", b"").unwrap();
+        assert_eq!(
+            String::from_utf8(empty).unwrap(),
+            "static const unsigned char e[1] = {\n    0x00,\n};\nstatic const unsigned int e_len = 0;\n"
+        );
+    }
+}
@@ -0,0 +1,195 @@
+//
+// Copyright (c) 2022-2023 Nathaniel Houghton <nathan@brainwerk.org>
+//
+// Permission to use, copy, modify, and distribute this software for
+// any purpose with or without fee is hereby granted, provided that
+// the above copyright notice and this permission notice appear in all
+// copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL
+// WARRANTIES WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE
+// AUTHOR BE LIABLE FOR ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL
+// DAMAGES OR ANY DAMAGES WHATSOEVER RESULTING FROM LOSS OF USE, DATA
+// OR PROFITS, WHETHER IN AN ACTION OF CONTRACT, NEGLIGENCE OR OTHER
+// TORTIOUS ACTION, ARISING OUT OF OR IN CONNECTION WITH THE USE OR
+// PERFORMANCE OF THIS SOFTWARE.
+//
+
+use std::fmt;
+
+/// Largest table file that is looked into. The table files of Descent 3 and
+/// its add-ons are a few MiB, so this leaves plenty of room.
+pub const MAX_TABLE_LEN: u64 = 32 << 20;
+
+// Each page of a table file starts with its type (a byte), followed by its
+// length, counting the four bytes of the length itself.
+const PAGE_HDR_LEN: usize = 5;
+
+/// The kinds of definitions ("pages") a Descent 3 table file holds.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum PageType {
+    Texture,
+    Weapon,
+    Robot,
+    Powerup,
+    Door,
+    Ship,
+    Sound,
+    Megacell,
+    Gamefile,
+    Generic,
+}
+
+impl PageType {
+    /// Every page type, in the order of their numbers in a table file.
+    pub const ALL: [PageType; 10] = [
+        PageType::Texture,
+        PageType::Weapon,
+        PageType::Robot,
+        PageType::Powerup,
+        PageType::Door,
+        PageType::Ship,
+        PageType::Sound,
+        PageType::Megacell,
+        PageType::Gamefile,
+        PageType::Generic,
+    ];
+
+    fn from_byte(x: u8) -> Option<Self> {
+        Self::ALL.get(usize::from(x).checked_sub(1)?).copied()
+    }
+
+    /// Returns the name of the page type (in the plural), for display.
+    pub fn name(&self) -> &'static str {
+        match self {
+            PageType::Texture => "textures",
+            PageType::Weapon => "weapons",
+            PageType::Robot => "robots",
+            PageType::Powerup => "powerups",
+            PageType::Door => "doors",
+            PageType::Ship => "ships",
+            PageType::Sound => "sounds",
+            PageType::Megacell => "megacells",
+            PageType::Gamefile => "game files",
+            PageType::Generic => "generic objects",
+        }
+    }
+}
+
+/// How many definitions of each kind a Descent 3 table file (.gam) holds.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct TableInfo {
+    /// The number of pages of each type, for the types there are any of (in
+    /// the order of PageType::ALL).
+    pub pages: Vec<(PageType, u64)>,
+
+    /// Pages of a type this does not know about.
+    pub unknown: u64,
+}
+
+impl fmt::Display for TableInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "table file:")?;
+
+        let mut counts: Vec<String> = self
+            .pages
+            .iter()
+            .map(|(page, count)| format!("{} {}", count, page.name()))
+            .collect();
+
+        if self.unknown > 0 {
+            counts.push(format!("{} of unknown types", self.unknown));
+        }
+
+        if counts.is_empty() {
+            write!(f, " no pages")
+        } else {
+            write!(f, " {}", counts.join(", "))
+        }
+    }
+}
+
+/// Counts the pages of the table file "data". Returns None if it does not look
+/// like a table file: a page runs past the end of it, or claims to be shorter
+/// than its own header.
+pub fn parse_table(data: &[u8]) -> Option<TableInfo> {
+    let mut counts = [0u64; PageType::ALL.len()];
+    let mut info = TableInfo::default();
+    let mut rest = data;
+
+    while !rest.is_empty() {
+        let hdr = rest.get(..PAGE_HDR_LEN)?;
+        let len = u32::from_le_bytes([hdr[1], hdr[2], hdr[3], hdr[4]]) as usize;
+
+        // The length counts from the length itself.
+        if len < PAGE_HDR_LEN - 1 {
+            return None;
+        }
+
+        rest = rest.get(1 + len..)?;
+
+        match PageType::from_byte(hdr[0]) {
+            Some(page) => counts[page as usize] += 1,
+            None => info.unknown += 1,
+        }
+    }
+
+    info.pages = PageType::ALL
+        .iter()
+        .zip(counts)
+        .filter(|(_, count)| *count > 0)
+        .map(|(page, count)| (*page, count))
+        .collect();
+
+    Some(info)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn page(page_type: u8, body: &[u8]) -> Vec<u8> {
+        let mut page = vec![page_type];
+        page.extend((body.len() as u32 + 4).to_le_bytes());
+        page.extend(body);
+
+        page
+    }
+
+    #[test]
+    fn test_parse_table() {
+        let mut data = Vec::new();
+        data.extend(page(1, b"rock"));
+        data.extend(page(1, b"lava"));
+        data.extend(page(7, b""));
+        data.extend(page(10, b"drone"));
+        data.extend(page(42, b"?"));
+
+        let info = parse_table(&data).unwrap();
+        assert_eq!(
+            info.pages,
+            [
+                (PageType::Texture, 2),
+                (PageType::Sound, 1),
+                (PageType::Generic, 1)
+            ]
+        );
+        assert_eq!(info.unknown, 1);
+        assert_eq!(
+            info.to_string(),
+            "table file: 2 textures, 1 sounds, 1 generic objects, 1 of unknown types"
+        );
+
+        assert_eq!(
+            parse_table(&[]).unwrap().to_string(),
+            "table file: no pages"
+        );
+
+        // Pages running past the end, or shorter than their header.
+        assert_eq!(parse_table(&data[..data.len() - 1]), None);
+        assert_eq!(parse_table(&[1, 0, 0]), None);
+        assert_eq!(parse_table(&[1, 3, 0, 0, 0]), None);
+        assert_eq!(parse_table(&[1, 0xff, 0xff, 0xff, 0xff]), None);
+    }
+}
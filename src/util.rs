@@ -16,12 +16,25 @@
 // PERFORMANCE OF THIS SOFTWARE.
 //
 
-use std::io::{self, Error, ErrorKind, Read, Write};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Error, ErrorKind, IoSlice, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::time::{Duration, Instant};
+
+use regex::Regex;
+use sha2::{Digest, Sha256};
+
+use crate::error::LimitExceeded;
 
 /// Copies up to "n" bytes from reader to writer. If reader runs  out of bytes
 /// before "n" bytes have been transfered, or if "n" bytes are transferred, Ok
 /// is returned.
 ///
+/// This goes through std::io::copy(), so the platform specific fast paths it
+/// has (such as copy_file_range() between files on Linux, or writing straight
+/// into the buffer of a BufWriter) are used when possible.
+///
 /// If ErrorKind::Interrupted occurs during reading or writing, this function
 /// will retry.
 ///
@@ -32,31 +45,200 @@ where
     R: Read + ?Sized,
     W: Write + ?Sized,
 {
-    let mut buf = [0; 4096];
+    let limiter = match rate_limit() {
+        Some(x) => x,
+        None => return io::copy(&mut reader.take(n), writer),
+    };
+
+    // Throttled copies go in chunks, so that they can be paced.
+    let chunk_len = (limiter.bytes_per_sec / 8).clamp(4096, 1024 * 1024);
     let mut copied = 0;
 
     while copied < n {
-        let max_read: usize = std::cmp::min(n - copied, buf.len() as u64)
-            .try_into()
-            .unwrap();
+        let want = std::cmp::min(n - copied, chunk_len);
+        let len = io::copy(&mut (&mut *reader).take(want), writer)?;
 
-        match reader.read(&mut buf[0..max_read]) {
-            Ok(len) if len == 0 => {
-                return Ok(copied);
-            }
-            Ok(len) => {
-                writer.write_all(&buf[0..len])?;
+        limiter.throttle(len);
+        copied += len;
 
-                copied += u64::try_from(len).unwrap();
-            }
-            Err(e) if e.kind() == ErrorKind::Interrupted => {
-                continue;
+        if len < want {
+            break;
+        }
+    }
+
+    Ok(copied)
+}
+
+/// Paces the copies of a number of threads so that, together, they move at
+/// most a number of bytes per second.
+///
+/// Once set with set_rate_limit(), all of the copy functions in this module
+/// are paced by it, so that extracting or testing HOG files on shared storage
+/// does not take up all of its bandwidth.
+#[derive(Debug)]
+pub struct RateLimiter {
+    bytes_per_sec: u64,
+
+    // When the bytes copied so far would be done at the given rate.
+    next: Mutex<Option<Instant>>,
+}
+
+impl RateLimiter {
+    /// A rate of zero is taken as one byte per second.
+    pub fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            bytes_per_sec: bytes_per_sec.max(1),
+            next: Mutex::new(None),
+        }
+    }
+
+    /// Counts "len" more bytes as copied at "now", returning how long to wait
+    /// before copying anything else.
+    pub fn reserve(&self, len: u64, now: Instant) -> Duration {
+        let mut next = self.next.lock().unwrap_or_else(PoisonError::into_inner);
+
+        // Time spent not copying cannot be made up for by going faster later.
+        let start = next.filter(|&x| x > now).unwrap_or(now);
+        let end = start + Duration::from_secs_f64(len as f64 / self.bytes_per_sec as f64);
+
+        *next = Some(end);
+
+        end - now
+    }
+
+    /// Counts "len" more bytes as copied, and waits until copying them at
+    /// the given rate would be done.
+    pub fn throttle(&self, len: u64) {
+        let wait = self.reserve(len, Instant::now());
+
+        if !wait.is_zero() {
+            std::thread::sleep(wait);
+        }
+    }
+}
+
+static RATE_LIMIT: OnceLock<RateLimiter> = OnceLock::new();
+
+/// Limits the copy functions in this module to "bytes_per_sec" bytes per
+/// second, across all threads, for the rest of the process. Only the first
+/// call has any effect.
+pub fn set_rate_limit(bytes_per_sec: u64) {
+    let _ = RATE_LIMIT.set(RateLimiter::new(bytes_per_sec));
+}
+
+// Returns the rate limit set with set_rate_limit(), if any.
+fn rate_limit() -> Option<&'static RateLimiter> {
+    RATE_LIMIT.get()
+}
+
+/// Like copy_n(), but the data goes through "buf", which the caller can reuse
+/// across calls. Each read fills as much of "buf" as it can before anything is
+/// written, so that small reads do not turn into small writes.
+///
+/// Panics if "buf" is empty.
+pub fn copy_n_with_buf<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    n: u64,
+    buf: &mut [u8],
+) -> io::Result<u64>
+where
+    R: Read + ?Sized,
+    W: Write + ?Sized,
+{
+    copy_n_after(reader, writer, &[], n, buf)
+}
+
+/// Like copy_n_with_buf(), but writes "header" before the copied bytes. The
+/// header and the first chunk of copied bytes are handed to the writer
+/// together, with write_vectored(), so writers that support vectored writes
+/// (such as files) get them in a single call.
+pub fn copy_n_after<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    header: &[u8],
+    n: u64,
+    buf: &mut [u8],
+) -> io::Result<u64>
+where
+    R: Read + ?Sized,
+    W: Write + ?Sized,
+{
+    if buf.is_empty() {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            "copy buffer is empty",
+        ));
+    }
+
+    let mut header = header;
+    let mut copied = 0;
+
+    loop {
+        // No larger than the buffer, so it fits in a usize.
+        let want = std::cmp::min(n - copied, buf.len() as u64) as usize;
+        let len = read_up_to(reader, &mut buf[..want])?;
+
+        write_all_vectored(
+            writer,
+            &mut [IoSlice::new(header), IoSlice::new(&buf[..len])],
+        )?;
+
+        if let Some(limiter) = rate_limit() {
+            limiter.throttle((header.len() + len) as u64);
+        }
+
+        header = &[];
+        copied += len as u64;
+
+        if len < want || copied == n {
+            return Ok(copied);
+        }
+    }
+}
+
+// Reads until "buf" is full, or the reader runs out of bytes. Returns the
+// number of bytes read.
+fn read_up_to<R: Read + ?Sized>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(len) => filled += len,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(filled)
+}
+
+/// Writes all of "bufs" to writer, using write_vectored(). This is what the
+/// (unstable) Write::write_all_vectored() does.
+///
+/// If ErrorKind::Interrupted occurs during writing, this function will retry.
+pub fn write_all_vectored<W: Write + ?Sized>(
+    writer: &mut W,
+    mut bufs: &mut [IoSlice],
+) -> io::Result<()> {
+    IoSlice::advance_slices(&mut bufs, 0);
+
+    while !bufs.is_empty() {
+        match writer.write_vectored(bufs) {
+            Ok(0) => {
+                return Err(Error::new(
+                    ErrorKind::WriteZero,
+                    "failed to write whole buffer",
+                ))
             }
+            Ok(len) => IoSlice::advance_slices(&mut bufs, len),
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
             Err(e) => return Err(e),
         }
     }
 
-    Ok(copied)
+    Ok(())
 }
 
 /// Copies exactly "n" bytes from reader to writer. If reader runs out of bytes
@@ -82,6 +264,535 @@ where
     }
 }
 
+/// Copies the contents and permissions of the file "from" to "to", like
+/// std::fs::copy(), but paced by the rate limit like the other copy functions.
+/// Returns the number of bytes copied.
+pub fn copy_file(from: &Path, to: &Path) -> io::Result<u64> {
+    let mut input = File::open(from)?;
+    let permissions = input.metadata()?.permissions();
+    let mut output = File::create(to)?;
+    let copied = copy_n(&mut input, &mut output, u64::MAX)?;
+
+    output.set_permissions(permissions)?;
+
+    Ok(copied)
+}
+
+/// Calculates the SHA-256 digest of all of the remaining bytes in reader.
+/// Reading is paced by the rate limit, like the copy functions.
+///
+/// If ErrorKind::Interrupted occurs during reading, this function will retry.
+pub fn sha256<R>(reader: &mut R) -> io::Result<[u8; 32]>
+where
+    R: Read + ?Sized,
+{
+    let mut hasher = Sha256::new();
+    let mut buf = [0; 4096];
+
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(len) => {
+                hasher.update(&buf[0..len]);
+
+                if let Some(limiter) = rate_limit() {
+                    limiter.throttle(len as u64);
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(hasher.finalize().into())
+}
+
+/// A writer that calculates the SHA-256 digest of everything written through
+/// it, before passing it on to the inner writer.
+pub struct Sha256Writer<W> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> Sha256Writer<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+        }
+    }
+
+    /// Returns the digest of everything written so far, and the inner writer.
+    pub fn finish(self) -> ([u8; 32], W) {
+        (self.hasher.finalize().into(), self.inner)
+    }
+}
+
+impl<W: Write> Write for Sha256Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.inner.write(buf)?;
+
+        self.hasher.update(&buf[0..len]);
+
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A reader that calculates the SHA-256 digest of everything read through it
+/// from the inner reader.
+pub struct Sha256Reader<R> {
+    inner: R,
+    hasher: Sha256,
+}
+
+impl<R: Read> Sha256Reader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+        }
+    }
+
+    /// Returns the digest of everything read so far, and the inner reader.
+    pub fn finish(self) -> ([u8; 32], R) {
+        (self.hasher.finalize().into(), self.inner)
+    }
+}
+
+impl<R: Read> Read for Sha256Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+
+        self.hasher.update(&buf[0..len]);
+
+        Ok(len)
+    }
+}
+
+/// Bounds on converting a single thing, such as decompressing a HOG file or
+/// piping a file through a filter command: how much output it may produce,
+/// and how long it may take. These keep a crafted file from using up all of
+/// the memory, or keeping a service that processes HOG files busy forever.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Limits {
+    /// Most bytes of output.
+    pub max_len: Option<u64>,
+
+    /// Longest time it may take.
+    pub max_time: Option<Duration>,
+}
+
+impl Limits {
+    /// Returns an error if converting for "elapsed" so far, producing "len"
+    /// bytes, went past the limits.
+    pub fn check(&self, len: u64, elapsed: Duration) -> Result<(), LimitExceeded> {
+        if let Some(max_len) = self.max_len.filter(|&x| len > x) {
+            return Err(LimitExceeded::Size(max_len));
+        }
+
+        if let Some(max_time) = self.max_time.filter(|&x| elapsed > x) {
+            return Err(LimitExceeded::Time(max_time));
+        }
+
+        Ok(())
+    }
+
+    /// Returns a writer that writes to "inner" until the limits are reached,
+    /// and fails from then on. The time limit counts from now.
+    pub fn writer<W: Write>(&self, inner: W) -> LimitedWriter<W> {
+        LimitedWriter {
+            inner,
+            limits: *self,
+            len: 0,
+            start: Instant::now(),
+        }
+    }
+}
+
+/// A writer that fails once more than the limits allow was written through
+/// it (see Limits::writer()). The io::Error it fails with wraps the
+/// LimitExceeded, so that HogError::from_io() can tell it apart.
+pub struct LimitedWriter<W> {
+    inner: W,
+    limits: Limits,
+    len: u64,
+    start: Instant,
+}
+
+impl<W> LimitedWriter<W> {
+    /// Returns the inner writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Returns the inner writer, giving up the limits.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for LimitedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.limits
+            .check(self.len + buf.len() as u64, self.start.elapsed())
+            .map_err(io::Error::other)?;
+
+        let len = self.inner.write(buf)?;
+        self.len += len as u64;
+
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// Size of the blocks SparseWriter checks for zeros.
+const SPARSE_BLOCK_LEN: usize = 4096;
+
+/// A writer that skips over blocks of zeros by seeking past them, instead of
+/// writing them. On filesystems that support it, this leaves holes in the
+/// file that take up no disk space.
+///
+/// flush() must be called once everything is written. If the file ends with
+/// a hole, the last byte is written then, so that the file has the full
+/// length.
+pub struct SparseWriter<W> {
+    inner: W,
+    block: Vec<u8>,
+
+    // True if the last block was skipped over.
+    hole: bool,
+}
+
+impl<W: Write + Seek> SparseWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            block: Vec::with_capacity(SPARSE_BLOCK_LEN),
+            hole: false,
+        }
+    }
+
+    // Writes (or skips over) the buffered block.
+    fn write_block(&mut self) -> io::Result<()> {
+        if self.block.iter().all(|&x| x == 0) {
+            self.inner
+                .seek(SeekFrom::Current(self.block.len() as i64))?;
+            self.hole = true;
+        } else {
+            self.inner.write_all(&self.block)?;
+            self.hole = false;
+        }
+
+        self.block.clear();
+
+        Ok(())
+    }
+}
+
+impl<W: Write + Seek> Write for SparseWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = std::cmp::min(buf.len(), SPARSE_BLOCK_LEN - self.block.len());
+
+        self.block.extend_from_slice(&buf[..len]);
+
+        if self.block.len() == SPARSE_BLOCK_LEN {
+            self.write_block()?;
+        }
+
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.block.is_empty() {
+            self.write_block()?;
+        }
+
+        if self.hole {
+            self.inner.seek(SeekFrom::Current(-1))?;
+            self.inner.write_all(&[0])?;
+            self.hole = false;
+        }
+
+        self.inner.flush()
+    }
+}
+
+/// Formats a digest as a lowercase hex string.
+pub fn to_hex(digest: &[u8]) -> String {
+    digest.iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+/// Formats a size for people to read, such as "1.4 MiB", using powers of 1000
+/// ("1.5 MB") instead of 1024 if "si" is true. Sizes below the first unit are
+/// given in bytes.
+pub fn human_size(bytes: u64, si: bool) -> String {
+    let (base, units) = if si {
+        (1000.0, ["kB", "MB", "GB", "TB", "PB", "EB"])
+    } else {
+        (1024.0, ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"])
+    };
+
+    if bytes < base as u64 {
+        return format!("{} bytes", bytes);
+    }
+
+    let mut size = bytes as f64 / base;
+    let mut unit = 0;
+
+    // Move on to the next unit when the size would be rounded up to "1024.0".
+    while size >= base - 0.05 && unit < units.len() - 1 {
+        size /= base;
+        unit += 1;
+    }
+
+    format!("{:.1} {}", size, units[unit])
+}
+
+/// Sorts paths in the order hogdump sorts them in everywhere: component by
+/// component, comparing the bytes of each one. Unlike the collation order of
+/// a locale, this is the same on every machine: uppercase letters come before
+/// all lowercase ones ("Z.TXT" before "a.txt"), and names are never folded to
+/// the same case or otherwise normalized, so names with bytes above 0x7f come
+/// after all ASCII ones. Comparing components (rather than whole paths) keeps
+/// the order the same whichever character separates them, so that "a/b" sorts
+/// before "a-b" on Windows too.
+///
+/// The sort is stable, so equal paths keep their order.
+pub fn sort_paths(paths: &mut [PathBuf]) {
+    paths.sort();
+}
+
+/// Returns "path" with ".N" added to the filename, such as "level01.rdl.1"
+/// for "level01.rdl". Wherever a file cannot take a name because it is taken
+/// already, the first free one of these (starting at 1) is used instead.
+pub fn numbered_path(path: &Path, n: u64) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(format!(".{}", n));
+
+    path.with_file_name(file_name)
+}
+
+/// The files matching a glob pattern, found by glob().
+#[derive(Debug, Default)]
+pub struct GlobMatches {
+    /// The matching paths, sorted (by sort_paths()).
+    pub paths: Vec<PathBuf>,
+
+    /// Directories that could not be searched, and why. The rest of the
+    /// pattern is still searched without them.
+    pub unreadable: Vec<(PathBuf, Error)>,
+}
+
+/// Returns the paths matching the glob pattern "pattern". "*" matches any run
+/// of characters in a path component and "?" any single character, while a
+/// "**" component matches any number of directories, so that
+/// "missions/**/*.hog" finds HOG files anywhere under "missions".
+pub fn glob(pattern: &str) -> io::Result<GlobMatches> {
+    let parts: Vec<&str> = pattern.split('/').collect();
+    let literal = parts.iter().take_while(|x| !x.contains(['*', '?'])).count();
+
+    let mut matches = GlobMatches::default();
+
+    if literal == parts.len() {
+        if Path::new(pattern).exists() {
+            matches.paths.push(PathBuf::from(pattern));
+        }
+
+        return Ok(matches);
+    }
+
+    // An absolute pattern starts with an empty component.
+    let base = match &parts[..literal] {
+        [""] => PathBuf::from("/"),
+        literal => PathBuf::from(literal.join("/")),
+    };
+
+    if !base.as_os_str().is_empty() && !base.is_dir() {
+        return Ok(matches);
+    }
+
+    let components = parts[literal..]
+        .iter()
+        .map(|&x| match x {
+            "**" => Ok(None),
+            x => glob_regex(x).map(Some),
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+
+    glob_walk(&base, &components, &mut matches);
+
+    sort_paths(&mut matches.paths);
+    matches.paths.dedup();
+
+    Ok(matches)
+}
+
+// Converts a component of a glob pattern into a regular expression matching
+// the whole component.
+fn glob_regex(component: &str) -> io::Result<Regex> {
+    let mut re = String::from("^");
+
+    for c in component.chars() {
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+
+    re.push('$');
+
+    Regex::new(&re).map_err(|e| Error::new(ErrorKind::InvalidInput, e))
+}
+
+// Adds the paths under "dir" matching "components" to "matches". A None
+// component stands for "**". Symbolic links to directories are not followed,
+// so that loops cannot make this go on forever. Directories that cannot be
+// read are added to the unreadable ones, and skipped.
+fn glob_walk(dir: &Path, components: &[Option<Regex>], matches: &mut GlobMatches) {
+    let Some((component, rest)) = components.split_first() else {
+        return;
+    };
+
+    if component.is_none() {
+        glob_walk(dir, rest, matches);
+    }
+
+    let read_dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+
+    let entries = match std::fs::read_dir(read_dir) {
+        Ok(x) => x,
+        Err(e) => {
+            // "**" searches the same directory again for the rest of the
+            // pattern, which only needs to be reported once.
+            if !matches.unreadable.iter().any(|(x, _)| x == read_dir) {
+                matches.unreadable.push((read_dir.to_path_buf(), e));
+            }
+
+            return;
+        }
+    };
+
+    for entry in entries {
+        let entry = entry.and_then(|x| {
+            let is_dir = x.file_type()?.is_dir();
+
+            Ok((x, is_dir))
+        });
+        let (entry, is_dir) = match entry {
+            Ok(x) => x,
+            Err(e) => {
+                matches.unreadable.push((read_dir.to_path_buf(), e));
+
+                continue;
+            }
+        };
+        let path = dir.join(entry.file_name());
+
+        match component {
+            None if is_dir => glob_walk(&path, components, matches),
+            None => {}
+            Some(re) if re.is_match(&entry.file_name().to_string_lossy()) => {
+                if rest.is_empty() {
+                    matches.paths.push(path);
+                } else if is_dir {
+                    glob_walk(&path, rest, matches);
+                }
+            }
+            Some(_) => {}
+        }
+    }
+}
+
+fn symlink_error(path: &Path) -> Error {
+    Error::other(format!("\"{}\" is a symbolic link", path.display()))
+}
+
+/// Opens the file at "path" with "opts", unless it is a symbolic link, in
+/// which case an error is returned instead. On Unix this is done with
+/// O_NOFOLLOW, so a link cannot be swapped in between checking and opening.
+/// Elsewhere, the link is checked for before opening the file.
+///
+/// When creating a new file (with create_new()), a symbolic link at "path"
+/// makes it fail with ErrorKind::AlreadyExists, like any other file would.
+pub fn open_no_follow(opts: &mut OpenOptions, path: &Path) -> io::Result<File> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        match opts.custom_flags(libc::O_NOFOLLOW).open(path) {
+            Err(e) if e.raw_os_error() == Some(libc::ELOOP) => Err(symlink_error(path)),
+            result => result,
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        if std::fs::symlink_metadata(path).is_ok_and(|x| x.file_type().is_symlink()) {
+            return Err(symlink_error(path));
+        }
+
+        opts.open(path)
+    }
+}
+
+/// Syncs the directory containing "path" to disk, so that the file being
+/// created (or renamed) there is still there after a crash. Directories
+/// cannot be synced this way on Windows, where this does nothing.
+pub fn sync_parent_dir(path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        let dir = match path.parent() {
+            Some(x) if !x.as_os_str().is_empty() => x,
+            _ => Path::new("."),
+        };
+
+        File::open(dir)?.sync_all()
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+
+        Ok(())
+    }
+}
+
+/// Returns an error if any of the directories leading to the file at "rel"
+/// (relative to "base", which is trusted) is a symbolic link. Directories
+/// that do not exist yet are fine, since creating them does not follow
+/// anything.
+///
+/// Unlike open_no_follow(), this is only a check: a directory swapped for a
+/// link after it returns is not noticed when the file is opened.
+pub fn check_no_symlink_dirs(base: &Path, rel: &Path) -> io::Result<()> {
+    let mut path = base.to_path_buf();
+
+    for component in rel.parent().into_iter().flat_map(|x| x.components()) {
+        path.push(component);
+
+        match std::fs::symlink_metadata(&path) {
+            Ok(x) if x.file_type().is_symlink() => return Err(symlink_error(&path)),
+            Ok(_) => {}
+            Err(e) if e.kind() == ErrorKind::NotFound => break,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -117,6 +828,89 @@ mod test {
         assert_eq!(b"elephant_input", &w[..]);
     }
 
+    #[test]
+    fn test_copy_file() {
+        let dir = std::env::temp_dir().join(format!("hogdump-{}-copy-file", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (from, to) = (dir.join("from"), dir.join("to"));
+        let data: Vec<u8> = (0..10_000u32).map(|x| x as u8).collect();
+        std::fs::write(&from, &data).unwrap();
+
+        assert_eq!(copy_file(&from, &to).unwrap(), 10_000);
+        assert_eq!(std::fs::read(&to).unwrap(), data);
+        assert!(copy_file(&dir.join("missing"), &to).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_copy_n_with_buf() {
+        let data: Vec<u8> = (0..10_000u32).map(|x| x as u8).collect();
+        let mut buf = [0; 64];
+        let mut w = Vec::new();
+
+        assert_eq!(
+            copy_n_with_buf(&mut data.as_slice(), &mut w, 1000, &mut buf).unwrap(),
+            1000
+        );
+        assert_eq!(w, &data[..1000]);
+
+        // The buffer is reused, stopping early when the reader runs out.
+        let mut w = Vec::new();
+        let result = copy_n_with_buf(&mut &data[..100], &mut w, 1000, &mut buf);
+        assert_eq!(result.unwrap(), 100);
+        assert_eq!(w, &data[..100]);
+
+        let mut w = Vec::new();
+        let result = copy_n_after(&mut data.as_slice(), &mut w, b"head", 200, &mut buf);
+        assert_eq!(result.unwrap(), 200);
+        assert_eq!(&w[..4], b"head");
+        assert_eq!(&w[4..], &data[..200]);
+
+        // The header is written even if nothing is copied.
+        let mut w = Vec::new();
+        let result = copy_n_after(&mut data.as_slice(), &mut w, b"head", 0, &mut buf);
+        assert_eq!(result.unwrap(), 0);
+        assert_eq!(w, b"head");
+
+        // Writers that only take a little at a time still get everything.
+        let mut w = [0u8; 6];
+        let result = copy_n_after(&mut &b"ab"[..], &mut w.as_mut_slice(), b"head", 2, &mut buf);
+        assert_eq!(result.unwrap(), 2);
+        assert_eq!(&w, b"headab");
+    }
+
+    #[test]
+    fn test_copy_n_file() {
+        use std::io::{Seek, SeekFrom};
+
+        let dir = std::env::temp_dir().join(format!("hogdump_test_copy_n_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let data: Vec<u8> = (0..100_000u32).map(|x| x as u8).collect();
+        std::fs::write(dir.join("in"), &data).unwrap();
+
+        // Copying between files may take a different path, which must still
+        // stop after "n" bytes, and leave the reader right after them.
+        let mut r = std::fs::File::open(dir.join("in")).unwrap();
+        let mut w = std::fs::File::create(dir.join("out")).unwrap();
+
+        assert_eq!(copy_n(&mut r, &mut w, 70_000).unwrap(), 70_000);
+        assert_eq!(r.stream_position().unwrap(), 70_000);
+        assert_eq!(copy_n(&mut r, &mut w, 50_000).unwrap(), 30_000);
+        drop(w);
+
+        assert_eq!(std::fs::read(dir.join("out")).unwrap(), data);
+
+        let mut r = std::io::BufReader::new(std::fs::File::open(dir.join("in")).unwrap());
+        r.seek(SeekFrom::Start(10)).unwrap();
+        let mut out = Vec::new();
+        assert_eq!(copy_n(&mut r, &mut out, 5).unwrap(), 5);
+        assert_eq!(out, &data[10..15]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
     #[test]
     fn test_copy_exactly_n() {
         let mut r1: Vec<u8> = Vec::new();
@@ -152,4 +946,229 @@ mod test {
         assert!(result.is_err(), "too many bytes requested, should fail");
         assert_eq!(b"testingt_input", &w[..]);
     }
+
+    #[test]
+    fn test_sha256() {
+        let result = sha256(&mut b"elephant".as_slice()).unwrap();
+        assert_eq!(
+            to_hex(&result),
+            "cd08c4c4316df20d9c30450fe776dcde4810029e641cde526c5bbffec1f770a3"
+        );
+    }
+
+    #[test]
+    fn test_sha256_writer() {
+        let mut w = Sha256Writer::new(Vec::new());
+
+        w.write_all(b"ele").unwrap();
+        w.write_all(b"phant").unwrap();
+
+        let (digest, inner) = w.finish();
+
+        assert_eq!(inner, b"elephant");
+        assert_eq!(digest, sha256(&mut b"elephant".as_slice()).unwrap());
+    }
+
+    #[test]
+    fn test_sha256_reader() {
+        let mut r = Sha256Reader::new(b"elephant".as_slice());
+        let mut out = Vec::new();
+
+        r.read_to_end(&mut out).unwrap();
+
+        assert_eq!(out, b"elephant");
+        assert_eq!(r.finish().0, sha256(&mut b"elephant".as_slice()).unwrap());
+    }
+
+    #[test]
+    fn test_sparse_writer() {
+        let mut data = vec![0; 3 * SPARSE_BLOCK_LEN];
+        data[SPARSE_BLOCK_LEN + 10] = 1;
+
+        for len in [data.len(), 2 * SPARSE_BLOCK_LEN - 5, 100, 0] {
+            let mut w = SparseWriter::new(io::Cursor::new(Vec::new()));
+
+            copy_n(&mut &data[..len], &mut w, len as u64).unwrap();
+            w.flush().unwrap();
+
+            assert_eq!(w.inner.into_inner(), &data[..len]);
+        }
+    }
+
+    #[test]
+    fn test_limited_writer() {
+        let limits = Limits {
+            max_len: Some(5),
+            max_time: None,
+        };
+        let mut data = Vec::new();
+        let mut w = limits.writer(&mut data);
+
+        w.write_all(b"abc").unwrap();
+        w.write_all(b"de").unwrap();
+
+        let e = w.write_all(b"f").unwrap_err();
+        let limit = e.get_ref().and_then(|x| x.downcast_ref::<LimitExceeded>());
+
+        assert_eq!(limit, Some(&LimitExceeded::Size(5)));
+        assert_eq!(data, b"abcde");
+
+        let limits = Limits {
+            max_len: None,
+            max_time: Some(Duration::ZERO),
+        };
+
+        assert_eq!(
+            limits.check(0, Duration::from_millis(1)),
+            Err(LimitExceeded::Time(Duration::ZERO))
+        );
+        assert_eq!(Limits::default().check(u64::MAX, Duration::MAX), Ok(()));
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new(1000);
+        let now = Instant::now();
+
+        assert_eq!(limiter.reserve(500, now), Duration::from_millis(500));
+        assert_eq!(limiter.reserve(1000, now), Duration::from_millis(1500));
+
+        // The wait only covers what is left of the earlier copies.
+        let later = now + Duration::from_secs(1);
+        assert_eq!(limiter.reserve(0, later), Duration::from_millis(500));
+
+        // Idle time is not saved up.
+        let idle = now + Duration::from_secs(10);
+        assert_eq!(limiter.reserve(100, idle), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_sort_paths() {
+        let mut paths: Vec<PathBuf> = ["a-b", "a/b", "B/c", "a/B", "\u{e9}", "a"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+
+        sort_paths(&mut paths);
+
+        assert_eq!(
+            paths,
+            ["B/c", "a", "a/B", "a/b", "a-b", "\u{e9}"]
+                .iter()
+                .map(PathBuf::from)
+                .collect::<Vec<_>>()
+        );
+
+        // Names that are not UTF-8 sort by their bytes too.
+        #[cfg(unix)]
+        {
+            use std::ffi::OsStr;
+            use std::os::unix::ffi::OsStrExt;
+
+            let mut paths = vec![
+                PathBuf::from(OsStr::from_bytes(b"\xff.pcx")),
+                PathBuf::from(OsStr::from_bytes(b"\xe9.pcx")),
+                PathBuf::from("z.pcx"),
+            ];
+
+            sort_paths(&mut paths);
+
+            assert_eq!(paths[0], Path::new("z.pcx"));
+            assert_eq!(paths[1].as_os_str().as_bytes(), b"\xe9.pcx");
+        }
+    }
+
+    #[test]
+    fn test_numbered_path() {
+        let numbered = |path: &str, n| numbered_path(Path::new(path), n);
+
+        assert_eq!(numbered("level01.rdl", 1), Path::new("level01.rdl.1"));
+        assert_eq!(numbered("out/a.tar.gz", 3), Path::new("out/a.tar.gz.3"));
+        assert_eq!(numbered("out/README", 2), Path::new("out/README.2"));
+        assert_eq!(numbered(".hidden", 10), Path::new(".hidden.10"));
+    }
+
+    #[test]
+    fn test_glob() {
+        let dir = std::env::temp_dir().join(format!("hogdump_test_glob_{}", std::process::id()));
+
+        for file in [
+            "a.hog",
+            "b.txt",
+            "sub/c.hog",
+            "sub/deeper/d.hog",
+            "sub/e.HOG",
+        ] {
+            let path = dir.join(file);
+
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, b"DHF").unwrap();
+        }
+
+        let base = dir.to_string_lossy();
+        let glob = |pattern: &str| glob(&format!("{}/{}", base, pattern)).unwrap().paths;
+        let paths = |files: &[&str]| files.iter().map(|x| dir.join(x)).collect::<Vec<_>>();
+
+        assert_eq!(glob("*.hog"), paths(&["a.hog"]));
+        assert_eq!(
+            glob("**/*.hog"),
+            paths(&["a.hog", "sub/c.hog", "sub/deeper/d.hog"])
+        );
+        assert_eq!(glob("sub/?.*"), paths(&["sub/c.hog", "sub/e.HOG"]));
+        assert_eq!(glob("b.txt"), paths(&["b.txt"]));
+        assert!(glob("*.pig").is_empty());
+        assert!(glob("missing/*.hog").is_empty());
+
+        // A directory that cannot be read is skipped, rather than stopping
+        // the search.
+        let mut matches = GlobMatches::default();
+        let components = [None, Some(glob_regex("*.hog").unwrap())];
+        glob_walk(&dir.join("gone"), &components, &mut matches);
+        glob_walk(&dir, &components, &mut matches);
+        assert_eq!(matches.unreadable.len(), 1);
+        assert_eq!(matches.unreadable[0].0, dir.join("gone"));
+        assert_eq!(matches.paths.len(), 3);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_no_follow() {
+        let dir =
+            std::env::temp_dir().join(format!("hogdump_test_no_follow_{}", std::process::id()));
+        let sub = dir.join("sub");
+
+        std::fs::create_dir_all(&sub).unwrap();
+        std::fs::write(dir.join("real.txt"), b"hi").unwrap();
+        std::os::unix::fs::symlink(dir.join("real.txt"), dir.join("link.txt")).unwrap();
+        std::os::unix::fs::symlink(&sub, dir.join("linked")).unwrap();
+
+        let read = || OpenOptions::new().read(true).clone();
+
+        assert!(open_no_follow(&mut read(), &dir.join("real.txt")).is_ok());
+        assert!(open_no_follow(&mut read(), &dir.join("link.txt")).is_err());
+
+        let create_new = || OpenOptions::new().write(true).create_new(true).clone();
+        let e = open_no_follow(&mut create_new(), &dir.join("link.txt")).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::AlreadyExists);
+
+        assert!(check_no_symlink_dirs(&dir, Path::new("sub/new/a.txt")).is_ok());
+        assert!(check_no_symlink_dirs(&dir, Path::new("link.txt")).is_ok());
+        assert!(check_no_symlink_dirs(&dir, Path::new("linked/a.txt")).is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_human_size() {
+        assert_eq!(human_size(0, false), "0 bytes");
+        assert_eq!(human_size(1023, false), "1023 bytes");
+        assert_eq!(human_size(1024, false), "1.0 KiB");
+        assert_eq!(human_size(1_468_006, false), "1.4 MiB");
+        assert_eq!(human_size(1_048_575, false), "1.0 MiB");
+        assert_eq!(human_size(1_468_006, true), "1.5 MB");
+        assert_eq!(human_size(999, true), "999 bytes");
+        assert_eq!(human_size(u64::MAX, false), "16.0 EiB");
+    }
 }
@@ -0,0 +1,193 @@
+//
+// Copyright (c) 2022-2023 Nathaniel Houghton <nathan@brainwerk.org>
+//
+// Permission to use, copy, modify, and distribute this software for
+// any purpose with or without fee is hereby granted, provided that
+// the above copyright notice and this permission notice appear in all
+// copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHOR DISCLAIMS ALL
+// WARRANTIES WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED
+// WARRANTIES OF MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE
+// AUTHOR BE LIABLE FOR ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL
+// DAMAGES OR ANY DAMAGES WHATSOEVER RESULTING FROM LOSS OF USE, DATA
+// OR PROFITS, WHETHER IN AN ACTION OF CONTRACT, NEGLIGENCE OR OTHER
+// TORTIOUS ACTION, ARISING OUT OF OR IN CONNECTION WITH THE USE OR
+// PERFORMANCE OF THIS SOFTWARE.
+//
+
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+
+use crate::error::HogError;
+use crate::hog::HogFileReader;
+
+/// Where a file looked up in a HogVfs was found.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Resolution {
+    /// The HOG file the file is in.
+    pub archive: PathBuf,
+
+    /// The (0 based) position of the file in the HOG file.
+    pub index: u64,
+
+    /// The name the file is stored as in the HOG file.
+    pub stored_name: String,
+
+    /// The name that was looked up (which may differ in case).
+    pub requested_name: String,
+
+    pub length: u32,
+}
+
+// A file in one of the HOG files of a HogVfs.
+struct VfsEntry {
+    index: u64,
+    stored_name: String,
+    length: u32,
+}
+
+// The files of a single HOG file, by their lower case names.
+struct VfsArchive {
+    path: PathBuf,
+    entries: HashMap<String, Vec<VfsEntry>>,
+}
+
+/// Looks up files across several HOG files, the way the game does: names are
+/// compared without regard to case, and the HOG files are searched in the
+/// order they were added, so a file in an earlier HOG file shadows files with
+/// the same name in later ones.
+#[derive(Default)]
+pub struct HogVfs {
+    archives: Vec<VfsArchive>,
+}
+
+impl HogVfs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the HOG file at "path", to be searched after the ones already
+    /// added.
+    pub fn add(&mut self, path: &impl AsRef<Path>) -> Result<(), HogError> {
+        let mut hog_file = HogFileReader::open(path)?;
+
+        self.add_reader(path, &mut hog_file)
+    }
+
+    /// Adds the HOG file read by "hog_file" like add(), under the name "path".
+    pub fn add_reader<R: Read + Seek>(
+        &mut self,
+        path: &impl AsRef<Path>,
+        hog_file: &mut HogFileReader<R>,
+    ) -> Result<(), HogError> {
+        let mut entries: HashMap<String, Vec<VfsEntry>> = HashMap::new();
+        let mut iter = hog_file.records()?;
+
+        while let Some(record) = iter.next() {
+            let record = record?;
+            let stored_name = record.filename.to_string_lossy().into_owned();
+
+            entries
+                .entry(stored_name.to_ascii_lowercase())
+                .or_default()
+                .push(VfsEntry {
+                    index: iter.record_index(),
+                    stored_name,
+                    length: record.length,
+                });
+        }
+
+        self.archives.push(VfsArchive {
+            path: path.as_ref().to_path_buf(),
+            entries,
+        });
+
+        Ok(())
+    }
+
+    /// Returns where the file "name" is found, if it is found at all.
+    pub fn resolve(&self, name: &str) -> Option<Resolution> {
+        self.resolve_all(name).into_iter().next()
+    }
+
+    /// Returns every copy of the file "name", in search order. The first one
+    /// is the one that resolve() returns, the rest are shadowed by it.
+    pub fn resolve_all(&self, name: &str) -> Vec<Resolution> {
+        let key = name.to_ascii_lowercase();
+
+        self.archives
+            .iter()
+            .flat_map(|archive| {
+                archive
+                    .entries
+                    .get(&key)
+                    .into_iter()
+                    .flatten()
+                    .map(|entry| Resolution {
+                        archive: archive.path.clone(),
+                        index: entry.index,
+                        stored_name: entry.stored_name.clone(),
+                        requested_name: name.to_string(),
+                        length: entry.length,
+                    })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    fn hog(files: &[(&str, &[u8])]) -> HogFileReader<Cursor<Vec<u8>>> {
+        let mut data = b"DHF".to_vec();
+
+        for (name, contents) in files {
+            let mut field = [0; 13];
+            field[..name.len()].copy_from_slice(name.as_bytes());
+
+            data.extend(field);
+            data.extend((contents.len() as u32).to_le_bytes());
+            data.extend(*contents);
+        }
+
+        HogFileReader::from_reader(Cursor::new(data)).unwrap()
+    }
+
+    #[test]
+    fn test_resolve() {
+        let mut vfs = HogVfs::new();
+
+        let mut mission = hog(&[("level01.rdl", b"mine"), ("briefing.txb", b"b")]);
+        vfs.add_reader(&"mission.hog", &mut mission).unwrap();
+
+        let mut descent = hog(&[("LEVEL01.RDL", b"original"), ("a.pcx", b"12")]);
+        vfs.add_reader(&"descent.hog", &mut descent).unwrap();
+
+        let found = vfs.resolve("Level01.rdl").unwrap();
+        assert_eq!(found.archive, Path::new("mission.hog"));
+        assert_eq!(found.stored_name, "level01.rdl");
+        assert_eq!(found.requested_name, "Level01.rdl");
+        assert_eq!(found.length, 4);
+
+        let all = vfs.resolve_all("LEVEL01.RDL");
+        assert_eq!(all.len(), 2);
+        assert_eq!(
+            all[0],
+            Resolution {
+                requested_name: "LEVEL01.RDL".to_string(),
+                ..found
+            }
+        );
+        assert_eq!(all[1].archive, Path::new("descent.hog"));
+        assert_eq!(all[1].stored_name, "LEVEL01.RDL");
+        assert_eq!(all[1].index, 0);
+
+        assert_eq!(vfs.resolve_all("a.pcx")[0].index, 1);
+        assert!(vfs.resolve("missing.txt").is_none());
+        assert!(vfs.resolve_all("missing.txt").is_empty());
+    }
+}